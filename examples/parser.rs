@@ -3,9 +3,13 @@
 //! Run with: cargo run --example parser
 
 use pzsh::config::CompiledConfig;
-use pzsh::parser::{ParsedCommand, Parser};
+use pzsh::parser::{ParsedCommand, Parser, Word};
 use std::time::Instant;
 
+fn words(args: &[Word]) -> Vec<String> {
+    args.iter().map(ToString::to_string).collect()
+}
+
 fn main() {
     println!("pzsh Parser Demo");
     println!("════════════════");
@@ -32,6 +36,8 @@ fn main() {
         "cd /home",             // Builtin
         "git commit -m 'test'", // Simple command
         "gs",                   // Alias
+        "ls | grep src > out",  // Pipeline with redirect
+        "make && make test",    // List
         "",                     // Empty
     ];
 
@@ -44,14 +50,22 @@ fn main() {
 
         let kind = match &result {
             ParsedCommand::Simple { command, args } => {
-                format!("Simple({} {:?})", command, args)
+                format!("Simple({} {:?})", command, words(args))
             }
-            ParsedCommand::Alias { name, expansion } => {
+            ParsedCommand::Alias {
+                name, expansion, ..
+            } => {
                 format!("Alias({} -> {})", name, expansion)
             }
             ParsedCommand::Builtin { name, args } => {
-                format!("Builtin({} {:?})", name, args)
+                format!("Builtin({} {:?})", name, words(args))
+            }
+            ParsedCommand::Pipeline(commands) => format!("Pipeline({} commands)", commands.len()),
+            ParsedCommand::List(items) => format!("List({} commands)", items.len()),
+            ParsedCommand::Redirect { redirects, .. } => {
+                format!("Redirect({} redirections)", redirects.len())
             }
+            ParsedCommand::Subshell(_) => "Subshell".to_string(),
             ParsedCommand::Empty => "Empty".to_string(),
         };
 
@@ -0,0 +1,323 @@
+//! Compiled init cache
+//!
+//! `pzsh compile --cache` writes the generated script to
+//! `$XDG_CACHE_HOME/pzsh/init.<ext>` (`~/.cache/pzsh` when unset). The
+//! first line records a key hashing the pzsh version, the machine and every
+//! input the script was generated from, so an unchanged config is not
+//! regenerated. Next to it, `init.<ext>.inputs` lists the files to watch,
+//! one per line. The shell init sources the cached file and only runs
+//! `pzsh compile` when a watched file is missing or newer than it.
+
+use crate::ShellType;
+use crate::config::{CompiledConfig, Machine, hash_inputs, references};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// First line of a cached script, followed by its key
+const HEADER: &str = "# pzsh init cache ";
+
+/// Directory of cached init scripts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitCache {
+    dir: PathBuf,
+}
+
+impl InitCache {
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$XDG_CACHE_HOME/pzsh`, or `~/.cache/pzsh` when it is unset or empty,
+    /// matching `${XDG_CACHE_HOME:-$HOME/.cache}` in the shell init
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".cache")))?;
+        Some(Self::new(base.join("pzsh")))
+    }
+
+    /// Directory holding the cached files
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cached script for `shell`
+    #[must_use]
+    pub fn path(&self, shell: ShellType) -> PathBuf {
+        let ext = match shell {
+            ShellType::Nushell => "nu",
+            shell => shell.name(),
+        };
+        self.dir.join(format!("init.{ext}"))
+    }
+
+    /// Files the shell init watches for the cached script of `shell`
+    #[must_use]
+    pub fn inputs_path(&self, shell: ShellType) -> PathBuf {
+        let mut path = self.path(shell).into_os_string();
+        path.push(".inputs");
+        PathBuf::from(path)
+    }
+
+    /// Whether the cached script for `shell` was generated with `key` and
+    /// its watched files are recorded
+    #[must_use]
+    pub fn is_current(&self, shell: ShellType, key: &str) -> bool {
+        self.inputs_path(shell).is_file()
+            && fs::read_to_string(self.path(shell)).is_ok_and(|script| {
+                script
+                    .lines()
+                    .next()
+                    .and_then(|line| line.strip_prefix(HEADER))
+                    == Some(key)
+            })
+    }
+
+    /// Mark the cached script as up to date, so the shell stops recompiling
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be opened or its mtime set
+    pub fn touch(&self, shell: ShellType) -> io::Result<PathBuf> {
+        let path = self.path(shell);
+        fs::File::options()
+            .append(true)
+            .open(&path)?
+            .set_modified(SystemTime::now())?;
+        Ok(path)
+    }
+
+    /// Replace the cached script for `shell` and its list of watched files
+    ///
+    /// A path containing a newline cannot be listed and is not watched.
+    ///
+    /// # Errors
+    /// Returns error if the directory or files cannot be written
+    pub fn write(
+        &self,
+        shell: ShellType,
+        key: &str,
+        script: &str,
+        watched: &[PathBuf],
+    ) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let mut inputs = String::new();
+        for path in watched {
+            let path = path.to_string_lossy();
+            if !path.contains('\n') {
+                inputs.push_str(&path);
+                inputs.push('\n');
+            }
+        }
+        replace(&self.inputs_path(shell), &inputs)?;
+        let path = self.path(shell);
+        replace(&path, &format!("{HEADER}{key}\n{script}"))?;
+        Ok(path)
+    }
+}
+
+/// Write `content` to `path` through a temporary file, so a shell starting
+/// meanwhile sees the old content or the new one
+fn replace(path: &Path, content: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".tmp.{}", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+    let result = fs::write(&tmp, content).and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Cache key for a script generated from `files` into `config` on
+/// `machine`
+///
+/// Hashes the files as [`hash_inputs`] does, and what compiling looked up
+/// outside them: which `[path]` directories exist and the environment
+/// variables `[env]` references.
+#[must_use]
+pub fn cache_key(files: &[PathBuf], config: &CompiledConfig, machine: &Machine) -> String {
+    let mut hash = hash_inputs(files, machine);
+    for search_path in &config.paths {
+        for dir in search_path.prepend.iter().chain(&search_path.append) {
+            hash.write(dir.as_os_str().as_encoded_bytes());
+            hash.write(b"\0dir");
+        }
+        for dir in &search_path.missing {
+            hash.write(dir.as_os_str().as_encoded_bytes());
+            hash.write(b"\0missing");
+        }
+    }
+    let referenced: BTreeSet<&str> = config
+        .env
+        .values()
+        .flat_map(|value| references(value))
+        .filter(|name| !config.env.contains_key(*name))
+        .collect();
+    for name in referenced {
+        hash.write(name.as_bytes());
+        match std::env::var_os(name) {
+            Some(value) => hash.write(value.as_encoded_bytes()),
+            None => hash.write(b"\0unset"),
+        }
+    }
+    format!("{:016x}", hash.finish())
+}
+
+/// Paths the shell init compares with the cached script
+///
+/// These are the `inputs` from [`CompiledConfig::inputs`], the pzsh binary,
+/// which changes on upgrade, and for each `[path]` directory of `config`
+/// that does not exist, its nearest existing parent, which changes when it
+/// is created.
+#[must_use]
+pub fn watched_paths(inputs: &[PathBuf], config: &CompiledConfig) -> Vec<PathBuf> {
+    let mut paths = inputs.to_vec();
+    paths.extend(std::env::current_exe().ok());
+    for dir in config.paths.iter().flat_map(|p| &p.missing) {
+        if let Some(parent) = dir.ancestors().skip(1).find(|a| a.is_dir()) {
+            if !paths.iter().any(|p| p == parent) {
+                paths.push(parent.to_path_buf());
+            }
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn machine(hostname: &str) -> Machine {
+        Machine {
+            hostname: hostname.to_string(),
+            os: "linux".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cache_key_tracks_inputs() {
+        let dir = temp_dir("cache-key");
+        let rc = dir.join("pzshrc");
+        let include = dir.join("work.toml");
+        fs::write(&rc, "include = [\"work.toml\"]\n").unwrap();
+        fs::write(&include, "[env]\nA = \"1\"\n").unwrap();
+        let files = [rc.clone(), include.clone()];
+
+        let config = CompiledConfig::default();
+
+        let key = cache_key(&files, &config, &machine("box"));
+        assert_eq!(key.len(), 16);
+        assert_eq!(key, cache_key(&files, &config, &machine("box")));
+        assert_ne!(key, cache_key(&files, &config, &machine("other")));
+
+        fs::write(&include, "[env]\nA = \"2\"\n").unwrap();
+        let changed = cache_key(&files, &config, &machine("box"));
+        assert_ne!(key, changed);
+        fs::remove_file(&include).unwrap();
+        assert_ne!(changed, cache_key(&files, &config, &machine("box")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_key_tracks_lookups() {
+        let dir = temp_dir("cache-key-lookups");
+        let bin = dir.join("bin");
+        fs::create_dir(&bin).unwrap();
+        let toml = format!("[path]\nprepend = [\"{}\"]\n", bin.display());
+        let compile = || CompiledConfig::from_toml(&toml).unwrap();
+
+        let key = cache_key(&[], &compile(), &machine("box"));
+        fs::remove_dir(&bin).unwrap();
+        assert_ne!(key, cache_key(&[], &compile(), &machine("box")));
+
+        // The templates are hashed with the files; here only the
+        // environment values differ
+        let home = CompiledConfig::from_toml("[env]\nA = \"${HOME}\"\n").unwrap();
+        let unset = CompiledConfig::from_toml("[env]\nA = \"${PZSH_TEST_UNSET}\"\n").unwrap();
+        assert_ne!(
+            cache_key(&[], &home, &machine("box")),
+            cache_key(&[], &unset, &machine("box"))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watched_paths_notice_created_dirs() {
+        let dir = temp_dir("cache-watch-dirs");
+        let bin = dir.join("newtool/bin");
+        let toml = format!("[path]\nprepend = [\"{}\"]\n", bin.display());
+        let compile = || CompiledConfig::from_toml(&toml).unwrap();
+        let rc = dir.join("pzshrc");
+
+        // First compile, before the directory exists
+        let config = compile();
+        let key = cache_key(&[], &config, &machine("box"));
+        let watched = watched_paths(std::slice::from_ref(&rc), &config);
+        assert_eq!(watched.first(), Some(&rc));
+        assert_eq!(watched.last(), Some(&dir));
+        let cache_dir = temp_dir("cache-watch-dirs-cache");
+        let cache = InitCache::new(&cache_dir);
+        let path = cache
+            .write(ShellType::Bash, &key, "true\n", &watched)
+            .unwrap();
+        let compiled_at = SystemTime::now() - std::time::Duration::from_secs(5);
+        for file in [&path, &dir] {
+            fs::File::open(file)
+                .unwrap()
+                .set_modified(compiled_at)
+                .unwrap();
+        }
+
+        // Creating it makes the watched parent newer than the cached
+        // script, and the recompiled key differs
+        fs::create_dir_all(&bin).unwrap();
+        let modified = |p: &Path| fs::metadata(p).unwrap().modified().unwrap();
+        assert!(modified(&dir) > modified(&path));
+        let config = compile();
+        assert!(!cache.is_current(ShellType::Bash, &cache_key(&[], &config, &machine("box"))));
+        assert!(!watched_paths(&[], &config).contains(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_init_cache_write_and_check() {
+        let dir = temp_dir("init-cache");
+        let cache = InitCache::new(dir.join("pzsh"));
+        assert_eq!(cache.path(ShellType::Nushell), dir.join("pzsh/init.nu"));
+        assert!(!cache.is_current(ShellType::Zsh, "k1"));
+        assert!(cache.touch(ShellType::Zsh).is_err());
+
+        let watched = [dir.join("pzshrc"), dir.join("bad\nname")];
+        let path = cache
+            .write(ShellType::Zsh, "k1", "export A=\"1\"\n", &watched)
+            .unwrap();
+        assert_eq!(path, dir.join("pzsh/init.zsh"));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# pzsh init cache k1\nexport A=\"1\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("pzsh/init.zsh.inputs")).unwrap(),
+            format!("{}\n", dir.join("pzshrc").display())
+        );
+        assert!(cache.is_current(ShellType::Zsh, "k1"));
+        assert!(!cache.is_current(ShellType::Zsh, "k2"));
+        assert!(!cache.is_current(ShellType::Bash, "k1"));
+        assert_eq!(cache.touch(ShellType::Zsh).unwrap(), path);
+        assert_eq!(fs::read_dir(dir.join("pzsh")).unwrap().count(), 2);
+
+        // A cache written before inputs were recorded is regenerated
+        fs::remove_file(cache.inputs_path(ShellType::Zsh)).unwrap();
+        assert!(!cache.is_current(ShellType::Zsh, "k1"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,521 @@
+//! Machine-applicable lint fixes
+//!
+//! Each fix replaces one line of the config. `pzsh fix` applies them with
+//! [`apply_edits`], previews them with [`unified_diff`] and writes the result
+//! with [`write_with_backup`].
+
+use super::lint::substitutions;
+use super::{LintIssue, LintMode};
+use crate::parser::parse_script;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Lines of context around each diff hunk
+const DIFF_CONTEXT: usize = 3;
+
+/// Commands lazily loaded by the nvm stub
+const NVM_COMMANDS: &[&str] = &["nvm", "node", "npm", "npx"];
+
+/// Machine-applicable fix: replace a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// Line number (1-based)
+    pub line: usize,
+    /// New line content; may span several lines
+    pub replacement: String,
+}
+
+/// Run a command whose output can be hardcoded into the config
+///
+/// Only `brew --prefix [formula]` is run: its output is a fixed path that
+/// only changes when Homebrew itself moves.
+#[must_use]
+pub fn resolve_command(command: &str) -> Option<String> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    if !matches!(
+        args.as_slice(),
+        ["brew", "--prefix"] | ["brew", "--prefix", _]
+    ) {
+        return None;
+    }
+    let output = Command::new(args[0]).args(&args[1..]).output().ok()?;
+    let path = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !path.is_empty()).then_some(path)
+}
+
+/// Replace `$(brew --prefix x)` and `` `brew --prefix x` `` with the
+/// resolved path
+///
+/// Substitutions come from the shell parser, so nested and quoted parens
+/// (`$(dirname $(brew --prefix x))`) are matched correctly; other
+/// substitutions are searched for nested `brew --prefix` calls.
+pub(super) fn resolve_substitutions(
+    line: &str,
+    resolve: &dyn Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let script = parse_script(line);
+    let mut output = String::with_capacity(line.len());
+    let mut copied = 0;
+    let mut changed = false;
+    for word in script.commands.iter().flat_map(|c| &c.words) {
+        for (start, backtick, command) in substitutions(line, word) {
+            let (open, close) = if backtick { ("`", "`") } else { ("$(", ")") };
+            let end = start + open.len() + command.len() + close.len();
+            if start < copied || line.get(end - close.len()..end) != Some(close) {
+                continue;
+            }
+            let replacement = if command.trim().starts_with("brew --prefix") {
+                resolve(command.trim())
+            } else {
+                resolve_substitutions(command, resolve).map(|inner| format!("{open}{inner}{close}"))
+            };
+            if let Some(replacement) = replacement {
+                output.push_str(&line[copied..start]);
+                output.push_str(&replacement);
+                copied = end;
+                changed = true;
+            }
+        }
+    }
+    output.push_str(&line[copied..]);
+    changed.then_some(output)
+}
+
+/// Turn an `nvm.sh` source line into a loader run by the first call to
+/// `nvm`, `node`, `npm` or `npx`
+pub(super) fn lazy_nvm(line: &str, _resolve: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    let trimmed = line.trim();
+    let sources = trimmed.starts_with(". ")
+        || trimmed.starts_with("\\. ")
+        || trimmed.contains("source ")
+        || trimmed.contains("&& . ")
+        || trimmed.contains("&& \\. ");
+    if !sources || is_lazy_loader(trimmed) {
+        return None;
+    }
+
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let commands = NVM_COMMANDS.join(" ");
+    // The source line keeps a line of its own: a trailing comment
+    // (`# This loads nvm`) would otherwise swallow the closing brace
+    let mut output = format!(
+        "{indent}# nvm loaded on first use (pzsh fix)\n\
+         {indent}__pzsh_lazy_nvm() {{\n\
+         {indent}  unset -f {commands} __pzsh_lazy_nvm\n\
+         {indent}  {}\n\
+         {indent}}}",
+        trimmed.trim_end_matches(';')
+    );
+    for command in NVM_COMMANDS {
+        output.push_str(&format!(
+            "\n{indent}{command}() {{ __pzsh_lazy_nvm; {command} \"$@\"; }}"
+        ));
+    }
+    Some(output)
+}
+
+/// Add `-C` to the first `compinit` command (not argument) on the line
+pub(super) fn compinit_cached(
+    line: &str,
+    _resolve: &dyn Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let mut start = 0;
+    while let Some(i) = line[start..].find("compinit") {
+        let end = start + i + "compinit".len();
+        let before = line[..start + i].trim_end();
+        let after = line[end..].chars().next();
+        if (before.is_empty()
+            || before.ends_with([';', '&', '|', '{'])
+            || before.ends_with(" then")
+            || before.ends_with(" do"))
+            && after.is_none_or(|c| c.is_whitespace() || matches!(c, ';' | '&' | '|'))
+        {
+            return Some(format!("{} -C{}", &line[..end], &line[end..]));
+        }
+        start = end;
+    }
+    None
+}
+
+/// Line defines a pzsh lazy loader (see `pzsh compile` and [`lazy_nvm`])
+fn is_lazy_loader(line: &str) -> bool {
+    line.trim_start().starts_with("__pzsh_lazy_")
+}
+
+/// Apply the issues' edits, at most one per line
+///
+/// Returns the new content and the number of edits applied.
+#[must_use]
+pub fn apply_edits(content: &str, issues: &[LintIssue]) -> (String, usize) {
+    let (output, applied, _) = apply(content, issues);
+    (output, applied)
+}
+
+/// Line number in `content` that each line of the [`apply_edits`] output
+/// came from (index 0 is line 1)
+///
+/// Every line of a multi-line replacement maps to the line it replaced.
+#[must_use]
+pub fn original_lines(content: &str, issues: &[LintIssue]) -> Vec<usize> {
+    apply(content, issues).2
+}
+
+fn apply(content: &str, issues: &[LintIssue]) -> (String, usize, Vec<usize>) {
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+    let mut edited = vec![false; lines.len()];
+    let mut applied = 0;
+    for edit in issues.iter().filter_map(|issue| issue.edit.as_ref()) {
+        let Some(index) = edit.line.checked_sub(1).filter(|&i| i < lines.len()) else {
+            continue;
+        };
+        if !edited[index] {
+            lines[index].clone_from(&edit.replacement);
+            edited[index] = true;
+            applied += 1;
+        }
+    }
+
+    let origins = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| std::iter::repeat_n(i + 1, line.lines().count().max(1)))
+        .collect();
+    let mut output = lines.join("\n");
+    if content.ends_with('\n') {
+        output.push('\n');
+    }
+    (output, applied, origins)
+}
+
+/// Check that fixed content still parses before it is written
+///
+/// TOML configs are parsed; shell files are run through `zsh -n` (files
+/// named like `.zshrc`) or `bash -n`. A shell that is not installed is not
+/// an error.
+///
+/// # Errors
+/// Returns the parser's message if the content does not parse.
+pub fn check_syntax(path: &Path, mode: LintMode, content: &str) -> Result<(), String> {
+    if mode == LintMode::Toml {
+        return toml::de::DeTable::parse(content)
+            .map(drop)
+            .map_err(|e| e.message().to_string());
+    }
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let shell = if name.contains("zsh") { "zsh" } else { "bash" };
+    let Ok(mut child) = Command::new(shell)
+        .arg("-n")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+    else {
+        return Ok(());
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // A shell that stops reading early reports the error below
+        let _ = stdin.write_all(content.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{shell} -n: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Unified diff of two versions of `path`, with 3 lines of context
+#[must_use]
+pub fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence of lines, from the end
+    let mut lcs = vec![vec![0usize; new_lines.len() + 1]; old_lines.len() + 1];
+    for i in (0..old_lines.len()).rev() {
+        for j in (0..new_lines.len()).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // (tag, line, old line number, new line number) before the op
+    let mut ops: Vec<(char, &str, usize, usize)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_lines.len() || j < new_lines.len() {
+        if i < old_lines.len() && j < new_lines.len() && old_lines[i] == new_lines[j] {
+            ops.push((' ', old_lines[i], i, j));
+            i += 1;
+            j += 1;
+        } else if i < old_lines.len() && (j == new_lines.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', old_lines[i], i, j));
+            i += 1;
+        } else {
+            ops.push(('+', new_lines[j], i, j));
+            j += 1;
+        }
+    }
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k].0 != ' ').collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut output = format!("--- {path}\n+++ {path}\n");
+    let mut next = 0;
+    while next < changes.len() {
+        // Extend the hunk while the next change is within the shared context
+        let mut end = next;
+        while end + 1 < changes.len() && changes[end + 1] - changes[end] <= 2 * DIFF_CONTEXT {
+            end += 1;
+        }
+        let first = changes[next].saturating_sub(DIFF_CONTEXT);
+        let last = (changes[end] + DIFF_CONTEXT).min(ops.len() - 1);
+        let hunk = &ops[first..=last];
+
+        let old_len = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_len = hunk.iter().filter(|op| op.0 != '-').count();
+        let start = |number: usize, len: usize| if len == 0 { number } else { number + 1 };
+        output.push_str(&format!(
+            "@@ -{},{old_len} +{},{new_len} @@\n",
+            start(hunk[0].2, old_len),
+            start(hunk[0].3, new_len)
+        ));
+        for (tag, line, _, _) in hunk {
+            output.push_str(&format!("{tag}{line}\n"));
+        }
+        next = end + 1;
+    }
+    output
+}
+
+/// Replace `path` with `content` atomically, keeping the old file as
+/// `<path>.bak`
+///
+/// Symlinks are followed so that a config managed in a dotfiles repository
+/// stays a symlink. Returns the backup path.
+///
+/// # Errors
+/// Returns error if the file cannot be read, backed up or replaced
+pub fn write_with_backup(path: &Path, content: &str) -> std::io::Result<PathBuf> {
+    let path = fs::canonicalize(path)?;
+    let backup = with_suffix(&path, ".bak");
+    fs::copy(&path, &backup)?;
+
+    // Write a sibling file, then rename it over the original
+    let tmp = with_suffix(&path, ".pzsh-tmp");
+    let result = fs::write(&tmp, content)
+        .and_then(|()| fs::set_permissions(&tmp, fs::metadata(&path)?.permissions()))
+        .and_then(|()| fs::rename(&tmp, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result.map(|()| backup)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brew(command: &str) -> Option<String> {
+        (command == "brew --prefix golang").then(|| "/opt/homebrew/opt/go".to_string())
+    }
+
+    #[test]
+    fn test_resolve_substitutions() {
+        assert_eq!(
+            resolve_substitutions("export GOROOT=\"$(brew --prefix golang)/libexec\"", &brew),
+            Some("export GOROOT=\"/opt/homebrew/opt/go/libexec\"".to_string())
+        );
+        assert_eq!(
+            resolve_substitutions("export GOROOT=`brew --prefix golang`", &brew),
+            Some("export GOROOT=/opt/homebrew/opt/go".to_string())
+        );
+        // Other substitutions and unresolvable formulas are left alone
+        assert_eq!(
+            resolve_substitutions("export A=$(date) B=$(brew --prefix golang)", &brew),
+            Some("export A=$(date) B=/opt/homebrew/opt/go".to_string())
+        );
+        assert_eq!(
+            resolve_substitutions("export X=$(brew --prefix nope)", &brew),
+            None
+        );
+        assert_eq!(resolve_substitutions("export A=$(date", &brew), None);
+        assert_eq!(
+            resolve_substitutions("export X=\"$HOME/$(brew --prefix golang)\"", &brew),
+            Some("export X=\"$HOME//opt/homebrew/opt/go\"".to_string())
+        );
+        assert_eq!(
+            resolve_substitutions("export X=\"\\$(brew --prefix golang)\"", &brew),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_nested_substitutions() {
+        // The outer command cannot be resolved without running `echo`
+        assert_eq!(
+            resolve_substitutions("export NVM_DIR=\"$(brew --prefix $(echo nvm))/nvm\"", &brew),
+            None
+        );
+        assert_eq!(
+            resolve_substitutions("export GOBIN=\"$(dirname $(brew --prefix golang))\"", &brew),
+            Some("export GOBIN=\"$(dirname /opt/homebrew/opt/go)\"".to_string())
+        );
+        assert_eq!(
+            resolve_substitutions(
+                "export A=\"$(echo \")\")\" B=$(brew --prefix golang)",
+                &brew
+            ),
+            Some("export A=\"$(echo \")\")\" B=/opt/homebrew/opt/go".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_command_only_runs_brew_prefix() {
+        assert_eq!(resolve_command("rm -rf /tmp/x"), None);
+        assert_eq!(resolve_command("brew install x"), None);
+        assert_eq!(resolve_command("brew --prefix a b"), None);
+    }
+
+    #[test]
+    fn test_lazy_nvm() {
+        let line = "[ -s \"$NVM_DIR/nvm.sh\" ] && \\. \"$NVM_DIR/nvm.sh\"";
+        let fixed = lazy_nvm(line, &|_| None).unwrap();
+        assert!(fixed.contains(
+            "__pzsh_lazy_nvm() {\n  unset -f nvm node npm npx __pzsh_lazy_nvm\n  [ -s \"$NVM_DIR/nvm.sh\" ] && \\. \"$NVM_DIR/nvm.sh\"\n}"
+        ));
+        assert!(fixed.contains("\nnode() { __pzsh_lazy_nvm; node \"$@\"; }"));
+
+        assert!(
+            lazy_nvm("  source ~/.nvm/nvm.sh", &|_| None)
+                .unwrap()
+                .contains("\n  nvm() {")
+        );
+        // Not a source line, or already lazy
+        assert!(lazy_nvm("export NVM_SH=nvm.sh", &|_| None).is_none());
+        let loader = fixed.lines().nth(1).unwrap();
+        assert!(lazy_nvm(loader, &|_| None).is_none());
+    }
+
+    #[test]
+    fn test_lazy_nvm_installer_line() {
+        // As written by the nvm install script
+        let content = "# shell setup\nexport NVM_DIR=\"$HOME/.nvm\"\n\
+            [ -s \"$NVM_DIR/nvm.sh\" ] && \\. \"$NVM_DIR/nvm.sh\"  # This loads nvm\n\
+            eval \"$(direnv hook bash)\"\n";
+        let issues = crate::cli::lint_config(content).issues;
+        let (fixed, applied) = apply_edits(content, &issues);
+        assert_eq!(applied, 1);
+        assert!(fixed.contains("\\. \"$NVM_DIR/nvm.sh\"  # This loads nvm\n}\n"));
+        assert_eq!(
+            check_syntax(Path::new(".bashrc"), LintMode::Shell, &fixed),
+            Ok(())
+        );
+
+        // Lines after the loader map back to their original line numbers
+        let origins = original_lines(content, &issues);
+        assert_eq!(origins.len(), fixed.lines().count());
+        let eval = fixed.lines().position(|l| l.starts_with("eval")).unwrap();
+        assert_eq!(origins[eval], 4);
+        assert!(origins[3..eval].iter().all(|&line| line == 3));
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        let bashrc = Path::new(".bashrc");
+        assert!(check_syntax(bashrc, LintMode::Shell, "f() { true; # }\n").is_err());
+        assert!(check_syntax(bashrc, LintMode::Shell, "f() { true; }\n").is_ok());
+        let pzshrc = Path::new(".pzshrc");
+        assert!(check_syntax(pzshrc, LintMode::Toml, "[env]\nA = \"x\"\n").is_ok());
+        assert!(check_syntax(pzshrc, LintMode::Toml, "[env\n").is_err());
+    }
+
+    #[test]
+    fn test_compinit_cached() {
+        let none = |_: &str| None;
+        assert_eq!(
+            compinit_cached("autoload -Uz compinit && compinit -i", &none).as_deref(),
+            Some("autoload -Uz compinit && compinit -C -i")
+        );
+        assert_eq!(
+            compinit_cached("compinit", &none).as_deref(),
+            Some("compinit -C")
+        );
+        assert_eq!(compinit_cached("compinit_hook", &none), None);
+    }
+
+    fn issue(line: usize, replacement: &str) -> LintIssue {
+        LintIssue {
+            rule: "test".to_string(),
+            severity: super::super::LintSeverity::Error,
+            message: String::new(),
+            line: Some(line),
+            column: None,
+            fix: None,
+            edit: Some(Edit {
+                line,
+                replacement: replacement.to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let content = "a\nb\nc\n";
+        let (fixed, applied) = apply_edits(content, &[issue(2, "B"), issue(2, "X"), issue(9, "?")]);
+        assert_eq!(fixed, "a\nB\nc\n");
+        assert_eq!(applied, 1);
+
+        let (fixed, _) = apply_edits("a\nb", &[issue(1, "1\n2")]);
+        assert_eq!(fixed, "1\n2\nb");
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n";
+        assert_eq!(
+            unified_diff(old, new, ".pzshrc"),
+            "--- .pzshrc\n+++ .pzshrc\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n"
+        );
+        assert!(unified_diff(old, old, ".pzshrc").is_empty());
+        assert_eq!(
+            unified_diff("", "a\n", "f"),
+            "--- f\n+++ f\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+    }
+
+    #[test]
+    fn test_write_with_backup() {
+        let dir = crate::test_util::temp_dir("fix");
+        let target = dir.join("pzshrc");
+        let link = dir.join(".pzshrc");
+        fs::write(&target, "old\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let backup = write_with_backup(&link, "new\n").unwrap();
+        assert_eq!(backup, fs::canonicalize(&dir).unwrap().join("pzshrc.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "old\n");
+        assert_eq!(fs::read_to_string(&link).unwrap(), "new\n");
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert!(!dir.join("pzshrc.pzsh-tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,624 @@
+//! Import an existing `.zshrc` or `.bashrc` into a `.pzshrc`
+//!
+//! Only statements with a pzsh equivalent are translated: `alias`,
+//! `export` and the oh-my-zsh `plugins=(...)` and `ZSH_THEME` settings.
+//! Everything else (functions, conditionals, `eval`, command
+//! substitutions) is listed in [`ImportResult::unsupported`] so that it can
+//! be moved by hand.
+
+use super::lint::code_part;
+use crate::config::{CompiledConfig, ShellTypeConfig, SourceConfig};
+use crate::parser::{ScriptCommand, Word, WordPart, parse_script};
+use crate::plugin::PluginManager;
+use crate::theme::ThemeRegistry;
+use std::fmt::Write;
+
+/// oh-my-zsh settings that have no pzsh equivalent
+const OMZ_SETTINGS: &[&str] = &[
+    "ZSH_CUSTOM",
+    "ZSH_CACHE_DIR",
+    "CASE_SENSITIVE",
+    "HYPHEN_INSENSITIVE",
+    "DISABLE_AUTO_UPDATE",
+    "DISABLE_UPDATE_PROMPT",
+    "UPDATE_ZSH_DAYS",
+    "DISABLE_MAGIC_FUNCTIONS",
+    "DISABLE_LS_COLORS",
+    "DISABLE_AUTO_TITLE",
+    "ENABLE_CORRECTION",
+    "COMPLETION_WAITING_DOTS",
+    "DISABLE_UNTRACKED_FILES_DIRTY",
+    "HIST_STAMPS",
+    "ZSH_THEME_RANDOM_CANDIDATES",
+];
+
+/// Command position keywords that open and close blocks
+const BLOCK_OPEN: &[&str] = &["if", "case", "for", "select", "while", "until", "{"];
+const BLOCK_CLOSE: &[&str] = &["fi", "esac", "done", "}"];
+
+/// Line of the rc file that was not imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    /// Line number (1-based)
+    pub line: usize,
+    /// Source text (first line of a block)
+    pub text: String,
+    /// Why it was not imported
+    pub reason: String,
+}
+
+/// Imported configuration
+#[derive(Debug)]
+pub struct ImportResult {
+    pub config: SourceConfig,
+    /// Statements left out, in source order
+    pub unsupported: Vec<Unsupported>,
+}
+
+impl ImportResult {
+    /// Render the configuration as a `.pzshrc`
+    ///
+    /// Unsupported lines are kept as comments at the end of the file.
+    #[must_use]
+    pub fn to_toml(&self, source: &str) -> String {
+        let config = &self.config;
+        let shell = match config.pzsh.shell {
+            ShellTypeConfig::Zsh => "zsh",
+            ShellTypeConfig::Bash => "bash",
+            ShellTypeConfig::Fish => "fish",
+            ShellTypeConfig::Nushell => "nushell",
+            ShellTypeConfig::Sh => "sh",
+        };
+        let mut out = format!(
+            "# pzsh configuration imported from {source}\n\n[pzsh]\nversion = {}\nshell = \"{shell}\"\n",
+            toml_string(&config.pzsh.version)
+        );
+
+        if let Some(theme) = &config.prompt.theme {
+            let _ = write!(out, "\n[prompt]\ntheme = {}\n", toml_string(theme));
+        }
+
+        for (section, values) in [("aliases", &config.aliases), ("env", &config.env)] {
+            let _ = write!(out, "\n[{section}]\n");
+            let mut values: Vec<_> = values.iter().collect();
+            values.sort();
+            for (key, value) in values {
+                let _ = writeln!(out, "{} = {}", toml_key(key), toml_string(value));
+            }
+        }
+
+        let enabled: Vec<String> = config
+            .plugins
+            .enabled
+            .iter()
+            .map(|p| toml_string(p))
+            .collect();
+        let _ = write!(out, "\n[plugins]\nenabled = [{}]\n", enabled.join(", "));
+
+        if !self.unsupported.is_empty() {
+            out.push_str("\n# Not imported from ");
+            out.push_str(source);
+            out.push_str(":\n");
+            for item in &self.unsupported {
+                let _ = writeln!(out, "# line {}: {} ({})", item.line, item.text, item.reason);
+            }
+        }
+        out
+    }
+
+    /// Summary of what was imported and what was left out
+    #[must_use]
+    pub fn format(&self) -> String {
+        let config = &self.config;
+        let mut out = format!(
+            "Imported {} aliases, {} env vars, {} plugins",
+            config.aliases.len(),
+            config.env.len(),
+            config.plugins.enabled.len()
+        );
+        if let Some(theme) = &config.prompt.theme {
+            let _ = write!(out, ", theme {theme}");
+        }
+        out.push('\n');
+        if !self.unsupported.is_empty() {
+            let _ = writeln!(out, "\nNot imported ({}):", self.unsupported.len());
+            for item in &self.unsupported {
+                let _ = writeln!(out, "  line {}: {}", item.line, item.text);
+                let _ = writeln!(out, "    {}", item.reason);
+            }
+        }
+        out
+    }
+}
+
+/// Import a shell startup file
+#[must_use]
+pub fn import_rc(content: &str, shell: ShellTypeConfig) -> ImportResult {
+    let mut importer = Importer {
+        result: ImportResult {
+            config: SourceConfig::default(),
+            unsupported: Vec::new(),
+        },
+        plugins: PluginManager::new(),
+        themes: ThemeRegistry::new(),
+    };
+    importer.result.config.pzsh.shell = shell;
+
+    let lines: Vec<&str> = content.lines().collect();
+    let mut next = 0;
+    while next < lines.len() {
+        let start = next;
+        let mut text = lines[next].to_string();
+        next += 1;
+        while text.ends_with('\\') && next < lines.len() {
+            text.pop();
+            text.push_str(lines[next]);
+            next += 1;
+        }
+        let code = code_part(&text).trim();
+        if code.is_empty() {
+            continue;
+        }
+
+        // oh-my-zsh plugin array, possibly over several lines; each name is
+        // reported on its own line
+        if let Some(rest) = code.strip_prefix("plugins=(") {
+            let (mut line, mut names) = (start, rest);
+            loop {
+                let closed = names.split_once(')').map(|(inside, _)| inside);
+                importer.plugins(line + 1, closed.unwrap_or(names));
+                if closed.is_some() || next >= lines.len() {
+                    break;
+                }
+                (line, names) = (next, code_part(lines[next]));
+                next += 1;
+            }
+            continue;
+        }
+
+        let mut depth = block_depth(code);
+        if depth > 0 {
+            while depth > 0 && next < lines.len() {
+                depth += block_depth(code_part(lines[next]));
+                next += 1;
+            }
+            let first = code.split_whitespace().next().unwrap_or_default();
+            let reason = if first.contains("()") || first == "function" {
+                "function definition"
+            } else {
+                "if, case, for or while block"
+            };
+            let text = if next - start > 1 {
+                format!("{code} ...")
+            } else {
+                code.to_string()
+            };
+            importer.unsupported(start + 1, &text, reason);
+            continue;
+        }
+
+        importer.line(start + 1, code);
+    }
+    importer.result
+}
+
+/// Change in block nesting over a line of code
+///
+/// Counts keywords in command position and standalone braces; quoted text
+/// is skipped.
+fn block_depth(code: &str) -> i32 {
+    let mut depth = 0;
+    let mut command_position = true;
+    for token in shell_tokens(code) {
+        let (word, separator) = token
+            .strip_suffix(';')
+            .map_or((token.as_str(), false), |w| (w, true));
+        if word == "{" || word.ends_with("(){") {
+            depth += 1;
+        } else if word == "}" {
+            depth -= 1;
+        } else if command_position && BLOCK_OPEN.contains(&word) {
+            depth += 1;
+        } else if command_position && BLOCK_CLOSE.contains(&word) {
+            depth -= 1;
+        }
+        command_position = separator
+            || matches!(
+                word,
+                "&&" | "||" | "|" | ";" | "then" | "do" | "else" | "{" | "!"
+            );
+    }
+    depth
+}
+
+/// Whitespace-separated tokens, keeping quoted text in one token
+fn shell_tokens(code: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in code.chars() {
+        match (quote, c) {
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (Some(q), _) if q == c => {
+                quote = None;
+                current.push(c);
+            }
+            (None, c) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Translation of one statement
+enum Action {
+    Alias(String, String),
+    Env(String, String),
+    Theme(Option<String>),
+    /// oh-my-zsh machinery that pzsh replaces
+    Drop,
+}
+
+struct Importer {
+    result: ImportResult,
+    plugins: PluginManager,
+    themes: ThemeRegistry,
+}
+
+impl Importer {
+    fn unsupported(&mut self, line: usize, text: &str, reason: &str) {
+        self.result.unsupported.push(Unsupported {
+            line,
+            text: text.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Map oh-my-zsh plugin names onto built-in plugins
+    fn plugins(&mut self, line: usize, names: &str) {
+        for name in names.split_whitespace() {
+            let name = name.trim_matches(|c| c == '"' || c == '\'');
+            if self.plugins.state(name).is_some() {
+                let enabled = &mut self.result.config.plugins.enabled;
+                if !enabled.iter().any(|p| p == name) {
+                    enabled.push(name.to_string());
+                }
+            } else {
+                self.unsupported(
+                    line,
+                    &format!("plugins=({name})"),
+                    "no built-in pzsh plugin with this name",
+                );
+            }
+        }
+    }
+
+    /// Translate a line of simple commands, all or nothing
+    fn line(&mut self, line: usize, code: &str) {
+        let script = parse_script(code);
+        if !script.unparsed.is_empty() {
+            self.unsupported(line, code, "could not be parsed");
+            return;
+        }
+        let actions: Result<Vec<Vec<Action>>, String> =
+            script.commands.iter().map(|c| self.translate(c)).collect();
+        match actions {
+            Ok(actions) => {
+                for action in actions.into_iter().flatten() {
+                    let config = &mut self.result.config;
+                    match action {
+                        Action::Alias(name, value) => {
+                            config.aliases.insert(name, value);
+                        }
+                        Action::Env(name, value) => {
+                            config.env.insert(name, value);
+                        }
+                        Action::Theme(theme) => config.prompt.theme = theme,
+                        Action::Drop => {}
+                    }
+                }
+            }
+            Err(reason) => self.unsupported(line, code, &reason),
+        }
+    }
+
+    fn translate(&self, command: &ScriptCommand) -> Result<Vec<Action>, String> {
+        if command.in_function {
+            return Err("function definition".to_string());
+        }
+        if !command.redirects.is_empty() {
+            return Err("command with redirections".to_string());
+        }
+        let Some(index) = command.command else {
+            return command
+                .words
+                .iter()
+                .map(|word| {
+                    let (name, value) = split_assignment(word).ok_or("not a simple command")?;
+                    self.assignment(&name, &value)
+                })
+                .collect();
+        };
+        if index > 0 {
+            return Err("command with variable assignments".to_string());
+        }
+
+        let name = command.name().unwrap_or_default();
+        let args = command.args();
+        match name.as_str() {
+            "alias" | "export" => args
+                .iter()
+                .map(|arg| {
+                    if arg.as_literal().is_some_and(|a| a.starts_with('-')) {
+                        return Err(format!("{name} options are not supported"));
+                    }
+                    let (key, value) =
+                        split_assignment(arg).ok_or_else(|| format!("{name} without a value"))?;
+                    if name == "alias" {
+                        Ok(Action::Alias(key.clone(), expand(&key, &value, false)?))
+                    } else if key == "ZSH" {
+                        Ok(Action::Drop)
+                    } else {
+                        Ok(Action::Env(key.clone(), expand(&key, &value, true)?))
+                    }
+                })
+                .collect(),
+            "source" | "."
+                if args
+                    .first()
+                    .is_some_and(|path| path.to_string().ends_with("oh-my-zsh.sh")) =>
+            {
+                Ok(vec![Action::Drop])
+            }
+            _ => Err("not an alias or export; move it to a shell file".to_string()),
+        }
+    }
+
+    /// Bare `NAME=value`: only the oh-my-zsh variables are understood
+    fn assignment(&self, name: &str, value: &Word) -> Result<Action, String> {
+        match name {
+            "ZSH" => Ok(Action::Drop),
+            "ZSH_THEME" => match value.as_literal().as_deref() {
+                Some("") => Ok(Action::Theme(None)),
+                Some(theme) if self.themes.get(theme).is_some() => {
+                    Ok(Action::Theme(Some(theme.to_string())))
+                }
+                _ => Err(format!(
+                    "no built-in pzsh theme with this name (available: {})",
+                    self.theme_names()
+                )),
+            },
+            _ if OMZ_SETTINGS.contains(&name) => {
+                Err("oh-my-zsh setting with no pzsh equivalent".to_string())
+            }
+            _ => Err("shell variable; use `export` to import it into [env]".to_string()),
+        }
+    }
+
+    fn theme_names(&self) -> String {
+        let mut names = self.themes.list();
+        names.sort_unstable();
+        names.join(", ")
+    }
+}
+
+/// `NAME=value` word split into the name and the value word
+///
+/// Names are checked by the caller: alias names are freer than variable
+/// names.
+fn split_assignment(word: &Word) -> Option<(String, Word)> {
+    let Some(WordPart::Literal(first)) = word.parts.first() else {
+        return None;
+    };
+    let (name, rest) = first.split_once('=')?;
+    if name.is_empty() {
+        return None;
+    }
+    let mut parts = Vec::with_capacity(word.parts.len());
+    if !rest.is_empty() {
+        parts.push(WordPart::Literal(rest.to_string()));
+    }
+    parts.extend(word.parts[1..].iter().cloned());
+    Some((
+        name.to_string(),
+        Word {
+            parts,
+            offset: word.offset,
+        },
+    ))
+}
+
+/// Value of an alias or env var as pzsh writes it
+///
+/// Env values are exported in double quotes, so a leading `~` becomes
+/// `$HOME` and literal `$` cannot be represented.
+fn expand(name: &str, value: &Word, env: bool) -> Result<String, String> {
+    let valid = if env {
+        name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    } else {
+        !name.contains(|c: char| "/$`'\"\\".contains(c) || c.is_whitespace())
+    };
+    if !valid {
+        return Err(format!(
+            "invalid {} name",
+            if env { "variable" } else { "alias" }
+        ));
+    }
+    let mut out = String::new();
+    for (i, part) in value.parts.iter().enumerate() {
+        match part {
+            WordPart::Literal(text) | WordPart::Quoted(text) => {
+                if env && text.contains(['$', '`']) {
+                    return Err("literal `$` cannot be exported by pzsh".to_string());
+                }
+                match (part, text.strip_prefix('~')) {
+                    (WordPart::Literal(_), Some(rest))
+                        if env && i == 0 && (rest.is_empty() || rest.starts_with('/')) =>
+                    {
+                        out.push_str("$HOME");
+                        out.push_str(rest);
+                    }
+                    _ => out.push_str(text),
+                }
+            }
+            WordPart::Var(var) => {
+                let _ = write!(out, "${{{var}}}");
+            }
+            WordPart::CommandSubst(_) | WordPart::Arith(_) => {
+                return Err(
+                    "command substitution runs at every startup; hardcode its output".to_string(),
+                );
+            }
+        }
+    }
+    CompiledConfig::check_forbidden_patterns(name, &out).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+/// TOML basic string
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+/// TOML key, quoted unless bare
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZSHRC: &str = r#"# Path to your oh-my-zsh installation.
+export ZSH="$HOME/.oh-my-zsh"
+ZSH_THEME="agnoster"
+DISABLE_AUTO_UPDATE="true"
+plugins=(
+  git
+  docker  # containers
+  zsh-autosuggestions
+)
+source $ZSH/oh-my-zsh.sh
+
+export EDITOR=vim PAGER='less -R'
+export GOPATH=~/go
+export PATH="$GOPATH/bin:$PATH"
+export GOROOT="$(brew --prefix go)/libexec"
+alias ll='ls -la' gs="git status"
+alias -g G='| grep'
+HISTSIZE=10000
+
+mkcd() {
+  mkdir -p "$1" && cd "$1"
+}
+if [ -f ~/.work ]; then
+  source ~/.work
+fi
+eval "$(starship init zsh)"
+"#;
+
+    fn unsupported_lines(result: &ImportResult) -> Vec<usize> {
+        result.unsupported.iter().map(|u| u.line).collect()
+    }
+
+    #[test]
+    fn test_import_oh_my_zsh() {
+        let result = import_rc(ZSHRC, ShellTypeConfig::Zsh);
+        let config = &result.config;
+        assert_eq!(config.prompt.theme.as_deref(), Some("agnoster"));
+        assert_eq!(config.plugins.enabled, ["git", "docker"]);
+        assert_eq!(config.aliases["ll"], "ls -la");
+        assert_eq!(config.aliases["gs"], "git status");
+        assert_eq!(config.env["EDITOR"], "vim");
+        assert_eq!(config.env["PAGER"], "less -R");
+        assert_eq!(config.env["GOPATH"], "$HOME/go");
+        assert_eq!(config.env["PATH"], "${GOPATH}/bin:${PATH}");
+        // ZSH itself is oh-my-zsh machinery
+        assert!(!config.env.contains_key("ZSH"));
+
+        assert_eq!(unsupported_lines(&result), [4, 8, 15, 17, 18, 20, 23, 26]);
+        let reasons: Vec<&str> = result
+            .unsupported
+            .iter()
+            .map(|u| u.reason.as_str())
+            .collect();
+        assert!(reasons[1].contains("no built-in pzsh plugin"));
+        assert!(reasons[2].contains("command substitution"));
+        assert_eq!(reasons[5], "function definition");
+        assert_eq!(result.unsupported[5].text, "mkcd() { ...");
+        assert_eq!(reasons[6], "if, case, for or while block");
+    }
+
+    #[test]
+    fn test_imported_config_compiles() {
+        let result = import_rc(ZSHRC, ShellTypeConfig::Zsh);
+        let toml = result.to_toml("~/.zshrc");
+        let compiled = CompiledConfig::from_toml(&toml).unwrap();
+        assert_eq!(compiled.get_alias("ll").unwrap(), "ls -la");
+        assert_eq!(compiled.prompt_theme.as_deref(), Some("agnoster"));
+        assert!(toml.contains("version = \"0.1.0\""));
+        assert!(toml.contains("\n# Not imported from ~/.zshrc:\n"));
+        assert!(toml.contains("# line 26: eval \"$(starship init zsh)\""));
+        // Lines are sorted for stable output
+        assert!(toml.find("\ngs = ").unwrap() < toml.find("\nll = ").unwrap());
+    }
+
+    #[test]
+    fn test_import_bashrc() {
+        let bashrc = "alias ..='cd ..'\nexport HISTCONTROL=ignoreboth\nshopt -s histappend\nexport LITERAL='$5'\n";
+        let result = import_rc(bashrc, ShellTypeConfig::Bash);
+        assert_eq!(result.config.aliases[".."], "cd ..");
+        assert_eq!(result.config.env["HISTCONTROL"], "ignoreboth");
+        assert_eq!(unsupported_lines(&result), [3, 4]);
+
+        let toml = result.to_toml(".bashrc");
+        assert!(toml.contains("shell = \"bash\""));
+        assert!(toml.contains("\"..\" = \"cd ..\""));
+        assert!(CompiledConfig::from_toml(&toml).is_ok());
+    }
+
+    #[test]
+    fn test_import_theme_and_line_atomicity() {
+        let result = import_rc(
+            "ZSH_THEME=\"powerlevel10k/powerlevel10k\"\nalias a=b; echo hi\n",
+            ShellTypeConfig::Zsh,
+        );
+        assert_eq!(result.config.prompt.theme, None);
+        let result_fn = import_rc("nvm() { :; }\n", ShellTypeConfig::Zsh);
+        assert_eq!(result_fn.unsupported[0].reason, "function definition");
+        // Nothing from a partly translatable line is imported
+        assert!(result.config.aliases.is_empty());
+        assert_eq!(unsupported_lines(&result), [1, 2]);
+        assert!(result.unsupported[0].reason.contains("robbyrussell"));
+    }
+
+    #[test]
+    fn test_block_depth() {
+        assert_eq!(block_depth("if [ -f x ]; then"), 1);
+        assert_eq!(block_depth("if x; then y; fi"), 0);
+        assert_eq!(block_depth("function lazy {"), 1);
+        assert_eq!(block_depth("for f in a b; do"), 1);
+        assert_eq!(block_depth("alias x='if'"), 0);
+        assert_eq!(block_depth("echo done"), 0);
+        assert_eq!(block_depth("}"), -1);
+    }
+}
@@ -0,0 +1,1352 @@
+//! Configuration linter
+//!
+//! The file name picks the [`LintMode`]. pzsh configuration (`.pzshrc`,
+//! `*.toml`) is checked value by value: keys and comments are never
+//! flagged. Anything else is treated as a shell startup file (`.zshrc`,
+//! `.bashrc`) and split into commands with [`parse_script`]; rules then
+//! match command substitutions and commands rather than raw text. Lines the
+//! shell parser cannot handle fall back to pattern matching.
+
+use super::fix::{self, Edit};
+use super::rules::{CONFIG_RULE, IGNORE_RULE, LintRules, SYNTAX_RULE, Suppressions};
+use crate::config::{LintSection, line_column};
+use crate::parser::{ScriptCommand, Word, WordPart, parse_script};
+use std::ops::Range;
+use std::path::Path;
+
+/// How a file is read by the linter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintMode {
+    /// pzsh configuration: only TOML string values are checked
+    Toml,
+    /// Shell startup file, split into commands
+    Shell,
+}
+
+impl LintMode {
+    /// TOML for `.pzshrc` and `*.toml` files, shell for anything else
+    #[must_use]
+    pub fn for_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if name.ends_with("pzshrc") || path.extension().is_some_and(|ext| ext == "toml") {
+            Self::Toml
+        } else {
+            Self::Shell
+        }
+    }
+}
+
+/// Lint result
+#[derive(Debug)]
+pub struct LintResult {
+    pub issues: Vec<LintIssue>,
+}
+
+#[derive(Debug)]
+pub struct LintIssue {
+    /// Rule id (`brew-prefix`, `eval`, ...)
+    pub rule: String,
+    pub severity: LintSeverity,
+    pub message: String,
+    /// Line number (1-based)
+    pub line: Option<usize>,
+    /// Column in characters (1-based)
+    pub column: Option<usize>,
+    pub fix: Option<String>,
+    /// Machine-applicable fix, applied by `pzsh fix`
+    pub edit: Option<Edit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl LintSeverity {
+    /// Lowercase name (`error`, `warning`, `info`)
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+        }
+    }
+
+    /// SARIF result level
+    const fn sarif_level(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "note",
+        }
+    }
+}
+
+impl LintResult {
+    /// Check if lint passed (no errors)
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.errors() == 0
+    }
+
+    /// Check if lint passed with at most `max_warnings` warnings
+    /// (`None` allows any number)
+    #[must_use]
+    pub fn passed_with(&self, max_warnings: Option<usize>) -> bool {
+        self.passed() && max_warnings.is_none_or(|max| self.warnings() <= max)
+    }
+
+    /// Number of errors
+    #[must_use]
+    pub fn errors(&self) -> usize {
+        self.count(LintSeverity::Error)
+    }
+
+    /// Number of warnings
+    #[must_use]
+    pub fn warnings(&self) -> usize {
+        self.count(LintSeverity::Warning)
+    }
+
+    fn count(&self, severity: LintSeverity) -> usize {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == severity)
+            .count()
+    }
+
+    /// Format as string
+    #[must_use]
+    pub fn format(&self) -> String {
+        if self.issues.is_empty() {
+            return "✓ 0 issues found".to_string();
+        }
+
+        let mut output = String::new();
+        for issue in &self.issues {
+            let line_info = match (issue.line, issue.column) {
+                (Some(l), Some(c)) => format!(" (line {}, column {})", l, c),
+                (Some(l), None) => format!(" (line {})", l),
+                _ => String::new(),
+            };
+
+            output.push_str(&format!(
+                "[{}]{}: {} ({})\n",
+                issue.severity.as_str(),
+                line_info,
+                issue.message,
+                issue.rule
+            ));
+
+            if let Some(fix) = &issue.fix {
+                let fixable = if issue.edit.is_some() {
+                    " (pzsh fix)"
+                } else {
+                    ""
+                };
+                output.push_str(&format!("  fix: {}{}\n", fix, fixable));
+            }
+        }
+
+        output.push_str(&format!(
+            "\n{} errors, {} warnings",
+            self.errors(),
+            self.warnings()
+        ));
+
+        output
+    }
+
+    /// Format as JSON: the issues of `path` and their counts
+    #[must_use]
+    pub fn to_json(&self, path: &str) -> String {
+        let issues: Vec<_> = self
+            .issues
+            .iter()
+            .map(|issue| {
+                serde_json::json!({
+                    "rule": issue.rule,
+                    "severity": issue.severity.as_str(),
+                    "message": issue.message,
+                    "line": issue.line,
+                    "column": issue.column,
+                    "fix": issue.fix,
+                    "fixable": issue.edit.is_some(),
+                })
+            })
+            .collect();
+        let output = serde_json::json!({
+            "file": path,
+            "issues": issues,
+            "errors": self.errors(),
+            "warnings": self.warnings(),
+        });
+        serde_json::to_string_pretty(&output).unwrap_or_default()
+    }
+
+    /// Format as a SARIF 2.1.0 log for code-scanning tools
+    ///
+    /// Machine-applicable edits become SARIF fixes that replace the whole
+    /// line; `content` is the linted text, used for the replaced region.
+    #[must_use]
+    pub fn to_sarif(&self, path: &str, content: &str) -> String {
+        let mut rules: Vec<serde_json::Value> = Vec::new();
+        let mut rule_ids: Vec<&str> = Vec::new();
+        for issue in &self.issues {
+            if !rule_ids.contains(&issue.rule.as_str()) {
+                rule_ids.push(&issue.rule);
+                let mut rule = serde_json::json!({
+                    "id": issue.rule,
+                    "shortDescription": { "text": issue.message },
+                    "defaultConfiguration": { "level": issue.severity.sarif_level() },
+                });
+                if let Some(fix) = &issue.fix {
+                    rule["help"] = serde_json::json!({ "text": fix });
+                }
+                rules.push(rule);
+            }
+        }
+
+        let results: Vec<_> = self
+            .issues
+            .iter()
+            .map(|issue| {
+                let mut region = serde_json::Map::new();
+                if let Some(line) = issue.line {
+                    region.insert("startLine".into(), line.into());
+                }
+                if let Some(column) = issue.column {
+                    region.insert("startColumn".into(), column.into());
+                }
+                let mut location = serde_json::json!({ "artifactLocation": { "uri": path } });
+                if !region.is_empty() {
+                    location["region"] = region.into();
+                }
+                let mut result = serde_json::json!({
+                    "ruleId": issue.rule,
+                    "ruleIndex": rule_ids.iter().position(|id| *id == issue.rule),
+                    "level": issue.severity.sarif_level(),
+                    "message": { "text": issue.message },
+                    "locations": [{ "physicalLocation": location }],
+                });
+                if let Some(edit) = &issue.edit {
+                    let old = content.lines().nth(edit.line - 1).unwrap_or_default();
+                    result["fixes"] = serde_json::json!([{
+                        "description": { "text": issue.fix.as_deref().unwrap_or(&issue.message) },
+                        "artifactChanges": [{
+                            "artifactLocation": { "uri": path },
+                            "replacements": [{
+                                "deletedRegion": {
+                                    "startLine": edit.line,
+                                    "startColumn": 1,
+                                    "endLine": edit.line,
+                                    "endColumn": old.chars().count() + 1,
+                                },
+                                "insertedContent": { "text": edit.replacement },
+                            }],
+                        }],
+                    }]);
+                }
+                result
+            })
+            .collect();
+
+        let output = serde_json::json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "pzsh",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                        "rules": rules,
+                    },
+                },
+                "results": results,
+            }],
+        });
+        serde_json::to_string_pretty(&output).unwrap_or_default()
+    }
+}
+
+/// Rewrites a matching line; `resolve` runs a command at fix time
+type EditFn = fn(&str, &dyn Fn(&str) -> Option<String>) -> Option<String>;
+
+/// What a rule matches
+#[derive(Debug, Clone, Copy)]
+enum Check {
+    /// Command substitution (`$()` or backticks when `backtick` is set)
+    /// whose command starts with `prefix`
+    Subst {
+        backtick: Option<bool>,
+        prefix: &'static str,
+    },
+    /// Command by name
+    Command(&'static str),
+    /// Command with a first argument (`conda init`)
+    Subcommand(&'static str, &'static str),
+    /// `source` or `.` of a path ending in the suffix
+    Source(&'static str),
+    /// Command called without a short flag (`compinit` without `-C`)
+    MissingFlag(&'static str, char),
+}
+
+struct LintRule {
+    /// Stable rule id, used in JSON and SARIF output
+    id: &'static str,
+    check: Check,
+    /// Text matched on lines the shell parser cannot handle
+    pattern: &'static str,
+    /// Also applies inside function bodies, which do not run at startup
+    in_functions: bool,
+    severity: LintSeverity,
+    message: &'static str,
+    fix: Option<&'static str>,
+    edit: Option<EditFn>,
+}
+
+/// Rules in priority order: a substitution is reported by the first
+/// matching rule only
+const LINT_RULES: &[LintRule] = &[
+    LintRule {
+        id: "brew-prefix",
+        check: Check::Subst {
+            backtick: None,
+            prefix: "brew --prefix",
+        },
+        pattern: "brew --prefix",
+        in_functions: false,
+        severity: LintSeverity::Error,
+        message: "brew --prefix is slow (50-100ms)",
+        fix: Some("run `brew --prefix <formula>` once and hardcode the path"),
+        edit: Some(fix::resolve_substitutions),
+    },
+    LintRule {
+        id: "command-substitution",
+        check: Check::Subst {
+            backtick: Some(false),
+            prefix: "",
+        },
+        pattern: "$(",
+        in_functions: false,
+        severity: LintSeverity::Error,
+        message: "subprocess call $() not allowed at startup",
+        fix: Some("use pre-resolved path instead"),
+        edit: None,
+    },
+    LintRule {
+        id: "backticks",
+        check: Check::Subst {
+            backtick: Some(true),
+            prefix: "",
+        },
+        pattern: "`",
+        in_functions: false,
+        severity: LintSeverity::Error,
+        message: "backtick substitution not allowed",
+        fix: Some("use pre-resolved value instead"),
+        edit: None,
+    },
+    LintRule {
+        id: "eval",
+        check: Check::Command("eval"),
+        pattern: "eval ",
+        in_functions: true,
+        severity: LintSeverity::Error,
+        message: "eval not allowed for safety",
+        fix: None,
+        edit: None,
+    },
+    LintRule {
+        id: "oh-my-zsh",
+        check: Check::Source("oh-my-zsh.sh"),
+        pattern: "oh-my-zsh",
+        in_functions: false,
+        severity: LintSeverity::Error,
+        message: "oh-my-zsh is slow (500-2000ms startup)",
+        fix: Some("remove oh-my-zsh, use pzsh plugins instead"),
+        edit: None,
+    },
+    LintRule {
+        id: "nvm",
+        check: Check::Source("nvm.sh"),
+        pattern: "nvm.sh",
+        in_functions: false,
+        severity: LintSeverity::Warning,
+        message: "NVM adds 200-500ms to startup",
+        fix: Some("use fnm or volta instead, or lazy-load NVM"),
+        edit: Some(fix::lazy_nvm),
+    },
+    LintRule {
+        id: "conda-init",
+        check: Check::Subcommand("conda", "init"),
+        pattern: "conda init",
+        in_functions: false,
+        severity: LintSeverity::Warning,
+        message: "conda init adds 200-400ms to startup",
+        fix: Some("lazy-load conda or use mamba"),
+        edit: None,
+    },
+    LintRule {
+        id: "conda-sh",
+        check: Check::Source("conda.sh"),
+        pattern: "conda.sh",
+        in_functions: false,
+        severity: LintSeverity::Warning,
+        message: "conda init adds 200-400ms to startup",
+        fix: Some("lazy-load conda or use mamba"),
+        edit: None,
+    },
+    LintRule {
+        id: "compinit-uncached",
+        check: Check::MissingFlag("compinit", 'C'),
+        pattern: "compinit",
+        in_functions: false,
+        severity: LintSeverity::Warning,
+        message: "compinit without -C rescans completion files (20-100ms)",
+        fix: Some("use `compinit -C` to trust the existing dump"),
+        edit: Some(fix::compinit_cached),
+    },
+];
+
+/// Ids of the built-in rules
+pub(super) fn builtin_rule_ids() -> impl Iterator<Item = &'static str> {
+    LINT_RULES.iter().map(|rule| rule.id)
+}
+
+/// Lint a shell startup file
+///
+/// Edits that need a command's output (`brew --prefix`) are left out; see
+/// [`lint_config_with_edits`].
+pub fn lint_config(content: &str) -> LintResult {
+    lint_config_with_edits(content, &|_| None)
+}
+
+/// Lint a shell startup file, resolving commands for edits with `resolve`
+/// (usually [`super::resolve_command`])
+pub fn lint_config_with_edits(
+    content: &str,
+    resolve: &dyn Fn(&str) -> Option<String>,
+) -> LintResult {
+    lint_config_with(content, LintMode::Shell, LintSection::default(), resolve)
+}
+
+/// Lint configuration content with custom rules and severity overrides
+/// (usually from `lint.toml`)
+///
+/// In [`LintMode::Toml`], the `[lint]` section of `content` is merged into
+/// `section`; problems with it are reported as `lint-config` issues and
+/// invalid TOML as a `toml-syntax` issue.
+pub fn lint_config_with(
+    content: &str,
+    mode: LintMode,
+    mut section: LintSection,
+    resolve: &dyn Fn(&str) -> Option<String>,
+) -> LintResult {
+    #[derive(serde::Deserialize)]
+    struct Inline {
+        #[serde(default)]
+        lint: LintSection,
+    }
+
+    let mut syntax_error = None;
+    let table = match mode {
+        LintMode::Toml => toml::de::DeTable::parse(content)
+            .map_err(|e| {
+                let offset = e.span().map_or(0, |span| span.start);
+                syntax_error = Some((offset, e.message().to_string()));
+            })
+            .ok(),
+        LintMode::Shell => None,
+    };
+    let mut config_error = None;
+    if let Some(table) = &table {
+        let offset = table.get_ref().get("lint").map_or(0, |v| v.span().start);
+        match toml::from_str::<Inline>(content) {
+            Ok(inline) => section.merge(inline.lint),
+            Err(e) => config_error = Some((offset, e.message().to_string())),
+        }
+    }
+    let rules = LintRules::new(&section).unwrap_or_else(|e| {
+        config_error.get_or_insert_with(|| (0, e.to_string()));
+        LintRules::default()
+    });
+
+    let mut linter = Linter {
+        content,
+        resolve,
+        rules: &rules,
+        issues: Vec::new(),
+    };
+    if let Some(table) = &table {
+        for (key, value) in table.get_ref() {
+            if key.get_ref() != "lint" {
+                linter.toml_value(value);
+            }
+        }
+    } else if mode == LintMode::Shell {
+        linter.shell(content, 0..content.len(), false);
+        let mut start = 0;
+        for line in content.split_inclusive('\n') {
+            let code = code_part(line.trim_end_matches('\n'));
+            linter.custom(code, start);
+            start += line.len();
+        }
+    }
+
+    let suppressions = Suppressions::parse(content, &rules);
+    let mut issues: Vec<LintIssue> = linter
+        .issues
+        .into_iter()
+        .filter(|issue| {
+            !issue
+                .line
+                .is_some_and(|l| suppressions.contains(l, &issue.rule))
+        })
+        .collect();
+    let errors = [(SYNTAX_RULE, syntax_error), (CONFIG_RULE, config_error)];
+    for (rule, (offset, message)) in errors
+        .into_iter()
+        .filter_map(|(rule, error)| Some((rule, error?)))
+    {
+        let (line, column) = line_column(content, offset);
+        issues.push(LintIssue {
+            rule: rule.to_string(),
+            severity: LintSeverity::Error,
+            message,
+            line: Some(line),
+            column: Some(column),
+            fix: None,
+            edit: None,
+        });
+    }
+    for (line, column, message) in suppressions.problems {
+        issues.push(LintIssue {
+            rule: IGNORE_RULE.to_string(),
+            severity: LintSeverity::Error,
+            message,
+            line: Some(line),
+            column: Some(column),
+            fix: None,
+            edit: None,
+        });
+    }
+    issues.sort_by_key(|issue| (issue.line, issue.column));
+    LintResult { issues }
+}
+
+struct Linter<'a> {
+    content: &'a str,
+    resolve: &'a dyn Fn(&str) -> Option<String>,
+    rules: &'a LintRules,
+    issues: Vec<LintIssue>,
+}
+
+impl Linter<'_> {
+    /// Lint shell code `source`, found at `span` of the file
+    ///
+    /// A TOML value (`in_toml`) is only checked for substitutions and
+    /// commands. When `source` is a decoded TOML string its offsets may not
+    /// match the file; positions are then looked up by pattern within `span`.
+    fn shell(&mut self, source: &str, span: Range<usize>, in_toml: bool) {
+        let exact = &self.content[span.clone()] == source;
+        let position = |offset: usize, pattern: &str| {
+            if exact {
+                span.start + offset
+            } else {
+                self.content[span.clone()]
+                    .find(pattern)
+                    .map_or(span.start, |i| span.start + i)
+            }
+        };
+
+        let script = parse_script(source);
+        let mut found: Vec<(usize, &'static LintRule)> = Vec::new();
+        for command in &script.commands {
+            let words = command
+                .words
+                .iter()
+                .chain(command.redirects.iter().map(|r| &r.target));
+            for word in words {
+                for (offset, backtick, inner) in substitutions(source, word) {
+                    let rule = LINT_RULES.iter().find(|rule| match rule.check {
+                        Check::Subst {
+                            backtick: b,
+                            prefix,
+                        } => {
+                            b.is_none_or(|b| b == backtick)
+                                && inner.trim_start().starts_with(prefix)
+                        }
+                        _ => false,
+                    });
+                    if let Some(rule) = rule.filter(|r| r.in_functions || !command.in_function) {
+                        let marker = if backtick { "`" } else { "$(" };
+                        found.push((position(offset, marker), rule));
+                    }
+                }
+            }
+
+            for rule in LINT_RULES {
+                if (command.in_function && !rule.in_functions)
+                    || (in_toml && !matches!(rule.check, Check::Command(_)))
+                {
+                    continue;
+                }
+                if matches_command(rule.check, command) {
+                    let name = &command.words[command.command.unwrap_or(0)];
+                    found.push((position(name.offset, rule.pattern.trim()), rule));
+                }
+            }
+        }
+
+        // Lines the parser rejected
+        for range in &script.unparsed {
+            let line = &source[range.clone()];
+            for rule in LINT_RULES {
+                if let Some(i) = line.find(rule.pattern) {
+                    found.push((position(range.start + i, rule.pattern), rule));
+                }
+            }
+        }
+
+        for (offset, rule) in found {
+            self.push(offset, rule);
+        }
+    }
+
+    /// Lint every string in a TOML value
+    fn toml_value(&mut self, value: &toml::Spanned<toml::de::DeValue<'_>>) {
+        match value.get_ref() {
+            toml::de::DeValue::String(text) => {
+                let span = value.span();
+                let raw = &self.content[span.clone()];
+                // Drop the quotes so that offsets line up for plain strings
+                let quotes = if raw.starts_with("\"\"\"") || raw.starts_with("'''") {
+                    3
+                } else {
+                    1
+                };
+                let start = (span.start + quotes).min(span.end);
+                let end = span.end.saturating_sub(quotes).max(start);
+                self.shell(text, start..end, true);
+                self.custom(&self.content[start..end], start);
+            }
+            toml::de::DeValue::Array(array) => {
+                for item in array.as_ref() {
+                    self.toml_value(item);
+                }
+            }
+            toml::de::DeValue::Table(table) => {
+                for (_, item) in table {
+                    self.toml_value(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Report custom rule matches in `text`, found at `offset` of the file
+    fn custom(&mut self, text: &str, offset: usize) {
+        for rule in &self.rules.custom {
+            let Some(severity) = self.rules.severity(&rule.id, LintSeverity::Warning) else {
+                continue;
+            };
+            for start in rule.find(text) {
+                let (line, column) = line_column(self.content, offset + start);
+                self.issues.push(LintIssue {
+                    rule: rule.id.clone(),
+                    severity,
+                    message: rule.message.clone(),
+                    line: Some(line),
+                    column: Some(column),
+                    fix: rule.fix.clone(),
+                    edit: None,
+                });
+            }
+        }
+    }
+
+    fn push(&mut self, offset: usize, rule: &LintRule) {
+        let Some(severity) = self.rules.severity(rule.id, rule.severity) else {
+            return;
+        };
+        let (line, column) = line_column(self.content, offset);
+        let text = self.content.lines().nth(line - 1).unwrap_or_default();
+        let edit = rule
+            .edit
+            .and_then(|edit| edit(text, self.resolve))
+            .map(|replacement| Edit { line, replacement });
+        self.issues.push(LintIssue {
+            rule: rule.id.to_string(),
+            severity,
+            message: rule.message.to_string(),
+            line: Some(line),
+            column: Some(column),
+            fix: rule.fix.map(String::from),
+            edit,
+        });
+    }
+}
+
+/// `line` without its trailing comment
+pub(super) fn code_part(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '#') if previous.is_whitespace() || previous == ';' => return &line[..i],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+fn matches_command(check: Check, command: &ScriptCommand) -> bool {
+    let Some(name) = command.name() else {
+        return false;
+    };
+    let first_arg = || command.args().first().and_then(Word::as_literal);
+    match check {
+        Check::Subst { .. } => false,
+        Check::Command(expected) => name == expected,
+        Check::Subcommand(expected, arg) => name == expected && first_arg().as_deref() == Some(arg),
+        Check::Source(suffix) => {
+            (name == "source" || name == ".")
+                && command
+                    .args()
+                    .first()
+                    .is_some_and(|path| path.to_string().ends_with(suffix))
+        }
+        Check::MissingFlag(expected, flag) => {
+            name == expected
+                && !command.args().iter().any(|arg| {
+                    arg.as_literal().is_some_and(|arg| {
+                        arg.starts_with('-') && !arg.starts_with("--") && arg.contains(flag)
+                    })
+                })
+        }
+    }
+}
+
+/// Command substitutions in `word`: `(offset in source, backtick, command)`
+///
+/// Scans the word's source for each `$(` or backtick, skipping quoted and
+/// escaped text and other expansions, and pairs them with the parsed
+/// [`WordPart::CommandSubst`] parts in order.
+pub(super) fn substitutions<'w>(source: &str, word: &'w Word) -> Vec<(usize, bool, &'w str)> {
+    let mut expansions = word
+        .parts
+        .iter()
+        .filter(|part| matches!(part, WordPart::CommandSubst(_) | WordPart::Arith(_)))
+        .peekable();
+    let bytes = source.as_bytes();
+    let mut found = Vec::new();
+    let mut double = false;
+    let mut i = word.offset;
+    while i < bytes.len() && expansions.peek().is_some() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'\\', _) => i += 2,
+            (b'"', _) => {
+                double = !double;
+                i += 1;
+            }
+            (b'\'', _) if !double => i = skip_quoted(bytes, i + 1, false),
+            (b'$', Some(b'\'')) if !double => i = skip_quoted(bytes, i + 2, true),
+            (b'$', Some(b'{')) => i = skip_braces(bytes, i + 2),
+            (b'$', Some(b'(')) | (b'`', _) => {
+                let backtick = bytes[i] == b'`';
+                match expansions.next() {
+                    Some(WordPart::CommandSubst(inner)) => {
+                        found.push((i, backtick, inner.as_str()));
+                        // Backtick bodies may drop escapes, so find the end
+                        i = if backtick {
+                            skip_backticks(bytes, i + 1)
+                        } else {
+                            i + inner.len() + 3
+                        };
+                    }
+                    Some(WordPart::Arith(expr)) => i += expr.len() + 5,
+                    _ => break,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    found
+}
+
+/// Index after the `'` closing a quote that starts at `i`; `ansi_c`
+/// quotes (`$'...'`) allow backslash escapes
+fn skip_quoted(bytes: &[u8], mut i: usize, ansi_c: bool) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if ansi_c => i += 2,
+            b'\'' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    i
+}
+
+/// Index after the backtick closing a substitution whose body starts at `i`
+fn skip_backticks(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    i
+}
+
+/// Index after the `}` closing a `${` whose body starts at `i`
+fn skip_braces(bytes: &[u8], mut i: usize) -> usize {
+    let mut depth = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::apply_edits;
+
+    #[test]
+    fn test_lint_detects_subprocess() {
+        let content = r#"
+export GOROOT="$(brew --prefix golang)/libexec"
+"#;
+        let result = lint_config(content);
+        assert!(!result.passed());
+        assert!(!result.issues.is_empty());
+    }
+
+    #[test]
+    fn test_lint_edits_need_resolver() {
+        let content = "export GOROOT=\"$(brew --prefix golang)/libexec\"\n";
+        let result = lint_config(content);
+        assert!(result.issues.iter().all(|i| i.edit.is_none()));
+
+        let resolve = |command: &str| {
+            (command == "brew --prefix golang").then(|| "/opt/homebrew/opt/go".to_string())
+        };
+        let result = lint_config_with_edits(content, &resolve);
+        let (fixed, applied) = apply_edits(content, &result.issues);
+        assert_eq!(applied, 1);
+        assert_eq!(fixed, "export GOROOT=\"/opt/homebrew/opt/go/libexec\"\n");
+        assert!(lint_config(&fixed).passed());
+    }
+
+    #[test]
+    fn test_lint_fix_nvm_is_lazy() {
+        let content = "export NVM_DIR=\"$HOME/.nvm\"\n. \"$NVM_DIR/nvm.sh\"\n";
+        let result = lint_config(content);
+        assert_eq!(result.issues.len(), 1);
+        assert!(result.format().contains("(pzsh fix)"));
+
+        let (fixed, _) = apply_edits(content, &result.issues);
+        assert!(fixed.contains("__pzsh_lazy_nvm() {"));
+        // The loader itself is not flagged again
+        assert!(lint_config(&fixed).issues.is_empty());
+    }
+
+    #[test]
+    fn test_lint_detects_backticks() {
+        let content = r"
+export DATE=`date`
+";
+        let result = lint_config(content);
+        assert!(!result.passed());
+    }
+
+    #[test]
+    fn test_lint_detects_oh_my_zsh() {
+        let content = r"
+source $ZSH/oh-my-zsh.sh
+";
+        let result = lint_config(content);
+        assert!(!result.passed());
+    }
+
+    #[test]
+    fn test_lint_clean_config() {
+        let content = r#"
+export EDITOR="vim"
+alias ll="ls -la"
+"#;
+        let result = lint_config(content);
+        assert!(result.passed());
+        assert!(result.issues.is_empty());
+    }
+
+    #[test]
+    fn test_lint_detects_nvm() {
+        let content = r"
+source ~/.nvm/nvm.sh
+";
+        let result = lint_config(content);
+        assert!(!result.issues.is_empty());
+        assert!(result.issues.iter().any(|i| i.message.contains("NVM")));
+    }
+
+    #[test]
+    fn test_lint_detects_conda() {
+        let content = r"
+source ~/miniconda3/etc/profile.d/conda.sh
+";
+        let result = lint_config(content);
+        assert!(!result.issues.is_empty());
+        assert!(result.issues.iter().any(|i| i.message.contains("conda")));
+    }
+
+    #[test]
+    fn test_lint_detects_eval() {
+        let content = r#"
+eval "$(pyenv init -)"
+"#;
+        let result = lint_config(content);
+        assert!(!result.passed());
+        assert!(result.issues.iter().any(|i| i.message.contains("eval")));
+    }
+
+    #[test]
+    fn test_lint_ignores_comments() {
+        let content = r#"
+# eval "$(something)"
+# `backticks`
+# source nvm.sh
+"#;
+        let result = lint_config(content);
+        // Comments should be ignored for backticks and eval, but not for other patterns
+        assert!(
+            !result.issues.iter().any(|i| i.message.contains("eval")),
+            "eval in comments should be ignored"
+        );
+        assert!(
+            !result.issues.iter().any(|i| i.message.contains("backtick")),
+            "backticks in comments should be ignored"
+        );
+    }
+
+    #[test]
+    fn test_lint_result_format_with_issues() {
+        let result = LintResult {
+            issues: vec![
+                LintIssue {
+                    rule: "test".to_string(),
+                    severity: LintSeverity::Error,
+                    message: "test error".to_string(),
+                    line: Some(10),
+                    column: None,
+                    fix: Some("fix it".to_string()),
+                    edit: None,
+                },
+                LintIssue {
+                    rule: "test".to_string(),
+                    severity: LintSeverity::Warning,
+                    message: "test warning".to_string(),
+                    line: None,
+                    column: None,
+                    fix: None,
+                    edit: None,
+                },
+                LintIssue {
+                    rule: "test".to_string(),
+                    severity: LintSeverity::Info,
+                    message: "test info".to_string(),
+                    line: Some(5),
+                    column: Some(3),
+                    fix: None,
+                    edit: None,
+                },
+            ],
+        };
+        let formatted = result.format();
+        assert!(formatted.contains("[error]"));
+        assert!(formatted.contains("[warning]"));
+        assert!(formatted.contains("[info]"));
+        assert!(formatted.contains("(line 10)"));
+        assert!(formatted.contains("fix: fix it"));
+        assert!(formatted.contains("test error (test)"));
+        assert!(formatted.contains("1 errors, 1 warnings"));
+    }
+
+    #[test]
+    fn test_lint_result_format_empty() {
+        let result = LintResult { issues: vec![] };
+        let formatted = result.format();
+        assert!(formatted.contains("0 issues found"));
+    }
+
+    #[test]
+    fn test_rule_ids_are_unique() {
+        let mut ids: Vec<&str> = LINT_RULES.iter().map(|rule| rule.id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), LINT_RULES.len());
+
+        let result = lint_config("source ~/.nvm/nvm.sh\ncompinit\n");
+        let rules: Vec<&str> = result.issues.iter().map(|i| i.rule.as_str()).collect();
+        assert_eq!(rules, ["nvm", "compinit-uncached"]);
+    }
+
+    #[test]
+    fn test_max_warnings() {
+        let result = lint_config("source ~/.nvm/nvm.sh\ncompinit\n");
+        assert_eq!((result.errors(), result.warnings()), (0, 2));
+        assert!(result.passed_with(None));
+        assert!(result.passed_with(Some(2)));
+        assert!(!result.passed_with(Some(1)));
+        assert!(!result.passed_with(Some(0)));
+
+        let result = lint_config("eval x\n");
+        assert!(!result.passed_with(None));
+    }
+
+    #[test]
+    fn test_lint_json() {
+        let content = "export X=`date`\ncompinit\n";
+        let json: serde_json::Value =
+            serde_json::from_str(&lint_config(content).to_json(".zshrc")).unwrap();
+        assert_eq!(json["file"], ".zshrc");
+        assert_eq!(json["errors"], 1);
+        assert_eq!(json["warnings"], 1);
+        let issue = &json["issues"][0];
+        assert_eq!(issue["rule"], "backticks");
+        assert_eq!(issue["severity"], "error");
+        assert_eq!(issue["line"], 1);
+        assert_eq!(issue["column"], 10);
+        assert_eq!(issue["fix"], "use pre-resolved value instead");
+        assert_eq!(issue["fixable"], false);
+        assert_eq!(json["issues"][1]["fixable"], true);
+    }
+
+    #[test]
+    fn test_lint_sarif() {
+        let content = "source ~/.nvm/nvm.sh\neval x\ncompinit\n";
+        let sarif: serde_json::Value =
+            serde_json::from_str(&lint_config(content).to_sarif("dotfiles/zshrc", content))
+                .unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "pzsh");
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), 3);
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results[1]["ruleId"], "eval");
+        assert_eq!(results[1]["level"], "error");
+        assert_eq!(results[1]["ruleIndex"], 1);
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "dotfiles/zshrc");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 1);
+        assert!(results[1].get("fixes").is_none());
+
+        // compinit -C replaces line 3
+        let replacement = &results[2]["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert_eq!(replacement["deletedRegion"]["startLine"], 3);
+        assert_eq!(replacement["deletedRegion"]["endColumn"], 9);
+        assert_eq!(replacement["insertedContent"]["text"], "compinit -C");
+    }
+
+    fn lint_with(content: &str, section: &str) -> LintResult {
+        lint_config_with(
+            content,
+            LintMode::Shell,
+            toml::from_str(section).unwrap(),
+            &|_| None,
+        )
+    }
+
+    fn lint_toml(content: &str) -> LintResult {
+        lint_config_with(content, LintMode::Toml, LintSection::default(), &|_| None)
+    }
+
+    fn rules(result: &LintResult) -> Vec<(&str, LintSeverity, Option<usize>)> {
+        result
+            .issues
+            .iter()
+            .map(|i| (i.rule.as_str(), i.severity, i.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_severity_overrides_and_custom_rules() {
+        let content =
+            "source ~/.nvm/nvm.sh\neval x\nsource ~/.sdkman/bin/sdkman-init.sh # sdkman-init.sh\n";
+        let result = lint_with(
+            content,
+            "[severity]\nnvm = \"info\"\neval = \"off\"\n\n\
+             [[rules]]\nid = \"sdkman\"\npattern = \"sdkman-init.sh\"\nmessage = \"sdkman is slow\"\n",
+        );
+        // The match in the comment is ignored
+        assert_eq!(
+            rules(&result),
+            [
+                ("nvm", LintSeverity::Info, Some(1)),
+                ("sdkman", LintSeverity::Warning, Some(3))
+            ]
+        );
+        assert_eq!(result.issues[1].column, Some(22));
+        assert!(result.passed_with(Some(1)));
+    }
+
+    #[test]
+    fn test_inline_lint_section() {
+        let content = r#"[env]
+SDKMAN_DIR = "/opt/sdkman"
+
+[lint.severity]
+command-substitution = "warning"
+
+[[lint.rules]]
+id = "opt"
+regex = '^/opt/'
+message = "prefer ~/.local"
+pattern_note = "x"
+"#;
+        // Unknown key in a rule
+        let result = lint_toml(content);
+        assert_eq!(
+            rules(&result),
+            [("lint-config", LintSeverity::Error, Some(4))]
+        );
+
+        let content = content.replace("pattern_note = \"x\"\n", "");
+        let content = format!("{content}[aliases]\nnow = \"echo $(date)\"\n");
+        let result = lint_toml(&content);
+        // Patterns in [lint] itself are not linted
+        assert_eq!(
+            rules(&result),
+            [
+                ("opt", LintSeverity::Warning, Some(2)),
+                ("command-substitution", LintSeverity::Warning, Some(12))
+            ]
+        );
+        assert_eq!(result.issues[0].column, Some(15));
+    }
+
+    #[test]
+    fn test_suppressed_issues() {
+        let content = "\
+eval \"$(direnv hook zsh)\"  # pzsh-ignore: eval, command-substitution -- trusted
+# pzsh-ignore: nvm -- lazy-loaded on work laptop
+source ~/.nvm/nvm.sh
+compinit # pzsh-ignore: compinit-uncached
+";
+        let result = lint_config(content);
+        assert_eq!(
+            rules(&result),
+            [
+                ("compinit-uncached", LintSeverity::Warning, Some(4)),
+                ("invalid-ignore", LintSeverity::Error, Some(4))
+            ]
+        );
+        assert!(result.issues[1].message.contains("needs a reason"));
+    }
+
+    #[test]
+    fn test_lint_positions() {
+        let content =
+            "export EDITOR=vim\nexport GOROOT=\"$(brew --prefix go)\" X=`date`\n  eval \"$x\"\n";
+        let result = lint_config(content);
+        let positions: Vec<(&str, Option<usize>, Option<usize>)> = result
+            .issues
+            .iter()
+            .map(|i| (i.message.as_str(), i.line, i.column))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                ("brew --prefix is slow (50-100ms)", Some(2), Some(16)),
+                ("backtick substitution not allowed", Some(2), Some(39)),
+                ("eval not allowed for safety", Some(3), Some(3)),
+            ]
+        );
+        // Each substitution is reported once, by the most specific rule
+        assert!(!result.issues.iter().any(|i| i.message.contains("$()")));
+    }
+
+    #[test]
+    fn test_lint_shell_structure() {
+        let content = r#"
+# eval "$(fnm env)" and `date` in a comment
+echo "eval is only an argument here"
+nvm() { source ~/.nvm/nvm.sh; nvm "$@"; }
+lazy() {
+  export X=$(slow)
+}
+if [ -f ~/.fzf.zsh ]; then source ~/.fzf.zsh; fi
+"#;
+        let result = lint_config(content);
+        assert!(result.issues.is_empty(), "{}", result.format());
+    }
+
+    #[test]
+    fn test_lint_compinit() {
+        let result = lint_config("autoload -Uz compinit\ncompinit\n");
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].line, Some(2));
+        assert_eq!(
+            result.issues[0].edit.as_ref().unwrap().replacement,
+            "compinit -C"
+        );
+
+        assert!(
+            lint_config("compinit -C\ncompinit -i -C\ncompinit -uC\n")
+                .issues
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_lint_finds_substitutions_after_expansions() {
+        let result = lint_config("export X=\"$HOME/$(brew --prefix foo)\"\n");
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].column, Some(17));
+
+        let result = lint_config("export X=\"${Y:-/a}'$(z)'`brew --prefix foo`\"\n");
+        let found: Vec<_> = result
+            .issues
+            .iter()
+            .map(|i| (i.rule.as_str(), i.column))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("command-substitution", Some(20)),
+                ("brew-prefix", Some(25))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_skips_escaped_substitutions() {
+        let result = lint_config("export X=\"\\$(brew --prefix a) $(brew --prefix b)\"\n");
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].column, Some(31));
+
+        let result = lint_config("export X='$(brew --prefix a)'$(brew --prefix b)\n");
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].column, Some(30));
+    }
+
+    #[test]
+    fn test_lint_unparsed_lines_fall_back_to_patterns() {
+        let content = "case $x in\n  a) source ~/.nvm/nvm.sh ;;\nesac\n";
+        let result = lint_config(content);
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(result.issues[0].line, Some(2));
+        assert_eq!(result.issues[0].column, Some(20));
+    }
+
+    #[test]
+    fn test_lint_toml_values() {
+        let content = r#"# $(comments) are fine
+[pzsh]
+shell = "zsh"
+
+[env]
+GOROOT = "$(brew --prefix go)/libexec"
+"$(key)" = "keys are not code"
+
+[aliases]
+now = 'echo `date`'
+x = "eval foo"
+src = "source ~/.nvm/nvm.sh"
+"#;
+        let result = lint_toml(content);
+        let positions: Vec<(Option<usize>, Option<usize>)> =
+            result.issues.iter().map(|i| (i.line, i.column)).collect();
+        assert_eq!(
+            positions,
+            [
+                (Some(6), Some(11)),
+                (Some(10), Some(13)),
+                (Some(11), Some(6))
+            ]
+        );
+        assert!(result.issues[0].message.contains("brew"));
+        assert!(result.issues[2].message.contains("eval"));
+    }
+
+    #[test]
+    fn test_lint_toml_escaped_string_positions() {
+        let content = "[env]\nA = \"\\t$(date)\"\n";
+        let result = lint_toml(content);
+        assert_eq!(result.issues.len(), 1);
+        assert_eq!(
+            (result.issues[0].line, result.issues[0].column),
+            (Some(2), Some(8))
+        );
+    }
+
+    #[test]
+    fn test_lint_mode_from_file_name() {
+        assert_eq!(LintMode::for_path(Path::new("~/.pzshrc")), LintMode::Toml);
+        assert_eq!(
+            LintMode::for_path(Path::new("dotfiles/pzshrc")),
+            LintMode::Toml
+        );
+        assert_eq!(LintMode::for_path(Path::new("work.toml")), LintMode::Toml);
+        assert_eq!(LintMode::for_path(Path::new("~/.zshrc")), LintMode::Shell);
+        assert_eq!(LintMode::for_path(Path::new("env.sh")), LintMode::Shell);
+
+        // Valid TOML is still a shell script in shell mode
+        let content = "N=1
+X=`date`
+";
+        assert_eq!(lint_config(content).issues.len(), 1);
+
+        let result = lint_toml(
+            "[env]
+X = $(date)
+",
+        );
+        assert_eq!(
+            rules(&result),
+            [("toml-syntax", LintSeverity::Error, Some(2))]
+        );
+    }
+
+    #[test]
+    fn test_lint_skips_heredoc_bodies() {
+        let content = "cat > ~/.npmrc <<'EOF'
+eval x
+source ~/.nvm/nvm.sh
+EOF
+eval y
+";
+        let result = lint_config(content);
+        assert_eq!(rules(&result), [("eval", LintSeverity::Error, Some(5))]);
+    }
+}
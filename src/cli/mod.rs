@@ -1,13 +1,33 @@
 //! CLI module for pzsh
 //!
-//! Commands: bench, lint, compile, fix, profile, status
+//! Commands: bench, lint, compile, prompt, fix, profile, status, explain,
+//! init, import, config
 
 use crate::config::CompiledConfig;
-use crate::{MAX_STARTUP_MS, Pzsh};
+use crate::{MAX_STARTUP_MS, Pzsh, ShellType};
+use ahash::AHashMap;
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::{Duration, Instant};
 
+mod cache;
+mod fix;
+mod import;
+mod lint;
+mod rules;
+pub use cache::{InitCache, cache_key, watched_paths};
+pub use fix::{
+    Edit, apply_edits, check_syntax, original_lines, resolve_command, unified_diff,
+    write_with_backup,
+};
+pub use import::{ImportResult, Unsupported, import_rc};
+pub use lint::{
+    LintIssue, LintMode, LintResult, LintSeverity, lint_config, lint_config_with,
+    lint_config_with_edits,
+};
+pub use rules::{LINT_TOML, find_lint_toml, load_lint_toml};
+
 /// pzsh: Performance-first shell framework
 #[derive(Parser, Debug)]
 #[command(name = "pzsh")]
@@ -28,6 +48,14 @@ pub enum Commands {
         /// Show detailed statistics
         #[arg(short, long)]
         verbose: bool,
+
+        /// Spawn the configured shell (zsh or bash) with the compiled init
+        #[arg(long)]
+        real: bool,
+
+        /// Path to configuration file (with --real)
+        #[arg(short, long, default_value = "~/.pzshrc")]
+        config: PathBuf,
     },
 
     /// Lint configuration for slow patterns
@@ -35,6 +63,14 @@ pub enum Commands {
         /// Path to configuration file
         #[arg(short, long, default_value = "~/.pzshrc")]
         config: PathBuf,
+
+        /// Output format
+        #[arg(long, default_value = "text", value_parser = ["text", "json", "sarif"])]
+        format: String,
+
+        /// Fail when there are more warnings than this (errors always fail)
+        #[arg(long, value_name = "N")]
+        max_warnings: Option<usize>,
     },
 
     /// Compile configuration to optimized form
@@ -46,6 +82,48 @@ pub enum Commands {
         /// Output path for compiled config
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// Write to the init cache (`$XDG_CACHE_HOME/pzsh`) unless it is
+        /// already up to date
+        #[arg(long, conflicts_with = "output")]
+        cache: bool,
+
+        /// Write a binary snapshot for embedding (default
+        /// `$XDG_CACHE_HOME/pzsh/config.bin`) instead of a script
+        #[arg(long, conflicts_with = "cache")]
+        binary: bool,
+
+        /// Generate for this shell instead of the configured one
+        #[arg(long, value_parser = ["zsh", "bash", "fish", "nushell", "sh"])]
+        shell: Option<String>,
+    },
+
+    /// Render the prompt; called by the shell before each prompt
+    Prompt {
+        /// Exit code of the last command
+        #[arg(long, default_value = "0", allow_negative_numbers = true)]
+        exit_code: i32,
+
+        /// Run time of the last command in milliseconds
+        #[arg(long, default_value = "0")]
+        duration_ms: u64,
+
+        /// Number of background jobs
+        #[arg(long, default_value = "0")]
+        jobs: usize,
+
+        /// Line editor keymap (zsh `$KEYMAP`, or `vi` / `emacs`)
+        #[arg(long)]
+        keymap: Option<String>,
+
+        /// Wrap escape sequences as zero-width for this shell's prompt
+        /// variable (`%{ %}` for zsh, `\[ \]` for bash)
+        #[arg(long, value_parser = ["zsh", "bash"])]
+        shell: Option<String>,
+
+        /// Path to configuration file
+        #[arg(short, long, default_value = "~/.pzshrc")]
+        config: PathBuf,
     },
 
     /// Auto-fix slow patterns
@@ -61,20 +139,78 @@ pub enum Commands {
 
     /// Profile detailed startup breakdown
     Profile {
-        /// Show timing for each component
+        /// Show line counts and share of the total for each section
         #[arg(short, long)]
         verbose: bool,
+
+        /// Path to configuration file
+        #[arg(short, long, default_value = "~/.pzshrc")]
+        config: PathBuf,
     },
 
     /// Show pzsh status
-    Status,
+    Status {
+        /// Path to configuration file
+        #[arg(short, long, default_value = "~/.pzshrc")]
+        config: PathBuf,
+    },
+
+    /// Show every definition of an alias or env var and which one wins
+    Explain {
+        /// What to explain
+        #[arg(value_parser = ["alias", "env"])]
+        kind: String,
+
+        /// Alias or variable name
+        name: String,
+
+        /// Path to configuration file
+        #[arg(short, long, default_value = "~/.pzshrc")]
+        config: PathBuf,
+    },
 
     /// Initialize pzsh configuration
     Init {
-        /// Shell type (zsh or bash)
-        #[arg(short, long, default_value = "zsh")]
+        /// Shell type (zsh, bash, fish, nushell or sh)
+        #[arg(short, long, default_value = "zsh", value_parser = ["zsh", "bash", "fish", "nushell", "sh"])]
         shell: String,
+
+        /// Print the startup snippet for the shell's rc file, which loads
+        /// the init cache, instead of creating ~/.pzshrc
+        #[arg(long)]
+        print: bool,
     },
+
+    /// Import an existing .zshrc, .bashrc or oh-my-zsh setup
+    Import {
+        /// Shell startup file to import
+        file: PathBuf,
+
+        /// Where to write the configuration
+        #[arg(short, long, default_value = "~/.pzshrc")]
+        output: PathBuf,
+
+        /// Shell type (default: bash if the file name contains "bash", else zsh)
+        #[arg(short, long, value_parser = ["zsh", "bash"])]
+        shell: Option<String>,
+
+        /// Print the configuration instead of writing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Inspect the configuration format
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+/// `pzsh config` subcommands
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the JSON Schema of .pzshrc for editor completion and validation
+    Schema,
 }
 
 /// Benchmark result
@@ -92,6 +228,55 @@ pub struct BenchResult {
 }
 
 impl BenchResult {
+    /// Compute statistics from raw timings (at least one)
+    #[must_use]
+    pub fn from_times(mut times: Vec<Duration>) -> Self {
+        let iterations = times.len() as u32;
+
+        // Sort for percentiles
+        times.sort();
+
+        let min = times[0];
+        let max = times[times.len() - 1];
+
+        let sum: Duration = times.iter().sum();
+        let mean = sum / iterations;
+
+        let p50_idx = (times.len() as f64 * 0.50) as usize;
+        let p95_idx = (times.len() as f64 * 0.95) as usize;
+        let p99_idx = (times.len() as f64 * 0.99) as usize;
+
+        let p50 = times[p50_idx.min(times.len() - 1)];
+        let p95 = times[p95_idx.min(times.len() - 1)];
+        let p99 = times[p99_idx.min(times.len() - 1)];
+
+        // Calculate standard deviation
+        let mean_nanos = mean.as_nanos() as f64;
+        let variance: f64 = times
+            .iter()
+            .map(|t| {
+                let diff = t.as_nanos() as f64 - mean_nanos;
+                diff * diff
+            })
+            .sum::<f64>()
+            / times.len() as f64;
+        let std_dev = Duration::from_nanos(variance.sqrt() as u64);
+
+        let passed = p99 < Duration::from_millis(MAX_STARTUP_MS);
+
+        Self {
+            iterations,
+            min,
+            max,
+            mean,
+            p50,
+            p95,
+            p99,
+            std_dev,
+            passed,
+        }
+    }
+
     /// Format as string
     #[must_use]
     pub fn format(&self) -> String {
@@ -158,289 +343,359 @@ pub fn run_bench(iterations: u32, verbose: bool) -> BenchResult {
         times.push(start.elapsed());
     }
 
-    // Sort for percentiles
-    times.sort();
-
-    let min = times[0];
-    let max = times[times.len() - 1];
-
-    let sum: Duration = times.iter().sum();
-    let mean = sum / iterations;
-
-    let p50_idx = (times.len() as f64 * 0.50) as usize;
-    let p95_idx = (times.len() as f64 * 0.95) as usize;
-    let p99_idx = (times.len() as f64 * 0.99) as usize;
-
-    let p50 = times[p50_idx.min(times.len() - 1)];
-    let p95 = times[p95_idx.min(times.len() - 1)];
-    let p99 = times[p99_idx.min(times.len() - 1)];
-
-    // Calculate standard deviation
-    let mean_nanos = mean.as_nanos() as f64;
-    let variance: f64 = times
-        .iter()
-        .map(|t| {
-            let diff = t.as_nanos() as f64 - mean_nanos;
-            diff * diff
-        })
-        .sum::<f64>()
-        / times.len() as f64;
-    let std_dev = Duration::from_nanos(variance.sqrt() as u64);
-
-    let passed = p99 < Duration::from_millis(MAX_STARTUP_MS);
-
-    BenchResult {
-        iterations,
-        min,
-        max,
-        mean,
-        p50,
-        p95,
-        p99,
-        std_dev,
-        passed,
-    }
-}
-
-/// Lint result
-#[derive(Debug)]
-pub struct LintResult {
-    pub issues: Vec<LintIssue>,
+    BenchResult::from_times(times)
 }
 
+/// Real shell startup benchmark: the shell with and without the pzsh init
 #[derive(Debug)]
-pub struct LintIssue {
-    pub severity: LintSeverity,
-    pub message: String,
-    pub line: Option<usize>,
-    pub fix: Option<String>,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum LintSeverity {
-    Error,
-    Warning,
-    Info,
+pub struct RealBenchResult {
+    pub shell: ShellType,
+    /// Shell started with an empty rc file
+    pub baseline: BenchResult,
+    /// Shell started with the compiled init as its rc file
+    pub with_pzsh: BenchResult,
+    /// Whether the mean overhead is within the startup budget
+    pub passed: bool,
 }
 
-impl LintResult {
-    /// Check if lint passed (no errors)
+impl RealBenchResult {
+    /// Time pzsh adds to shell startup (mean)
     #[must_use]
-    pub fn passed(&self) -> bool {
-        !self
-            .issues
-            .iter()
-            .any(|i| matches!(i.severity, LintSeverity::Error))
+    pub fn overhead(&self) -> Duration {
+        self.with_pzsh.mean.saturating_sub(self.baseline.mean)
     }
 
     /// Format as string
     #[must_use]
     pub fn format(&self) -> String {
-        if self.issues.is_empty() {
-            return "✓ 0 issues found".to_string();
-        }
-
-        let mut output = String::new();
-        for issue in &self.issues {
-            let severity = match issue.severity {
-                LintSeverity::Error => "error",
-                LintSeverity::Warning => "warning",
-                LintSeverity::Info => "info",
-            };
-
-            let line_info = issue
-                .line
-                .map_or(String::new(), |l| format!(" (line {})", l));
-
-            output.push_str(&format!("[{}]{}: {}\n", severity, line_info, issue.message));
-
-            if let Some(fix) = &issue.fix {
-                output.push_str(&format!("  fix: {}\n", fix));
-            }
-        }
-
-        let error_count = self
-            .issues
-            .iter()
-            .filter(|i| matches!(i.severity, LintSeverity::Error))
-            .count();
-        let warning_count = self
-            .issues
-            .iter()
-            .filter(|i| matches!(i.severity, LintSeverity::Warning))
-            .count();
-
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let row = |name: &str, base: Duration, with: Duration| {
+            format!(
+                "{name:<7} {:>8.3}ms {:>8.3}ms {:>+8.3}ms\n",
+                ms(base),
+                ms(with),
+                ms(with) - ms(base)
+            )
+        };
+        let (base, with) = (&self.baseline, &self.with_pzsh);
+        let status = if self.passed { "✓" } else { "✗" };
+        let mut output = format!(
+            "Real Startup Benchmark: {} -i -c exit ({} iterations)\n\
+             ────────────────────────────────────────────\n\
+             {:<7} {:>10} {:>10} {:>10}\n",
+            self.shell.name(),
+            with.iterations,
+            "",
+            "baseline",
+            "pzsh",
+            "overhead"
+        );
+        output.push_str(&row("min:", base.min, with.min));
+        output.push_str(&row("mean:", base.mean, with.mean));
+        output.push_str(&row("p50:", base.p50, with.p50));
+        output.push_str(&row("p95:", base.p95, with.p95));
+        output.push_str(&row("p99:", base.p99, with.p99));
+        output.push_str(&row("max:", base.max, with.max));
         output.push_str(&format!(
-            "\n{} errors, {} warnings",
-            error_count, warning_count
+            "────────────────────────────────────────────\n\
+             Overhead: {:.3}ms {} (mean < {}ms)",
+            ms(self.overhead()),
+            status,
+            MAX_STARTUP_MS
         ));
-
         output
     }
 }
 
-struct LintRule {
-    pattern: &'static str,
-    skip_comments: bool,
-    severity: LintSeverity,
-    message: &'static str,
-    fix: Option<&'static str>,
+/// Benchmark real shell startup with the compiled init `script`
+///
+/// Spawns `zsh -i -c exit` or `bash -i -c exit` `iterations` times with the
+/// script as the only rc file, and as many times with an empty rc file for
+/// the baseline. The user's own rc files are never read.
+///
+/// # Errors
+/// Returns error for shells other than zsh and bash, or if the shell cannot
+/// be started
+pub fn run_real_bench(
+    shell: ShellType,
+    script: &str,
+    iterations: u32,
+) -> std::io::Result<RealBenchResult> {
+    if !matches!(shell, ShellType::Zsh | ShellType::Bash) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("real benchmark supports zsh and bash, not {}", shell.name()),
+        ));
+    }
+
+    let dir = std::env::temp_dir().join(format!("pzsh-bench-{}", std::process::id()));
+    let result = bench_in(&dir, shell, script, iterations.max(1));
+    let _ = std::fs::remove_dir_all(&dir);
+    result
 }
 
-const LINT_RULES: &[LintRule] = &[
-    LintRule {
-        pattern: "$(",
-        skip_comments: false,
-        severity: LintSeverity::Error,
-        message: "subprocess call $() not allowed at startup",
-        fix: Some("use pre-resolved path instead"),
-    },
-    LintRule {
-        pattern: "`",
-        skip_comments: true,
-        severity: LintSeverity::Error,
-        message: "backtick substitution not allowed",
-        fix: Some("use pre-resolved value instead"),
-    },
-    LintRule {
-        pattern: "brew --prefix",
-        skip_comments: false,
-        severity: LintSeverity::Error,
-        message: "brew --prefix is slow (50-100ms)",
-        fix: Some("run `brew --prefix <formula>` once and hardcode the path"),
-    },
-    LintRule {
-        pattern: "eval ",
-        skip_comments: true,
-        severity: LintSeverity::Error,
-        message: "eval not allowed for safety",
-        fix: None,
-    },
-    LintRule {
-        pattern: "oh-my-zsh",
-        skip_comments: false,
-        severity: LintSeverity::Error,
-        message: "oh-my-zsh is slow (500-2000ms startup)",
-        fix: Some("remove oh-my-zsh, use pzsh plugins instead"),
-    },
-    LintRule {
-        pattern: "nvm.sh",
-        skip_comments: true,
-        severity: LintSeverity::Warning,
-        message: "NVM adds 200-500ms to startup",
-        fix: Some("use fnm or volta instead, or lazy-load NVM"),
-    },
-    LintRule {
-        pattern: "conda init",
-        skip_comments: false,
-        severity: LintSeverity::Warning,
-        message: "conda init adds 200-400ms to startup",
-        fix: Some("lazy-load conda or use mamba"),
-    },
-    LintRule {
-        pattern: "conda.sh",
-        skip_comments: false,
-        severity: LintSeverity::Warning,
-        message: "conda init adds 200-400ms to startup",
-        fix: Some("lazy-load conda or use mamba"),
-    },
-];
+fn bench_in(
+    dir: &Path,
+    shell: ShellType,
+    script: &str,
+    iterations: u32,
+) -> std::io::Result<RealBenchResult> {
+    let time = |rc_dir: &Path, content: &str| -> std::io::Result<Vec<Duration>> {
+        let mut command = interactive_shell(shell, rc_dir, content)?;
+
+        // Warmup (page cache, shell binary)
+        command.status()?;
+        let mut times = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            command.status()?;
+            times.push(start.elapsed());
+        }
+        Ok(times)
+    };
+
+    let baseline = BenchResult::from_times(time(&dir.join("baseline"), "")?);
+    let with_pzsh = BenchResult::from_times(time(&dir.join("pzsh"), script)?);
+    let passed =
+        with_pzsh.mean.saturating_sub(baseline.mean) < Duration::from_millis(MAX_STARTUP_MS);
+    Ok(RealBenchResult {
+        shell,
+        baseline,
+        with_pzsh,
+        passed,
+    })
+}
 
-/// Lint configuration content
-pub fn lint_config(content: &str) -> LintResult {
-    let mut issues = Vec::new();
+/// `<shell> -i -c exit` with `script` as its only rc file, kept in `rc_dir`
+///
+/// zsh reads `$ZDOTDIR/.zshrc`; bash gets its `.bashrc` via `--rcfile`.
+fn interactive_shell(shell: ShellType, rc_dir: &Path, script: &str) -> std::io::Result<Command> {
+    std::fs::create_dir_all(rc_dir)?;
+    let rc_file = rc_dir.join(rc_file_name(shell));
+    std::fs::write(&rc_file, script)?;
 
-    for (line_num, line) in content.lines().enumerate() {
-        let is_comment = line.trim_start().starts_with('#');
+    let mut command = Command::new(shell.name());
+    match shell {
+        ShellType::Bash => command.arg("--rcfile").arg(rc_file),
+        _ => command.env("ZDOTDIR", rc_dir),
+    };
+    command
+        .args(["-i", "-c", "exit"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    Ok(command)
+}
 
-        for rule in LINT_RULES {
-            if rule.skip_comments && is_comment {
-                continue;
-            }
-            if line.contains(rule.pattern) {
-                issues.push(LintIssue {
-                    severity: rule.severity,
-                    message: rule.message.to_string(),
-                    line: Some(line_num + 1),
-                    fix: rule.fix.map(String::from),
-                });
-            }
-        }
+/// Name of the interactive rc file `shell` reads
+const fn rc_file_name(shell: ShellType) -> &'static str {
+    match shell {
+        ShellType::Bash => ".bashrc",
+        _ => ".zshrc",
     }
+}
 
-    LintResult { issues }
+/// Number of shell runs averaged by [`run_profile`]
+pub const PROFILE_RUNS: u32 = 5;
+
+/// Time spent in one part of the generated script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionProfile {
+    /// Part name; plugin parts are nested as `plugins/<name>`
+    pub name: String,
+    /// Mean time from this part's marker to the next
+    pub time: Duration,
+    /// Lines of generated code
+    pub lines: usize,
 }
 
 /// Profile result
 #[derive(Debug)]
 pub struct ProfileResult {
-    pub parse_time: Duration,
-    pub env_time: Duration,
-    pub alias_time: Duration,
-    pub prompt_time: Duration,
+    pub shell: ShellType,
+    pub runs: u32,
+    pub sections: Vec<SectionProfile>,
     pub total_time: Duration,
     pub passed: bool,
 }
 
 impl ProfileResult {
-    /// Format as string
+    /// Format as a tree; `verbose` adds line counts and share of the total
     #[must_use]
-    pub fn format(&self) -> String {
+    pub fn format(&self, verbose: bool) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let detail = |time: Duration, lines: usize| {
+            if verbose {
+                let share = if self.total_time.is_zero() {
+                    0.0
+                } else {
+                    time.as_secs_f64() / self.total_time.as_secs_f64() * 100.0
+                };
+                format!("  {lines:>4} lines {share:>5.1}%")
+            } else {
+                String::new()
+            }
+        };
+
+        // Group `plugins/<name>` parts under one `plugins` node
+        let mut nodes: Vec<(&str, Vec<&SectionProfile>)> = Vec::new();
+        for section in &self.sections {
+            let (parent, child) = section.name.split_once('/').unwrap_or((&section.name, ""));
+            match nodes.last_mut() {
+                Some((name, children)) if !child.is_empty() && *name == parent => {
+                    children.push(section);
+                }
+                _ => nodes.push((parent, vec![section])),
+            }
+        }
+
+        let mut output = format!(
+            "Startup Profile: {} (mean of {} runs)\n",
+            self.shell.name(),
+            self.runs
+        );
+        for (name, children) in &nodes {
+            let time: Duration = children.iter().map(|s| s.time).sum();
+            let lines: usize = children.iter().map(|s| s.lines).sum();
+            output.push_str(&format!(
+                "├─ {name:<16} {:>8.3}ms{}\n",
+                ms(time),
+                detail(time, lines)
+            ));
+            if !children[0].name.contains('/') {
+                continue;
+            }
+            for (i, child) in children.iter().enumerate() {
+                let branch = if i + 1 == children.len() {
+                    "└─"
+                } else {
+                    "├─"
+                };
+                let child_name = child.name.split_once('/').map_or("", |(_, c)| c);
+                output.push_str(&format!(
+                    "│  {branch} {child_name:<13} {:>8.3}ms{}\n",
+                    ms(child.time),
+                    detail(child.time, child.lines)
+                ));
+            }
+        }
         let status = if self.passed { "✓" } else { "✗" };
-        format!(
-            "Startup Profile\n\
-             ├─ parse:  {:>6.3}ms\n\
-             ├─ env:    {:>6.3}ms\n\
-             ├─ alias:  {:>6.3}ms\n\
-             ├─ prompt: {:>6.3}ms\n\
-             └─ total:  {:>6.3}ms {}\n",
-            self.parse_time.as_secs_f64() * 1000.0,
-            self.env_time.as_secs_f64() * 1000.0,
-            self.alias_time.as_secs_f64() * 1000.0,
-            self.prompt_time.as_secs_f64() * 1000.0,
-            self.total_time.as_secs_f64() * 1000.0,
+        output.push_str(&format!(
+            "└─ {:<16} {:>8.3}ms {} (< {}ms)\n",
+            "total",
+            ms(self.total_time),
             status,
-        )
+            MAX_STARTUP_MS
+        ));
+        output
     }
 }
 
-/// Run profile
-pub fn run_profile() -> ProfileResult {
-    use crate::executor::Executor;
-    use crate::parser::Parser;
-    use crate::prompt::Prompt;
-
-    let config = CompiledConfig::default();
-    let total_start = Instant::now();
-
-    // Parser
-    let start = Instant::now();
-    let _ = Parser::new(&config);
-    let parse_time = start.elapsed();
-
-    // Executor (env + alias)
-    let start = Instant::now();
-    let _ = Executor::new(&config);
-    let env_time = start.elapsed();
-
-    let alias_time = Duration::ZERO; // Included in executor
+/// Profile the generated init script in the real shell
+///
+/// Runs `<shell> -i -c exit` [`PROFILE_RUNS`] times with the script from
+/// [`crate::shell::ShellIntegration::generate_profiled`] and averages the time between
+/// consecutive markers.
+///
+/// # Errors
+/// Returns error for shells other than zsh and bash, if the shell cannot be
+/// started or if it does not write every marker
+pub fn run_profile(integration: &crate::shell::ShellIntegration) -> std::io::Result<ProfileResult> {
+    let shell = integration.shell_type();
+    let Some(script) = integration.generate_profiled() else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("profile supports zsh and bash, not {}", shell.name()),
+        ));
+    };
+
+    let dir = std::env::temp_dir().join(format!("pzsh-profile-{}", std::process::id()));
+    let log = dir.join("profile.log");
+    let result = interactive_shell(shell, &dir, &script).and_then(|mut command| {
+        command.env("PZSH_PROFILE_LOG", &log);
+        for _ in 0..PROFILE_RUNS {
+            command.status()?;
+        }
+        std::fs::read_to_string(&log)
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let runs = profile_runs(shell, &result?)?;
+    let lines: AHashMap<String, usize> = integration
+        .generate_parts()
+        .into_iter()
+        .map(|(name, code)| (name, code.lines().count()))
+        .collect();
+
+    let mut sections: Vec<SectionProfile> = Vec::new();
+    for run in &runs {
+        for (i, (name, time)) in run.iter().enumerate() {
+            match sections.get_mut(i) {
+                Some(section) => section.time += *time,
+                None => sections.push(SectionProfile {
+                    name: name.clone(),
+                    time: *time,
+                    lines: lines.get(name).copied().unwrap_or(0),
+                }),
+            }
+        }
+    }
+    let count = runs.len() as u32;
+    for section in &mut sections {
+        section.time /= count;
+    }
+    let total_time: Duration = sections.iter().map(|s| s.time).sum();
 
-    // Prompt
-    let start = Instant::now();
-    let _ = Prompt::new(&config);
-    let prompt_time = start.elapsed();
+    Ok(ProfileResult {
+        shell,
+        runs: count,
+        sections,
+        total_time,
+        passed: total_time < Duration::from_millis(MAX_STARTUP_MS),
+    })
+}
 
-    let total_time = total_start.elapsed();
-    let passed = total_time < Duration::from_millis(MAX_STARTUP_MS);
+/// Parse a marker log, explaining why it is unusable
+fn profile_runs(shell: ShellType, log: &str) -> std::io::Result<Vec<Vec<(String, Duration)>>> {
+    // Markers print `$EPOCHREALTIME`, which is empty before bash 5
+    if shell == ShellType::Bash && log.lines().any(|line| line.ends_with(' ')) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "profile requires bash 5 or later ($EPOCHREALTIME is not set)",
+        ));
+    }
+    parse_profile_log(log).ok_or_else(|| {
+        std::io::Error::other(format!(
+            "{} did not finish the profiled script",
+            shell.name()
+        ))
+    })
+}
 
-    ProfileResult {
-        parse_time,
-        env_time,
-        alias_time,
-        prompt_time,
-        total_time,
-        passed,
+/// Split a marker log into runs of `(part, time to next marker)`
+///
+/// Returns `None` unless every run ends with the `end` marker.
+fn parse_profile_log(log: &str) -> Option<Vec<Vec<(String, Duration)>>> {
+    let mut runs = Vec::new();
+    let mut run: Vec<(String, f64)> = Vec::new();
+    for line in log.lines() {
+        let (name, seconds) = line.rsplit_once(' ')?;
+        // bash formats $EPOCHREALTIME with the locale's decimal separator
+        let seconds: f64 = seconds.replace(',', ".").parse().ok()?;
+        run.push((name.to_string(), seconds));
+        if name == "end" {
+            let times = run
+                .windows(2)
+                .map(|w| {
+                    let elapsed = (w[1].1 - w[0].1).max(0.0);
+                    (w[0].0.clone(), Duration::from_secs_f64(elapsed))
+                })
+                .collect();
+            runs.push(times);
+            run.clear();
+        }
     }
+    (run.is_empty() && !runs.is_empty()).then_some(runs)
 }
 
 /// Generate initial configuration
@@ -461,12 +716,14 @@ prompt_budget_ms = 2
 lazy_load = true
 
 [prompt]
-# Format: {{user}}, {{host}}, {{cwd}}, {{git}}, {{char}}
+# Format: {{user}}, {{host}}, {{cwd}}, {{git}}, {{char}}, {{status}}, {{duration}}, {{jobs}}, {{keymap}}
 format = "{{user}}@{{host}} {{cwd}} {{git}} {{char}} "
 git_async = true
 git_cache_ms = 1000
 # Enable ANSI colors in prompt (oh-my-zsh style)
 colors = true
+# Segment colors: robbyrussell, agnoster, simple, pure, spaceship
+# theme = "robbyrussell"
 
 [aliases]
 # Add your aliases here (no subprocess calls!)
@@ -488,39 +745,95 @@ glog = "git log --oneline --graph"
 # Add your environment variables here (pre-resolved paths only!)
 EDITOR = "vim"
 # GOROOT = "/usr/local/opt/go/libexec"  # Example: hardcoded, not $(brew --prefix)
+# GOBIN = "$HOME/go/bin"  # $HOME is expanded by the shell at startup
+
+[path]
+# Directories missing on this machine are skipped
+# prepend = ["~/.local/bin", "~/.cargo/bin"]
+# append = ["/opt/go/bin"]
 
 [plugins]
 # Built-in plugins: git, docker
 # Plugins provide aliases and shell integration while maintaining O(1) startup
 enabled = ["git"]
 lazy = ["docker"]
+# External plugins: <dir>/<name>/plugin.toml
+# dir = "~/.pzsh/plugins"
 
 [completion]
 # Enable intelligent auto-complete
 enabled = true
+# case_insensitive = true
+# fuzzy = true     # Typo tolerance
+# menu = true      # Cycle candidates in a menu
+# cache = true
 # Use aprender-shell ML model for predictions (when available)
-# model_path = "~/.pzsh/models/aprender-shell.onnx"
+# model_path = "~/.pzsh/models/aprender-shell.apr"
 
 [keybindings]
+# Emacs-mode defaults; override a chord or set it to "none"
 # ctrl-r = "history-search"
 # ctrl-p = "completion-prev"
 # ctrl-n = "completion-next"
+# "ctrl-x ctrl-e" = "edit-command-line"
 "#
     )
 }
 
+/// Format resolved plugin load order, plugin errors and conflicts for `pzsh status`
+#[must_use]
+pub fn format_plugin_status(integration: &crate::shell::ShellIntegration) -> String {
+    let plugins = integration.plugins();
+    let order = plugins.load_order();
+    let mut output = if order.is_empty() {
+        "Plugins: none\n".to_string()
+    } else {
+        format!("Plugins: {} (load order)\n", order.join(" → "))
+    };
+    let deferred: Vec<String> = plugins.deferred().map(|p| p.info().name).collect();
+    if !deferred.is_empty() {
+        output.push_str(&format!("Lazy: {}\n", deferred.join(", ")));
+    }
+    for e in integration.plugin_errors() {
+        output.push_str(&format!("  ✗ {e}\n"));
+    }
+    for conflict in integration.conflicts() {
+        output.push_str(&format!("  ⚠ {conflict}\n"));
+    }
+    output
+}
+
+/// Format every definition of `name` for `pzsh explain` (None if undefined)
+#[must_use]
+pub fn format_explanation(
+    integration: &crate::shell::ShellIntegration,
+    kind: crate::shell::DefinitionKind,
+    name: &str,
+) -> Option<String> {
+    let sources = integration.explain(kind, name);
+    let ((winner, value), shadowed) = sources.split_first()?;
+    let mut output = format!("{} {name}\n  ✓ {winner}: {value}\n", kind.name());
+    for (origin, value) in shadowed {
+        output.push_str(&format!("    {origin}: {value} (overridden)\n"));
+    }
+    Some(output)
+}
+
 /// Generate shell initialization script
 #[must_use]
 pub fn generate_shell_init(shell: &str) -> String {
     match shell {
         "bash" => generate_bash_init(),
+        "fish" => generate_fish_init(),
+        "nushell" => generate_nushell_init(),
+        "sh" => generate_sh_init(),
         _ => generate_zsh_init(),
     }
 }
 
 fn generate_zsh_init() -> String {
-    r#"# pzsh initialization for zsh
-# Add to your .zshrc: eval "$(pzsh init zsh)"
+    r"# pzsh initialization for zsh
+# Add to your .zshrc: pzsh init --shell zsh --print >> ~/.zshrc
 
 # Completion system
 autoload -Uz compinit
@@ -542,18 +855,32 @@ bindkey '^[[A' history-search-backward
 bindkey '^[[B' history-search-forward
 bindkey '^R' history-incremental-search-backward
 
-# Load pzsh config
+# Load pzsh config from the init cache; compile only when an input changed
 if [[ -f ~/.pzshrc ]]; then
-    # Source compiled config (O(1))
-    source <(pzsh compile --config ~/.pzshrc 2>/dev/null || echo "")
+    __pzsh_init=${XDG_CACHE_HOME:-$HOME/.cache}/pzsh/init.zsh
+    __pzsh_stale=1
+    if [[ -f $__pzsh_init && -f $__pzsh_init.inputs ]]; then
+        __pzsh_stale=0
+        while IFS= read -r __pzsh_input; do
+            if [[ ! -e $__pzsh_input || $__pzsh_input -nt $__pzsh_init ]]; then
+                __pzsh_stale=1
+                break
+            fi
+        done < $__pzsh_init.inputs
+    fi
+    if (( __pzsh_stale )); then
+        pzsh compile --config ~/.pzshrc --cache --shell zsh >/dev/null 2>&1
+    fi
+    [[ -f $__pzsh_init ]] && source $__pzsh_init
+    unset __pzsh_init __pzsh_stale __pzsh_input
 fi
-"#
+"
     .to_string()
 }
 
 fn generate_bash_init() -> String {
     r#"# pzsh initialization for bash
-# Add to your .bashrc: eval "$(pzsh init bash)"
+# Add to your .bashrc: pzsh init --shell bash --print >> ~/.bashrc
 
 # Enable colors
 export CLICOLOR=1
@@ -565,10 +892,117 @@ HISTFILESIZE=20000
 HISTCONTROL=ignoreboth
 shopt -s histappend
 
-# Load pzsh config
+# Load pzsh config from the init cache; compile only when an input changed
 if [[ -f ~/.pzshrc ]]; then
-    # Source compiled config (O(1))
-    eval "$(pzsh compile --config ~/.pzshrc 2>/dev/null || echo "")"
+    __pzsh_init=${XDG_CACHE_HOME:-$HOME/.cache}/pzsh/init.bash
+    __pzsh_stale=1
+    if [[ -f $__pzsh_init && -f $__pzsh_init.inputs ]]; then
+        __pzsh_stale=0
+        while IFS= read -r __pzsh_input; do
+            if [[ ! -e $__pzsh_input || $__pzsh_input -nt $__pzsh_init ]]; then
+                __pzsh_stale=1
+                break
+            fi
+        done < "$__pzsh_init.inputs"
+    fi
+    if (( __pzsh_stale )); then
+        pzsh compile --config ~/.pzshrc --cache --shell bash >/dev/null 2>&1
+    fi
+    [[ -f $__pzsh_init ]] && . "$__pzsh_init"
+    unset __pzsh_init __pzsh_stale __pzsh_input
+fi
+"#
+    .to_string()
+}
+
+fn generate_fish_init() -> String {
+    r#"# pzsh initialization for fish
+# Add to your config.fish:
+# pzsh init --shell fish --print >> ~/.config/fish/config.fish
+
+# No startup banner
+set -g fish_greeting ''
+
+# Load pzsh config from the init cache; compile only when an input changed
+if test -f ~/.pzshrc
+    set -l pzsh_cache ~/.cache
+    test -n "$XDG_CACHE_HOME"; and set pzsh_cache $XDG_CACHE_HOME
+    set -l pzsh_init $pzsh_cache/pzsh/init.fish
+    set -l pzsh_stale 1
+    if test -f $pzsh_init; and test -f $pzsh_init.inputs
+        set pzsh_stale 0
+        set -l pzsh_mtime (path mtime $pzsh_init)
+        while read -l pzsh_input
+            if not test -e $pzsh_input; or test (path mtime $pzsh_input) -ge $pzsh_mtime
+                set pzsh_stale 1
+                break
+            end
+        end <$pzsh_init.inputs
+    end
+    if test $pzsh_stale = 1
+        pzsh compile --config ~/.pzshrc --cache --shell fish >/dev/null 2>&1
+    end
+    test -f $pzsh_init; and source $pzsh_init
+end
+"#
+    .to_string()
+}
+
+fn generate_nushell_init() -> String {
+    r#"# pzsh initialization for Nushell
+# Add to your env.nu: pzsh init --shell nushell --print >> ~/.config/nushell/env.nu
+# then add to config.nu: source ~/.cache/pzsh/init.nu
+# (Nushell only sources files that exist when config.nu is parsed; use
+# $XDG_CACHE_HOME/pzsh/init.nu there if you set XDG_CACHE_HOME)
+
+# Load pzsh config from the init cache; compile only when an input changed
+let pzsh_init = ($env.XDG_CACHE_HOME? | default ($env.HOME | path join .cache) | path join pzsh init.nu)
+if ("~/.pzshrc" | path expand | path exists) {
+    let pzsh_inputs = $"($pzsh_init).inputs"
+    let pzsh_stale = if ($pzsh_init | path exists) and ($pzsh_inputs | path exists) {
+        let pzsh_mtime = (ls -D $pzsh_init | get 0.modified)
+        open --raw $pzsh_inputs | lines | any {|input|
+            (not ($input | path exists)) or ((ls -D $input | get 0.modified) >= $pzsh_mtime)
+        }
+    } else {
+        true
+    }
+    if $pzsh_stale {
+        ^pzsh compile --config ~/.pzshrc --cache --shell nushell | complete | ignore
+    }
+}
+if not ($pzsh_init | path exists) {
+    mkdir ($pzsh_init | path dirname)
+    "" | save -f $pzsh_init
+}
+"#
+    .to_string()
+}
+
+fn generate_sh_init() -> String {
+    r#"# pzsh initialization for POSIX sh
+# Add to your ~/.profile (or the file named by $ENV):
+# pzsh init --shell sh --print >> ~/.profile
+
+# Load pzsh config from the init cache; compile only when an input changed
+if [ -f ~/.pzshrc ]; then
+    __pzsh_init=${XDG_CACHE_HOME:-$HOME/.cache}/pzsh/init.sh
+    __pzsh_stale() {
+        [ -f "$__pzsh_init" ] && [ -f "$__pzsh_init.inputs" ] || return 0
+        set --
+        while IFS= read -r __pzsh_input; do
+            [ -e "$__pzsh_input" ] || return 0
+            set -- "$@" "$__pzsh_input"
+        done < "$__pzsh_init.inputs"
+        # `find -newer` is the portable form of `test -nt`
+        [ $# -gt 0 ] && [ -n "$(find "$@" -prune -newer "$__pzsh_init")" ]
+    }
+    if __pzsh_stale; then
+        pzsh compile --config ~/.pzshrc --cache --shell sh >/dev/null 2>&1
+    fi
+    [ -f "$__pzsh_init" ] && . "$__pzsh_init"
+    unset -f __pzsh_stale
+    unset __pzsh_init __pzsh_input
 fi
 "#
     .to_string()
@@ -585,106 +1019,214 @@ mod tests {
     }
 
     #[test]
-    fn test_lint_detects_subprocess() {
-        let content = r#"
-export GOROOT="$(brew --prefix golang)/libexec"
-"#;
-        let result = lint_config(content);
-        assert!(!result.passed());
-        assert!(!result.issues.is_empty());
+    fn test_bench_result_from_times() {
+        let times = (1..=100).rev().map(Duration::from_millis).collect();
+        let result = BenchResult::from_times(times);
+        assert_eq!(result.iterations, 100);
+        assert_eq!(result.min, Duration::from_millis(1));
+        assert_eq!(result.max, Duration::from_millis(100));
+        assert_eq!(result.p50, Duration::from_millis(51));
+        assert_eq!(result.p99, Duration::from_millis(100));
+        assert!(!result.passed);
     }
 
-    #[test]
-    fn test_lint_detects_backticks() {
-        let content = r"
-export DATE=`date`
-";
-        let result = lint_config(content);
-        assert!(!result.passed());
+    fn have_bash() -> bool {
+        Command::new("bash")
+            .args(["-c", "exit"])
+            .status()
+            .is_ok_and(|s| s.success())
     }
 
     #[test]
-    fn test_lint_detects_oh_my_zsh() {
-        let content = r"
-source $ZSH/oh-my-zsh.sh
-";
-        let result = lint_config(content);
-        assert!(!result.passed());
+    fn test_real_bench_runs_script() {
+        if !have_bash() {
+            return;
+        }
+        let marker = std::env::temp_dir().join(format!("pzsh-real-bench-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let script = format!("echo run >> '{}'\n", marker.display());
+
+        let result = run_real_bench(ShellType::Bash, &script, 3).unwrap();
+        assert_eq!(result.baseline.iterations, 3);
+        assert_eq!(result.with_pzsh.iterations, 3);
+        // Warmup plus three timed runs; the baseline never sources the script
+        let runs = std::fs::read_to_string(&marker).unwrap();
+        assert_eq!(runs.lines().count(), 4);
+        std::fs::remove_file(&marker).unwrap();
+
+        let output = result.format();
+        assert!(output.contains("bash -i -c exit (3 iterations)"));
+        assert!(output.contains("baseline"));
+        assert!(output.contains("Overhead:"));
     }
 
     #[test]
-    fn test_lint_clean_config() {
-        let content = r#"
-export EDITOR="vim"
-alias ll="ls -la"
-"#;
-        let result = lint_config(content);
-        assert!(result.passed());
-        assert!(result.issues.is_empty());
+    fn test_init_recompiles_when_include_changes() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::SystemTime;
+
+        if !have_bash() {
+            return;
+        }
+        for shell in [ShellType::Bash, ShellType::Sh] {
+            let dir = crate::test_util::temp_dir(&format!("init-stale-{}", shell.name()));
+            let rc = dir.join(".pzshrc");
+            let include = dir.join("work.toml");
+            let set_modified = |file: &Path, time: SystemTime| {
+                std::fs::File::options()
+                    .append(true)
+                    .open(file)
+                    .unwrap()
+                    .set_modified(time)
+                    .unwrap();
+            };
+            for (file, content) in [(&rc, "include = [\"work.toml\"]\n"), (&include, "")] {
+                std::fs::write(file, content).unwrap();
+                set_modified(file, SystemTime::now() - Duration::from_secs(10));
+            }
+            // Stand-in for pzsh that logs how it was called
+            let log = dir.join("log");
+            let bin = dir.join("bin");
+            std::fs::create_dir(&bin).unwrap();
+            let pzsh = bin.join("pzsh");
+            std::fs::write(
+                &pzsh,
+                format!("#!/bin/sh\necho \"$@\" >> '{}'\n", log.display()),
+            )
+            .unwrap();
+            std::fs::set_permissions(&pzsh, std::fs::Permissions::from_mode(0o755)).unwrap();
+            InitCache::new(dir.join("cache/pzsh"))
+                .write(shell, "key", "", &[rc.clone(), include.clone()])
+                .unwrap();
+
+            let init = generate_shell_init(shell.name());
+            let path = format!(
+                "{}:{}",
+                bin.display(),
+                std::env::var("PATH").unwrap_or_default()
+            );
+            let start = || {
+                let status = Command::new(shell.name())
+                    .args(["-c", &init])
+                    .env("HOME", &dir)
+                    .env("XDG_CACHE_HOME", dir.join("cache"))
+                    .env("PATH", &path)
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            };
+            start();
+            assert!(!log.exists(), "{}", shell.name());
+
+            std::fs::write(&include, "[env]\nA = \"1\"\n").unwrap();
+            set_modified(&include, SystemTime::now() + Duration::from_secs(10));
+            start();
+            assert_eq!(
+                std::fs::read_to_string(&log).unwrap(),
+                format!(
+                    "compile --config {} --cache --shell {}\n",
+                    rc.display(),
+                    shell.name()
+                )
+            );
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
     }
 
     #[test]
-    fn test_profile_under_10ms() {
-        let result = run_profile();
-        assert!(result.passed, "Profile should pass under 10ms");
+    fn test_interactive_shell_rc_file_per_shell() {
+        let dir = crate::test_util::temp_dir("rc-name");
+        let command = interactive_shell(ShellType::Bash, &dir, "true\n").unwrap();
+        let rc_file = dir.join(".bashrc");
+        assert!(rc_file.exists());
+        assert!(command.get_args().any(|arg| arg == rc_file.as_os_str()));
+        assert!(!dir.join(".zshrc").exists());
+
+        interactive_shell(ShellType::Zsh, &dir, "true\n").unwrap();
+        assert!(dir.join(".zshrc").exists());
+        std::fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
-    fn test_generate_init_config() {
-        let config = generate_init_config("zsh");
-        assert!(config.contains("shell = \"zsh\""));
-        assert!(config.contains("startup_budget_ms = 10"));
+    fn test_real_bench_unsupported_shell() {
+        let err = run_real_bench(ShellType::Fish, "", 1).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
     }
 
     #[test]
-    fn test_lint_detects_nvm() {
-        let content = r"
-source ~/.nvm/nvm.sh
-";
-        let result = lint_config(content);
-        assert!(!result.issues.is_empty());
-        assert!(result.issues.iter().any(|i| i.message.contains("NVM")));
+    fn test_profile_runs_generated_script() {
+        if !have_bash() {
+            return;
+        }
+        let config = CompiledConfig::from_toml(
+            "[pzsh]\nshell = \"bash\"\n[aliases]\nll = \"ls -la\"\n[plugins]\nenabled = [\"git\"]\nlazy = [\"docker\"]\n",
+        )
+        .unwrap();
+        let integration = crate::shell::ShellIntegration::new(ShellType::Bash, config);
+        let result = run_profile(&integration).unwrap();
+        assert_eq!(result.runs, PROFILE_RUNS);
+
+        let names: Vec<&str> = result.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(&names[..3], ["header", "env", "aliases"]);
+        assert!(names.contains(&"keybindings"));
+        assert!(names.contains(&"plugins/git"));
+        assert!(names.contains(&"plugins/lazy"));
+        let aliases = &result.sections[2];
+        assert!(aliases.lines > 0);
+        assert_eq!(
+            result.total_time,
+            result.sections.iter().map(|s| s.time).sum::<Duration>()
+        );
     }
 
     #[test]
-    fn test_lint_detects_conda() {
-        let content = r"
-source ~/miniconda3/etc/profile.d/conda.sh
-";
-        let result = lint_config(content);
-        assert!(!result.issues.is_empty());
-        assert!(result.issues.iter().any(|i| i.message.contains("conda")));
+    fn test_profile_unsupported_shell() {
+        let integration =
+            crate::shell::ShellIntegration::new(ShellType::Fish, CompiledConfig::default());
+        let err = run_profile(&integration).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
     }
 
     #[test]
-    fn test_lint_detects_eval() {
-        let content = r#"
-eval "$(pyenv init -)"
-"#;
-        let result = lint_config(content);
-        assert!(!result.passed());
-        assert!(result.issues.iter().any(|i| i.message.contains("eval")));
+    fn test_parse_profile_log() {
+        let log = "header 10.000\nenv 10.001\nend 10,004\nheader 20.000\nenv 20.003\nend 20.004\n";
+        let runs = parse_profile_log(log).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0][0].0, "header");
+        assert_eq!(runs[0][1].0, "env");
+        assert_eq!(runs[1][0].1.as_micros(), 3000);
+
+        // A run that never reached the end marker
+        assert!(parse_profile_log("header 1.0\nenv 1.1\n").is_none());
+        assert!(parse_profile_log("").is_none());
     }
 
     #[test]
-    fn test_lint_ignores_comments() {
-        let content = r#"
-# eval "$(something)"
-# `backticks`
-# source nvm.sh
-"#;
-        let result = lint_config(content);
-        // Comments should be ignored for backticks and eval, but not for other patterns
+    fn test_profile_runs_requires_bash_5() {
+        let err = profile_runs(ShellType::Bash, "header \nenv \nend \n").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("bash 5"));
+
+        let err = profile_runs(ShellType::Zsh, "header 1.0\n").unwrap_err();
         assert!(
-            !result.issues.iter().any(|i| i.message.contains("eval")),
-            "eval in comments should be ignored"
+            err.to_string()
+                .contains("zsh did not finish the profiled script")
         );
-        assert!(
-            !result.issues.iter().any(|i| i.message.contains("backtick")),
-            "backticks in comments should be ignored"
+        assert_eq!(
+            profile_runs(ShellType::Bash, "header 1.0\nend 1.5\n")
+                .unwrap()
+                .len(),
+            1
         );
     }
 
+    #[test]
+    fn test_generate_init_config() {
+        let config = generate_init_config("zsh");
+        assert!(config.contains("shell = \"zsh\""));
+        assert!(config.contains("startup_budget_ms = 10"));
+    }
+
     #[test]
     fn test_bench_result_format() {
         let result = BenchResult {
@@ -721,74 +1263,54 @@ eval "$(pyenv init -)"
         assert!(formatted.contains("✗"));
     }
 
-    #[test]
-    fn test_lint_result_format_with_issues() {
-        let result = LintResult {
-            issues: vec![
-                LintIssue {
-                    severity: LintSeverity::Error,
-                    message: "test error".to_string(),
-                    line: Some(10),
-                    fix: Some("fix it".to_string()),
-                },
-                LintIssue {
-                    severity: LintSeverity::Warning,
-                    message: "test warning".to_string(),
-                    line: None,
-                    fix: None,
-                },
-                LintIssue {
-                    severity: LintSeverity::Info,
-                    message: "test info".to_string(),
-                    line: Some(5),
-                    fix: None,
-                },
-            ],
-        };
-        let formatted = result.format();
-        assert!(formatted.contains("[error]"));
-        assert!(formatted.contains("[warning]"));
-        assert!(formatted.contains("[info]"));
-        assert!(formatted.contains("(line 10)"));
-        assert!(formatted.contains("fix: fix it"));
-        assert!(formatted.contains("1 errors, 1 warnings"));
-    }
-
-    #[test]
-    fn test_lint_result_format_empty() {
-        let result = LintResult { issues: vec![] };
-        let formatted = result.format();
-        assert!(formatted.contains("0 issues found"));
+    fn profile(sections: &[(&str, u64)], passed: bool) -> ProfileResult {
+        let sections: Vec<SectionProfile> = sections
+            .iter()
+            .map(|(name, micros)| SectionProfile {
+                name: (*name).to_string(),
+                time: Duration::from_micros(*micros),
+                lines: 2,
+            })
+            .collect();
+        ProfileResult {
+            shell: ShellType::Zsh,
+            runs: PROFILE_RUNS,
+            total_time: sections.iter().map(|s| s.time).sum(),
+            sections,
+            passed,
+        }
     }
 
     #[test]
     fn test_profile_result_format() {
-        let result = ProfileResult {
-            parse_time: Duration::from_micros(500),
-            env_time: Duration::from_micros(100),
-            alias_time: Duration::from_micros(50),
-            prompt_time: Duration::from_micros(200),
-            total_time: Duration::from_micros(850),
-            passed: true,
-        };
-        let formatted = result.format();
-        assert!(formatted.contains("parse:"));
-        assert!(formatted.contains("env:"));
-        assert!(formatted.contains("total:"));
-        assert!(formatted.contains("✓"));
+        let result = profile(
+            &[
+                ("header", 10),
+                ("env", 100),
+                ("plugins/git", 300),
+                ("plugins/lazy", 200),
+            ],
+            true,
+        );
+        let formatted = result.format(false);
+        assert!(formatted.contains("Startup Profile: zsh"));
+        assert!(formatted.contains("├─ env"));
+        assert!(formatted.contains("├─ plugins"));
+        assert!(formatted.contains("0.500ms"));
+        assert!(formatted.contains("│  ├─ git"));
+        assert!(formatted.contains("│  └─ lazy"));
+        assert!(formatted.contains("└─ total"));
+        assert!(formatted.contains("0.610ms ✓"));
+        assert!(!formatted.contains("lines"));
+
+        let verbose = result.format(true);
+        assert!(verbose.contains("4 lines  82.0%"));
     }
 
     #[test]
     fn test_profile_result_format_failed() {
-        let result = ProfileResult {
-            parse_time: Duration::from_millis(5),
-            env_time: Duration::from_millis(3),
-            alias_time: Duration::from_millis(2),
-            prompt_time: Duration::from_millis(4),
-            total_time: Duration::from_millis(14),
-            passed: false,
-        };
-        let formatted = result.format();
+        let result = profile(&[("completion", 14_000)], false);
+        let formatted = result.format(false);
         assert!(formatted.contains("✗"));
     }
 
@@ -798,6 +1320,13 @@ eval "$(pyenv init -)"
         assert!(config.contains("shell = \"bash\""));
     }
 
+    #[test]
+    fn test_generate_init_config_fish_compiles() {
+        let config = generate_init_config("fish");
+        let compiled = crate::config::CompiledConfig::from_toml(&config).unwrap();
+        assert_eq!(compiled.shell_type, crate::ShellType::Fish);
+    }
+
     #[test]
     fn test_generate_init_config_has_colors() {
         let config = generate_init_config("zsh");
@@ -819,12 +1348,55 @@ eval "$(pyenv init -)"
         assert!(config.contains("docker"));
     }
 
+    #[test]
+    fn test_format_plugin_status() {
+        let config = CompiledConfig {
+            plugins_enabled: vec!["docker".to_string(), "nope".to_string()],
+            plugins_lazy: vec!["kubectl".to_string()],
+            ..CompiledConfig::default()
+        };
+        let integration = crate::shell::ShellIntegration::new(crate::ShellType::Zsh, config);
+        let status = format_plugin_status(&integration);
+        assert!(status.contains("Plugins: docker (load order)\n"));
+        assert!(status.contains("Lazy: kubectl\n"));
+        assert!(status.contains("✗ plugin not found: nope"));
+
+        let integration =
+            crate::shell::ShellIntegration::new(crate::ShellType::Zsh, CompiledConfig::default());
+        assert_eq!(format_plugin_status(&integration), "Plugins: none\n");
+    }
+
+    #[test]
+    fn test_format_explanation() {
+        use crate::shell::DefinitionKind;
+
+        let config = CompiledConfig {
+            aliases: std::iter::once(("gs".to_string(), "git status -sb".to_string())).collect(),
+            plugins_enabled: vec!["git".to_string()],
+            ..CompiledConfig::default()
+        };
+        let integration = crate::shell::ShellIntegration::new(crate::ShellType::Zsh, config);
+        assert_eq!(
+            format_explanation(&integration, DefinitionKind::Alias, "gs").unwrap(),
+            "alias gs\n  ✓ config: git status -sb\n    plugin git: git status (overridden)\n"
+        );
+        assert_eq!(
+            format_explanation(&integration, DefinitionKind::Alias, "gd").unwrap(),
+            "alias gd\n  ✓ plugin git: git diff\n"
+        );
+        assert!(format_explanation(&integration, DefinitionKind::Env, "NOPE").is_none());
+    }
+
     #[test]
     fn test_generate_shell_init_zsh() {
         let init = generate_shell_init("zsh");
         assert!(init.contains("compinit"));
         assert!(init.contains("colors"));
         assert!(init.contains("HISTSIZE"));
+        assert!(init.contains("${XDG_CACHE_HOME:-$HOME/.cache}/pzsh/init.zsh"));
+        assert!(init.contains("$__pzsh_input -nt $__pzsh_init"));
+        assert!(init.contains("--cache --shell zsh"));
+        assert!(!init.contains("source <("));
     }
 
     #[test]
@@ -834,4 +1406,41 @@ eval "$(pyenv init -)"
         assert!(init.contains("HISTSIZE"));
         assert!(init.contains("shopt"));
     }
+
+    #[test]
+    fn test_generate_shell_init_fish() {
+        let init = generate_shell_init("fish");
+        assert!(init.contains("source $pzsh_init"));
+        assert!(init.contains("fish_greeting"));
+        assert!(!init.contains("[["));
+    }
+
+    #[test]
+    fn test_generate_shell_init_nushell() {
+        let init = generate_shell_init("nushell");
+        assert!(init.contains("^pzsh compile --config ~/.pzshrc --cache --shell nushell"));
+        assert!(init.contains("source ~/.cache/pzsh/init.nu"));
+        assert!(init.contains("$\"($pzsh_init).inputs\""));
+        assert!(!init.contains("--output"));
+    }
+
+    #[test]
+    fn test_generate_shell_init_sh() {
+        let init = generate_shell_init("sh");
+        assert!(init.contains("if [ -f ~/.pzshrc ]"));
+        assert!(!init.contains("[["));
+        assert!(!init.contains("shopt"));
+    }
+
+    #[test]
+    fn test_generate_init_config_nushell_and_sh_compile() {
+        for (shell, expected) in [
+            ("nushell", crate::ShellType::Nushell),
+            ("sh", crate::ShellType::Sh),
+        ] {
+            let config = generate_init_config(shell);
+            let compiled = crate::config::CompiledConfig::from_toml(&config).unwrap();
+            assert_eq!(compiled.shell_type, expected);
+        }
+    }
 }
@@ -0,0 +1,413 @@
+//! Lint rule configuration and inline suppressions
+//!
+//! Built-in rules can be re-graded or turned off and custom rules added
+//! through a [`LintSection`]: the `[lint]` section of the linted file or a
+//! `lint.toml` in the dotfiles repository. Single lines are exempted with a
+//! comment naming the rule and a reason:
+//!
+//! ```sh
+//! eval "$(direnv hook zsh)"  # pzsh-ignore: eval -- direnv output is trusted
+//! # pzsh-ignore: nvm, command-substitution -- work laptop only
+//! source "$(brew --prefix nvm)/nvm.sh"
+//! ```
+
+use super::lint::{LintSeverity, builtin_rule_ids};
+use crate::config::{ConfigError, CustomLintRule, LintLevel, LintSection};
+use ahash::AHashMap;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// File name of the repository-wide lint configuration
+pub const LINT_TOML: &str = "lint.toml";
+
+/// Rule id of issues about the `[lint]` configuration itself
+pub(super) const CONFIG_RULE: &str = "lint-config";
+
+/// Rule id of `.pzshrc` files that are not valid TOML
+pub(super) const SYNTAX_RULE: &str = "toml-syntax";
+
+/// Rule id of malformed `# pzsh-ignore` comments
+pub(super) const IGNORE_RULE: &str = "invalid-ignore";
+
+const IGNORE_MARKER: &str = "pzsh-ignore:";
+
+/// Validated lint configuration
+#[derive(Debug, Default)]
+pub(super) struct LintRules {
+    severity: AHashMap<String, LintLevel>,
+    pub(super) custom: Vec<CustomRule>,
+}
+
+/// Custom rule with its matcher compiled
+#[derive(Debug)]
+pub(super) struct CustomRule {
+    pub(super) id: String,
+    matcher: Matcher,
+    severity: LintLevel,
+    pub(super) message: String,
+    pub(super) fix: Option<String>,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Text(String),
+    Regex(Regex),
+}
+
+impl LintRules {
+    /// Validate `section`
+    ///
+    /// # Errors
+    /// Returns error for invalid or duplicate rule ids, rules without
+    /// exactly one of `pattern` and `regex`, invalid regular expressions
+    /// and severity overrides of unknown rules
+    pub(super) fn new(section: &LintSection) -> Result<Self, ConfigError> {
+        let invalid = |reason: String| ConfigError::Invalid(format!("[lint] {reason}"));
+        let mut custom: Vec<CustomRule> = Vec::new();
+        for rule in &section.rules {
+            let CustomLintRule {
+                id,
+                pattern,
+                regex,
+                severity,
+                message,
+                fix,
+            } = rule;
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            {
+                return Err(invalid(format!(
+                    "invalid rule id {id:?}: use lowercase letters, digits and '-'"
+                )));
+            }
+            if is_reserved(id) || custom.iter().any(|r| &r.id == id) {
+                return Err(invalid(format!("duplicate rule id: {id}")));
+            }
+            let matcher = match (pattern, regex) {
+                (Some(pattern), None) if !pattern.is_empty() => Matcher::Text(pattern.clone()),
+                (None, Some(regex)) => Matcher::Regex(
+                    Regex::new(regex).map_err(|e| invalid(format!("rule {id}: {e}")))?,
+                ),
+                _ => {
+                    return Err(invalid(format!(
+                        "rule {id}: set exactly one of `pattern` and `regex`"
+                    )));
+                }
+            };
+            custom.push(CustomRule {
+                id: id.clone(),
+                matcher,
+                severity: *severity,
+                message: message.clone(),
+                fix: fix.clone(),
+            });
+        }
+
+        let mut ids: Vec<&String> = section.severity.keys().collect();
+        ids.sort();
+        for id in ids {
+            if !builtin_rule_ids().any(|b| b == id) && !custom.iter().any(|r| &r.id == id) {
+                return Err(invalid(format!("unknown rule in [lint.severity]: {id}")));
+            }
+        }
+
+        Ok(Self {
+            severity: section.severity.clone(),
+            custom,
+        })
+    }
+
+    /// Severity of rule `id`, `None` when it is turned off
+    pub(super) fn severity(&self, id: &str, default: LintSeverity) -> Option<LintSeverity> {
+        let level = self.severity.get(id).copied().or_else(|| {
+            self.custom
+                .iter()
+                .find(|rule| rule.id == id)
+                .map(|rule| rule.severity)
+        });
+        match level {
+            None => Some(default),
+            Some(LintLevel::Error) => Some(LintSeverity::Error),
+            Some(LintLevel::Warning) => Some(LintSeverity::Warning),
+            Some(LintLevel::Info) => Some(LintSeverity::Info),
+            Some(LintLevel::Off) => None,
+        }
+    }
+
+    /// Check whether `id` names a built-in or custom rule
+    fn knows(&self, id: &str) -> bool {
+        builtin_rule_ids().any(|b| b == id) || self.custom.iter().any(|rule| rule.id == id)
+    }
+}
+
+impl CustomRule {
+    /// Byte offsets of the matches in `text`
+    pub(super) fn find(&self, text: &str) -> Vec<usize> {
+        match &self.matcher {
+            Matcher::Text(pattern) => text.match_indices(pattern.as_str()).map(|m| m.0).collect(),
+            Matcher::Regex(regex) => regex.find_iter(text).map(|m| m.start()).collect(),
+        }
+    }
+}
+
+/// Ids of built-in rules and of issues the linter reports about itself
+fn is_reserved(id: &str) -> bool {
+    id == CONFIG_RULE
+        || id == IGNORE_RULE
+        || id == SYNTAX_RULE
+        || builtin_rule_ids().any(|b| b == id)
+}
+
+/// Read and validate a `lint.toml`
+///
+/// # Errors
+/// Returns error if the file cannot be read or is not a valid lint
+/// configuration
+pub fn load_lint_toml(path: &Path) -> Result<LintSection, ConfigError> {
+    let content = std::fs::read_to_string(path)?;
+    let section: LintSection = toml::from_str(&content)?;
+    LintRules::new(&section)?;
+    Ok(section)
+}
+
+/// `lint.toml` for the file at `path`
+///
+/// Looks in the directory of the file, following symlinks (so that
+/// `~/.zshrc -> ~/dotfiles/zshrc` finds `~/dotfiles/lint.toml`), and in
+/// its parents up to the root of the git repository.
+#[must_use]
+pub fn find_lint_toml(path: &Path) -> Option<PathBuf> {
+    let path = path.canonicalize().ok()?;
+    for dir in path.ancestors().skip(1) {
+        let candidate = dir.join(LINT_TOML);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Parsed `# pzsh-ignore` comments
+#[derive(Debug, Default)]
+pub(super) struct Suppressions {
+    /// Suppressed rule ids by line (1-based)
+    lines: AHashMap<usize, Vec<String>>,
+    /// Malformed comments: (line, column, message)
+    pub(super) problems: Vec<(usize, usize, String)>,
+}
+
+impl Suppressions {
+    /// Collect the suppressions in `content`
+    ///
+    /// A comment after code applies to its own line; a comment on a line of
+    /// its own applies to the next line that is not a comment. The reason
+    /// after `--` is required, and comments without one suppress nothing.
+    pub(super) fn parse(content: &str, rules: &LintRules) -> Self {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut suppressions = Self::default();
+        for (index, line) in lines.iter().enumerate() {
+            let Some(marker) = line.find(IGNORE_MARKER) else {
+                continue;
+            };
+            let Some(hash) = line[..marker].trim_end().strip_suffix('#') else {
+                continue;
+            };
+            let column = hash.chars().count() + 1;
+            let problem = |message: String| (index + 1, column, message);
+
+            let rest = &line[marker + IGNORE_MARKER.len()..];
+            let (ids, reason) = rest.split_once("--").unwrap_or((rest, ""));
+            let ids: Vec<String> = ids
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect();
+            if ids.is_empty() {
+                suppressions
+                    .problems
+                    .push(problem("pzsh-ignore without a rule id".to_string()));
+                continue;
+            }
+            if reason.trim().is_empty() {
+                suppressions.problems.push(problem(format!(
+                    "pzsh-ignore needs a reason: `# pzsh-ignore: {} -- <reason>`",
+                    ids.join(", ")
+                )));
+                continue;
+            }
+            for id in ids.iter().filter(|id| !rules.knows(id)) {
+                suppressions
+                    .problems
+                    .push(problem(format!("pzsh-ignore of unknown rule: {id}")));
+            }
+
+            let target = if hash.trim().is_empty() {
+                lines[index + 1..]
+                    .iter()
+                    .position(|l| !l.trim_start().starts_with('#'))
+                    .map_or(lines.len() + 1, |i| index + i + 2)
+            } else {
+                index + 1
+            };
+            suppressions.lines.entry(target).or_default().extend(ids);
+        }
+        suppressions
+    }
+
+    /// Check whether rule `id` is suppressed on `line`
+    pub(super) fn contains(&self, line: usize, id: &str) -> bool {
+        self.lines
+            .get(&line)
+            .is_some_and(|ids| ids.iter().any(|i| i == id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(content: &str) -> LintSection {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_severity_overrides() {
+        let rules =
+            LintRules::new(&section("[severity]\nnvm = \"info\"\neval = \"off\"\n")).unwrap();
+        assert_eq!(
+            rules.severity("nvm", LintSeverity::Warning),
+            Some(LintSeverity::Info)
+        );
+        assert_eq!(rules.severity("eval", LintSeverity::Error), None);
+        assert_eq!(
+            rules.severity("backticks", LintSeverity::Error),
+            Some(LintSeverity::Error)
+        );
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules = LintRules::new(&section(
+            r#"
+[[rules]]
+id = "sdkman"
+pattern = "sdkman-init.sh"
+message = "sdkman is slow"
+
+[[rules]]
+id = "pyenv"
+regex = 'pyenv (init|virtualenv-init)'
+severity = "error"
+message = "pyenv init is slow"
+"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            rules.custom[0].find("source ~/.sdkman/bin/sdkman-init.sh"),
+            [21]
+        );
+        assert_eq!(rules.custom[1].find("x; pyenv virtualenv-init -"), [3]);
+        assert_eq!(
+            rules.severity("sdkman", LintSeverity::Error),
+            Some(LintSeverity::Warning)
+        );
+        assert_eq!(
+            rules.severity("pyenv", LintSeverity::Warning),
+            Some(LintSeverity::Error)
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for (content, expected) in [
+            (
+                "[[rules]]\nid = \"eval\"\npattern = \"x\"\nmessage = \"m\"\n",
+                "duplicate rule id",
+            ),
+            (
+                "[[rules]]\nid = \"My Rule\"\npattern = \"x\"\nmessage = \"m\"\n",
+                "invalid rule id",
+            ),
+            ("[[rules]]\nid = \"x\"\nmessage = \"m\"\n", "exactly one of"),
+            (
+                "[[rules]]\nid = \"x\"\nregex = \"(\"\nmessage = \"m\"\n",
+                "rule x",
+            ),
+            ("[severity]\nnope = \"off\"\n", "unknown rule"),
+        ] {
+            let err = LintRules::new(&section(content)).unwrap_err().to_string();
+            assert!(err.contains(expected), "{content:?} gave {err}");
+        }
+        assert!(toml::from_str::<LintSection>("[severity]\neval = \"loud\"\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_later_wins() {
+        let mut base = section(
+            "[severity]\nnvm = \"info\"\n[[rules]]\nid = \"x\"\npattern = \"a\"\nmessage = \"m\"\n",
+        );
+        base.merge(section(
+            "[severity]\nnvm = \"error\"\n[[rules]]\nid = \"x\"\npattern = \"b\"\nmessage = \"m\"\n",
+        ));
+        assert_eq!(base.severity["nvm"], LintLevel::Error);
+        assert_eq!(base.rules.len(), 1);
+        assert_eq!(base.rules[0].pattern.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_suppressions() {
+        let rules = LintRules::default();
+        let content = "\
+eval x  # pzsh-ignore: eval -- trusted
+# pzsh-ignore: nvm, compinit-uncached -- work laptop
+# another comment
+source ~/.nvm/nvm.sh
+eval y # pzsh-ignore: eval
+eval z # pzsh-ignore: evil -- typo
+";
+        let suppressions = Suppressions::parse(content, &rules);
+        assert!(suppressions.contains(1, "eval"));
+        assert!(suppressions.contains(4, "nvm"));
+        assert!(suppressions.contains(4, "compinit-uncached"));
+        assert!(!suppressions.contains(2, "nvm"));
+        // No reason: nothing suppressed
+        assert!(!suppressions.contains(5, "eval"));
+        assert_eq!(
+            suppressions.problems,
+            [
+                (
+                    5,
+                    8,
+                    "pzsh-ignore needs a reason: `# pzsh-ignore: eval -- <reason>`".to_string()
+                ),
+                (6, 8, "pzsh-ignore of unknown rule: evil".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_lint_toml() {
+        let dir = crate::test_util::temp_dir("lint-toml");
+        let repo = dir.join("dotfiles");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("zsh")).unwrap();
+        std::fs::write(repo.join("zsh/zshrc"), "").unwrap();
+        std::fs::write(dir.join(LINT_TOML), "").unwrap();
+
+        // Stops at the repository root
+        assert_eq!(find_lint_toml(&repo.join("zsh/zshrc")), None);
+
+        std::fs::write(repo.join(LINT_TOML), "").unwrap();
+        assert_eq!(
+            find_lint_toml(&repo.join("zsh/zshrc")),
+            Some(repo.canonicalize().unwrap().join(LINT_TOML))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,10 +3,11 @@
 //! O(1) ANSI color rendering with pre-computed escape sequences.
 //! Provides oh-my-zsh compatible color support while maintaining performance.
 
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// ANSI color codes (16-color palette)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Color {
     // Standard colors
@@ -50,7 +51,7 @@ impl Color {
 }
 
 /// Text style attributes
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct Style {
     pub fg: Option<ColorSpec>,
     pub bg: Option<ColorSpec>,
@@ -61,7 +62,7 @@ pub struct Style {
 }
 
 /// Color specification (supports 16, 256, and true color)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ColorSpec {
     /// 16-color ANSI
     Ansi(Color),
@@ -145,6 +146,89 @@ impl Style {
     /// Returns empty string if no styling applied
     #[must_use]
     pub fn to_ansi(&self) -> String {
+        self.sgr_params()
+            .map_or_else(String::new, |params| format!("\x1b[{params}m"))
+    }
+
+    /// Generate bash `PS1` escape for this style (wrapped in `\[ \]`)
+    /// Returns empty string if no styling applied
+    #[must_use]
+    pub fn to_bash(&self) -> String {
+        self.sgr_params()
+            .map_or_else(String::new, |params| format!("\\[\\033[{params}m\\]"))
+    }
+
+    /// Generate zsh prompt escapes for this style (`%B`, `%U`, `%F{..}`, `%K{..}`)
+    /// Returns empty string if no styling applied
+    #[must_use]
+    pub fn to_zsh(&self) -> String {
+        let mut out = String::new();
+        if self.bold {
+            out.push_str("%B");
+        }
+        if self.underline {
+            out.push_str("%U");
+        }
+        if let Some(fg) = &self.fg {
+            out.push_str(&format!("%F{{{}}}", fg.zsh_name()));
+        }
+        if let Some(bg) = &self.bg {
+            out.push_str(&format!("%K{{{}}}", bg.zsh_name()));
+        }
+        out
+    }
+
+    /// Generate zsh prompt escapes that undo [`Style::to_zsh`]
+    #[must_use]
+    pub fn zsh_reset(&self) -> String {
+        let mut out = String::new();
+        if self.bg.is_some() {
+            out.push_str("%k");
+        }
+        if self.fg.is_some() {
+            out.push_str("%f");
+        }
+        if self.underline {
+            out.push_str("%u");
+        }
+        if self.bold {
+            out.push_str("%b");
+        }
+        out
+    }
+
+    /// Generate a fish `set_color` command for this style
+    /// Returns empty string if no styling applied
+    #[must_use]
+    pub fn to_fish(&self) -> String {
+        let mut args = Vec::new();
+        if self.bold {
+            args.push("-o".to_string());
+        }
+        if self.dim {
+            args.push("-d".to_string());
+        }
+        if self.italic {
+            args.push("-i".to_string());
+        }
+        if self.underline {
+            args.push("-u".to_string());
+        }
+        if let Some(bg) = &self.bg {
+            args.push(format!("-b {}", bg.fish_name()));
+        }
+        if let Some(fg) = &self.fg {
+            args.push(fg.fish_name());
+        }
+        if args.is_empty() {
+            String::new()
+        } else {
+            format!("set_color {}", args.join(" "))
+        }
+    }
+
+    /// SGR parameters (`1;32`), or `None` if no styling applied
+    fn sgr_params(&self) -> Option<String> {
         if self.fg.is_none()
             && self.bg.is_none()
             && !self.bold
@@ -152,7 +236,7 @@ impl Style {
             && !self.italic
             && !self.underline
         {
-            return String::new();
+            return None;
         }
 
         let mut codes = Vec::with_capacity(8);
@@ -186,7 +270,73 @@ impl Style {
             }
         }
 
-        format!("\x1b[{}m", codes.join(";"))
+        Some(codes.join(";"))
+    }
+}
+
+impl ColorSpec {
+    /// Color name as accepted by zsh `%F{..}` / `%K{..}`
+    #[must_use]
+    pub fn zsh_name(&self) -> String {
+        match self {
+            Self::Ansi(c) => match c {
+                Color::Black => "black".to_string(),
+                Color::Red => "red".to_string(),
+                Color::Green => "green".to_string(),
+                Color::Yellow => "yellow".to_string(),
+                Color::Blue => "blue".to_string(),
+                Color::Magenta => "magenta".to_string(),
+                Color::Cyan => "cyan".to_string(),
+                Color::White => "white".to_string(),
+                bright => (*bright as u8).to_string(),
+            },
+            Self::Palette(n) => n.to_string(),
+            Self::Rgb(r, g, b) => format!("#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+
+    /// Color name as accepted by fish `set_color` (palette colors become RGB)
+    #[must_use]
+    pub fn fish_name(&self) -> String {
+        const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+        const NAMES: [&str; 16] = [
+            "black",
+            "red",
+            "green",
+            "yellow",
+            "blue",
+            "magenta",
+            "cyan",
+            "white",
+            "brblack",
+            "brred",
+            "brgreen",
+            "bryellow",
+            "brblue",
+            "brmagenta",
+            "brcyan",
+            "brwhite",
+        ];
+        match *self {
+            Self::Ansi(c) => NAMES[c as usize].to_string(),
+            Self::Palette(n @ 0..=15) => NAMES[n as usize].to_string(),
+            Self::Palette(n @ 16..=231) => {
+                // 6x6x6 color cube
+                let cube = n - 16;
+                format!(
+                    "{:02x}{:02x}{:02x}",
+                    LEVELS[(cube / 36) as usize],
+                    LEVELS[(cube / 6 % 6) as usize],
+                    LEVELS[(cube % 6) as usize]
+                )
+            }
+            Self::Palette(n) => {
+                // Grayscale ramp
+                let level = 8 + (n - 232) * 10;
+                format!("{level:02x}{level:02x}{level:02x}")
+            }
+            Self::Rgb(r, g, b) => format!("{r:02x}{g:02x}{b:02x}"),
+        }
     }
 }
 
@@ -640,6 +790,53 @@ mod tests {
         assert_eq!(style.to_ansi(), "\x1b[43m");
     }
 
+    #[test]
+    fn test_style_to_zsh() {
+        let style = Style::new()
+            .fg_ansi(Color::Green)
+            .bg_ansi(Color::Blue)
+            .bold();
+        assert_eq!(style.to_zsh(), "%B%F{green}%K{blue}");
+        assert_eq!(style.zsh_reset(), "%k%f%b");
+        assert_eq!(Style::new().to_zsh(), "");
+        assert_eq!(Style::new().zsh_reset(), "");
+    }
+
+    #[test]
+    fn test_colorspec_zsh_name() {
+        assert_eq!(ColorSpec::Ansi(Color::Cyan).zsh_name(), "cyan");
+        assert_eq!(ColorSpec::Ansi(Color::BrightBlack).zsh_name(), "8");
+        assert_eq!(ColorSpec::Palette(208).zsh_name(), "208");
+        assert_eq!(ColorSpec::Rgb(255, 128, 0).zsh_name(), "#ff8000");
+    }
+
+    #[test]
+    fn test_style_to_fish() {
+        let style = Style::new()
+            .fg_ansi(Color::Green)
+            .bg_ansi(Color::Blue)
+            .bold();
+        assert_eq!(style.to_fish(), "set_color -o -b blue green");
+        assert_eq!(Style::new().to_fish(), "");
+    }
+
+    #[test]
+    fn test_colorspec_fish_name() {
+        assert_eq!(ColorSpec::Ansi(Color::Cyan).fish_name(), "cyan");
+        assert_eq!(ColorSpec::Ansi(Color::BrightBlack).fish_name(), "brblack");
+        assert_eq!(ColorSpec::Palette(9).fish_name(), "brred");
+        assert_eq!(ColorSpec::Palette(208).fish_name(), "ff8700");
+        assert_eq!(ColorSpec::Palette(244).fish_name(), "808080");
+        assert_eq!(ColorSpec::Rgb(255, 128, 0).fish_name(), "ff8000");
+    }
+
+    #[test]
+    fn test_style_to_bash() {
+        let style = Style::new().fg_ansi(Color::Green).bold();
+        assert_eq!(style.to_bash(), "\\[\\033[1;32m\\]");
+        assert_eq!(Style::new().to_bash(), "");
+    }
+
     #[test]
     fn test_reset_constant() {
         assert_eq!(RESET, "\x1b[0m");
@@ -3,6 +3,7 @@
 //! Provides O(1) cached completions with optional ML-based inference.
 //! Supports aprender-shell model for intelligent auto-complete.
 
+use crate::config::CompiledConfig;
 use ahash::AHashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -575,6 +576,12 @@ impl CompletionEngine {
         self.ml_provider = Some(Box::new(provider));
     }
 
+    /// Whether an ML provider is set
+    #[must_use]
+    pub fn has_ml_provider(&self) -> bool {
+        self.ml_provider.is_some()
+    }
+
     /// Generate completions for input
     #[must_use]
     pub fn complete(&self, line: &str, cursor: usize) -> Vec<CompletionItem> {
@@ -635,6 +642,21 @@ pub fn default_engine(aliases: Arc<AHashMap<String, String>>) -> CompletionEngin
     engine
 }
 
+/// Completion engine for a compiled config, with the `[completion]`
+/// `model_path` model loaded when set
+///
+/// # Errors
+/// Returns error if the configured model cannot be loaded
+pub fn engine_for_config(config: &CompiledConfig) -> Result<CompletionEngine, String> {
+    let mut engine = default_engine(Arc::new(config.aliases.clone()));
+    if let Some(path) = &config.completion_model {
+        let mut completer = AprenderShellCompleter::new();
+        completer.load_model(path.clone())?;
+        engine.set_ml_provider(completer);
+    }
+    Ok(engine)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1027,7 +1049,24 @@ mod tests {
     fn test_completion_engine_set_ml_provider() {
         let mut engine = CompletionEngine::new();
         engine.set_ml_provider(AprenderShellCompleter::new());
-        // Should not panic
+        assert!(engine.has_ml_provider());
+    }
+
+    #[test]
+    fn test_engine_for_config_loads_model() {
+        let mut config = CompiledConfig::default();
+        assert!(!engine_for_config(&config).unwrap().has_ml_provider());
+
+        let dir = crate::test_util::temp_dir("model");
+        let model = dir.join("shell.apr");
+        std::fs::write(&model, b"apr").unwrap();
+        config.completion_model = Some(model);
+        assert!(engine_for_config(&config).unwrap().has_ml_provider());
+
+        config.completion_model = Some(dir.join("missing.apr"));
+        let err = engine_for_config(&config).err().unwrap();
+        assert!(err.contains("Model not found"));
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
@@ -0,0 +1,63 @@
+//! Content hashing for cache keys and snapshot checksums
+
+use super::Machine;
+use std::fs;
+use std::path::PathBuf;
+
+/// 64-bit FNV-1a, stable across builds and platforms
+pub struct Fnv1a(u64);
+
+impl Fnv1a {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// Hash `bytes` with a length prefix, so fields can't run together
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    #[must_use]
+    pub const fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hash of the pzsh version, `machine` and each input's path and content
+///
+/// A missing file hashes differently from an empty one, and a directory
+/// hashes its sorted entry names, so adding a plugin changes the hash.
+#[must_use]
+pub fn hash_inputs(inputs: &[PathBuf], machine: &Machine) -> Fnv1a {
+    let mut hash = Fnv1a::new();
+    hash.write(env!("CARGO_PKG_VERSION").as_bytes());
+    hash.write(machine.hostname.as_bytes());
+    hash.write(machine.os.as_bytes());
+    for input in inputs {
+        hash.write(input.as_os_str().as_encoded_bytes());
+        if let Ok(entries) = fs::read_dir(input) {
+            let mut names: Vec<_> = entries.flatten().map(|entry| entry.file_name()).collect();
+            names.sort();
+            hash.write(b"\0dir");
+            for name in names {
+                hash.write(name.as_encoded_bytes());
+            }
+            continue;
+        }
+        match fs::read(input) {
+            Ok(content) => hash.write(&content),
+            Err(_) => hash.write(b"\0missing"),
+        }
+    }
+    hash
+}
@@ -0,0 +1,396 @@
+//! `${VAR}` interpolation for `[env]`
+//!
+//! Values may reference other variables as `$VAR`, `${VAR}` or
+//! `${VAR:-default}` (default when unset or empty); `$$` is a literal `$`.
+//!
+//! Compiling resolves references between variables of the same table.
+//! Anything else, including a variable referencing itself as in
+//! `PATH = "${GOPATH}/bin:${PATH}"`, is kept as a reference for the shell to
+//! expand at startup. The compile-time environment is never read: compiled
+//! scripts are cached and reused, so a baked-in `$PATH` or `$HOME` would be
+//! whatever the compiling process had. The compiled values are templates: `$$` is a literal
+//! `$` and `${VAR}` or `${VAR:-default}` is a runtime reference. A table
+//! variable whose value still has runtime references is itself referenced
+//! at runtime, which works because variables are exported in dependency
+//! order.
+
+use ahash::AHashMap;
+
+/// Piece of a compiled value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvPart<'a> {
+    /// Literal text
+    Literal(&'a str),
+    /// `${NAME}` or `${NAME:-default}`; the default is a template
+    Ref {
+        name: &'a str,
+        default: Option<&'a str>,
+    },
+}
+
+/// Split a compiled value into literal text and runtime references
+pub fn env_parts(template: &str) -> Vec<EnvPart<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(at) = rest.find('$') {
+        if at > 0 {
+            parts.push(EnvPart::Literal(&rest[..at]));
+        }
+        let after = &rest[at + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            parts.push(EnvPart::Literal("$"));
+            rest = after;
+        } else if let Some((inner, end)) = after
+            .strip_prefix('{')
+            .and_then(|inner| Some((inner, closing_brace(inner)?)))
+        {
+            let (name, default) = match inner[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&inner[..end], None),
+            };
+            parts.push(EnvPart::Ref { name, default });
+            rest = &inner[end + 1..];
+        } else {
+            parts.push(EnvPart::Literal("$"));
+            rest = after;
+        }
+    }
+    if !rest.is_empty() {
+        parts.push(EnvPart::Literal(rest));
+    }
+    parts
+}
+
+/// Expand the runtime references of a compiled value with `lookup`
+pub fn render(template: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    for part in env_parts(template) {
+        match part {
+            EnvPart::Literal(text) => output.push_str(text),
+            EnvPart::Ref { name, default } => match (lookup(name), default) {
+                (Some(value), Some(_)) if !value.is_empty() => output.push_str(&value),
+                (Some(value), None) => output.push_str(&value),
+                (_, Some(default)) => output.push_str(&render(default, lookup)),
+                (None, None) => {}
+            },
+        }
+    }
+    output
+}
+
+/// Expand compiled values in export order; a reference to an earlier
+/// variable sees its expanded value, anything else comes from `lookup`
+pub fn render_all<'a>(
+    vars: impl IntoIterator<Item = (&'a str, &'a str)>,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Vec<(String, String)> {
+    let mut rendered: Vec<(String, String)> = Vec::new();
+    for (name, template) in vars {
+        let value = render(template, &|var| {
+            rendered
+                .iter()
+                .rev()
+                .find(|(n, _)| n == var && var != name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| lookup(var))
+        });
+        rendered.push((name.to_string(), value));
+    }
+    rendered
+}
+
+/// Names of the variables a compiled value references at runtime
+pub fn references(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    for part in env_parts(template) {
+        if let EnvPart::Ref { name, default } = part {
+            names.push(name);
+            names.extend(default.map(references).unwrap_or_default());
+        }
+    }
+    names
+}
+
+/// Resolve references between the variables of `env`
+///
+/// Returns the compiled values in export order: each variable after the
+/// variables it references, otherwise sorted by name.
+///
+/// # Errors
+/// Returns an error for malformed references and reference cycles
+pub fn interpolate_env(env: &AHashMap<String, String>) -> Result<Vec<(String, String)>, String> {
+    let mut names: Vec<&String> = env.keys().collect();
+    names.sort_unstable();
+    let mut resolver = Resolver {
+        env,
+        resolved: AHashMap::with_capacity(env.len()),
+        order: Vec::with_capacity(env.len()),
+        stack: Vec::new(),
+    };
+    for name in names {
+        resolver.value(name)?;
+    }
+    let Resolver {
+        mut resolved,
+        order,
+        ..
+    } = resolver;
+    Ok(order
+        .into_iter()
+        .map(|name| {
+            let value = resolved.remove(&name).unwrap_or_default();
+            (name, value)
+        })
+        .collect())
+}
+
+struct Resolver<'a> {
+    env: &'a AHashMap<String, String>,
+    /// Compiled values
+    resolved: AHashMap<String, String>,
+    /// Names in the order they finished resolving
+    order: Vec<String>,
+    /// Names being resolved, outermost first
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// Compiled value of a table variable
+    fn value(&mut self, name: &str) -> Result<String, String> {
+        if let Some(value) = self.resolved.get(name) {
+            return Ok(value.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|n| n == name) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(format!("cycle: {}", cycle.join(" -> ")));
+        }
+        let raw = self.env.get(name).map_or("", String::as_str);
+        self.stack.push(name.to_string());
+        let value = self.expand(raw)?;
+        self.stack.pop();
+        self.resolved.insert(name.to_string(), value.clone());
+        self.order.push(name.to_string());
+        Ok(value)
+    }
+
+    fn expand(&mut self, text: &str) -> Result<String, String> {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(at) = rest.find('$') {
+            output.push_str(&rest[..at]);
+            let after = &rest[at + 1..];
+            if let Some(after) = after.strip_prefix('$') {
+                output.push_str("$$");
+                rest = after;
+            } else if let Some(inner) = after.strip_prefix('{') {
+                let end = closing_brace(inner)
+                    .ok_or_else(|| self.error(&format!("unterminated ${{{inner}")))?;
+                output.push_str(&self.reference(&inner[..end])?);
+                rest = &inner[end + 1..];
+            } else {
+                let len = name_len(after);
+                if len == 0 {
+                    output.push_str("$$");
+                } else {
+                    output.push_str(&self.reference(&after[..len])?);
+                }
+                rest = &after[len..];
+            }
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    /// Compile `NAME` or `NAME:-default`
+    fn reference(&mut self, reference: &str) -> Result<String, String> {
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if name.is_empty() || name_len(name) != name.len() {
+            return Err(self.error(&format!("bad substitution ${{{reference}}}")));
+        }
+        let default = default.map(|d| self.expand(d)).transpose()?;
+        let runtime = || match &default {
+            Some(default) => format!("${{{name}:-{default}}}"),
+            None => format!("${{{name}}}"),
+        };
+
+        // Other variables and self-references expand at startup
+        let is_self = self.stack.last().is_some_and(|current| current == name);
+        if is_self || !self.env.contains_key(name) {
+            return Ok(runtime());
+        }
+        let value = self.value(name)?;
+        if !references(&value).is_empty() {
+            // Only known at startup, where `name` is already exported
+            return Ok(runtime());
+        }
+        Ok(match default {
+            Some(default) if value.is_empty() => default,
+            _ => value,
+        })
+    }
+
+    fn error(&self, message: &str) -> String {
+        match self.stack.last() {
+            Some(name) => format!("{name}: {message}"),
+            None => message.to_string(),
+        }
+    }
+}
+
+/// Length of the variable name at the start of `text`
+fn name_len(text: &str) -> usize {
+    if text.starts_with(|c: char| c.is_ascii_digit()) {
+        return 0;
+    }
+    text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len())
+}
+
+/// Index of the `}` closing a `${`, allowing nested references
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(pairs: &[(&str, &str)]) -> AHashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/u".to_string()),
+            "PATH" => Some("/usr/bin:/bin".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    fn resolve(pairs: &[(&str, &str)]) -> Result<Vec<(String, String)>, String> {
+        interpolate_env(&env(pairs))
+    }
+
+    fn value<'a>(resolved: &'a [(String, String)], name: &str) -> &'a str {
+        &resolved.iter().find(|(k, _)| k == name).unwrap().1
+    }
+
+    #[test]
+    fn test_interpolate_in_dependency_order() {
+        let resolved = resolve(&[
+            ("GOBIN", "$GOPATH/bin"),
+            ("GOPATH", "/opt/go"),
+            ("PATH", "${GOBIN}:${PATH}"),
+            ("EDITOR", "vim"),
+        ])
+        .unwrap();
+        let pairs: Vec<(&str, &str)> = resolved
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("EDITOR", "vim"),
+                ("GOPATH", "/opt/go"),
+                ("GOBIN", "/opt/go/bin"),
+                // The self-reference expands at startup
+                ("PATH", "/opt/go/bin:${PATH}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpolate_keeps_runtime_references() {
+        let resolved = resolve(&[
+            ("GOPATH", "$HOME/go"),
+            ("GOBIN", "${GOPATH}/bin"),
+            ("CC", "${PZSH_CC:-${B}}"),
+            ("B", "clang"),
+            ("LESS", "${LESS} -R"),
+        ])
+        .unwrap();
+        assert_eq!(value(&resolved, "GOPATH"), "${HOME}/go");
+        // GOPATH is only known at startup, where it is already exported
+        assert_eq!(value(&resolved, "GOBIN"), "${GOPATH}/bin");
+        assert_eq!(value(&resolved, "CC"), "${PZSH_CC:-clang}");
+        assert_eq!(value(&resolved, "LESS"), "${LESS} -R");
+
+        let names: Vec<&str> = resolved.iter().map(|(k, _)| k.as_str()).collect();
+        let position = |name| names.iter().position(|n| *n == name).unwrap();
+        assert!(position("GOPATH") < position("GOBIN"));
+    }
+
+    #[test]
+    fn test_interpolate_defaults_and_literals() {
+        let resolved = resolve(&[
+            ("A", "${UNSET:-/opt/${B}}"),
+            ("B", "b"),
+            ("C", "${EMPTY:-fallback} ${EMPTY}"),
+            ("D", "price: $$5, $1 and $"),
+            ("E", "${UNSET:-}"),
+            ("F", ""),
+            ("G", "${F:-/opt}"),
+        ])
+        .unwrap();
+        assert_eq!(value(&resolved, "A"), "${UNSET:-/opt/b}");
+        assert_eq!(value(&resolved, "D"), "price: $$5, $$1 and $$");
+        assert_eq!(value(&resolved, "G"), "/opt");
+
+        let rendered = |name| render(value(&resolved, name), &lookup);
+        assert_eq!(rendered("A"), "/opt/b");
+        assert_eq!(rendered("C"), "fallback ");
+        assert_eq!(rendered("D"), "price: $5, $1 and $");
+        assert_eq!(rendered("E"), "");
+    }
+
+    #[test]
+    fn test_render_all_sees_earlier_values() {
+        let resolved = resolve(&[("PATH", "/opt/x:${PATH}"), ("SAVED", "${PATH}")]).unwrap();
+        assert_eq!(value(&resolved, "SAVED"), "${PATH}");
+
+        let vars = resolved.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+        let rendered = render_all(vars, &lookup);
+        assert_eq!(
+            rendered,
+            [
+                ("PATH".to_string(), "/opt/x:/usr/bin:/bin".to_string()),
+                ("SAVED".to_string(), "/opt/x:/usr/bin:/bin".to_string()),
+            ]
+        );
+        assert_eq!(references("a ${B:-${C}} $$D"), ["B", "C"]);
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        assert_eq!(
+            resolve(&[("A", "$B"), ("B", "${C}"), ("C", "${A:-/x}")]).unwrap_err(),
+            "cycle: A -> B -> C -> A"
+        );
+        assert_eq!(
+            resolve(&[("A", "${HOME")]).unwrap_err(),
+            "A: unterminated ${HOME"
+        );
+        assert_eq!(
+            resolve(&[("A", "${HOME:0:2}")]).unwrap_err(),
+            "A: bad substitution ${HOME:0:2}"
+        );
+    }
+}
@@ -0,0 +1,424 @@
+//! Config layering: `include` files and `[host."glob"]` / `[os.<name>]`
+//! overrides
+//!
+//! Layers merge table by table. A key set by a later layer replaces the
+//! earlier value; arrays are replaced, not appended. The order is:
+//!
+//! 1. included files, in list order (their own includes first)
+//! 2. the including file
+//! 3. `[os.<name>]` for the operating system compiled on
+//! 4. `[host."glob"]` tables matching the hostname, least specific first
+//!    (globs before exact names, shorter globs before longer ones)
+
+use super::schema::{validate, with_suggestion};
+use super::{ConfigError, SourceConfig, expand_home};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::de::{DeTable, DeValue};
+use toml::{Spanned, Table, Value};
+
+/// Operating systems accepted in `[os.<name>]`
+pub const OS_NAMES: &[&str] = &["linux", "macos", "freebsd", "openbsd", "netbsd"];
+
+/// Keys that only make sense at the top level of a file
+const TOP_LEVEL_ONLY: &[&str] = &["include", "host", "os"];
+
+/// Machine a configuration is compiled for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine {
+    pub hostname: String,
+    /// Operating system, as in `std::env::consts::OS`
+    pub os: String,
+}
+
+impl Machine {
+    /// The machine pzsh is running on
+    #[must_use]
+    pub fn current() -> Self {
+        Self {
+            hostname: hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_default(),
+            os: std::env::consts::OS.to_string(),
+        }
+    }
+}
+
+/// Read `path` and merge in its includes; also returns every file read, in
+/// load order
+pub(super) fn load(path: &Path) -> Result<(SourceConfig, Vec<PathBuf>), ConfigError> {
+    let mut files = Vec::new();
+    let table = load_table(path, &mut Vec::new(), &mut files)?;
+    Ok((from_table(table)?, files))
+}
+
+/// Parse `content` and merge in its includes, resolving relative include
+/// paths against `dir`
+pub(super) fn parse_in(content: &str, dir: &Path) -> Result<SourceConfig, ConfigError> {
+    from_table(resolve(content, dir, &mut Vec::new(), &mut Vec::new())?)
+}
+
+/// Validated table of `content` with its includes merged in; `stack` holds
+/// the files being loaded, to detect cycles, and `files` collects every file
+/// read
+fn resolve(
+    content: &str,
+    dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<Table, ConfigError> {
+    let source = SourceConfig::parse(content)?;
+    let mut table: Table = content.parse()?;
+    if table.remove("include").is_none() {
+        return Ok(table);
+    }
+    let mut merged = Table::new();
+    for include in &source.include {
+        merge(
+            &mut merged,
+            load_table(&dir.join(expand_home(include)), stack, files)?,
+        );
+    }
+    merge(&mut merged, table);
+    Ok(merged)
+}
+
+fn load_table(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<Table, ConfigError> {
+    let in_file = |source: ConfigError| ConfigError::File {
+        path: path.to_path_buf(),
+        source: Box::new(source),
+    };
+    let canonical = path.canonicalize().map_err(|e| in_file(e.into()))?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(ConfigError::Invalid(format!(
+            "include cycle: {}",
+            chain.join(" -> ")
+        )));
+    }
+    let content = fs::read_to_string(&canonical).map_err(|e| in_file(e.into()))?;
+    let dir = canonical
+        .parent()
+        .map_or_else(|| PathBuf::from("/"), Path::to_path_buf);
+    files.push(canonical.clone());
+    stack.push(canonical);
+    let table = resolve(&content, &dir, stack, files).map_err(in_file)?;
+    stack.pop();
+    Ok(table)
+}
+
+/// Merge `overlay` into `base`: tables key by key, anything else replaced
+pub(super) fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn from_table(table: Table) -> Result<SourceConfig, ConfigError> {
+    Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| ConfigError::Invalid(with_suggestion(e.message())))
+}
+
+/// Problems in the `[host]` and `[os]` tables of a parsed file, each at
+/// the position of the offending key; `schema` is the JSON Schema of an
+/// override table
+pub(super) fn check_overrides(
+    content: &str,
+    document: &DeTable<'_>,
+    schema: &serde_json::Value,
+) -> Vec<ConfigError> {
+    let at = |offset: usize, message: String| {
+        let (line, column) = super::line_column(content, offset);
+        ConfigError::At {
+            line,
+            column,
+            message,
+        }
+    };
+    let mut errors = Vec::new();
+    for kind in ["host", "os"] {
+        let Some(DeValue::Table(tables)) = document.get(kind).map(Spanned::get_ref) else {
+            continue;
+        };
+        for (name, value) in tables {
+            let name_span = name.span();
+            let name = name.get_ref().as_ref();
+            if kind == "os" && !OS_NAMES.contains(&name) {
+                let expected: Vec<String> = OS_NAMES.iter().map(|n| format!("`{n}`")).collect();
+                let message = format!(
+                    "unknown variant `{name}`, expected one of {}",
+                    expected.join(", ")
+                );
+                errors.push(at(
+                    name_span.start,
+                    format!("[os] {}", with_suggestion(&message)),
+                ));
+                continue;
+            }
+            // A non-table value is a type error reported for the whole file
+            let DeValue::Table(table) = value.get_ref() else {
+                continue;
+            };
+            let header = format!("[{kind}.{}]", Value::String(name.to_string()));
+            let mut table = table.clone();
+            for key in TOP_LEVEL_ONLY {
+                let Some(offset) = table
+                    .keys()
+                    .find(|k| k.get_ref() == key)
+                    .map(|k| k.span().start)
+                else {
+                    continue;
+                };
+                errors.push(at(
+                    offset,
+                    format!("{header} `{key}` is only allowed at the top level"),
+                ));
+                table.remove(*key);
+            }
+            let table = Spanned::new(value.span(), table);
+            if let Err(invalid) =
+                validate::<SourceConfig>(content, &table, schema, &[], &format!("{header} "))
+            {
+                errors.extend(invalid);
+            }
+        }
+    }
+    errors
+}
+
+/// Apply the `[os]` and `[host]` overrides matching `machine`
+pub(super) fn apply_overrides(
+    mut source: SourceConfig,
+    machine: &Machine,
+) -> Result<SourceConfig, ConfigError> {
+    let os = std::mem::take(&mut source.os);
+    let hosts = std::mem::take(&mut source.host);
+    let hostname = machine.hostname.to_ascii_lowercase();
+    let mut matching: Vec<(String, Table)> = hosts
+        .into_iter()
+        .filter(|(pattern, _)| glob_match(&pattern.to_ascii_lowercase(), &hostname))
+        .collect();
+    matching.sort_by(|(a, _), (b, _)| specificity(a).cmp(&specificity(b)));
+    let layers: Vec<Table> = os
+        .into_iter()
+        .filter(|(name, _)| *name == machine.os)
+        .chain(matching)
+        .map(|(_, table)| table)
+        .collect();
+    if layers.is_empty() {
+        return Ok(source);
+    }
+
+    let mut table = Table::try_from(&source).map_err(|e| ConfigError::Invalid(e.to_string()))?;
+    for layer in layers {
+        merge(&mut table, layer);
+    }
+    from_table(table)
+}
+
+fn specificity(pattern: &str) -> (bool, usize, &str) {
+    (!pattern.contains(['*', '?']), pattern.len(), pattern)
+}
+
+/// Match `text` against a glob with `*` (any run) and `?` (one character)
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it matched up to
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((after, matched)) = star {
+            p = after;
+            t = matched + 1;
+            star = Some((after, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CompiledConfig;
+    use crate::test_util::temp_dir;
+
+    fn machine(hostname: &str, os: &str) -> Machine {
+        Machine {
+            hostname: hostname.to_string(),
+            os: os.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("buildbox-*", "buildbox-12"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.corp", "a.b.corp"));
+        assert!(glob_match("dev-?", "dev-1"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("dev-?", "dev-12"));
+        assert!(!glob_match("buildbox-*", "laptop"));
+        assert!(!glob_match("laptop", "laptop2"));
+    }
+
+    #[test]
+    fn test_merge_replaces_values_and_arrays() {
+        let mut base: Table =
+            "[aliases]\nll = \"ls -l\"\ngs = \"git status\"\n[plugins]\nenabled = [\"git\"]\n"
+                .parse()
+                .unwrap();
+        let overlay: Table = "[aliases]\nll = \"eza -l\"\n[plugins]\nenabled = [\"docker\"]\n"
+            .parse()
+            .unwrap();
+        merge(&mut base, overlay);
+        assert_eq!(base["aliases"]["ll"].as_str(), Some("eza -l"));
+        assert_eq!(base["aliases"]["gs"].as_str(), Some("git status"));
+        assert_eq!(base["plugins"]["enabled"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_overrides_by_os_and_host() {
+        let source = SourceConfig::parse(
+            r#"
+[aliases]
+ll = "ls -l"
+open = "xdg-open"
+
+[env]
+EDITOR = "vim"
+
+[os.macos]
+aliases = { open = "open" }
+
+[host."buildbox-*"]
+env = { EDITOR = "nano", CI = "1" }
+
+[host."buildbox-12"]
+env = { EDITOR = "ed" }
+"#,
+        )
+        .unwrap();
+
+        let config =
+            CompiledConfig::compile_for(source.clone(), &machine("laptop", "macos")).unwrap();
+        assert_eq!(config.aliases["open"], "open");
+        assert_eq!(config.env["EDITOR"], "vim");
+
+        let config =
+            CompiledConfig::compile_for(source.clone(), &machine("BuildBox-7", "linux")).unwrap();
+        assert_eq!(config.aliases["open"], "xdg-open");
+        assert_eq!(config.aliases["ll"], "ls -l");
+        assert_eq!(config.env["EDITOR"], "nano");
+        assert_eq!(config.env["CI"], "1");
+
+        // The exact hostname applies after the glob
+        let config = CompiledConfig::compile_for(source, &machine("buildbox-12", "linux")).unwrap();
+        assert_eq!(config.env["EDITOR"], "ed");
+        assert_eq!(config.env["CI"], "1");
+    }
+
+    #[test]
+    fn test_invalid_overrides() {
+        let error = |content: &str| SourceConfig::parse(content).unwrap_err().to_string();
+        assert_eq!(
+            error("[os.linx]\naliases = {}\n"),
+            "line 1, column 5: [os] unknown variant `linx`, expected one of `linux`, `macos`, \
+             `freebsd`, `openbsd`, `netbsd`; did you mean `linux`?"
+        );
+        assert_eq!(
+            error("[env]\nA = \"1\"\n\n[host.\"ci-*\"]\npromt = {}\n"),
+            "line 5, column 1: [host.\"ci-*\"] unknown field `promt`, expected one of `aliases`, \
+             `completion`, `env`, `keybindings`, `lint`, `path`, `performance`, `plugins`, `prompt`, \
+             `pzsh`; did you mean `prompt`?"
+        );
+        assert!(
+            error("[os.linux]\ninclude = [\"a.toml\"]\n")
+                .contains("[os.\"linux\"] `include` is only allowed at the top level")
+        );
+
+        let errors = error(
+            "[host.\"b*\"]\nalias = {}\n\n[host.\"b*\".env]\nA = \"1\"\n[host.\"b*\".pzsh]\nshel = \"zsh\"\n",
+        );
+        let lines: Vec<&str> = errors.lines().collect();
+        assert_eq!(lines.len(), 2, "{errors}");
+        assert!(lines[0].starts_with("line 2, column 1: [host.\"b*\"] unknown field `alias`"));
+        assert!(lines[1].starts_with("line 7, column 1: [host.\"b*\"] unknown field `shel`"));
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = temp_dir("include");
+        fs::write(
+            dir.join("base.toml"),
+            "[aliases]\nll = \"ls -l\"\ngs = \"git status\"\n\n[env]\nEDITOR = \"vim\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("work.toml"),
+            "include = [\"base.toml\"]\n\n[env]\nEDITOR = \"code -w\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("pzshrc"),
+            "include = [\"work.toml\"]\n\n[aliases]\nll = \"eza -l\"\n",
+        )
+        .unwrap();
+
+        let source = SourceConfig::load(&dir.join("pzshrc")).unwrap();
+        assert!(source.include.is_empty());
+        assert_eq!(source.aliases["ll"], "eza -l");
+        assert_eq!(source.aliases["gs"], "git status");
+        assert_eq!(source.env["EDITOR"], "code -w");
+        let (_, files) = SourceConfig::load_with_files(&dir.join("pzshrc")).unwrap();
+        let names: Vec<_> = files.iter().filter_map(|f| f.file_name()).collect();
+        assert_eq!(names, ["pzshrc", "work.toml", "base.toml"]);
+
+        fs::write(dir.join("base.toml"), "include = [\"pzshrc\"]\n").unwrap();
+        let error = SourceConfig::load(&dir.join("pzshrc"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("include cycle: "), "{error}");
+
+        fs::write(dir.join("base.toml"), "[aliases]\nll = 1\n").unwrap();
+        let error = SourceConfig::load(&dir.join("pzshrc"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.ends_with(
+                "base.toml: line 2, column 6: invalid type: integer `1`, expected a string"
+            ),
+            "{error}"
+        );
+
+        fs::write(dir.join("pzshrc"), "include = [\"missing.toml\"]\n").unwrap();
+        let error = SourceConfig::load(&dir.join("pzshrc"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("missing.toml: io error: "), "{error}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
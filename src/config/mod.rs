@@ -1,10 +1,29 @@
 //! Configuration module for pzsh
 //!
-//! Provides O(1) compiled configuration with no runtime parsing overhead.
+//! Provides O(1) compiled configuration with no runtime parsing overhead:
+//! `.pzshrc` is parsed once by `pzsh compile`, and processes embedding pzsh
+//! can load a binary [`Snapshot`] instead.
 
 use crate::ShellType;
+use crate::shell::KeyBinding;
 use ahash::AHashMap;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+mod hash;
+mod interpolate;
+mod layers;
+mod paths;
+mod schema;
+mod snapshot;
+
+pub use hash::{Fnv1a, hash_inputs};
+pub(crate) use interpolate::{EnvPart, env_parts, interpolate_env, references, render_all};
+pub use layers::{Machine, OS_NAMES};
+pub use paths::{PathVar, SearchPath};
+pub use schema::json_schema;
+pub(crate) use schema::line_column;
+pub use snapshot::{SNAPSHOT_FILE, Snapshot, SnapshotError};
 
 /// Configuration errors
 #[derive(Debug, thiserror::Error)]
@@ -18,13 +37,39 @@ pub enum ConfigError {
     #[error("parse error: {0}")]
     Parse(#[from] toml::de::Error),
 
+    /// Parse or schema error at a position in the source
+    #[error("line {line}, column {column}: {message}")]
+    At {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    /// Several errors in one file, one per line
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Multiple(Vec<Self>),
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+
+    /// Error in a config file, with its path
+    #[error("{}: {source}", path.display())]
+    File { path: PathBuf, source: Box<Self> },
 }
 
 /// Source configuration (human-readable .pzshrc)
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct SourceConfig {
+    /// Files merged before this one (see `layers`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Overrides for hostnames matching a glob
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub host: AHashMap<String, toml::Table>,
+    /// Overrides for an operating system (`linux`, `macos`, ...)
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub os: AHashMap<String, toml::Table>,
     #[serde(default)]
     pub pzsh: PzshSection,
     #[serde(default)]
@@ -36,10 +81,20 @@ pub struct SourceConfig {
     #[serde(default)]
     pub env: AHashMap<String, String>,
     #[serde(default)]
+    pub path: PathSection,
+    #[serde(default)]
     pub plugins: PluginsSection,
+    #[serde(default)]
+    pub lint: LintSection,
+    #[serde(default)]
+    pub completion: CompletionSection,
+    /// Key chord to widget name (see `shell::keys`)
+    #[serde(default)]
+    pub keybindings: AHashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PzshSection {
     #[serde(default = "default_version")]
     pub version: String,
@@ -53,9 +108,14 @@ pub enum ShellTypeConfig {
     #[default]
     Zsh,
     Bash,
+    Fish,
+    #[serde(alias = "nu")]
+    Nushell,
+    Sh,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct PerformanceSection {
     #[serde(default = "default_startup_budget")]
     pub startup_budget_ms: u64,
@@ -66,6 +126,7 @@ pub struct PerformanceSection {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct PromptSection {
     #[serde(default = "default_prompt_format")]
     pub format: String,
@@ -75,14 +136,178 @@ pub struct PromptSection {
     pub git_cache_ms: u64,
     #[serde(default = "default_true")]
     pub colors: bool,
+    /// Theme providing segment colors (see `theme::ThemeRegistry`)
+    #[serde(default)]
+    pub theme: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct PluginsSection {
     #[serde(default)]
     pub enabled: Vec<String>,
     #[serde(default)]
     pub lazy: Vec<String>,
+    /// Directory of external plugins (`<dir>/<name>/plugin.toml`)
+    #[serde(default)]
+    pub dir: Option<String>,
+}
+
+/// `[path]` section: directories added to `PATH` and other search paths
+///
+/// ```toml
+/// [path]
+/// prepend = ["~/.cargo/bin", "/opt/go/bin"]
+/// append = ["/usr/local/sbin"]
+///
+/// [path.manpath]
+/// prepend = ["~/.local/share/man"]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PathSection {
+    /// Directories put in front of `PATH`
+    #[serde(default)]
+    pub prepend: Vec<String>,
+    /// Directories added at the end of `PATH`
+    #[serde(default)]
+    pub append: Vec<String>,
+    #[serde(default)]
+    pub manpath: PathList,
+    /// zsh function search path
+    #[serde(default)]
+    pub fpath: PathList,
+    #[serde(default)]
+    pub ld_library_path: PathList,
+}
+
+/// Directories to add to a search path other than `PATH`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PathList {
+    #[serde(default)]
+    pub prepend: Vec<String>,
+    #[serde(default)]
+    pub append: Vec<String>,
+}
+
+/// `[completion]` section; every feature is on by default
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompletionSection {
+    /// Generate completion setup at all
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Match `foo` against `Foo` (zsh matcher-list, readline ignore-case)
+    #[serde(default = "default_true")]
+    pub case_insensitive: bool,
+    /// Correct typos in completed words (zsh `_approximate`)
+    #[serde(default = "default_true")]
+    pub fuzzy: bool,
+    /// Cycle candidates in a menu
+    #[serde(default = "default_true")]
+    pub menu: bool,
+    /// Cache completer results (zsh `use-cache`)
+    #[serde(default = "default_true")]
+    pub cache: bool,
+    /// ML completion model (`.apr`)
+    #[serde(default)]
+    pub model_path: Option<String>,
+}
+
+impl Default for CompletionSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            case_insensitive: true,
+            fuzzy: true,
+            menu: true,
+            cache: true,
+            model_path: None,
+        }
+    }
+}
+
+/// `[lint]` section, also the format of `lint.toml`
+///
+/// ```toml
+/// [lint.severity]
+/// nvm = "info"
+///
+/// [[lint.rules]]
+/// id = "sdkman"
+/// pattern = "sdkman-init.sh"
+/// severity = "warning"
+/// message = "sdkman adds 100ms to startup"
+/// fix = "lazy-load sdkman"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LintSection {
+    /// Severity overrides by rule id
+    #[serde(default)]
+    pub severity: AHashMap<String, LintLevel>,
+    /// Custom rules
+    #[serde(default)]
+    pub rules: Vec<CustomLintRule>,
+}
+
+impl LintSection {
+    /// Add the rules and overrides of `other`, which win on conflicts
+    pub fn merge(&mut self, other: Self) {
+        self.severity.extend(other.severity);
+        for rule in other.rules {
+            self.rules.retain(|r| r.id != rule.id);
+            self.rules.push(rule);
+        }
+    }
+}
+
+/// Severity of a lint rule; `off` disables it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Error,
+    Warning,
+    Info,
+    Off,
+}
+
+/// User-defined lint rule matching a substring or a regular expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomLintRule {
+    /// Stable id, used by suppressions and reports
+    pub id: String,
+    /// Text to find
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Regular expression to find (instead of `pattern`)
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default = "default_lint_level")]
+    pub severity: LintLevel,
+    pub message: String,
+    #[serde(default)]
+    pub fix: Option<String>,
+}
+
+const fn default_lint_level() -> LintLevel {
+    LintLevel::Warning
+}
+
+/// Expand `~` or a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    let rest = if path == "~" {
+        Some("")
+    } else {
+        path.strip_prefix("~/")
+    };
+    match (rest, dirs::home_dir()) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
 }
 
 fn default_version() -> String {
@@ -123,21 +348,86 @@ impl Default for PerformanceSection {
     }
 }
 
+impl Default for PzshSection {
+    fn default() -> Self {
+        Self {
+            version: default_version(),
+            shell: ShellTypeConfig::default(),
+        }
+    }
+}
+
+impl SourceConfig {
+    /// Parse a `.pzshrc`, rejecting unknown keys
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::At`] with the position of the bad key or value
+    /// and, for a misspelled key, the closest valid one;
+    /// [`ConfigError::Multiple`] when there are several problems
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        let document =
+            toml::de::DeTable::parse(content).map_err(|e| schema::located(content, &e))?;
+        let schema = &*schema::SCHEMA;
+        let (source, mut errors) =
+            match schema::validate(content, &document, schema, &["host", "os"], "") {
+                Ok(source) => (Some(source), Vec::new()),
+                Err(errors) => (None, errors),
+            };
+        errors.extend(layers::check_overrides(
+            content,
+            document.get_ref(),
+            &schema["$defs"]["overrides"],
+        ));
+        match source {
+            Some(source) if errors.is_empty() => Ok(source),
+            _ => Err(schema::combined(errors)),
+        }
+    }
+
+    /// Read a `.pzshrc` and merge in the files it includes
+    ///
+    /// Relative include paths are resolved against the including file.
+    ///
+    /// # Errors
+    /// Returns error if a file cannot be read or parsed, or includes form a
+    /// cycle
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        layers::load(path).map(|(source, _)| source)
+    }
+
+    /// Like [`SourceConfig::load`], also returning every file read: `path`
+    /// and its includes, canonicalized, in load order
+    ///
+    /// # Errors
+    /// Returns error if a file cannot be read or parsed, or includes form a
+    /// cycle
+    pub fn load_with_files(path: &Path) -> Result<(Self, Vec<PathBuf>), ConfigError> {
+        layers::load(path)
+    }
+}
+
 impl Default for SourceConfig {
     fn default() -> Self {
         Self {
+            include: Vec::new(),
+            host: AHashMap::new(),
+            os: AHashMap::new(),
             pzsh: PzshSection::default(),
             performance: PerformanceSection::default(),
             prompt: PromptSection::default(),
             aliases: AHashMap::new(),
             env: AHashMap::new(),
+            path: PathSection::default(),
             plugins: PluginsSection::default(),
+            lint: LintSection::default(),
+            completion: CompletionSection::default(),
+            keybindings: AHashMap::new(),
         }
     }
 }
 
 /// Compiled configuration (O(1) lookup, no parsing at runtime)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompiledConfig {
     pub shell_type: ShellType,
     pub startup_budget_ms: u64,
@@ -148,12 +438,27 @@ pub struct CompiledConfig {
     pub git_cache_ms: u64,
     /// Enable ANSI colors in prompt
     pub colors_enabled: bool,
+    /// Prompt theme name (None = default pzsh colors)
+    pub prompt_theme: Option<String>,
     /// O(1) alias lookup via perfect hash
     pub aliases: AHashMap<String, String>,
-    /// O(1) environment lookup
+    /// O(1) environment lookup; references between `[env]` variables are
+    /// resolved, others are kept as `${VAR}` for the shell (`$$` is a
+    /// literal `$`)
     pub env: AHashMap<String, String>,
+    /// `env` names in export order, each after the variables it references
+    pub env_order: Vec<String>,
+    /// Search path additions from `[path]`, one per variable
+    pub paths: Vec<SearchPath>,
     pub plugins_enabled: Vec<String>,
     pub plugins_lazy: Vec<String>,
+    /// External plugin directory (default `~/.pzsh/plugins`)
+    pub plugin_dir: Option<PathBuf>,
+    pub completion: CompletionSection,
+    /// ML completion model, `~` expanded
+    pub completion_model: Option<PathBuf>,
+    /// User key bindings, sorted by chord
+    pub keybindings: Vec<KeyBinding>,
 }
 
 impl Default for CompiledConfig {
@@ -167,20 +472,37 @@ impl Default for CompiledConfig {
             git_async: true,
             git_cache_ms: 1000,
             colors_enabled: true,
+            prompt_theme: None,
             aliases: AHashMap::new(),
             env: AHashMap::new(),
+            env_order: Vec::new(),
+            paths: Vec::new(),
             plugins_enabled: Vec::new(),
             plugins_lazy: Vec::new(),
+            plugin_dir: None,
+            completion: CompletionSection::default(),
+            completion_model: None,
+            keybindings: Vec::new(),
         }
     }
 }
 
 impl CompiledConfig {
-    /// Compile from source configuration
+    /// Compile from source configuration for this machine
     ///
     /// # Errors
     /// Returns error if forbidden patterns detected
     pub fn compile(source: SourceConfig) -> Result<Self, ConfigError> {
+        Self::compile_for(source, &Machine::current())
+    }
+
+    /// Compile from source configuration, applying the `[os]` and `[host]`
+    /// overrides that match `machine`
+    ///
+    /// # Errors
+    /// Returns error if forbidden patterns detected
+    pub fn compile_for(source: SourceConfig, machine: &Machine) -> Result<Self, ConfigError> {
+        let source = layers::apply_overrides(source, machine)?;
         // Check for forbidden patterns in env
         for (key, value) in &source.env {
             Self::check_forbidden_patterns(key, value)?;
@@ -191,9 +513,35 @@ impl CompiledConfig {
             Self::check_forbidden_patterns(key, value)?;
         }
 
+        // Theme must be one of the registered themes
+        if let Some(theme) = &source.prompt.theme
+            && crate::theme::ThemeRegistry::new().get(theme).is_none()
+        {
+            return Err(ConfigError::Invalid(format!(
+                "unknown prompt theme: {theme}"
+            )));
+        }
+
+        let env =
+            interpolate_env(&source.env).map_err(|e| ConfigError::Invalid(format!("[env] {e}")))?;
+        let env_order = env.iter().map(|(name, _)| name.clone()).collect();
+
+        let mut keybindings = source
+            .keybindings
+            .iter()
+            .map(|(chord, widget)| {
+                KeyBinding::parse(chord, widget)
+                    .map_err(|e| ConfigError::Invalid(format!("[keybindings] {e}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        keybindings.sort_by(|a, b| a.chord.cmp(&b.chord));
+
         let shell_type = match source.pzsh.shell {
             ShellTypeConfig::Zsh => ShellType::Zsh,
             ShellTypeConfig::Bash => ShellType::Bash,
+            ShellTypeConfig::Fish => ShellType::Fish,
+            ShellTypeConfig::Nushell => ShellType::Nushell,
+            ShellTypeConfig::Sh => ShellType::Sh,
         };
 
         Ok(Self {
@@ -205,15 +553,27 @@ impl CompiledConfig {
             git_async: source.prompt.git_async,
             git_cache_ms: source.prompt.git_cache_ms,
             colors_enabled: source.prompt.colors,
+            prompt_theme: source.prompt.theme,
             aliases: source.aliases,
-            env: source.env,
+            env: env.into_iter().collect(),
+            env_order,
+            paths: paths::compile(&source.path)?,
             plugins_enabled: source.plugins.enabled,
             plugins_lazy: source.plugins.lazy,
+            plugin_dir: source
+                .plugins
+                .dir
+                .as_deref()
+                .map(expand_home)
+                .or_else(|| dirs::home_dir().map(|home| home.join(".pzsh/plugins"))),
+            completion_model: source.completion.model_path.as_deref().map(expand_home),
+            completion: source.completion,
+            keybindings,
         })
     }
 
     /// Check for forbidden patterns that would violate O(1) constraint
-    fn check_forbidden_patterns(_key: &str, value: &str) -> Result<(), ConfigError> {
+    pub(crate) fn check_forbidden_patterns(_key: &str, value: &str) -> Result<(), ConfigError> {
         // Forbidden: subprocess calls
         if value.contains("$(") || value.contains("`") {
             return Err(ConfigError::ForbiddenPattern(
@@ -252,13 +612,34 @@ impl CompiledConfig {
         self.env.get(name)
     }
 
-    /// Parse from TOML string
+    /// Parse from TOML string; relative includes are resolved against the
+    /// current directory
     ///
     /// # Errors
     /// Returns error on parse failure or forbidden patterns
     pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
-        let source: SourceConfig = toml::from_str(content)?;
-        Self::compile(source)
+        Self::compile(layers::parse_in(content, Path::new("."))?)
+    }
+
+    /// Read, merge and compile a `.pzshrc`
+    ///
+    /// # Errors
+    /// Returns error on read or parse failure or forbidden patterns
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Self::compile(SourceConfig::load(path)?)
+    }
+
+    /// Everything compiling read: `config_files` from
+    /// [`SourceConfig::load_with_files`], then the plugin directory if it
+    /// exists and the external plugin manifests in it
+    #[must_use]
+    pub fn inputs(&self, config_files: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut inputs = config_files;
+        if let Some(dir) = self.plugin_dir.as_ref().filter(|dir| dir.is_dir()) {
+            inputs.push(dir.clone());
+            inputs.extend(crate::plugin::discover(dir).unwrap_or_default());
+        }
+        inputs
     }
 }
 
@@ -342,6 +723,30 @@ dangerous = "eval $SOME_VAR"
         assert!(result.unwrap_err().to_string().contains("eval"));
     }
 
+    #[test]
+    fn test_env_interpolation() {
+        let toml = r#"
+[env]
+GOBIN = "$GOPATH/bin"
+GOPATH = "${PZSH_TEST_UNSET:-/opt}/go"
+EDITOR = "vim"
+"#;
+        let config = CompiledConfig::from_toml(toml).unwrap();
+        // GOPATH is only known at startup, so GOBIN references it there
+        assert_eq!(
+            config.get_env("GOPATH"),
+            Some(&"${PZSH_TEST_UNSET:-/opt}/go".to_string())
+        );
+        assert_eq!(config.get_env("GOBIN"), Some(&"${GOPATH}/bin".to_string()));
+        assert_eq!(config.env_order, ["EDITOR", "GOPATH", "GOBIN"]);
+
+        let err = CompiledConfig::from_toml("[env]\nA = \"$B\"\nB = \"${A}\"\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid configuration: [env] cycle: A -> B -> A"
+        );
+    }
+
     #[test]
     fn test_valid_config_parses() {
         let toml = r#"
@@ -413,6 +818,87 @@ shell = "bash"
         assert_eq!(config.shell_type, crate::ShellType::Bash);
     }
 
+    #[test]
+    fn test_shell_type_fish_config() {
+        let toml = r#"
+[pzsh]
+shell = "fish"
+"#;
+        let config = CompiledConfig::from_toml(toml).unwrap();
+        assert_eq!(config.shell_type, crate::ShellType::Fish);
+    }
+
+    #[test]
+    fn test_plugin_dir() {
+        let config = CompiledConfig::from_toml("[plugins]\ndir = \"/opt/pzsh\"\n").unwrap();
+        assert_eq!(config.plugin_dir, Some(PathBuf::from("/opt/pzsh")));
+
+        if let Some(home) = dirs::home_dir() {
+            let config = CompiledConfig::from_toml("[plugins]\ndir = \"~/plugins\"\n").unwrap();
+            assert_eq!(config.plugin_dir, Some(home.join("plugins")));
+            let config = CompiledConfig::from_toml("").unwrap();
+            assert_eq!(config.plugin_dir, Some(home.join(".pzsh/plugins")));
+        }
+        assert!(CompiledConfig::default().plugin_dir.is_none());
+    }
+
+    #[test]
+    fn test_completion_and_keybindings() {
+        let config = CompiledConfig::from_toml("").unwrap();
+        assert_eq!(config.completion, CompletionSection::default());
+        assert!(config.completion_model.is_none());
+        assert!(config.keybindings.is_empty());
+
+        let toml = r#"
+[completion]
+fuzzy = false
+model_path = "/opt/models/shell.apr"
+
+[keybindings]
+ctrl-r = "history-search"
+"ctrl-x ctrl-e" = "edit-command-line"
+ctrl-s = "none"
+"#;
+        let config = CompiledConfig::from_toml(toml).unwrap();
+        assert!(config.completion.enabled);
+        assert!(!config.completion.fuzzy);
+        assert_eq!(
+            config.completion_model,
+            Some(PathBuf::from("/opt/models/shell.apr"))
+        );
+        let chords: Vec<&str> = config
+            .keybindings
+            .iter()
+            .map(|b| b.chord.as_str())
+            .collect();
+        assert_eq!(chords, ["ctrl-r", "ctrl-s", "ctrl-x ctrl-e"]);
+        assert!(config.keybindings[1].widget.is_none());
+    }
+
+    #[test]
+    fn test_invalid_keybindings() {
+        let err = CompiledConfig::from_toml("[keybindings]\nctrl-r = \"fzf\"\n").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("[keybindings] unknown widget \"fzf\"")
+        );
+        let err = CompiledConfig::from_toml("[keybindings]\nsuper-r = \"yank\"\n").unwrap_err();
+        assert!(err.to_string().contains("unknown key chord: super-r"));
+    }
+
+    #[test]
+    fn test_shell_type_nushell_and_sh_config() {
+        for (name, expected) in [
+            ("nushell", crate::ShellType::Nushell),
+            ("nu", crate::ShellType::Nushell),
+            ("sh", crate::ShellType::Sh),
+        ] {
+            let toml = format!("[pzsh]\nshell = \"{name}\"\n");
+            let config = CompiledConfig::from_toml(&toml).unwrap();
+            assert_eq!(config.shell_type, expected);
+        }
+    }
+
     #[test]
     fn test_forbidden_backticks_in_alias() {
         let toml = r#"
@@ -465,4 +951,30 @@ git_cache_ms = 500
         assert!(!config.git_async);
         assert_eq!(config.git_cache_ms, 500);
     }
+
+    #[test]
+    fn test_prompt_theme_config() {
+        let toml = r#"
+[prompt]
+theme = "agnoster"
+"#;
+        let config = CompiledConfig::from_toml(toml).unwrap();
+        assert_eq!(config.prompt_theme.as_deref(), Some("agnoster"));
+        assert!(CompiledConfig::default().prompt_theme.is_none());
+    }
+
+    #[test]
+    fn test_unknown_prompt_theme_rejected() {
+        let toml = r#"
+[prompt]
+theme = "nonexistent"
+"#;
+        let result = CompiledConfig::from_toml(toml);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("unknown prompt theme")
+        );
+    }
 }
@@ -0,0 +1,186 @@
+//! `[path]` compilation: `~` expansion, existence checks and deduplication
+//!
+//! Directories are resolved when the config is compiled, so the generated
+//! script only adds paths that exist on the machine it was compiled on.
+
+use super::{ConfigError, PathList, PathSection, expand_home};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Search path variable managed by `[path]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathVar {
+    Path,
+    Manpath,
+    /// zsh function search path (`fpath` array)
+    Fpath,
+    LdLibraryPath,
+}
+
+impl PathVar {
+    /// Environment variable name
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Path => "PATH",
+            Self::Manpath => "MANPATH",
+            Self::Fpath => "FPATH",
+            Self::LdLibraryPath => "LD_LIBRARY_PATH",
+        }
+    }
+
+    /// Key of the variable in `[path]`
+    const fn key(self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Manpath => "path.manpath",
+            Self::Fpath => "path.fpath",
+            Self::LdLibraryPath => "path.ld_library_path",
+        }
+    }
+}
+
+/// Directories to add to one search path variable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchPath {
+    pub var: PathVar,
+    /// Put in front, in order; a directory already in the variable moves
+    pub prepend: Vec<PathBuf>,
+    /// Added at the end unless already in the variable
+    pub append: Vec<PathBuf>,
+    /// Configured directories that do not exist on this machine
+    pub missing: Vec<PathBuf>,
+}
+
+/// Resolve the `[path]` section, skipping variables with no entries
+pub(super) fn compile(section: &PathSection) -> Result<Vec<SearchPath>, ConfigError> {
+    let path = PathList {
+        prepend: section.prepend.clone(),
+        append: section.append.clone(),
+    };
+    [
+        (PathVar::Path, &path),
+        (PathVar::Manpath, &section.manpath),
+        (PathVar::Fpath, &section.fpath),
+        (PathVar::LdLibraryPath, &section.ld_library_path),
+    ]
+    .into_iter()
+    .filter(|(_, list)| !list.prepend.is_empty() || !list.append.is_empty())
+    .map(|(var, list)| search_path(var, list))
+    .collect()
+}
+
+fn search_path(var: PathVar, list: &PathList) -> Result<SearchPath, ConfigError> {
+    let mut seen = Vec::new();
+    let mut missing = Vec::new();
+    let mut resolve = |entries: &[String]| -> Result<Vec<PathBuf>, ConfigError> {
+        let mut dirs = Vec::new();
+        for entry in entries {
+            let dir = directory(var, entry)?;
+            if seen.contains(&dir) {
+                continue;
+            }
+            seen.push(dir.clone());
+            if dir.is_dir() {
+                dirs.push(dir);
+            } else {
+                missing.push(dir);
+            }
+        }
+        Ok(dirs)
+    };
+    let prepend = resolve(&list.prepend)?;
+    let append = resolve(&list.append)?;
+    Ok(SearchPath {
+        var,
+        prepend,
+        append,
+        missing,
+    })
+}
+
+/// Expand and check one entry
+fn directory(var: PathVar, entry: &str) -> Result<PathBuf, ConfigError> {
+    let invalid =
+        |reason: &str| ConfigError::Invalid(format!("[{}] {entry:?}: {reason}", var.key()));
+    if entry.contains(['$', '`']) {
+        return Err(invalid(
+            "variables are not expanded; use ~/ or an absolute path",
+        ));
+    }
+    if entry.contains(':') {
+        return Err(invalid(
+            "list each directory separately instead of joining with `:`",
+        ));
+    }
+    let dir = expand_home(entry);
+    if !dir.is_absolute() {
+        return Err(invalid("use ~/ or an absolute path"));
+    }
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CompiledConfig;
+    use std::fs;
+
+    #[test]
+    fn test_compile_paths() {
+        let dir = crate::test_util::temp_dir("path");
+        for sub in ["bin", "man", "lib"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        let d = dir.display();
+        let toml = format!(
+            r#"
+[path]
+prepend = ["{d}/bin", "{d}/missing", "{d}/bin/"]
+append = ["{d}/lib", "{d}/bin"]
+
+[path.manpath]
+append = ["{d}/man"]
+"#
+        );
+        let config = CompiledConfig::from_toml(&toml).unwrap();
+        assert_eq!(
+            config.paths,
+            [
+                SearchPath {
+                    var: PathVar::Path,
+                    prepend: vec![dir.join("bin")],
+                    append: vec![dir.join("lib")],
+                    missing: vec![dir.join("missing")],
+                },
+                SearchPath {
+                    var: PathVar::Manpath,
+                    prepend: Vec::new(),
+                    append: vec![dir.join("man")],
+                    missing: Vec::new(),
+                },
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        if let Some(home) = dirs::home_dir() {
+            let config = CompiledConfig::from_toml("[path]\nprepend = [\"~\"]\n").unwrap();
+            assert_eq!(config.paths[0].prepend, [home]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        let error = |toml: &str| CompiledConfig::from_toml(toml).unwrap_err().to_string();
+        assert_eq!(
+            error("[path]\nprepend = [\"$GOPATH/bin\"]\n"),
+            "invalid configuration: [path] \"$GOPATH/bin\": variables are not expanded; \
+             use ~/ or an absolute path"
+        );
+        assert!(
+            error("[path.fpath]\nappend = [\"functions\"]\n")
+                .contains("[path.fpath] \"functions\": use ~/ or an absolute path")
+        );
+        assert!(error("[path]\nappend = [\"/a:/b\"]\n").contains("instead of joining"));
+    }
+}
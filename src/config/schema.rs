@@ -0,0 +1,480 @@
+//! Strict validation errors and the JSON Schema of `.pzshrc`
+//!
+//! Unknown keys are found by walking the parsed table against the schema and
+//! type errors by serde; this module turns both into positions and
+//! did-you-mean suggestions. The schema
+//! lets editors complete and check `.pzshrc` (via taplo or Even Better TOML).
+
+use super::ConfigError;
+use super::OS_NAMES;
+use crate::shell::WIDGETS;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::sync::LazyLock;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+/// Convert a TOML error into a [`ConfigError::At`] (or
+/// [`ConfigError::Parse`] when the error has no position)
+pub(super) fn located(content: &str, error: &toml::de::Error) -> ConfigError {
+    let Some(span) = error.span() else {
+        return ConfigError::Parse(error.clone());
+    };
+    let (line, column) = line_column(content, span.start);
+    ConfigError::At {
+        line,
+        column,
+        message: with_suggestion(error.message()),
+    }
+}
+
+/// Check `table` against the object `schema`: every unknown key, then the
+/// first type error serde finds in the rest
+///
+/// Unknown keys are found by walking the table, so they are all reported
+/// wherever serde stops. Values under `opaque` keys are checked elsewhere;
+/// messages start with `prefix`.
+pub(super) fn validate<T: DeserializeOwned>(
+    content: &str,
+    table: &Spanned<DeTable<'_>>,
+    schema: &Value,
+    opaque: &[&str],
+    prefix: &str,
+) -> Result<T, Vec<ConfigError>> {
+    let at = |offset: usize, message: &str| {
+        let (line, column) = line_column(content, offset);
+        ConfigError::At {
+            line,
+            column,
+            message: format!("{prefix}{}", with_suggestion(message)),
+        }
+    };
+    let mut known = table.clone();
+    let mut unknown = Vec::new();
+    unknown_keys(known.get_mut(), schema, opaque, &mut unknown);
+    let mut errors: Vec<ConfigError> = unknown
+        .iter()
+        .map(|(offset, message)| at(*offset, message))
+        .collect();
+    match T::deserialize(toml::de::Deserializer::from(known)) {
+        Ok(value) if errors.is_empty() => return Ok(value),
+        Ok(_) => {}
+        Err(error) => errors.push(match error.span() {
+            Some(span) => at(span.start, error.message()),
+            None if prefix.is_empty() => ConfigError::Parse(error),
+            None => at(table.span().start, error.message()),
+        }),
+    }
+    Err(errors)
+}
+
+/// Remove the keys of `table` that `schema` does not allow, recording their
+/// offsets and serde-style messages
+fn unknown_keys(
+    table: &mut DeTable<'_>,
+    schema: &Value,
+    opaque: &[&str],
+    unknown: &mut Vec<(usize, String)>,
+) {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+    let mut removed = Vec::new();
+    for (key, value) in table.iter_mut() {
+        let name = key.get_ref().as_ref();
+        let child = match properties.and_then(|p| p.get(name)) {
+            Some(child) => child,
+            None if closed => {
+                let expected: Vec<String> = properties
+                    .into_iter()
+                    .flat_map(|p| p.keys())
+                    .map(|k| format!("`{k}`"))
+                    .collect();
+                let expected = match expected.as_slice() {
+                    [] => "there are no fields".to_string(),
+                    [one] => format!("expected {one}"),
+                    _ => format!("expected one of {}", expected.join(", ")),
+                };
+                unknown.push((
+                    key.span().start,
+                    format!("unknown field `{name}`, {expected}"),
+                ));
+                removed.push(name.to_string());
+                continue;
+            }
+            None => match schema.get("additionalProperties") {
+                Some(child) => child,
+                None => continue,
+            },
+        };
+        if opaque.contains(&name) {
+            continue;
+        }
+        match value.get_mut() {
+            DeValue::Table(inner) => unknown_keys(inner, child, &[], unknown),
+            DeValue::Array(items) => {
+                for item in items.iter_mut() {
+                    if let DeValue::Table(inner) = item.get_mut() {
+                        unknown_keys(inner, &child["items"], &[], unknown);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    for name in removed {
+        table.remove(name.as_str());
+    }
+}
+
+/// Combine the errors of one file, in source order
+pub(super) fn combined(mut errors: Vec<ConfigError>) -> ConfigError {
+    errors.sort_by_key(|error| match error {
+        ConfigError::At { line, column, .. } => (*line, *column),
+        _ => (usize::MAX, usize::MAX),
+    });
+    if errors.len() == 1 {
+        errors.remove(0)
+    } else {
+        ConfigError::Multiple(errors)
+    }
+}
+
+/// `message` with the closest valid name appended, if there is one
+pub(super) fn with_suggestion(message: &str) -> String {
+    let mut message = message.trim_end().to_string();
+    if let Some(suggestion) = suggestion(&message) {
+        message.push_str(&format!("; did you mean `{suggestion}`?"));
+    }
+    message
+}
+
+/// 1-based line and column (in characters) of a byte offset
+pub fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Closest expected name for serde's "unknown field `x`, expected one of
+/// `a`, `b`" (and "unknown variant") messages
+fn suggestion(message: &str) -> Option<&str> {
+    let rest = message
+        .strip_prefix("unknown field `")
+        .or_else(|| message.strip_prefix("unknown variant `"))?;
+    let (unknown, expected) = rest.split_once('`')?;
+    expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|name| (edit_distance(unknown, name), name))
+        .filter(|(distance, _)| *distance <= (unknown.chars().count() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Edit distance in characters, counting a swap of adjacent characters
+/// as one edit (optimal string alignment)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+fn object(description: &str, properties: Value) -> Value {
+    let mut schema = json!({
+        "type": "object",
+        "description": description,
+        "additionalProperties": false,
+    });
+    schema["properties"] = properties;
+    schema
+}
+
+fn string_map(description: &str, values: Value) -> Value {
+    let mut schema = json!({ "type": "object", "description": description });
+    schema["additionalProperties"] = values;
+    schema
+}
+
+fn field(kind: &str, description: &str, default: Value) -> Value {
+    let mut schema = json!({ "type": kind, "description": description });
+    schema["default"] = default;
+    schema
+}
+
+fn strings(description: &str) -> Value {
+    json!({
+        "type": "array",
+        "description": description,
+        "items": { "type": "string" },
+        "default": [],
+    })
+}
+
+fn search_path(description: &str) -> Value {
+    object(
+        description,
+        json!({
+            "prepend": strings("Directories placed in front"),
+            "append": strings("Directories added at the end"),
+        }),
+    )
+}
+
+/// [`json_schema`], built once for validation
+pub(super) static SCHEMA: LazyLock<Value> = LazyLock::new(json_schema);
+
+/// JSON Schema (draft 2020-12) for `SourceConfig`
+#[must_use]
+pub fn json_schema() -> Value {
+    let levels = json!(["error", "warning", "info", "off"]);
+    let mut widgets: Vec<&str> = WIDGETS.iter().map(|w| w.name).collect();
+    widgets.push("none");
+
+    let mut sections = json!({
+            "pzsh": object("pzsh settings", json!({
+                "version": field("string", "Configuration format version", json!("0.1.0")),
+                "shell": {
+                    "description": "Shell to generate code for",
+                    "enum": ["zsh", "bash", "fish", "nushell", "nu", "sh"],
+                    "default": "zsh",
+                },
+            })),
+            "performance": object("Startup budgets", json!({
+                "startup_budget_ms": field("integer", "Startup budget in milliseconds", json!(10)),
+                "prompt_budget_ms": field("integer", "Prompt budget in milliseconds", json!(2)),
+                "lazy_load": field("boolean", "Lazy-load plugins", json!(true)),
+            })),
+            "prompt": object("Prompt", json!({
+                "format": field(
+                    "string",
+                    "Prompt format with {user}, {host}, {cwd}, {git}, {char}, {status}, {duration}, {jobs} and {keymap} segments",
+                    json!("{user}@{host} {cwd} {git} {char}"),
+                ),
+                "git_async": field("boolean", "Read git status asynchronously", json!(true)),
+                "git_cache_ms": field("integer", "Git status cache lifetime in milliseconds", json!(1000)),
+                "colors": field("boolean", "Enable ANSI colors", json!(true)),
+                "theme": { "type": "string", "description": "Theme providing segment colors" },
+            })),
+            "aliases": string_map("Alias name to command", json!({ "type": "string" })),
+            "env": string_map("Environment variables to export", json!({ "type": "string" })),
+            "path": object("Search path directories", json!({
+                "prepend": strings("Directories placed in front of PATH"),
+                "append": strings("Directories added at the end of PATH"),
+                "manpath": search_path("MANPATH directories"),
+                "fpath": search_path("zsh function directories"),
+                "ld_library_path": search_path("LD_LIBRARY_PATH directories"),
+            })),
+            "plugins": object("Plugins", json!({
+                "enabled": strings("Plugins loaded at startup"),
+                "lazy": strings("Plugins loaded on first use"),
+                "dir": { "type": "string", "description": "Directory of external plugins" },
+            })),
+            "lint": object("Lint rule configuration", json!({
+                "severity": string_map("Severity overrides by rule id", json!({ "enum": levels })),
+                "rules": {
+                    "type": "array",
+                    "description": "Custom lint rules",
+                    "items": {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "required": ["id", "message"],
+                        "properties": {
+                            "id": { "type": "string", "pattern": "^[a-z0-9-]+$" },
+                            "pattern": { "type": "string", "description": "Text to find" },
+                            "regex": { "type": "string", "description": "Regular expression to find" },
+                            "severity": { "enum": levels, "default": "warning" },
+                            "message": { "type": "string" },
+                            "fix": { "type": "string" },
+                        },
+                    },
+                },
+            })),
+            "completion": object("Completion setup", json!({
+                "enabled": field("boolean", "Generate completion setup", json!(true)),
+                "case_insensitive": field("boolean", "Case-insensitive matching", json!(true)),
+                "fuzzy": field("boolean", "Correct typos in completed words", json!(true)),
+                "menu": field("boolean", "Cycle candidates in a menu", json!(true)),
+                "cache": field("boolean", "Cache completer results", json!(true)),
+                "model_path": { "type": "string", "description": "ML completion model (.apr)" },
+            })),
+            "keybindings": string_map("Key chord to widget", json!({ "enum": widgets })),
+    });
+    let overrides = json!({ "$ref": "#/$defs/overrides" });
+    let os: serde_json::Map<String, Value> = OS_NAMES
+        .iter()
+        .map(|os| ((*os).to_string(), overrides.clone()))
+        .collect();
+    let mut schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": concat!(env!("CARGO_PKG_REPOSITORY"), "/pzshrc.schema.json"),
+        "title": "pzsh configuration (.pzshrc)",
+        "$defs": {
+            "overrides": object("Sections overriding the base configuration", sections.clone()),
+        },
+    });
+    sections["include"] = strings("Files merged before this one, relative to it");
+    sections["host"] = string_map(
+        "Overrides for hostnames matching a glob (`*`, `?`)",
+        overrides,
+    );
+    sections["os"] = object("Overrides by operating system", Value::Object(os));
+    let root = object("pzsh configuration", sections);
+    for (key, value) in root.as_object().into_iter().flatten() {
+        schema[key] = value.clone();
+    }
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CustomLintRule, LintLevel, SourceConfig};
+
+    fn error(content: &str) -> String {
+        SourceConfig::parse(content).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_unknown_keys_suggest() {
+        assert_eq!(
+            error("[pzsh]\nshell = \"zsh\"\n\n[promt]\nformat = \"$ \"\n"),
+            "line 4, column 2: unknown field `promt`, expected one of `aliases`, `completion`, `env`, \
+             `host`, `include`, `keybindings`, `lint`, `os`, `path`, `performance`, `plugins`, `prompt`, `pzsh`; \
+             did you mean `prompt`?"
+        );
+        let message = error("[prompt]\ngit_cach_ms = 500\n");
+        assert!(message.starts_with("line 2, column 1: unknown field `git_cach_ms`"));
+        assert!(message.ends_with("did you mean `git_cache_ms`?"));
+        assert!(error("[pzsh]\nshell = \"zhs\"\n").ends_with("did you mean `zsh`?"));
+        assert!(!error("[plugins]\nsomething = 1\n").contains("did you mean"));
+    }
+
+    #[test]
+    fn test_every_unknown_key_reported() {
+        let message = error("promt = {}\n\n[pzsh]\nshel = \"zsh\"\n[prompt]\ngit_cach_ms = 1\n");
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines.len(), 3, "{message}");
+        assert!(lines[0].starts_with("line 1, column 1: unknown field `promt`"));
+        assert!(lines[1].starts_with("line 4, column 1: unknown field `shel`"));
+        assert!(lines[2].starts_with("line 6, column 1: unknown field `git_cach_ms`"));
+    }
+
+    #[test]
+    fn test_unknown_keys_around_type_error() {
+        let message = error(
+            "[promt]\n[prompt]\ngit_cache_ms = \"fast\"\n[performence]\n[lint]\n\
+             [[lint.rules]]\nid = \"x\"\nmessage = \"m\"\nmesage = \"m\"\n",
+        );
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines.len(), 4, "{message}");
+        assert!(lines[0].starts_with("line 1, column 2: unknown field `promt`"));
+        assert!(lines[1].starts_with("line 3, column 16: invalid type: string \"fast\""));
+        assert!(lines[2].starts_with("line 4, column 2: unknown field `performence`"));
+        assert!(lines[3].starts_with("line 9, column 1: unknown field `mesage`"));
+    }
+
+    #[test]
+    fn test_type_errors_have_positions() {
+        let message = error("[performance]\nstartup_budget_ms = \"fast\"\n");
+        assert!(message.starts_with("line 2, column 21: invalid type: string \"fast\""));
+        let message = error("[aliases]\nll = 1\n");
+        assert!(message.starts_with("line 2, column 6: invalid type: integer"));
+        assert!(error("[prompt\n").starts_with("line 1, column 8: "));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("promt", "prompt"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("zhs", "zsh"), 1);
+        assert_eq!(suggestion("unknown field `x`, there are no fields"), None);
+    }
+
+    /// Inline TOML value accepted by `schema`
+    fn sample(schema: &Value) -> String {
+        match (schema["type"].as_str(), &schema["enum"]) {
+            (Some("string"), _) => "\"x\"".to_string(),
+            (Some("integer"), _) => "1".to_string(),
+            (Some("boolean"), _) => "true".to_string(),
+            (Some("array"), _) => "[]".to_string(),
+            (Some("object"), _) => {
+                let fields: Vec<String> = schema["properties"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(key, property)| format!("{key} = {}", sample(property)))
+                    .collect();
+                format!("{{ {} }}", fields.join(", "))
+            }
+            (_, Value::Array(values)) => values[0].to_string(),
+            _ if schema["$ref"].is_string() => "{}".to_string(),
+            other => panic!("unexpected schema {other:?}"),
+        }
+    }
+
+    /// Every key of the schema is accepted, and every field of the
+    /// config is in the schema
+    #[test]
+    fn test_schema_matches_source_config() {
+        let schema = json_schema();
+        let sections = schema["properties"].as_object().unwrap();
+        let mut toml = String::new();
+        for (name, section) in sections {
+            toml.push_str(&format!("{name} = {}\n", sample(section)));
+        }
+        SourceConfig::parse(&toml).unwrap();
+        let overrides = sample(&schema["$defs"]["overrides"]);
+        SourceConfig::parse(&format!("host = {{ \"ci-*\" = {overrides} }}\n")).unwrap();
+
+        let mut source = SourceConfig::default();
+        source.lint.rules.push(CustomLintRule {
+            id: "x".to_string(),
+            pattern: None,
+            regex: None,
+            severity: LintLevel::Warning,
+            message: String::new(),
+            fix: None,
+        });
+        let config = serde_json::to_value(&source).unwrap();
+        for (name, section) in config.as_object().unwrap() {
+            let schema_section = &sections[name];
+            assert!(schema_section.is_object(), "{name} missing from schema");
+            if let (Some(fields), Some(properties)) = (
+                section.as_object(),
+                schema_section["properties"].as_object(),
+            ) {
+                for key in fields.keys() {
+                    assert!(properties.contains_key(key), "{name}.{key} missing");
+                }
+            }
+        }
+        let rule_fields = config["lint"]["rules"][0].as_object().unwrap();
+        let rule_schema = &sections["lint"]["properties"]["rules"]["items"]["properties"];
+        for key in rule_fields.keys() {
+            assert!(rule_schema[key].is_object(), "lint.rules.{key} missing");
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Binary snapshots of a compiled configuration
+//!
+//! `pzsh compile --binary` writes a [`Snapshot`]: the compiled config with
+//! plugin aliases and env merged in and the prompt theme resolved, so a
+//! process embedding pzsh loads it without parsing TOML. The layout is:
+//!
+//! | Bytes | Content |
+//! |-------|---------|
+//! | 8 | magic `PZSHSNAP` |
+//! | 4 | format version, little endian |
+//! | 2 + n | length and bytes of the pzsh version that wrote it |
+//! | 2 | number of input files |
+//! | 2 + n | length and UTF-8 path of each input, the config first |
+//! | 8 | [`hash_inputs`] key of the inputs, little endian |
+//! | 8 | FNV-1a checksum of the payload, little endian |
+//! | rest | payload, bincode |
+//!
+//! Snapshots from another format or pzsh version are rejected.
+//! [`Snapshot::load_or_compile`] recompiles them, and snapshots of another
+//! config or whose inputs changed.
+
+use super::{CompiledConfig, ConfigError, Fnv1a, Machine, SourceConfig, hash_inputs};
+use crate::shell::{PromptStyles, ShellIntegration};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Snapshot file name in the pzsh cache directory
+pub const SNAPSHOT_FILE: &str = "config.bin";
+
+const MAGIC: &[u8; 8] = b"PZSHSNAP";
+
+/// Bumped whenever the header or payload layout changes
+const FORMAT: u32 = 2;
+
+/// Snapshot errors
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("not a pzsh snapshot")]
+    NotSnapshot,
+
+    /// Written by another pzsh version or snapshot format
+    #[error(
+        "snapshot written by pzsh {version} (format {format}), expected pzsh {} (format {FORMAT})",
+        env!("CARGO_PKG_VERSION")
+    )]
+    Version { version: String, format: u32 },
+
+    #[error("snapshot checksum mismatch")]
+    Checksum,
+
+    #[error("snapshot decode error: {0}")]
+    Decode(String),
+
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+}
+
+/// Compiled configuration with plugins and theme resolved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Compiled config; `aliases` and `env` include the winning plugin
+    /// definitions, and `env_order` lists every exported variable
+    pub config: CompiledConfig,
+    /// Loaded plugins, in load order
+    pub plugins: Vec<String>,
+    /// Prompt segment styles (`None` when colors are disabled)
+    pub prompt_styles: Option<PromptStyles>,
+    /// Files compiled from, the config first; empty when not compiled from
+    /// files. Stored in the header.
+    #[serde(skip)]
+    pub inputs: Vec<PathBuf>,
+    /// [`hash_inputs`] key of `inputs` when compiled
+    #[serde(skip)]
+    pub input_key: u64,
+}
+
+impl Snapshot {
+    /// Load, compile and resolve the config at `path` for this machine
+    ///
+    /// # Errors
+    /// Returns error if the config cannot be read or compiled
+    pub fn compile(path: &Path) -> Result<Self, SnapshotError> {
+        let (source, files) = SourceConfig::load_with_files(path)?;
+        let machine = Machine::current();
+        let config = CompiledConfig::compile_for(source, &machine)?;
+        let inputs = config.inputs(files);
+        Ok(ShellIntegration::new(config.shell_type, config)
+            .snapshot()
+            .with_inputs(inputs, &machine))
+    }
+
+    /// Record the files the snapshot was compiled from on `machine`, the
+    /// config first
+    #[must_use]
+    pub fn with_inputs(mut self, inputs: Vec<PathBuf>, machine: &Machine) -> Self {
+        self.input_key = hash_inputs(&inputs, machine).finish();
+        self.inputs = inputs;
+        self
+    }
+
+    /// Whether the snapshot was compiled from `config` and its inputs are
+    /// unchanged on this machine
+    #[must_use]
+    pub fn is_current(&self, config: &Path) -> bool {
+        let Ok(config) = fs::canonicalize(config) else {
+            return false;
+        };
+        self.inputs.first() == Some(&config)
+            && hash_inputs(&self.inputs, &Machine::current()).finish() == self.input_key
+    }
+
+    /// Encode with header and checksum
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let payload = bincode::serialize(self).expect("snapshot types always serialize");
+        let version = env!("CARGO_PKG_VERSION").as_bytes();
+        let mut checksum = Fnv1a::new();
+        checksum.write(&payload);
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 32 + version.len() + payload.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT.to_le_bytes());
+        bytes.extend_from_slice(&(version.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(version);
+        bytes.extend_from_slice(&(self.inputs.len() as u16).to_le_bytes());
+        for input in &self.inputs {
+            let input = input.to_string_lossy();
+            bytes.extend_from_slice(&(input.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(input.as_bytes());
+        }
+        bytes.extend_from_slice(&self.input_key.to_le_bytes());
+        bytes.extend_from_slice(&checksum.finish().to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    /// Decode bytes written by [`Snapshot::to_bytes`]
+    ///
+    /// # Errors
+    /// Returns error if the bytes are not a snapshot, come from another
+    /// pzsh version or format, or fail the checksum
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len()) != Some(MAGIC) {
+            return Err(SnapshotError::NotSnapshot);
+        }
+        let format = reader.u32().ok_or(SnapshotError::NotSnapshot)?;
+        let version = reader
+            .u16()
+            .and_then(|len| reader.take(usize::from(len)))
+            .ok_or(SnapshotError::NotSnapshot)?;
+        let version = String::from_utf8_lossy(version);
+        if format != FORMAT || version != env!("CARGO_PKG_VERSION") {
+            return Err(SnapshotError::Version {
+                version: version.into_owned(),
+                format,
+            });
+        }
+        let count = reader.u16().ok_or(SnapshotError::NotSnapshot)?;
+        let inputs = (0..count)
+            .map(|_| {
+                let input = reader.u16().and_then(|len| reader.take(usize::from(len)))?;
+                Some(PathBuf::from(String::from_utf8_lossy(input).into_owned()))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(SnapshotError::NotSnapshot)?;
+        let input_key = reader.u64().ok_or(SnapshotError::NotSnapshot)?;
+        let expected = reader.u64().ok_or(SnapshotError::Checksum)?;
+        let mut checksum = Fnv1a::new();
+        checksum.write(reader.0);
+        if checksum.finish() != expected {
+            return Err(SnapshotError::Checksum);
+        }
+        let snapshot: Self =
+            bincode::deserialize(reader.0).map_err(|e| SnapshotError::Decode(e.to_string()))?;
+        Ok(Self {
+            inputs,
+            input_key,
+            ..snapshot
+        })
+    }
+
+    /// Read a snapshot file
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be read or decoded
+    pub fn read(path: &Path) -> Result<Self, SnapshotError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Write a snapshot file, replacing any existing one atomically
+    ///
+    /// # Errors
+    /// Returns error if the directory or file cannot be written
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+        let result = fs::write(&tmp, self.to_bytes()).and_then(|()| fs::rename(&tmp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    /// Read `snapshot`, recompiling it from `config` when it is missing,
+    /// unreadable, from another pzsh version, compiled from another config
+    /// or when any of its inputs changed
+    ///
+    /// # Errors
+    /// Returns error if the config cannot be compiled or the new snapshot
+    /// cannot be written
+    pub fn load_or_compile(snapshot: &Path, config: &Path) -> Result<Self, SnapshotError> {
+        if let Ok(loaded) = Self::read(snapshot)
+            && loaded.is_current(config)
+        {
+            return Ok(loaded);
+        }
+        let compiled = Self::compile(config)?;
+        compiled.write(snapshot)?;
+        Ok(compiled)
+    }
+}
+
+/// Cursor over the header fields
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2)?.try_into().ok().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)?.try_into().ok().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    const CONFIG: &str = r#"
+[pzsh]
+shell = "bash"
+
+[prompt]
+theme = "agnoster"
+
+[aliases]
+ll = "ls -la"
+
+[env]
+GOPATH = "/opt/go"
+GOBIN = "${GOPATH}/bin"
+
+[plugins]
+enabled = ["git"]
+
+[keybindings]
+ctrl-r = "history-search"
+"#;
+
+    fn snapshot() -> Snapshot {
+        let config = CompiledConfig::from_toml(CONFIG).unwrap();
+        ShellIntegration::new(config.shell_type, config).snapshot()
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.plugins, ["git"]);
+        assert_eq!(snapshot.config.env_order, ["GOPATH", "GOBIN"]);
+        assert!(snapshot.config.aliases.contains_key("gst"));
+        assert!(snapshot.prompt_styles.is_some());
+
+        let loaded = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(loaded.plugins, snapshot.plugins);
+        assert_eq!(loaded.prompt_styles, snapshot.prompt_styles);
+        assert_eq!(loaded.config.aliases, snapshot.config.aliases);
+        assert_eq!(loaded.config.env, snapshot.config.env);
+        assert_eq!(loaded.config.env_order, snapshot.config.env_order);
+        assert_eq!(loaded.config.keybindings, snapshot.config.keybindings);
+        assert_eq!(loaded.config.shell_type, crate::ShellType::Bash);
+    }
+
+    #[test]
+    fn test_snapshot_rejects_bad_bytes() {
+        let bytes = snapshot().to_bytes();
+        assert!(matches!(
+            Snapshot::from_bytes(b"[pzsh]\n"),
+            Err(SnapshotError::NotSnapshot)
+        ));
+
+        let mut corrupt = bytes.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            Snapshot::from_bytes(&corrupt),
+            Err(SnapshotError::Checksum)
+        ));
+
+        // Version string right after the magic and format
+        let mut old = bytes;
+        old[14] = b'9';
+        let err = Snapshot::from_bytes(&old).unwrap_err();
+        assert!(matches!(err, SnapshotError::Version { .. }));
+        assert!(
+            err.to_string().starts_with("snapshot written by pzsh 9"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_load_or_compile_recompiles_stale_snapshots() {
+        let dir = temp_dir("snapshot");
+        let config = dir.join("pzshrc");
+        let path = dir.join("cache").join(SNAPSHOT_FILE);
+        fs::write(&config, "[aliases]\nll = \"ls -la\"\n").unwrap();
+
+        let compiled = Snapshot::load_or_compile(&path, &config).unwrap();
+        assert_eq!(compiled.config.aliases["ll"], "ls -la");
+        assert!(Snapshot::read(&path).is_ok());
+
+        // A snapshot from another version is replaced
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[14] = b'9';
+        fs::write(&path, &bytes).unwrap();
+        assert!(Snapshot::load_or_compile(&path, &config).is_ok());
+        assert!(Snapshot::read(&path).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_or_compile_checks_inputs() {
+        let dir = temp_dir("snapshot-inputs");
+        let config = dir.join("pzshrc");
+        let include = dir.join("work.toml");
+        let path = dir.join("cache").join(SNAPSHOT_FILE);
+        fs::write(&config, "include = [\"work.toml\"]\n").unwrap();
+        fs::write(&include, "[aliases]\nll = \"ls -la\"\n").unwrap();
+
+        let compiled = Snapshot::load_or_compile(&path, &config).unwrap();
+        let config = fs::canonicalize(&config).unwrap();
+        assert_eq!(
+            compiled.inputs,
+            [config.clone(), fs::canonicalize(&include).unwrap()]
+        );
+        let loaded = Snapshot::read(&path).unwrap();
+        assert_eq!(loaded.inputs, compiled.inputs);
+        assert_eq!(loaded.input_key, compiled.input_key);
+        assert!(loaded.is_current(&config));
+
+        // Editing an include recompiles, whatever the mtimes
+        fs::write(&include, "[aliases]\nll = \"eza -l\"\n").unwrap();
+        assert!(!loaded.is_current(&config));
+        let recompiled = Snapshot::load_or_compile(&path, &config).unwrap();
+        assert_eq!(recompiled.config.aliases["ll"], "eza -l");
+
+        // So does loading the snapshot for another config
+        let other = dir.join("other");
+        fs::write(&other, "[aliases]\nll = \"lsd -l\"\n").unwrap();
+        assert!(!recompiled.is_current(&other));
+        let other = Snapshot::load_or_compile(&path, &other).unwrap();
+        assert_eq!(other.config.aliases["ll"], "lsd -l");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,389 @@
+//! In-process builtins
+//!
+//! Builtins that change shell state (`cd`, `export`, `source`, ...) must run
+//! inside pzsh. Those that are not implemented are an
+//! [`ExecError::Unsupported`] error rather than a lookup on `PATH`. Other
+//! names recognised by the parser (`printf`, `test`, ...) fall through to
+//! the external program of the same name.
+
+use super::io::Io;
+use super::{ExecError, Executor};
+use crate::Result;
+use std::path::{Component, Path, PathBuf};
+
+/// Builtins implemented in-process
+const BUILTINS: &[&str] = &[
+    "cd", "pushd", "popd", "dirs", "pwd", "export", "unset", "echo", "true", "false", ":", "exit",
+    "eval", "source", ".", "alias", "unalias", "wait", "jobs",
+];
+
+/// Builtins that would change shell state but are not implemented
+const UNSUPPORTED: &[&str] = &[
+    "set", "local", "return", "readonly", "umask", "exec", "trap", "shift", "let", "fg", "bg",
+    "ulimit", "history", "hash",
+];
+
+/// Check if `name` runs in-process (or is refused as unsupported)
+pub(super) fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name) || UNSUPPORTED.contains(&name)
+}
+
+impl Executor {
+    /// Run a builtin, returning its exit code
+    pub(super) fn builtin(&mut self, words: &[String], io: &Io) -> Result<i32> {
+        let args = &words[1..];
+        let code = match words[0].as_str() {
+            "cd" => self.cd(args, io),
+            "pushd" => self.pushd(args, io),
+            "popd" => self.popd(io),
+            "dirs" => self.dirs(io),
+            "pwd" => io
+                .out(&format!("{}\n", self.session.cwd.display()))
+                .map(|()| 0),
+            "export" => self.export(args, io),
+            "unset" => {
+                for name in args.iter().filter(|a| !a.starts_with('-')) {
+                    self.session.vars.insert(name.clone(), None);
+                }
+                Ok(0)
+            }
+            "echo" => echo(args, io),
+            "true" | ":" => Ok(0),
+            "false" => Ok(1),
+            "exit" => {
+                self.exit_requested = true;
+                return Ok(args
+                    .first()
+                    .map_or(self.last_status, |code| code.parse().unwrap_or(2)));
+            }
+            "eval" => {
+                let command = self.parser.parse(&args.join(" "))?;
+                return self.exec(&command, io);
+            }
+            "source" | "." => {
+                let Some(file) = args.first() else {
+                    io.err(&format!("pzsh: {}: filename argument required\n", words[0]))
+                        .map_err(ExecError::Io)?;
+                    return Ok(2);
+                };
+                let script = match std::fs::read_to_string(self.session.cwd.join(file)) {
+                    Ok(script) => script,
+                    Err(e) => {
+                        io.err(&format!("pzsh: {}: {file}: {e}\n", words[0]))
+                            .map_err(ExecError::Io)?;
+                        return Ok(1);
+                    }
+                };
+                let command = self.parser.parse_uncached(&script)?;
+                return self.exec(&command, io);
+            }
+            "alias" => self.alias(args, io),
+            "unalias" => self.unalias(args, io),
+            "wait" => return self.wait(args, io),
+            "jobs" => self.list_jobs(io),
+            name => {
+                return Err(ExecError::Unsupported(format!("builtin `{name}`")).into());
+            }
+        };
+        Ok(code.map_err(ExecError::Io)?)
+    }
+
+    fn cd(&mut self, args: &[String], io: &Io) -> std::io::Result<i32> {
+        let target = match args.first().map(String::as_str) {
+            None => self.var("HOME").unwrap_or_else(|| "/".to_string()),
+            Some("-") => {
+                let Some(old) = self.var("OLDPWD") else {
+                    io.err("pzsh: cd: OLDPWD not set\n")?;
+                    return Ok(1);
+                };
+                io.out(&format!("{old}\n"))?;
+                old
+            }
+            Some(dir) => dir.to_string(),
+        };
+        if self.change_dir(&target) {
+            Ok(0)
+        } else {
+            io.err(&format!("pzsh: cd: no such file or directory: {target}\n"))?;
+            Ok(1)
+        }
+    }
+
+    fn pushd(&mut self, args: &[String], io: &Io) -> std::io::Result<i32> {
+        let previous = self.session.cwd.clone();
+        match args.first() {
+            None => {
+                let Some(top) = self.session.dir_stack.first().cloned() else {
+                    io.err("pzsh: pushd: no other directory\n")?;
+                    return Ok(1);
+                };
+                if !self.change_dir(&top.to_string_lossy()) {
+                    io.err(&format!(
+                        "pzsh: pushd: no such file or directory: {}\n",
+                        top.display()
+                    ))?;
+                    return Ok(1);
+                }
+                self.session.dir_stack[0] = previous;
+            }
+            Some(dir) => {
+                if !self.change_dir(dir) {
+                    io.err(&format!("pzsh: pushd: no such file or directory: {dir}\n"))?;
+                    return Ok(1);
+                }
+                self.session.dir_stack.insert(0, previous);
+            }
+        }
+        self.dirs(io)
+    }
+
+    fn popd(&mut self, io: &Io) -> std::io::Result<i32> {
+        if self.session.dir_stack.is_empty() {
+            io.err("pzsh: popd: directory stack empty\n")?;
+            return Ok(1);
+        }
+        let top = self.session.dir_stack.remove(0);
+        if !self.change_dir(&top.to_string_lossy()) {
+            io.err(&format!(
+                "pzsh: popd: no such file or directory: {}\n",
+                top.display()
+            ))?;
+            return Ok(1);
+        }
+        self.dirs(io)
+    }
+
+    fn dirs(&self, io: &Io) -> std::io::Result<i32> {
+        let mut line = self.session.cwd.display().to_string();
+        for dir in &self.session.dir_stack {
+            line.push(' ');
+            line.push_str(&dir.display().to_string());
+        }
+        line.push('\n');
+        io.out(&line)?;
+        Ok(0)
+    }
+
+    fn export(&mut self, args: &[String], io: &Io) -> std::io::Result<i32> {
+        let args: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+        if args.is_empty() {
+            let mut vars: Vec<(String, String)> = self
+                .env
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .filter(|(k, _)| !self.session.vars.contains_key(k))
+                .collect();
+            vars.extend(
+                self.session
+                    .vars
+                    .iter()
+                    .filter_map(|(k, v)| v.clone().map(|v| (k.clone(), v))),
+            );
+            vars.sort_unstable();
+            let mut output = String::new();
+            for (key, value) in vars {
+                output.push_str(&format!("export {key}=\"{value}\"\n"));
+            }
+            io.out(&output)?;
+            return Ok(0);
+        }
+
+        let mut code = 0;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if !is_identifier(name) {
+                io.err(&format!("pzsh: export: not a valid identifier: {name}\n"))?;
+                code = 1;
+                continue;
+            }
+            // Every variable is already exported; `export NAME` only needs a value
+            if let Some(value) = value {
+                self.session
+                    .vars
+                    .insert(name.to_string(), Some(value.to_string()));
+            }
+        }
+        Ok(code)
+    }
+
+    fn alias(&mut self, args: &[String], io: &Io) -> std::io::Result<i32> {
+        if args.is_empty() {
+            let mut aliases: Vec<(&String, &String)> = self.aliases.iter().collect();
+            aliases.sort_unstable();
+            let mut output = String::new();
+            for (name, expansion) in aliases {
+                output.push_str(&alias_line(name, expansion));
+            }
+            io.out(&output)?;
+            return Ok(0);
+        }
+
+        let mut code = 0;
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, expansion)) => {
+                    self.aliases.insert(name.to_string(), expansion.to_string());
+                    self.parser.set_alias(name, Some(expansion));
+                }
+                None => {
+                    if let Some(expansion) = self.aliases.get(arg) {
+                        io.out(&alias_line(arg, expansion))?;
+                    } else {
+                        io.err(&format!("pzsh: alias: {arg}: not found\n"))?;
+                        code = 1;
+                    }
+                }
+            }
+        }
+        Ok(code)
+    }
+
+    fn unalias(&mut self, args: &[String], io: &Io) -> std::io::Result<i32> {
+        if args.first().is_some_and(|a| a == "-a") {
+            for name in std::mem::take(&mut self.aliases).into_keys() {
+                self.parser.set_alias(&name, None);
+            }
+            return Ok(0);
+        }
+        let mut code = 0;
+        for name in args {
+            if self.aliases.remove(name).is_some() {
+                self.parser.set_alias(name, None);
+            } else {
+                io.err(&format!("pzsh: unalias: {name}: not found\n"))?;
+                code = 1;
+            }
+        }
+        Ok(code)
+    }
+
+    /// `wait` for every background job (status 0), or `wait PID...` for
+    /// the given jobs (status of the last one)
+    fn wait(&mut self, args: &[String], io: &Io) -> Result<i32> {
+        if args.is_empty() {
+            self.wait_jobs()?;
+            return Ok(0);
+        }
+        let mut code = 0;
+        for arg in args {
+            let index = self
+                .jobs
+                .iter()
+                .position(|child| child.id().to_string() == *arg);
+            code = if let Some(index) = index {
+                let status = self.jobs.remove(index).wait().map_err(ExecError::Io)?;
+                super::run::exit_code(status)
+            } else {
+                io.err(&format!(
+                    "pzsh: wait: pid {arg} is not a child of this shell\n"
+                ))
+                .map_err(ExecError::Io)?;
+                127
+            };
+        }
+        Ok(code)
+    }
+
+    fn list_jobs(&mut self, io: &Io) -> std::io::Result<i32> {
+        let mut output = String::new();
+        for (i, child) in self.jobs.iter_mut().enumerate() {
+            let state = match child.try_wait()? {
+                Some(status) => format!("Done({})", super::run::exit_code(status)),
+                None => "Running".to_string(),
+            };
+            output.push_str(&format!("[{}] {} {state}\n", i + 1, child.id()));
+        }
+        io.out(&output)?;
+        Ok(0)
+    }
+
+    /// Change the session directory, updating `PWD` and `OLDPWD`
+    fn change_dir(&mut self, target: &str) -> bool {
+        let path = normalize(&self.session.cwd.join(target));
+        if !path.is_dir() {
+            return false;
+        }
+        let old = std::mem::replace(&mut self.session.cwd, path);
+        self.session.vars.insert(
+            "OLDPWD".to_string(),
+            Some(old.to_string_lossy().into_owned()),
+        );
+        self.session.vars.insert(
+            "PWD".to_string(),
+            Some(self.session.cwd.to_string_lossy().into_owned()),
+        );
+        true
+    }
+}
+
+fn echo(args: &[String], io: &Io) -> std::io::Result<i32> {
+    let newline = args.first().is_none_or(|a| a != "-n");
+    let args = if newline { args } else { &args[1..] };
+    let mut line = args.join(" ");
+    if newline {
+        line.push('\n');
+    }
+    io.out(&line)?;
+    Ok(0)
+}
+
+/// `alias` output line, quoted so it can be read back
+fn alias_line(name: &str, expansion: &str) -> String {
+    format!("alias {name}='{}'\n", expansion.replace('\'', "'\\''"))
+}
+
+pub(super) fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Resolve `.` and `..` lexically, like a logical `cd`
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_builtin() {
+        assert!(is_builtin("cd"));
+        assert!(is_builtin("export"));
+        assert!(is_builtin("."));
+        assert!(is_builtin("umask"));
+        assert!(!is_builtin("printf"));
+        assert!(!is_builtin("ls"));
+    }
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("PATH"));
+        assert!(is_identifier("_x1"));
+        assert!(!is_identifier("1x"));
+        assert!(!is_identifier("A-B"));
+        assert!(!is_identifier(""));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("/a/b/../c/./d")),
+            PathBuf::from("/a/c/d")
+        );
+        assert_eq!(normalize(Path::new("/..")), PathBuf::from("/"));
+    }
+}
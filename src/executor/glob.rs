@@ -0,0 +1,213 @@
+//! Pathname expansion for unquoted `*`, `?` and `[...]`
+//!
+//! Patterns use `\` to escape a character, which is how quoted and
+//! expanded text is kept from matching as a pattern.
+
+use std::fs;
+use std::path::Path;
+
+/// Escape `text` so that it only matches itself
+pub(super) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Check if `pattern` has an unescaped `*`, `?` or `[`
+pub(super) fn has_magic(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Paths matching `pattern`, sorted
+///
+/// Relative patterns are matched against `cwd` and the matches stay
+/// relative. Hidden files only match components that start with `.`.
+pub(super) fn glob(pattern: &str, cwd: &Path) -> Vec<String> {
+    let mut matches = vec![if pattern.starts_with('/') {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for prefix in &matches {
+            if !has_magic(component) {
+                next.push(join(prefix, &unescape(component)));
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(cwd.join(prefix)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if (!name.starts_with('.') || component.starts_with('.'))
+                    && matches_name(component, &name)
+                {
+                    next.push(join(prefix, &name));
+                }
+            }
+        }
+        matches = next;
+    }
+
+    let dirs_only = pattern.ends_with('/');
+    matches.retain(|m| {
+        let path = cwd.join(m);
+        if dirs_only {
+            path.is_dir()
+        } else {
+            path.symlink_metadata().is_ok()
+        }
+    });
+    if dirs_only {
+        for m in &mut matches {
+            if !m.ends_with('/') {
+                m.push('/');
+            }
+        }
+    }
+    matches.sort_unstable();
+    matches
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if prefix.ends_with('/') {
+        format!("{prefix}{name}")
+    } else {
+        format!("{prefix}/{name}")
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        out.push(if c == '\\' {
+            chars.next().unwrap_or(c)
+        } else {
+            c
+        });
+    }
+    out
+}
+
+/// Match a single path component against a pattern component
+fn matches_name(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_chars(&pattern, &name)
+}
+
+fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| matches_chars(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && matches_chars(&pattern[1..], &name[1..]),
+        Some('[') => match (bracket(&pattern[1..], name.first().copied()), name.first()) {
+            (Some((len, matched)), Some(_)) => {
+                matched && matches_chars(&pattern[len + 1..], &name[1..])
+            }
+            (Some(_), None) => false,
+            // No closing `]`: a literal `[`
+            (None, _) => name.first() == Some(&'[') && matches_chars(&pattern[1..], &name[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            name.first() == Some(&pattern[1]) && matches_chars(&pattern[2..], &name[1..])
+        }
+        Some(c) => name.first() == Some(c) && matches_chars(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Match `c` against the bracket expression after a `[`, returning the
+/// expression's length (through the `]`) and whether `c` matched
+fn bracket(set: &[char], c: Option<char>) -> Option<(usize, bool)> {
+    let negate = matches!(set.first(), Some('!' | '^'));
+    let mut i = usize::from(negate);
+    let mut matched = false;
+    let mut first = true;
+    while i < set.len() {
+        let start = match set[i] {
+            ']' if !first => return Some((i + 1, matched != negate)),
+            '\\' if i + 1 < set.len() => {
+                i += 1;
+                set[i]
+            }
+            other => other,
+        };
+        first = false;
+        if set.get(i + 1) == Some(&'-') && set.get(i + 2).is_some_and(|&end| end != ']') {
+            let end = set[i + 2];
+            matched |= c.is_some_and(|c| (start..=end).contains(&c));
+            i += 3;
+        } else {
+            matched |= c == Some(start);
+            i += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_name() {
+        assert!(matches_name("*.toml", "Cargo.toml"));
+        assert!(!matches_name("*.toml", "Cargo.lock"));
+        assert!(matches_name("a?c", "abc"));
+        assert!(matches_name("[a-c]x", "bx"));
+        assert!(!matches_name("[!a-c]x", "bx"));
+        assert!(matches_name("[]]", "]"));
+        assert!(matches_name("\\*", "*"));
+        assert!(!matches_name("\\*", "x"));
+        assert!(matches_name("[x", "[x"));
+    }
+
+    #[test]
+    fn test_escape() {
+        let escaped = escape("a*[b]?\\");
+        assert!(!has_magic(&escaped));
+        assert!(matches_name(&escaped, "a*[b]?\\"));
+        assert!(has_magic("src/*.rs"));
+    }
+
+    #[test]
+    fn test_glob() {
+        let dir = crate::test_util::temp_dir("glob");
+        for file in ["a.toml", "b.toml", ".hidden.toml", "sub/c.toml", "c.txt"] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        assert_eq!(glob("*.toml", &dir), ["a.toml", "b.toml"]);
+        assert_eq!(glob(".*.toml", &dir), [".hidden.toml"]);
+        assert_eq!(glob("*/*.toml", &dir), ["sub/c.toml"]);
+        assert_eq!(glob("*/", &dir), ["sub/"]);
+        assert!(glob("*.md", &dir).is_empty());
+        let absolute = format!("{}/*.txt", escape(&dir.display().to_string()));
+        assert_eq!(glob(&absolute, &dir), [format!("{}/c.txt", dir.display())]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
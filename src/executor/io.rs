@@ -0,0 +1,152 @@
+//! Standard stream plumbing for command execution
+//!
+//! Each command runs against an [`Io`] triple. Handles are duplicated
+//! (never shared) so that closing a pipe end in one stage cannot leak
+//! into another.
+
+use std::fs::File;
+use std::io::{self, PipeReader, PipeWriter, Read, Write};
+use std::process::Stdio;
+use std::thread::{self, JoinHandle};
+
+/// A single standard stream
+#[derive(Debug)]
+pub enum Handle {
+    /// Stream inherited from the pzsh process
+    Inherit,
+    /// `/dev/null`
+    Null,
+    /// Redirected file
+    File(File),
+    /// Read end of a pipe
+    PipeReader(PipeReader),
+    /// Write end of a pipe
+    PipeWriter(PipeWriter),
+}
+
+impl Handle {
+    /// Duplicate the underlying descriptor
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Self::Inherit => Self::Inherit,
+            Self::Null => Self::Null,
+            Self::File(file) => Self::File(file.try_clone()?),
+            Self::PipeReader(reader) => Self::PipeReader(reader.try_clone()?),
+            Self::PipeWriter(writer) => Self::PipeWriter(writer.try_clone()?),
+        })
+    }
+
+    /// Convert into a child process stream
+    pub fn into_stdio(self) -> Stdio {
+        match self {
+            Self::Inherit => Stdio::inherit(),
+            Self::Null => Stdio::null(),
+            Self::File(file) => file.into(),
+            Self::PipeReader(reader) => reader.into(),
+            Self::PipeWriter(writer) => writer.into(),
+        }
+    }
+
+    /// Write from an in-process builtin (`stderr` selects the inherited stream)
+    pub fn write_all(&self, bytes: &[u8], stderr: bool) -> io::Result<()> {
+        let result = match self {
+            Self::Inherit if stderr => io::stderr().write_all(bytes),
+            Self::Inherit => {
+                let mut out = io::stdout().lock();
+                out.write_all(bytes).and_then(|()| out.flush())
+            }
+            Self::Null => Ok(()),
+            Self::File(file) => (&*file).write_all(bytes),
+            Self::PipeWriter(writer) => (&*writer).write_all(bytes),
+            Self::PipeReader(_) => Err(io::Error::other("stream is not writable")),
+        };
+        // A closed downstream reader is not an error for the writer
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            other => other,
+        }
+    }
+}
+
+/// stdin, stdout and stderr of a command
+#[derive(Debug)]
+pub struct Io {
+    pub stdin: Handle,
+    pub stdout: Handle,
+    pub stderr: Handle,
+}
+
+impl Io {
+    /// Streams inherited from the pzsh process
+    pub const fn inherit() -> Self {
+        Self {
+            stdin: Handle::Inherit,
+            stdout: Handle::Inherit,
+            stderr: Handle::Inherit,
+        }
+    }
+
+    /// Duplicate all three streams
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            stdin: self.stdin.try_clone()?,
+            stdout: self.stdout.try_clone()?,
+            stderr: self.stderr.try_clone()?,
+        })
+    }
+
+    /// Stream for file descriptor 0, 1 or 2
+    pub const fn slot(&mut self, fd: u32) -> Option<&mut Handle> {
+        match fd {
+            0 => Some(&mut self.stdin),
+            1 => Some(&mut self.stdout),
+            2 => Some(&mut self.stderr),
+            _ => None,
+        }
+    }
+
+    /// Write to stdout
+    pub fn out(&self, text: &str) -> io::Result<()> {
+        self.stdout.write_all(text.as_bytes(), false)
+    }
+
+    /// Write to stderr
+    pub fn err(&self, text: &str) -> io::Result<()> {
+        self.stderr.write_all(text.as_bytes(), true)
+    }
+}
+
+/// Pipe whose read end is drained into memory by a background thread
+pub struct Capture {
+    reader: JoinHandle<io::Result<Vec<u8>>>,
+}
+
+impl Capture {
+    /// Create the pipe, returning the collector and the write end
+    pub fn new() -> io::Result<(Self, PipeWriter)> {
+        let (mut reader, writer) = io::pipe()?;
+        let reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+            Ok(buf)
+        });
+        Ok((Self { reader }, writer))
+    }
+
+    /// Wait for every write end to close and return the collected bytes
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        self.reader
+            .join()
+            .map_err(|_| io::Error::other("capture thread panicked"))?
+    }
+}
+
+/// Pipe read end that yields `bytes` (written by a background thread)
+pub fn feed(bytes: Vec<u8>) -> io::Result<Handle> {
+    let (reader, mut writer) = io::pipe()?;
+    thread::spawn(move || {
+        // The reader may exit without consuming its input
+        let _ = writer.write_all(&bytes);
+    });
+    Ok(Handle::PipeReader(reader))
+}
@@ -2,12 +2,71 @@
 //!
 //! O(1) execution with 2ms budget constraint.
 //! No subprocess spawning at startup.
+//!
+//! Parsed commands run on demand: external programs are spawned with the
+//! frozen environment, pipelines and redirections are wired with OS pipes
+//! and files, and builtins such as `cd`, `export` and `pushd` run in-process.
+
+mod builtins;
+mod glob;
+mod io;
+mod run;
 
-use crate::config::CompiledConfig;
+use crate::config::{CompiledConfig, render_all};
+use crate::parser::{ParsedCommand, Parser};
 use crate::{MAX_EXECUTOR_MS, PzshError, Result};
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
+use io::{Capture, Handle, Io};
+use std::path::{Path, PathBuf};
+use std::process::Child;
 use std::time::{Duration, Instant};
 
+/// Execution errors (command failures are reported as exit codes instead)
+#[derive(Debug, thiserror::Error)]
+pub enum ExecError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+}
+
+/// Exit status and wall-clock time of an executed command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecStatus {
+    /// Exit code (`128 + signal` for signalled processes)
+    pub code: i32,
+    /// Time from start to completion
+    pub duration: Duration,
+}
+
+impl ExecStatus {
+    /// Check if the command exited with status 0
+    #[must_use]
+    pub const fn success(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// Result of [`Executor::run_captured`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOutput {
+    pub status: ExecStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Mutable shell state, saved and restored around subshells
+#[derive(Debug, Clone)]
+struct Session {
+    /// Working directory for spawned commands
+    cwd: PathBuf,
+    /// `pushd`/`popd` stack (most recent first)
+    dir_stack: Vec<PathBuf>,
+    /// Variables set (`Some`) or unset (`None`) at runtime, layered over the frozen env
+    vars: AHashMap<String, Option<String>>,
+}
+
 /// Frozen environment (immutable after startup)
 #[derive(Debug, Clone)]
 pub struct FrozenEnv {
@@ -15,11 +74,26 @@ pub struct FrozenEnv {
 }
 
 impl FrozenEnv {
-    /// Create frozen environment from config
+    /// Create frozen environment from config, expanding `${VAR}`
+    /// references against the pzsh process environment
     #[must_use]
     pub fn new(config: &CompiledConfig) -> Self {
+        let ordered: AHashSet<&str> = config.env_order.iter().map(String::as_str).collect();
+        let mut rest: Vec<&String> = config
+            .env
+            .keys()
+            .filter(|name| !ordered.contains(name.as_str()))
+            .collect();
+        rest.sort_unstable();
+        let vars = config
+            .env_order
+            .iter()
+            .chain(rest)
+            .filter_map(|name| Some((name.as_str(), config.env.get(name)?.as_str())));
         Self {
-            vars: config.env.clone(),
+            vars: render_all(vars, &|name| std::env::var(name).ok())
+                .into_iter()
+                .collect(),
         }
     }
 
@@ -58,6 +132,18 @@ pub struct Executor {
     aliases: AHashMap<String, String>,
     /// Initialized flag
     initialized: bool,
+    /// Parser for alias expansions, `eval` and command substitutions
+    parser: Parser,
+    /// Runtime shell state
+    session: Session,
+    /// Aliases being expanded (stops `ls = "ls -G"` recursing)
+    alias_stack: Vec<String>,
+    /// Background jobs started with `&`
+    jobs: Vec<Child>,
+    /// Exit code of the last command (`$?`)
+    last_status: i32,
+    /// Set by `exit`; stops the remaining commands of the current run
+    exit_requested: bool,
 }
 
 impl Executor {
@@ -68,6 +154,31 @@ impl Executor {
             env: FrozenEnv::new(config),
             aliases: config.aliases.clone(),
             initialized: false,
+            parser: Parser::new(config),
+            session: Session {
+                cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+                dir_stack: Vec::new(),
+                vars: AHashMap::new(),
+            },
+            alias_stack: Vec::new(),
+            jobs: Vec::new(),
+            last_status: 0,
+            exit_requested: false,
+        }
+    }
+
+    /// Copy of this executor for a pipeline stage (state changes stay local)
+    fn fork(&self) -> Self {
+        Self {
+            env: self.env.clone(),
+            aliases: self.aliases.clone(),
+            initialized: self.initialized,
+            parser: self.parser.clone(),
+            session: self.session.clone(),
+            alias_stack: self.alias_stack.clone(),
+            jobs: Vec::new(),
+            last_status: self.last_status,
+            exit_requested: false,
         }
     }
 
@@ -152,11 +263,123 @@ impl Executor {
 
         output
     }
+
+    /// Run a parsed command with the streams of the pzsh process
+    ///
+    /// # Errors
+    /// Returns error on I/O failures or unsupported syntax; a failing or
+    /// missing command is reported through [`ExecStatus::code`] instead.
+    pub fn run(&mut self, command: &ParsedCommand) -> Result<ExecStatus> {
+        let start = Instant::now();
+        let code = self.run_with(command, &Io::inherit())?;
+        Ok(ExecStatus {
+            code,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Run a parsed command with stdin from `/dev/null`, capturing stdout and stderr
+    ///
+    /// # Errors
+    /// Returns error on I/O failures or unsupported syntax
+    pub fn run_captured(&mut self, command: &ParsedCommand) -> Result<ExecOutput> {
+        let start = Instant::now();
+        let (stdout, out_writer) = Capture::new().map_err(ExecError::Io)?;
+        let (stderr, err_writer) = Capture::new().map_err(ExecError::Io)?;
+        let io = Io {
+            stdin: Handle::Null,
+            stdout: Handle::PipeWriter(out_writer),
+            stderr: Handle::PipeWriter(err_writer),
+        };
+        let code = self.run_with(command, &io);
+        // Close our write ends so the collectors see EOF
+        drop(io);
+        let stdout = stdout.finish().map_err(ExecError::Io)?;
+        let stderr = stderr.finish().map_err(ExecError::Io)?;
+        Ok(ExecOutput {
+            status: ExecStatus {
+                code: code?,
+                duration: start.elapsed(),
+            },
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        })
+    }
+
+    /// Parse and run a command line
+    ///
+    /// # Errors
+    /// Returns error if parsing fails or on execution errors (see [`Self::run`])
+    pub fn execute(&mut self, input: &str) -> Result<ExecStatus> {
+        let command = self.parser.parse(input)?;
+        self.run(&command)
+    }
+
+    /// Wait for all background jobs, returning their exit codes in start order
+    ///
+    /// # Errors
+    /// Returns error if waiting on a job fails
+    pub fn wait_jobs(&mut self) -> Result<Vec<i32>> {
+        self.jobs
+            .drain(..)
+            .map(|mut child| {
+                child
+                    .wait()
+                    .map(run::exit_code)
+                    .map_err(|e| ExecError::Io(e).into())
+            })
+            .collect()
+    }
+
+    /// Current working directory of the session
+    #[must_use]
+    pub fn cwd(&self) -> &Path {
+        &self.session.cwd
+    }
+
+    /// `pushd` directory stack (most recent first)
+    #[must_use]
+    pub fn dir_stack(&self) -> &[PathBuf] {
+        &self.session.dir_stack
+    }
+
+    /// Effective value of a variable (runtime exports over the frozen env over the process env)
+    #[must_use]
+    pub fn var(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => return Some(self.last_status.to_string()),
+            "$" => return Some(std::process::id().to_string()),
+            _ => {}
+        }
+        match self.session.vars.get(name) {
+            Some(value) => value.clone(),
+            None => self
+                .env
+                .get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok()),
+        }
+    }
+
+    /// Exit code of the last command
+    #[must_use]
+    pub const fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    fn run_with(&mut self, command: &ParsedCommand, io: &Io) -> Result<i32> {
+        self.exit_requested = false;
+        let code = self.exec(command, io)?;
+        self.exit_requested = false;
+        self.last_status = code;
+        Ok(code)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::temp_dir;
     use std::time::Instant;
 
     fn test_config() -> CompiledConfig {
@@ -323,4 +546,390 @@ mod tests {
 
         assert_eq!(lines1, lines2, "Executor must be deterministic");
     }
+
+    fn exec_config() -> CompiledConfig {
+        let mut config = test_config();
+        config
+            .aliases
+            .insert("greet".to_string(), "echo hello".to_string());
+        config
+            .aliases
+            .insert("echo".to_string(), "echo pre".to_string());
+        config
+            .aliases
+            .insert("shout".to_string(), "tr a-z A-Z".to_string());
+        config
+    }
+
+    fn capture(executor: &mut Executor, input: &str) -> ExecOutput {
+        let command = executor.parser.parse(input).unwrap();
+        executor.run_captured(&command).unwrap()
+    }
+
+    #[test]
+    fn test_run_simple_command() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let output = capture(&mut executor, "printf '%s-%s' a 'b c'");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, "a-b c");
+        assert!(output.status.duration > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_run_exit_status() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let output = capture(&mut executor, "sh -c 'exit 3'");
+        assert_eq!(output.status.code, 3);
+        assert_eq!(executor.last_status(), 3);
+
+        let output = capture(&mut executor, "pzsh-no-such-command");
+        assert_eq!(output.status.code, 127);
+        assert!(
+            output
+                .stderr
+                .contains("pzsh-no-such-command: command not found")
+        );
+    }
+
+    #[test]
+    fn test_run_pipeline() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let output = capture(&mut executor, "printf 'b\\na\\n' | sort | head -n 1");
+        assert_eq!(output.stdout, "a\n");
+
+        // In-process builtin feeding an external command
+        let output = capture(&mut executor, "echo hello | tr a-z A-Z");
+        assert_eq!(output.stdout, "HELLO\n");
+
+        // Status of the last stage wins
+        let output = capture(&mut executor, "false | true");
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn test_run_pipeline_streams_in_process_stages() {
+        let mut config = CompiledConfig::default();
+        config.aliases.insert("y".to_string(), "yes".to_string());
+        let mut executor = Executor::new(&config);
+
+        // An aliased endless producer must not be drained before `head` starts
+        let output = capture(&mut executor, "y | head -n 1");
+        assert_eq!(output.stdout, "y\n");
+
+        let output = capture(&mut executor, "(yes no) | head -n 1");
+        assert_eq!(output.stdout, "no\n");
+
+        // Stages run in their own copy of the session
+        let cwd = executor.session.cwd.clone();
+        capture(&mut executor, "cd / | true");
+        assert_eq!(executor.session.cwd, cwd);
+    }
+
+    #[test]
+    fn test_run_list_operators() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let output = capture(&mut executor, "false && echo no || echo yes; echo done");
+        assert_eq!(output.stdout, "yes\ndone\n");
+
+        let output = capture(&mut executor, "echo a; exit 4; echo b");
+        assert_eq!(output.stdout, "a\n");
+        assert_eq!(output.status.code, 4);
+    }
+
+    #[test]
+    fn test_run_redirections() {
+        let dir = temp_dir("exec-redirect");
+        let mut executor = Executor::new(&CompiledConfig::default());
+        capture(&mut executor, &format!("cd {}", dir.display()));
+
+        capture(&mut executor, "echo one > out.txt; echo two >> out.txt");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("out.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+
+        let output = capture(&mut executor, "wc -l < out.txt");
+        assert_eq!(output.stdout.trim(), "2");
+
+        let output = capture(&mut executor, "sh -c 'echo err >&2' 2>&1");
+        assert_eq!(output.stdout, "err\n");
+        assert!(output.stderr.is_empty());
+
+        let output = capture(&mut executor, "tr a-z A-Z <<< abc");
+        assert_eq!(output.stdout, "ABC\n");
+
+        let output = capture(&mut executor, "cat < missing.txt");
+        assert_eq!(output.status.code, 1);
+        assert!(output.stderr.contains("missing.txt"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn test_run_uses_frozen_env() {
+        let mut executor = Executor::new(&test_config());
+
+        let output = capture(&mut executor, "sh -c 'echo $EDITOR'");
+        assert_eq!(output.stdout, "vim\n");
+
+        let output = capture(&mut executor, "echo $EDITOR ${UNSET_PZSH_VAR:-fallback}");
+        assert_eq!(output.stdout, "vim fallback\n");
+    }
+
+    #[test]
+    fn test_builtins_change_session_state() {
+        let dir = temp_dir("exec-builtins");
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let output = capture(&mut executor, &format!("cd {} && pwd", dir.display()));
+        assert_eq!(output.stdout, format!("{}\n", dir.display()));
+        assert_eq!(executor.cwd(), dir);
+
+        // Children start in the session directory
+        let output = capture(&mut executor, "sh -c pwd");
+        assert_eq!(
+            output.stdout.trim(),
+            dir.canonicalize().unwrap().to_str().unwrap()
+        );
+
+        capture(&mut executor, "export PZSH_EXEC_TEST=42");
+        let output = capture(&mut executor, "sh -c 'echo $PZSH_EXEC_TEST'");
+        assert_eq!(output.stdout, "42\n");
+        capture(&mut executor, "unset PZSH_EXEC_TEST");
+        assert_eq!(executor.var("PZSH_EXEC_TEST"), None);
+
+        let output = capture(&mut executor, "cd /nonexistent-pzsh-dir");
+        assert_eq!(output.status.code, 1);
+        assert_eq!(executor.cwd(), dir);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_pushd_popd() {
+        let dir = temp_dir("exec-pushd");
+        let mut executor = Executor::new(&CompiledConfig::default());
+        let start = executor.cwd().to_path_buf();
+
+        capture(&mut executor, &format!("pushd {}", dir.display()));
+        assert_eq!(executor.cwd(), dir);
+        assert_eq!(executor.dir_stack(), std::slice::from_ref(&start));
+
+        capture(&mut executor, "popd");
+        assert_eq!(executor.cwd(), start);
+        assert!(executor.dir_stack().is_empty());
+
+        let output = capture(&mut executor, "popd");
+        assert_eq!(output.status.code, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_subshell_isolates_state() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+        let start = executor.cwd().to_path_buf();
+
+        let output = capture(&mut executor, "(cd / && export PZSH_SUB=1 && pwd); pwd");
+        assert_eq!(output.stdout, format!("/\n{}\n", start.display()));
+        assert_eq!(executor.cwd(), start);
+        assert_eq!(executor.var("PZSH_SUB"), None);
+    }
+
+    #[test]
+    fn test_run_aliases_end_to_end() {
+        let mut executor = Executor::new(&exec_config());
+
+        let output = capture(&mut executor, "greet world");
+        assert_eq!(output.stdout, "pre hello world\n");
+
+        // Self-referencing alias expands once
+        let output = capture(&mut executor, "echo x");
+        assert_eq!(output.stdout, "pre x\n");
+
+        let output = capture(&mut executor, "printf abc | shout");
+        assert_eq!(output.stdout, "ABC");
+    }
+
+    #[test]
+    fn test_assignments_and_substitution() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let output = capture(&mut executor, "PZSH_ONE=1 sh -c 'echo $PZSH_ONE'");
+        assert_eq!(output.stdout, "1\n");
+        assert_eq!(executor.var("PZSH_ONE"), None);
+
+        capture(&mut executor, "PZSH_TWO=\"a b\"");
+        assert_eq!(executor.var("PZSH_TWO").as_deref(), Some("a b"));
+
+        let output = capture(&mut executor, "echo \"[$(printf 'x\\n\\n')]\" $?");
+        assert_eq!(output.stdout, "[x] 0\n");
+    }
+
+    #[test]
+    fn test_background_jobs() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let status = executor.execute("sh -c 'exit 5' &").unwrap();
+        assert!(status.success());
+        assert_eq!(executor.wait_jobs().unwrap(), vec![5]);
+        assert!(executor.wait_jobs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_source_and_alias_builtins() {
+        let dir = temp_dir("exec-source");
+        let mut executor = Executor::new(&CompiledConfig::default());
+        capture(&mut executor, &format!("cd {}", dir.display()));
+        std::fs::write(
+            dir.join("env.sh"),
+            "export PZSH_SOURCED=1\nalias hi='echo hi there'\ncd /\n",
+        )
+        .unwrap();
+
+        let output = capture(&mut executor, "source env.sh");
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(executor.var("PZSH_SOURCED").as_deref(), Some("1"));
+        assert_eq!(executor.cwd(), Path::new("/"));
+        assert_eq!(capture(&mut executor, "hi").stdout, "hi there\n");
+        assert_eq!(
+            capture(&mut executor, "alias hi").stdout,
+            "alias hi='echo hi there'\n"
+        );
+
+        capture(&mut executor, "unalias hi");
+        assert_eq!(executor.get_alias("hi"), None);
+        assert_eq!(capture(&mut executor, "alias hi").status.code, 1);
+
+        let output = capture(&mut executor, &format!(". {}/missing.sh", dir.display()));
+        assert_eq!(output.status.code, 1);
+        assert!(output.stderr.contains("missing.sh"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_prefix_assignments_for_builtins() {
+        let dir = temp_dir("exec-prefix");
+        let mut executor = Executor::new(&CompiledConfig::default());
+        capture(&mut executor, &format!("cd {}", dir.display()));
+        std::fs::write(dir.join("show.sh"), "echo \"sourced $PZSH_PREFIX\"\n").unwrap();
+        capture(&mut executor, "PZSH_KEEP=old");
+
+        assert_eq!(
+            capture(&mut executor, "PZSH_PREFIX=bar eval 'echo $PZSH_PREFIX'").stdout,
+            "bar\n"
+        );
+        assert_eq!(
+            capture(&mut executor, "PZSH_PREFIX=bar source show.sh").stdout,
+            "sourced bar\n"
+        );
+        assert_eq!(
+            capture(
+                &mut executor,
+                "PZSH_KEEP=new eval 'sh -c \"echo $PZSH_KEEP\"'"
+            )
+            .stdout,
+            "new\n"
+        );
+        // Restored afterwards
+        assert_eq!(executor.var("PZSH_PREFIX"), None);
+        assert_eq!(executor.var("PZSH_KEEP").as_deref(), Some("old"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_wait_and_jobs_builtins() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        capture(&mut executor, "sh -c 'exit 3' &");
+        let pid = executor.jobs[0].id();
+        assert!(
+            capture(&mut executor, "jobs")
+                .stdout
+                .starts_with(&format!("[1] {pid} "))
+        );
+        assert_eq!(
+            capture(&mut executor, &format!("wait {pid}")).status.code,
+            3
+        );
+        assert!(executor.jobs.is_empty());
+
+        capture(&mut executor, "sh -c 'exit 4' &");
+        assert_eq!(capture(&mut executor, "wait").status.code, 0);
+        assert!(executor.jobs.is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_builtins_are_not_spawned() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        for input in [
+            "set -e",
+            "local x=1",
+            "return 1",
+            "readonly X",
+            "umask 022",
+            "exec ls",
+        ] {
+            let command = executor.parser.parse(input).unwrap();
+            assert!(
+                matches!(
+                    executor.run_captured(&command),
+                    Err(PzshError::Exec(ExecError::Unsupported(_)))
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_glob_expansion() {
+        let dir = temp_dir("exec-glob");
+        let mut executor = Executor::new(&CompiledConfig::default());
+        capture(&mut executor, &format!("cd {}", dir.display()));
+        for file in ["b.toml", "a.toml", "c.txt"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            capture(&mut executor, "echo *.toml").stdout,
+            "a.toml b.toml\n"
+        );
+        assert_eq!(capture(&mut executor, "echo ?.txt").stdout, "c.txt\n");
+        // Quoted and unmatched patterns are left alone
+        assert_eq!(capture(&mut executor, "echo '*.toml'").stdout, "*.toml\n");
+        assert_eq!(capture(&mut executor, "echo *.md").stdout, "*.md\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_pipe_with_stderr_and_ansi_c_escapes() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let output = capture(&mut executor, "sh -c 'echo out; echo err >&2' |& sort");
+        assert_eq!(output.stdout, "err\nout\n");
+        assert!(output.stderr.is_empty());
+
+        let output = capture(&mut executor, "printf '%s' $'\\x'");
+        assert_eq!(output.stdout, "\\x");
+    }
+
+    #[test]
+    fn test_unsupported_expansion_is_an_error() {
+        let mut executor = Executor::new(&CompiledConfig::default());
+
+        let command = executor.parser.parse("echo $((1 + 2))").unwrap();
+        assert!(matches!(
+            executor.run_captured(&command),
+            Err(PzshError::Exec(ExecError::Unsupported(_)))
+        ));
+    }
 }
@@ -0,0 +1,558 @@
+//! Command execution: word expansion, process spawning, pipelines,
+//! lists and redirections
+
+use super::io::{Capture, Handle, Io, feed};
+use super::{ExecError, Executor, builtins, glob};
+use crate::Result;
+use crate::parser::{ListOp, ParsedCommand, RedirectKind, Redirection, Word, WordPart};
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+use std::thread::JoinHandle;
+
+/// Outcome of starting a command
+enum Started {
+    /// Ran to completion in-process
+    Done(i32),
+    /// Spawned; the caller decides when to wait
+    Child(Child),
+}
+
+/// Non-final pipeline stage that is still running
+enum Running {
+    /// External program
+    Child(Child),
+    /// In-process stage writing into its pipe
+    Thread(JoinHandle<Result<i32>>),
+}
+
+impl Running {
+    fn wait(self) -> Result<i32> {
+        match self {
+            Self::Child(child) => wait(child),
+            Self::Thread(handle) => handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+        }
+    }
+}
+
+impl Executor {
+    /// Run a command to completion
+    pub(super) fn exec(&mut self, command: &ParsedCommand, io: &Io) -> Result<i32> {
+        let code = match command {
+            ParsedCommand::Empty => 0,
+            ParsedCommand::Simple { .. }
+            | ParsedCommand::Builtin { .. }
+            | ParsedCommand::Alias { .. }
+            | ParsedCommand::Redirect { .. } => {
+                let io = io.try_clone().map_err(ExecError::Io)?;
+                match self.start(command, io)? {
+                    Started::Done(code) => code,
+                    Started::Child(child) => wait(child)?,
+                }
+            }
+            ParsedCommand::Pipeline(stages) => self.pipeline(stages, io)?,
+            ParsedCommand::List(items) => self.list(items, io)?,
+            ParsedCommand::Subshell(inner) => {
+                let saved = self.session.clone();
+                let code = self.exec(inner, io);
+                self.session = saved;
+                self.exit_requested = false;
+                code?
+            }
+        };
+        self.last_status = code;
+        Ok(code)
+    }
+
+    /// Start a command, spawning external programs without waiting
+    fn start(&mut self, command: &ParsedCommand, io: Io) -> Result<Started> {
+        match command {
+            ParsedCommand::Simple { command, args } => {
+                let words: Vec<&Word> = std::iter::once(command).chain(args).collect();
+                let split = words
+                    .iter()
+                    .position(|w| assignment(w).is_none())
+                    .unwrap_or(words.len());
+                let mut assignments = Vec::with_capacity(split);
+                for word in &words[..split] {
+                    if let Some((name, value)) = assignment(word) {
+                        let value = self.expand(&value)?.unwrap_or_default();
+                        assignments.push((name, value));
+                    }
+                }
+                let argv = self.expand_all(&words[split..])?;
+                self.start_argv(assignments, &argv, io)
+            }
+            ParsedCommand::Builtin { name, args } => {
+                let mut argv = vec![name.clone()];
+                argv.extend(self.expand_all(args)?);
+                self.start_argv(Vec::new(), &argv, io)
+            }
+            ParsedCommand::Alias { name, args, .. } if self.alias_stack.contains(name) => {
+                let mut argv = vec![name.clone()];
+                argv.extend(self.expand_all(args)?);
+                self.start_argv(Vec::new(), &argv, io)
+            }
+            ParsedCommand::Alias {
+                name,
+                expansion,
+                args,
+            } => {
+                let expanded = append_args(self.parser.parse(expansion)?, args);
+                self.alias_stack.push(name.clone());
+                let started = self.start(&expanded, io);
+                self.alias_stack.pop();
+                started
+            }
+            ParsedCommand::Redirect { command, redirects } => {
+                match self.redirect(io, redirects)? {
+                    Some(io) => self.start(command, io),
+                    None => Ok(Started::Done(1)),
+                }
+            }
+            _ => Ok(Started::Done(self.exec(command, &io)?)),
+        }
+    }
+
+    /// Run a builtin in-process or spawn an external program
+    fn start_argv(
+        &mut self,
+        assignments: Vec<(String, String)>,
+        argv: &[String],
+        io: Io,
+    ) -> Result<Started> {
+        let Some(program) = argv.first() else {
+            // Bare `NAME=value` sets a session variable
+            for (name, value) in assignments {
+                self.session.vars.insert(name, Some(value));
+            }
+            return Ok(Started::Done(0));
+        };
+        if builtins::is_builtin(program) {
+            // `FOO=bar eval ...`: the assignments last for the builtin only
+            let saved: Vec<(String, Option<Option<String>>)> = assignments
+                .into_iter()
+                .map(|(name, value)| {
+                    let old = self.session.vars.insert(name.clone(), Some(value));
+                    (name, old)
+                })
+                .collect();
+            let status = self.builtin(argv, &io);
+            for (name, old) in saved.into_iter().rev() {
+                match old {
+                    Some(old) => self.session.vars.insert(name, old),
+                    None => self.session.vars.remove(&name),
+                };
+            }
+            return Ok(Started::Done(status?));
+        }
+
+        let path = if program.contains('/') {
+            self.session.cwd.join(program)
+        } else {
+            PathBuf::from(program)
+        };
+        let mut cmd = Command::new(path);
+        cmd.args(&argv[1..]).current_dir(&self.session.cwd);
+        for (key, value) in self.env.iter() {
+            cmd.env(key, value);
+        }
+        for (key, value) in &self.session.vars {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+        cmd.envs(assignments);
+        let stderr = io.stderr.try_clone().map_err(ExecError::Io)?;
+        cmd.stdin(io.stdin.into_stdio())
+            .stdout(io.stdout.into_stdio())
+            .stderr(io.stderr.into_stdio());
+
+        match cmd.spawn() {
+            Ok(child) => Ok(Started::Child(child)),
+            Err(e) => {
+                let (message, code) = match e.kind() {
+                    std::io::ErrorKind::NotFound => ("command not found".to_string(), 127),
+                    std::io::ErrorKind::PermissionDenied => ("permission denied".to_string(), 126),
+                    _ => (e.to_string(), 126),
+                };
+                stderr
+                    .write_all(format!("pzsh: {program}: {message}\n").as_bytes(), true)
+                    .map_err(ExecError::Io)?;
+                Ok(Started::Done(code))
+            }
+        }
+    }
+
+    /// Run `a | b | c`, returning the status of the last stage
+    fn pipeline(&mut self, stages: &[ParsedCommand], io: &Io) -> Result<i32> {
+        let mut running = Vec::new();
+        let code = self.start_pipeline(stages, io, &mut running);
+        // Reap every started stage, even when a later one failed to start
+        let mut reaped = Ok(0);
+        for stage in running {
+            let waited = stage.wait();
+            if reaped.is_ok() {
+                reaped = waited;
+            }
+        }
+        let code = code?;
+        reaped?;
+        Ok(code)
+    }
+
+    /// Start every stage, pushing the non-final ones onto `running`
+    fn start_pipeline(
+        &mut self,
+        stages: &[ParsedCommand],
+        io: &Io,
+        running: &mut Vec<Running>,
+    ) -> Result<i32> {
+        let mut stdin = io.stdin.try_clone().map_err(ExecError::Io)?;
+
+        for (i, stage) in stages.iter().enumerate() {
+            let stderr = io.stderr.try_clone().map_err(ExecError::Io)?;
+            if i + 1 == stages.len() {
+                let stage_io = Io {
+                    stdin,
+                    stdout: io.stdout.try_clone().map_err(ExecError::Io)?,
+                    stderr,
+                };
+                return match self.start(stage, stage_io)? {
+                    Started::Done(code) => Ok(code),
+                    Started::Child(child) => wait(child),
+                };
+            }
+
+            let (reader, writer) = std::io::pipe().map_err(ExecError::Io)?;
+            let stage_io = Io {
+                stdin,
+                stdout: Handle::PipeWriter(writer),
+                stderr,
+            };
+            if runs_in_process(stage) {
+                // Stream into the pipe from a thread so an endless producer
+                // cannot block the stages after it from starting
+                let mut shell = self.fork();
+                let stage = stage.clone();
+                running.push(Running::Thread(std::thread::spawn(move || {
+                    shell.exec(&stage, &stage_io)
+                })));
+            } else if let Started::Child(child) = self.start(stage, stage_io)? {
+                running.push(Running::Child(child));
+            }
+            stdin = Handle::PipeReader(reader);
+        }
+        Ok(0)
+    }
+
+    /// Run `a && b || c; d &`
+    fn list(&mut self, items: &[(ParsedCommand, ListOp)], io: &Io) -> Result<i32> {
+        let mut code = 0;
+        let mut skip = false;
+        for (command, op) in items {
+            if !skip {
+                code = if *op == ListOp::Background {
+                    self.background(command, io)?
+                } else {
+                    self.exec(command, io)?
+                };
+            }
+            if self.exit_requested {
+                break;
+            }
+            skip = match op {
+                ListOp::And => code != 0,
+                ListOp::Or => code == 0,
+                ListOp::Seq | ListOp::Background => false,
+            };
+        }
+        Ok(code)
+    }
+
+    /// Start a command without waiting (compound commands run in the foreground)
+    fn background(&mut self, command: &ParsedCommand, io: &Io) -> Result<i32> {
+        let io = io.try_clone().map_err(ExecError::Io)?;
+        if let Started::Child(child) = self.start(command, io)? {
+            self.jobs.push(child);
+        }
+        Ok(0)
+    }
+
+    /// Apply redirections in order; `None` if a target could not be opened
+    fn redirect(&mut self, mut io: Io, redirects: &[Redirection]) -> Result<Option<Io>> {
+        for redirect in redirects {
+            let fd = redirect.source_fd();
+            if fd > 2 {
+                return Err(ExecError::Unsupported(format!("redirection of fd {fd}")).into());
+            }
+            let target = self.expand(&redirect.target)?.unwrap_or_default();
+            let path = self.session.cwd.join(&target);
+
+            let opened = match redirect.kind {
+                RedirectKind::Input => File::open(&path).map(Handle::File),
+                RedirectKind::Output | RedirectKind::OutputAll => {
+                    File::create(&path).map(Handle::File)
+                }
+                RedirectKind::Append | RedirectKind::AppendAll => OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&path)
+                    .map(Handle::File),
+                RedirectKind::HereString => feed(format!("{target}\n").into_bytes()),
+                RedirectKind::DupInput | RedirectKind::DupOutput => {
+                    if target == "-" {
+                        Ok(Handle::Null)
+                    } else {
+                        match target.parse::<u32>().ok().and_then(|n| io.slot(n)) {
+                            Some(handle) => handle.try_clone(),
+                            None => {
+                                return Err(ExecError::Unsupported(format!(
+                                    "redirection target &{target}"
+                                ))
+                                .into());
+                            }
+                        }
+                    }
+                }
+            };
+            let handle = match opened {
+                Ok(handle) => handle,
+                Err(e) => {
+                    io.err(&format!("pzsh: {target}: {e}\n"))
+                        .map_err(ExecError::Io)?;
+                    return Ok(None);
+                }
+            };
+
+            if matches!(
+                redirect.kind,
+                RedirectKind::OutputAll | RedirectKind::AppendAll
+            ) {
+                io.stderr = handle.try_clone().map_err(ExecError::Io)?;
+            }
+            if let Some(slot) = io.slot(fd) {
+                *slot = handle;
+            }
+        }
+        Ok(Some(io))
+    }
+
+    /// Expand words into arguments, dropping unquoted words that expand to
+    /// nothing and globbing unquoted `*`, `?` and `[...]`
+    ///
+    /// A pattern that matches nothing is passed on unchanged.
+    fn expand_all(&mut self, words: &[impl std::borrow::Borrow<Word>]) -> Result<Vec<String>> {
+        let mut argv = Vec::with_capacity(words.len());
+        for word in words {
+            let (arg, pattern) = self.expand_word(word.borrow())?;
+            let matches = pattern.map_or_else(Vec::new, |p| glob::glob(&p, &self.session.cwd));
+            if matches.is_empty() {
+                argv.extend(arg);
+            } else {
+                argv.extend(matches);
+            }
+        }
+        Ok(argv)
+    }
+
+    /// Expand a single word: `~`, parameters and command substitutions
+    ///
+    /// Globbing (see [`Self::expand_all`]) and field splitting are not
+    /// performed.
+    fn expand(&mut self, word: &Word) -> Result<Option<String>> {
+        Ok(self.expand_word(word)?.0)
+    }
+
+    /// Expand a word, also returning it as a glob pattern when its unquoted
+    /// literal text has glob characters
+    fn expand_word(&mut self, word: &Word) -> Result<(Option<String>, Option<String>)> {
+        let mut out = String::new();
+        let mut pattern = String::new();
+        let mut magic = false;
+        // Quotes or literal text keep the word even if it expands to nothing
+        let mut keep = false;
+        for (i, part) in word.parts.iter().enumerate() {
+            let expanded = match part {
+                WordPart::Literal(text) => {
+                    keep |= !text.is_empty();
+                    magic |= glob::has_magic(text);
+                    let mut text = text.as_str();
+                    if i == 0
+                        && let Some(rest) = text.strip_prefix('~')
+                        && (rest.is_empty() || rest.starts_with('/'))
+                    {
+                        let home = self.var("HOME").unwrap_or_default();
+                        out.push_str(&home);
+                        pattern.push_str(&glob::escape(&home));
+                        text = rest;
+                    }
+                    out.push_str(text);
+                    pattern.push_str(text);
+                    continue;
+                }
+                WordPart::Quoted(text) => {
+                    keep = true;
+                    text.clone()
+                }
+                WordPart::Var(expr) => self.expand_var(expr)?,
+                WordPart::CommandSubst(src) => self.substitute(src)?,
+                WordPart::Arith(expr) => {
+                    return Err(ExecError::Unsupported(format!("$(({expr}))")).into());
+                }
+            };
+            out.push_str(&expanded);
+            pattern.push_str(&glob::escape(&expanded));
+        }
+        let value = (keep || !out.is_empty()).then_some(out);
+        Ok((value, magic.then_some(pattern)))
+    }
+
+    /// `${NAME}`, `${NAME:-default}`, `${NAME-default}` and `${NAME:+alt}`
+    fn expand_var(&self, expr: &str) -> Result<String> {
+        let name_len = if expr.starts_with(|c: char| "?$!#@*-".contains(c) || c.is_ascii_digit()) {
+            1
+        } else {
+            expr.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(expr.len())
+        };
+        let (name, rest) = expr.split_at(name_len);
+        let value = self.var(name);
+
+        if rest.is_empty() {
+            Ok(value.unwrap_or_default())
+        } else if let Some(default) = rest.strip_prefix(":-") {
+            Ok(value
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| default.to_string()))
+        } else if let Some(default) = rest.strip_prefix('-') {
+            Ok(value.unwrap_or_else(|| default.to_string()))
+        } else if let Some(alt) = rest.strip_prefix(":+") {
+            Ok(value
+                .filter(|v| !v.is_empty())
+                .map(|_| alt.to_string())
+                .unwrap_or_default())
+        } else {
+            Err(ExecError::Unsupported(format!("${{{expr}}}")).into())
+        }
+    }
+
+    /// Run `$(...)` and return its stdout without trailing newlines
+    fn substitute(&mut self, src: &str) -> Result<String> {
+        let command = self.parser.parse(src)?;
+        let (capture, writer) = Capture::new().map_err(ExecError::Io)?;
+        let io = Io {
+            stdin: Handle::Inherit,
+            stdout: Handle::PipeWriter(writer),
+            stderr: Handle::Inherit,
+        };
+        let code = self.exec(&command, &io);
+        drop(io);
+        let bytes = capture.finish().map_err(ExecError::Io)?;
+        code?;
+        let mut out = String::from_utf8_lossy(&bytes).into_owned();
+        out.truncate(out.trim_end_matches('\n').len());
+        Ok(out)
+    }
+}
+
+/// Split a `NAME=value` word into its name and value word
+fn assignment(word: &Word) -> Option<(String, Word)> {
+    let Some(WordPart::Literal(first)) = word.parts.first() else {
+        return None;
+    };
+    let (name, value) = first.split_once('=')?;
+    if !builtins::is_identifier(name) {
+        return None;
+    }
+    let mut parts = vec![WordPart::Literal(value.to_string())];
+    parts.extend(word.parts[1..].iter().cloned());
+    Some((
+        name.to_string(),
+        Word {
+            parts,
+            offset: word.offset,
+        },
+    ))
+}
+
+/// Append alias arguments to the last command of an expansion
+fn append_args(command: ParsedCommand, extra: &[Word]) -> ParsedCommand {
+    if extra.is_empty() {
+        return command;
+    }
+    match command {
+        ParsedCommand::Simple { command, mut args } => {
+            args.extend_from_slice(extra);
+            ParsedCommand::Simple { command, args }
+        }
+        ParsedCommand::Builtin { name, mut args } => {
+            args.extend_from_slice(extra);
+            ParsedCommand::Builtin { name, args }
+        }
+        ParsedCommand::Alias {
+            name,
+            expansion,
+            mut args,
+        } => {
+            args.extend_from_slice(extra);
+            ParsedCommand::Alias {
+                name,
+                expansion,
+                args,
+            }
+        }
+        ParsedCommand::Pipeline(mut stages) => {
+            if let Some(last) = stages.pop() {
+                stages.push(append_args(last, extra));
+            }
+            ParsedCommand::Pipeline(stages)
+        }
+        ParsedCommand::List(mut items) => {
+            if let Some((last, op)) = items.pop() {
+                items.push((append_args(last, extra), op));
+            }
+            ParsedCommand::List(items)
+        }
+        ParsedCommand::Redirect { command, redirects } => ParsedCommand::Redirect {
+            command: Box::new(append_args(*command, extra)),
+            redirects,
+        },
+        ParsedCommand::Empty => ParsedCommand::Simple {
+            command: extra[0].clone(),
+            args: extra[1..].to_vec(),
+        },
+        subshell @ ParsedCommand::Subshell(_) => subshell,
+    }
+}
+
+/// Check if a pipeline stage runs inside the pzsh process
+fn runs_in_process(command: &ParsedCommand) -> bool {
+    match command {
+        ParsedCommand::Simple { command, .. } => command
+            .as_literal()
+            .is_some_and(|name| builtins::is_builtin(&name)),
+        ParsedCommand::Builtin { name, .. } => builtins::is_builtin(name),
+        ParsedCommand::Redirect { command, .. } => runs_in_process(command),
+        _ => true,
+    }
+}
+
+fn wait(mut child: Child) -> Result<i32> {
+    Ok(exit_code(child.wait().map_err(ExecError::Io)?))
+}
+
+/// Shell exit code for a process status (`128 + signal` when killed)
+pub(super) fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
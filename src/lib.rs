@@ -19,6 +19,9 @@ pub mod shell;
 pub mod theme;
 pub mod zsh;
 
+#[cfg(test)]
+mod test_util;
+
 use std::time::Duration;
 
 /// Maximum allowed startup time (hard constraint)
@@ -51,6 +54,12 @@ pub enum PzshError {
     #[error("config error: {0}")]
     Config(#[from] config::ConfigError),
 
+    #[error("parse error: {0}")]
+    Parse(#[from] parser::ParseError),
+
+    #[error("exec error: {0}")]
+    Exec(#[from] executor::ExecError),
+
     #[error("forbidden pattern detected: {0}")]
     ForbiddenPattern(String),
 }
@@ -59,11 +68,38 @@ pub enum PzshError {
 pub type Result<T> = std::result::Result<T, PzshError>;
 
 /// Shell type supported by pzsh
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub enum ShellType {
     #[default]
     Zsh,
     Bash,
+    Fish,
+    /// Nushell (`config.nu` fragment)
+    Nushell,
+    /// Strict POSIX sh (dash, busybox)
+    Sh,
+}
+
+impl ShellType {
+    /// Shell name as used in config files and on the command line
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Zsh => "zsh",
+            Self::Bash => "bash",
+            Self::Fish => "fish",
+            Self::Nushell => "nushell",
+            Self::Sh => "sh",
+        }
+    }
+
+    /// Shell named `name`, the inverse of [`ShellType::name`]
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Zsh, Self::Bash, Self::Fish, Self::Nushell, Self::Sh]
+            .into_iter()
+            .find(|shell| shell.name() == name)
+    }
 }
 
 /// Main pzsh shell instance
@@ -221,6 +257,9 @@ mod tests {
 
         let err = PzshError::ForbiddenPattern("test pattern".to_string());
         assert!(err.to_string().contains("forbidden pattern"));
+
+        let err = PzshError::from(parser::ParseError::UnexpectedEof);
+        assert!(err.to_string().contains("parse error"));
     }
 
     #[test]
@@ -228,4 +267,20 @@ mod tests {
         let shell = ShellType::default();
         assert_eq!(shell, ShellType::Zsh);
     }
+
+    #[test]
+    fn test_shell_type_name() {
+        assert_eq!(ShellType::Zsh.name(), "zsh");
+        assert_eq!(ShellType::Bash.name(), "bash");
+        assert_eq!(ShellType::Fish.name(), "fish");
+        assert_eq!(ShellType::Nushell.name(), "nushell");
+        assert_eq!(ShellType::Sh.name(), "sh");
+    }
+
+    #[test]
+    fn test_shell_type_from_name() {
+        assert_eq!(ShellType::from_name("fish"), Some(ShellType::Fish));
+        assert_eq!(ShellType::from_name("nushell"), Some(ShellType::Nushell));
+        assert_eq!(ShellType::from_name("csh"), None);
+    }
 }
@@ -3,7 +3,12 @@
 //! Core invariant: No shell startup shall exceed 10ms.
 
 use clap::Parser;
-use pzsh::cli::{self, Cli, Commands};
+use pzsh::ShellType;
+use pzsh::cli::{self, Cli, Commands, ConfigCommand};
+use pzsh::config::{
+    CompiledConfig, LintSection, Machine, SNAPSHOT_FILE, ShellTypeConfig, Snapshot, SourceConfig,
+};
+use pzsh::prompt::{GIT_STATUS_FILE, Prompt, PromptContext};
 use std::fs;
 use std::path::PathBuf;
 use std::process::ExitCode;
@@ -26,6 +31,17 @@ fn read_config(path: &PathBuf) -> Result<String, ExitCode> {
     })
 }
 
+/// Lint configuration from the `lint.toml` next to the config, if any
+fn lint_section(config: &PathBuf) -> Result<LintSection, ExitCode> {
+    let Some(path) = cli::find_lint_toml(&expand_path(config)) else {
+        return Ok(LintSection::default());
+    };
+    cli::load_lint_toml(&path).map_err(|e| {
+        eprintln!("Error in {}: {e}", path.display());
+        ExitCode::FAILURE
+    })
+}
+
 fn pass_fail(passed: bool) -> ExitCode {
     if passed {
         ExitCode::SUCCESS
@@ -34,44 +50,223 @@ fn pass_fail(passed: bool) -> ExitCode {
     }
 }
 
-fn cmd_bench(iterations: u32, verbose: bool) -> ExitCode {
-    let result = cli::run_bench(iterations, verbose);
-    println!("{}", result.format());
-    pass_fail(result.passed)
+/// Read and compile a config into its shell integration
+fn compile_integration(config: &PathBuf) -> Result<pzsh::shell::ShellIntegration, ExitCode> {
+    match CompiledConfig::load(&expand_path(config)) {
+        Ok(compiled) => Ok(pzsh::shell::ShellIntegration::new(
+            compiled.shell_type,
+            compiled,
+        )),
+        Err(e) => {
+            eprintln!("Compile error: {e}");
+            Err(ExitCode::FAILURE)
+        }
+    }
 }
 
-fn cmd_lint(config: &PathBuf) -> ExitCode {
-    let content = match read_config(config) {
-        Ok(c) => c,
+fn cmd_bench(iterations: u32, verbose: bool, real: bool, config: &PathBuf) -> ExitCode {
+    if !real {
+        let result = cli::run_bench(iterations, verbose);
+        println!("{}", result.format());
+        return pass_fail(result.passed);
+    }
+
+    let integration = match compile_integration(config) {
+        Ok(integration) => integration,
         Err(code) => return code,
     };
-    let result = cli::lint_config(&content);
-    println!("{}", result.format());
-    pass_fail(result.passed())
+    let shell_type = integration.shell_type();
+    let script = integration.generate();
+    match cli::run_real_bench(shell_type, &script, iterations) {
+        Ok(result) => {
+            println!("{}", result.format());
+            pass_fail(result.passed)
+        }
+        Err(e) => {
+            eprintln!("Benchmark error ({}): {e}", shell_type.name());
+            ExitCode::FAILURE
+        }
+    }
 }
 
-fn cmd_compile(config: &PathBuf, output: Option<PathBuf>) -> ExitCode {
+fn cmd_lint(config: &PathBuf, format: &str, max_warnings: Option<usize>) -> ExitCode {
     let content = match read_config(config) {
         Ok(c) => c,
         Err(code) => return code,
     };
-    match pzsh::config::CompiledConfig::from_toml(&content) {
-        Ok(compiled) => {
-            let shell_code = pzsh::shell::generate_init(compiled.shell_type, compiled);
-            if let Some(output_path) = output {
-                let output_path = expand_path(&output_path);
-                if let Err(e) = fs::write(&output_path, &shell_code) {
-                    eprintln!("Error writing {}: {e}", output_path.display());
-                    return ExitCode::FAILURE;
+    let section = match lint_section(config) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+    let mode = cli::LintMode::for_path(config);
+    let result = cli::lint_config_with(&content, mode, section, &|_| None);
+    let path = config.display().to_string();
+    match format {
+        "json" => println!("{}", result.to_json(&path)),
+        "sarif" => println!("{}", result.to_sarif(&path, &content)),
+        _ => println!("{}", result.format()),
+    }
+    if let Some(max) = max_warnings.filter(|&max| result.warnings() > max) {
+        eprintln!("✗ {} warnings (max {max})", result.warnings());
+    }
+    pass_fail(result.passed_with(max_warnings))
+}
+
+fn cmd_compile(
+    config: &PathBuf,
+    output: Option<PathBuf>,
+    cache: bool,
+    binary: bool,
+    shell: Option<&str>,
+) -> ExitCode {
+    let (source, files) = match SourceConfig::load_with_files(&expand_path(config)) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Compile error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let machine = Machine::current();
+    let compiled = match CompiledConfig::compile_for(source, &machine) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            eprintln!("Compile error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let shell_type = shell
+        .and_then(ShellType::from_name)
+        .unwrap_or(compiled.shell_type);
+    let inputs = compiled.inputs(files);
+
+    let cached = if cache {
+        let Some(init_cache) = cli::InitCache::from_env() else {
+            eprintln!("Error: no cache directory; set XDG_CACHE_HOME or HOME");
+            return ExitCode::FAILURE;
+        };
+        let key = cli::cache_key(&inputs, &compiled, &machine);
+        if init_cache.is_current(shell_type, &key) {
+            return match init_cache.touch(shell_type) {
+                Ok(path) => {
+                    eprintln!("✓ Cache up to date: {}", path.display());
+                    ExitCode::SUCCESS
                 }
-                eprintln!("✓ Compiled to {}", output_path.display());
-            } else {
-                print!("{shell_code}");
+                Err(e) => {
+                    eprintln!("Error updating cache: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        let watched = cli::watched_paths(&inputs, &compiled);
+        Some((init_cache, key, watched))
+    } else {
+        None
+    };
+
+    for dir in compiled.paths.iter().flat_map(|p| &p.missing) {
+        eprintln!("⚠ Path: {} does not exist, skipped", dir.display());
+    }
+    let integration = pzsh::shell::ShellIntegration::new(shell_type, compiled);
+    for e in integration.plugin_errors() {
+        eprintln!("⚠ Plugin: {e}");
+    }
+    for conflict in integration.conflicts() {
+        eprintln!("⚠ Conflict: {conflict}");
+    }
+    let skipped = integration.skipped_sections();
+    if !skipped.is_empty() {
+        let names: Vec<&str> = skipped.iter().map(|s| s.name()).collect();
+        eprintln!(
+            "⚠ Skipped sections not expressible in {}: {}",
+            shell_type.name(),
+            names.join(", ")
+        );
+    }
+    if binary {
+        let snapshot = integration.snapshot().with_inputs(inputs, &machine);
+        return write_snapshot(&snapshot, output);
+    }
+    let shell_code = integration.generate();
+
+    if let Some((init_cache, key, watched)) = cached {
+        match init_cache.write(shell_type, &key, &shell_code, &watched) {
+            Ok(path) => eprintln!("✓ Cached {}", path.display()),
+            Err(e) => {
+                eprintln!("Error writing cache: {e}");
+                return ExitCode::FAILURE;
             }
+        }
+    } else if let Some(output_path) = output {
+        let output_path = expand_path(&output_path);
+        if let Err(e) = fs::write(&output_path, &shell_code) {
+            eprintln!("Error writing {}: {e}", output_path.display());
+            return ExitCode::FAILURE;
+        }
+        eprintln!("✓ Compiled to {}", output_path.display());
+    } else {
+        print!("{shell_code}");
+    }
+    ExitCode::SUCCESS
+}
+
+fn write_snapshot(snapshot: &Snapshot, output: Option<PathBuf>) -> ExitCode {
+    let path = if let Some(path) = output {
+        expand_path(&path)
+    } else {
+        let Some(cache) = cli::InitCache::from_env() else {
+            eprintln!("Error: no cache directory; set XDG_CACHE_HOME or HOME, or use --output");
+            return ExitCode::FAILURE;
+        };
+        cache.dir().join(SNAPSHOT_FILE)
+    };
+    match snapshot.write(&path) {
+        Ok(()) => {
+            eprintln!("✓ Snapshot written to {}", path.display());
             ExitCode::SUCCESS
         }
         Err(e) => {
-            eprintln!("Compile error: {e}");
+            eprintln!("Error writing {}: {e}", path.display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn cmd_prompt(config: &PathBuf, shell: Option<&str>, context: PromptContext) -> ExitCode {
+    let config = expand_path(config);
+    let cache = cli::InitCache::from_env();
+    // The snapshot spares parsing the config before every prompt
+    let snapshot = match &cache {
+        Some(cache) => Snapshot::load_or_compile(&cache.dir().join(SNAPSHOT_FILE), &config),
+        None => Snapshot::compile(&config),
+    };
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut prompt = Prompt::from_snapshot(&snapshot);
+    prompt.set_context(context);
+    if let Some(cache) = &cache {
+        prompt.set_git_status_file(cache.dir().join(GIT_STATUS_FILE));
+    }
+    match shell {
+        Some("zsh") => prompt.set_shell(ShellType::Zsh),
+        Some("bash") => prompt.set_shell(ShellType::Bash),
+        _ => {}
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        prompt.refresh_git(&cwd);
+    }
+    match prompt.render() {
+        Ok(rendered) => {
+            print!("{rendered}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
             ExitCode::FAILURE
         }
     }
@@ -82,16 +277,64 @@ fn cmd_fix(config: &PathBuf, dry_run: bool) -> ExitCode {
         Ok(c) => c,
         Err(code) => return code,
     };
-    let lint_result = cli::lint_config(&content);
+    let section = match lint_section(config) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+    let mode = cli::LintMode::for_path(config);
+    let lint_result = cli::lint_config_with(&content, mode, section.clone(), &cli::resolve_command);
     if lint_result.issues.is_empty() {
         println!("✓ No issues to fix");
         return ExitCode::SUCCESS;
     }
-    println!("Found {} issues:", lint_result.issues.len());
-    for issue in &lint_result.issues {
-        if let Some(fix) = &issue.fix {
-            let prefix = if dry_run { "Would fix" } else { "Fix" };
-            println!("  {prefix}: {} -> {fix}", issue.message);
+
+    let path = expand_path(config);
+    let (fixed, applied) = cli::apply_edits(&content, &lint_result.issues);
+    if applied > 0 {
+        if let Err(e) = cli::check_syntax(&path, mode, &fixed) {
+            eprintln!(
+                "Error: fixes to {} do not parse, nothing written",
+                path.display()
+            );
+            eprintln!("  {e}");
+            return ExitCode::FAILURE;
+        }
+        if dry_run {
+            print!(
+                "{}",
+                cli::unified_diff(&content, &fixed, &path.display().to_string())
+            );
+        } else {
+            match cli::write_with_backup(&path, &fixed) {
+                Ok(backup) => println!(
+                    "✓ Applied {applied} fixes to {} (backup: {})",
+                    path.display(),
+                    backup.display()
+                ),
+                Err(e) => {
+                    eprintln!("Error writing {}: {e}", path.display());
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+
+    // Whatever the edits did not fix, at its line in the original file
+    let remaining = cli::lint_config_with(&fixed, mode, section, &|_| None);
+    if !remaining.issues.is_empty() {
+        let origins = cli::original_lines(&content, &lint_result.issues);
+        println!("Needs manual changes:");
+        for issue in &remaining.issues {
+            let original = issue
+                .line
+                .map(|l| origins.get(l.wrapping_sub(1)).copied().unwrap_or(l));
+            let line = match (original, issue.column) {
+                (Some(l), Some(c)) => format!(" (line {l}, column {c})"),
+                (Some(l), None) => format!(" (line {l})"),
+                _ => String::new(),
+            };
+            let fix = issue.fix.as_deref().unwrap_or("no automatic fix");
+            println!("  {}{line} -> {fix}", issue.message);
         }
     }
     if dry_run {
@@ -100,16 +343,24 @@ fn cmd_fix(config: &PathBuf, dry_run: bool) -> ExitCode {
     ExitCode::SUCCESS
 }
 
-fn cmd_profile(verbose: bool) -> ExitCode {
-    if verbose {
-        eprintln!("Warning: --verbose is not yet implemented for profile command. Flag ignored.");
+fn cmd_profile(verbose: bool, config: &PathBuf) -> ExitCode {
+    let integration = match compile_integration(config) {
+        Ok(integration) => integration,
+        Err(code) => return code,
+    };
+    match cli::run_profile(&integration) {
+        Ok(result) => {
+            print!("{}", result.format(verbose));
+            pass_fail(result.passed)
+        }
+        Err(e) => {
+            eprintln!("Profile error ({}): {e}", integration.shell_type().name());
+            ExitCode::FAILURE
+        }
     }
-    let result = cli::run_profile();
-    println!("{}", result.format());
-    pass_fail(result.passed)
 }
 
-fn cmd_status() -> ExitCode {
+fn cmd_status(config: &PathBuf) -> ExitCode {
     println!("pzsh v{}", env!("CARGO_PKG_VERSION"));
     println!("────────────────────────────");
     let bench = cli::run_bench(10, false);
@@ -119,10 +370,56 @@ fn cmd_status() -> ExitCode {
         pzsh::MAX_STARTUP_MS,
         if bench.passed { "✓" } else { "✗" }
     );
+
+    // Plugins are only reported when there is a usable configuration
+    let path = expand_path(config);
+    if !path.is_file() {
+        return ExitCode::SUCCESS;
+    }
+    match CompiledConfig::load(&path) {
+        Ok(compiled) => {
+            if let Some(model) = &compiled.completion_model {
+                match pzsh::completion::engine_for_config(&compiled) {
+                    Ok(_) => println!("Completion model: {} ✓", model.display()),
+                    Err(e) => println!("Completion model: {e} ✗"),
+                }
+            }
+            let integration = pzsh::shell::ShellIntegration::new(compiled.shell_type, compiled);
+            print!("{}", cli::format_plugin_status(&integration));
+        }
+        Err(e) => println!("Config: {e} ✗"),
+    }
     ExitCode::SUCCESS
 }
 
-fn cmd_init(shell: &str) -> ExitCode {
+fn cmd_explain(kind: &str, name: &str, config: &PathBuf) -> ExitCode {
+    let compiled = match CompiledConfig::load(&expand_path(config)) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            eprintln!("Compile error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let kind = if kind == "env" {
+        pzsh::shell::DefinitionKind::Env
+    } else {
+        pzsh::shell::DefinitionKind::Alias
+    };
+    let integration = pzsh::shell::ShellIntegration::new(compiled.shell_type, compiled);
+    if let Some(explanation) = cli::format_explanation(&integration, kind, name) {
+        print!("{explanation}");
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("{} {name} is not defined", kind.name());
+        ExitCode::FAILURE
+    }
+}
+
+fn cmd_init(shell: &str, print: bool) -> ExitCode {
+    if print {
+        print!("{}", cli::generate_shell_init(shell));
+        return ExitCode::SUCCESS;
+    }
     let config = cli::generate_init_config(shell);
     let home = dirs::home_dir().expect("Could not find home directory");
     let config_path = home.join(".pzshrc");
@@ -137,7 +434,23 @@ fn cmd_init(shell: &str) -> ExitCode {
             println!("\nNext steps:");
             println!("  1. Edit ~/.pzshrc to add your aliases and env vars");
             println!("  2. Run `pzsh compile` to compile the configuration");
-            println!("  3. Add `eval \"$(pzsh compile)\"` to your ~/.zshrc");
+            let rc = match shell {
+                "fish" => "~/.config/fish/config.fish",
+                "bash" => "~/.bashrc",
+                "nushell" => "~/.config/nushell/config.nu",
+                "sh" => "~/.profile",
+                _ => "~/.zshrc",
+            };
+            match shell {
+                // Nushell sources files, not command output
+                "nushell" => {
+                    println!(
+                        "  3. Run `pzsh init --shell nushell --print >> ~/.config/nushell/env.nu`"
+                    );
+                    println!("  4. Add `source ~/.cache/pzsh/init.nu` to your {rc}");
+                }
+                _ => println!("  3. Run `pzsh init --shell {shell} --print >> {rc}`"),
+            }
             ExitCode::SUCCESS
         }
         Err(e) => {
@@ -147,18 +460,110 @@ fn cmd_init(shell: &str) -> ExitCode {
     }
 }
 
+fn cmd_import(file: &PathBuf, output: &PathBuf, shell: Option<&str>, dry_run: bool) -> ExitCode {
+    let content = match read_config(file) {
+        Ok(c) => c,
+        Err(code) => return code,
+    };
+    let bash = shell.map_or_else(
+        || file.to_string_lossy().contains("bash"),
+        |shell| shell == "bash",
+    );
+    let shell = if bash {
+        ShellTypeConfig::Bash
+    } else {
+        ShellTypeConfig::Zsh
+    };
+    let result = cli::import_rc(&content, shell);
+    let config = result.to_toml(&file.display().to_string());
+    if dry_run {
+        print!("{config}");
+        eprint!("\n{}", result.format());
+        return ExitCode::SUCCESS;
+    }
+
+    let path = expand_path(output);
+    if path.exists() {
+        eprintln!("Error: {} already exists", path.display());
+        eprintln!("Remove it first, or use --output or --dry-run");
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = fs::write(&path, &config) {
+        eprintln!("Error writing {}: {e}", path.display());
+        return ExitCode::FAILURE;
+    }
+    print!("{}", result.format());
+    println!("\n✓ Created {}", path.display());
+    if !result.unsupported.is_empty() {
+        println!("  Lines that were not imported are listed at the end of the file");
+    }
+    ExitCode::SUCCESS
+}
+
+fn cmd_config_schema() -> ExitCode {
+    match serde_json::to_string_pretty(&pzsh::config::json_schema()) {
+        Ok(schema) => {
+            println!("{schema}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
 fn main() -> ExitCode {
     let cli = Cli::parse();
     match cli.command {
         Commands::Bench {
             iterations,
             verbose,
-        } => cmd_bench(iterations, verbose),
-        Commands::Lint { config } => cmd_lint(&config),
-        Commands::Compile { config, output } => cmd_compile(&config, output),
+            real,
+            config,
+        } => cmd_bench(iterations, verbose, real, &config),
+        Commands::Lint {
+            config,
+            format,
+            max_warnings,
+        } => cmd_lint(&config, &format, max_warnings),
+        Commands::Compile {
+            config,
+            output,
+            cache,
+            binary,
+            shell,
+        } => cmd_compile(&config, output, cache, binary, shell.as_deref()),
+        Commands::Prompt {
+            exit_code,
+            duration_ms,
+            jobs,
+            keymap,
+            shell,
+            config,
+        } => cmd_prompt(
+            &config,
+            shell.as_deref(),
+            PromptContext {
+                exit_code,
+                duration_ms,
+                jobs,
+                keymap,
+            },
+        ),
         Commands::Fix { config, dry_run } => cmd_fix(&config, dry_run),
-        Commands::Profile { verbose } => cmd_profile(verbose),
-        Commands::Status => cmd_status(),
-        Commands::Init { shell } => cmd_init(&shell),
+        Commands::Profile { verbose, config } => cmd_profile(verbose, &config),
+        Commands::Status { config } => cmd_status(&config),
+        Commands::Explain { kind, name, config } => cmd_explain(&kind, &name, &config),
+        Commands::Init { shell, print } => cmd_init(&shell, print),
+        Commands::Import {
+            file,
+            output,
+            shell,
+            dry_run,
+        } => cmd_import(&file, &output, shell.as_deref(), dry_run),
+        Commands::Config {
+            command: ConfigCommand::Schema,
+        } => cmd_config_schema(),
     }
 }
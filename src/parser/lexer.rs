@@ -0,0 +1,774 @@
+//! Shell tokenizer for pzsh
+//!
+//! Single pass over the input: quoting, escapes and `$` expansions are
+//! resolved into [`Word`] parts, operators and redirections into tokens.
+
+use super::{ParseError, RedirectKind};
+use std::fmt;
+
+/// Part of a shell word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Unquoted literal text (subject to tilde and glob expansion)
+    Literal(String),
+    /// Quoted literal text (`'...'`, `"..."`, `$'...'` or backslash-escaped)
+    Quoted(String),
+    /// Parameter expansion: `$VAR`, `${VAR}` or `${VAR:-default}` (inner text)
+    Var(String),
+    /// Command substitution: `$(...)` or backticks (inner source)
+    CommandSubst(String),
+    /// Arithmetic expansion: `$((...))` (inner expression)
+    Arith(String),
+}
+
+/// Shell word made of literal and expansion parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    /// Word parts in source order
+    pub parts: Vec<WordPart>,
+    /// Byte offset of the word in the parsed input
+    pub offset: usize,
+}
+
+impl Word {
+    /// Create a word from a single unquoted literal
+    #[must_use]
+    pub fn literal(text: impl Into<String>) -> Self {
+        Self {
+            parts: vec![WordPart::Literal(text.into())],
+            offset: 0,
+        }
+    }
+
+    /// Literal value if the word contains no expansions
+    #[must_use]
+    pub fn as_literal(&self) -> Option<String> {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                WordPart::Literal(s) | WordPart::Quoted(s) => out.push_str(s),
+                _ => return None,
+            }
+        }
+        Some(out)
+    }
+
+    /// Check if the word is entirely unquoted literal text
+    #[must_use]
+    pub fn is_plain(&self) -> bool {
+        self.parts.iter().all(|p| matches!(p, WordPart::Literal(_)))
+    }
+
+    /// Check if the word contains a command substitution
+    #[must_use]
+    pub fn has_command_subst(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|p| matches!(p, WordPart::CommandSubst(_)))
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                WordPart::Literal(s) | WordPart::Quoted(s) => write!(f, "{s}")?,
+                WordPart::Var(name) => write!(f, "${{{name}}}")?,
+                WordPart::CommandSubst(src) => write!(f, "$({src})")?,
+                WordPart::Arith(expr) => write!(f, "$(({expr}))")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq<str> for Word {
+    fn eq(&self, other: &str) -> bool {
+        self.as_literal().as_deref() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for Word {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// Control operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Pipe,
+    And,
+    Or,
+    Semi,
+    Amp,
+    Newline,
+    LParen,
+    RParen,
+}
+
+impl Op {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pipe => "|",
+            Self::And => "&&",
+            Self::Or => "||",
+            Self::Semi => ";",
+            Self::Amp => "&",
+            Self::Newline => "newline",
+            Self::LParen => "(",
+            Self::RParen => ")",
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Lexical token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Word(Word),
+    Op(Op, usize),
+    Redirect {
+        fd: Option<u32>,
+        kind: RedirectKind,
+        offset: usize,
+    },
+}
+
+/// Word under construction (adjacent parts of the same kind are merged)
+#[derive(Default)]
+struct WordBuilder {
+    parts: Vec<WordPart>,
+    start: Option<usize>,
+}
+
+impl WordBuilder {
+    fn begin(&mut self, offset: usize) {
+        self.start.get_or_insert(offset);
+    }
+
+    fn push_literal(&mut self, offset: usize, ch: char) {
+        self.begin(offset);
+        if let Some(WordPart::Literal(s)) = self.parts.last_mut() {
+            s.push(ch);
+        } else {
+            self.parts.push(WordPart::Literal(ch.to_string()));
+        }
+    }
+
+    fn push_quoted(&mut self, offset: usize, text: &str) {
+        self.begin(offset);
+        if let Some(WordPart::Quoted(s)) = self.parts.last_mut() {
+            s.push_str(text);
+        } else {
+            self.parts.push(WordPart::Quoted(text.to_string()));
+        }
+    }
+
+    fn push(&mut self, offset: usize, part: WordPart) {
+        match part {
+            WordPart::Quoted(s) => self.push_quoted(offset, &s),
+            other => {
+                self.begin(offset);
+                self.parts.push(other);
+            }
+        }
+    }
+
+    /// IO number candidate (`2` in `2>file`)
+    fn io_number(&self) -> Option<u32> {
+        match self.parts.as_slice() {
+            [WordPart::Literal(s)] if s.bytes().all(|b| b.is_ascii_digit()) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn take(&mut self) -> Option<Word> {
+        let offset = self.start.take()?;
+        Some(Word {
+            parts: std::mem::take(&mut self.parts),
+            offset,
+        })
+    }
+}
+
+/// Shell tokenizer
+pub struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+    tokens: Vec<Token>,
+    word: WordBuilder,
+}
+
+impl<'a> Lexer<'a> {
+    pub(crate) fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            tokens: Vec::new(),
+            word: WordBuilder::default(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.src[self.pos..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn finish_word(&mut self) {
+        if let Some(word) = self.word.take() {
+            self.tokens.push(Token::Word(word));
+        }
+    }
+
+    fn op(&mut self, op: Op, offset: usize) {
+        self.finish_word();
+        self.tokens.push(Token::Op(op, offset));
+    }
+
+    /// Tokenize the whole input
+    pub(crate) fn tokenize(mut self) -> Result<Vec<Token>, ParseError> {
+        while let Some(ch) = self.peek() {
+            let start = self.pos;
+            match ch {
+                ' ' | '\t' | '\r' => {
+                    self.bump();
+                    self.finish_word();
+                }
+                '\n' => {
+                    self.bump();
+                    self.op(Op::Newline, start);
+                }
+                '#' if self.word.start.is_none() => {
+                    while let Some(c) = self.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.bump();
+                    }
+                }
+                '|' => {
+                    self.bump();
+                    if self.eat('|') {
+                        self.op(Op::Or, start);
+                    } else {
+                        if self.eat('&') {
+                            // `a |& b` is shorthand for `a 2>&1 | b`
+                            self.finish_word();
+                            self.tokens.push(Token::Redirect {
+                                fd: Some(2),
+                                kind: RedirectKind::DupOutput,
+                                offset: start,
+                            });
+                            self.tokens.push(Token::Word(Word {
+                                parts: vec![WordPart::Literal("1".to_string())],
+                                offset: start,
+                            }));
+                        }
+                        self.op(Op::Pipe, start);
+                    }
+                }
+                '&' => {
+                    self.bump();
+                    if self.eat('&') {
+                        self.op(Op::And, start);
+                    } else if self.eat('>') {
+                        self.finish_word();
+                        let kind = if self.eat('>') {
+                            RedirectKind::AppendAll
+                        } else {
+                            RedirectKind::OutputAll
+                        };
+                        self.tokens.push(Token::Redirect {
+                            fd: None,
+                            kind,
+                            offset: start,
+                        });
+                    } else {
+                        self.op(Op::Amp, start);
+                    }
+                }
+                ';' => {
+                    self.bump();
+                    if self.peek() == Some(';') {
+                        return Err(ParseError::Unsupported(";;".to_string(), start));
+                    }
+                    self.op(Op::Semi, start);
+                }
+                '(' => {
+                    self.bump();
+                    self.op(Op::LParen, start);
+                }
+                ')' => {
+                    self.bump();
+                    self.op(Op::RParen, start);
+                }
+                '<' | '>' => self.redirect(start)?,
+                '\\' => {
+                    self.bump();
+                    match self.bump() {
+                        // Line continuation
+                        Some('\n') => {}
+                        Some(c) => self.word.push_quoted(start, &c.to_string()),
+                        None => self.word.push_literal(start, '\\'),
+                    }
+                }
+                '\'' => {
+                    self.bump();
+                    let text = self.single_quoted(start)?;
+                    self.word.push_quoted(start, &text);
+                }
+                '"' => {
+                    self.bump();
+                    self.double_quoted(start)?;
+                }
+                '$' => {
+                    let part = self.dollar(false)?;
+                    self.word.push(start, part);
+                }
+                '`' => {
+                    self.bump();
+                    let src = self.backtick(start)?;
+                    self.word.push(start, WordPart::CommandSubst(src));
+                }
+                _ => {
+                    self.bump();
+                    self.word.push_literal(start, ch);
+                }
+            }
+        }
+        self.finish_word();
+        Ok(self.tokens)
+    }
+
+    fn redirect(&mut self, start: usize) -> Result<(), ParseError> {
+        let fd = self.word.io_number();
+        if fd.is_some() {
+            self.word = WordBuilder::default();
+        } else {
+            self.finish_word();
+        }
+        let offset = self.pos;
+        let kind = match self.bump() {
+            Some('<') => {
+                if self.eat('<') {
+                    if self.eat('<') {
+                        RedirectKind::HereString
+                    } else {
+                        return Err(ParseError::Unsupported("<<".to_string(), start));
+                    }
+                } else if self.eat('&') {
+                    RedirectKind::DupInput
+                } else {
+                    RedirectKind::Input
+                }
+            }
+            _ => {
+                if self.eat('>') {
+                    RedirectKind::Append
+                } else if self.eat('&') {
+                    RedirectKind::DupOutput
+                } else {
+                    // `>|` forces clobber; same as `>` here
+                    self.eat('|');
+                    RedirectKind::Output
+                }
+            }
+        };
+        self.tokens.push(Token::Redirect { fd, kind, offset });
+        Ok(())
+    }
+
+    fn single_quoted(&mut self, start: usize) -> Result<String, ParseError> {
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(text),
+                Some(c) => text.push(c),
+                None => return Err(ParseError::UnterminatedQuote('\'', start)),
+            }
+        }
+    }
+
+    fn double_quoted(&mut self, start: usize) -> Result<(), ParseError> {
+        // Empty quotes still produce a (empty) word
+        self.word.push_quoted(start, "");
+        loop {
+            let offset = self.pos;
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.bump();
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(c @ ('$' | '`' | '"' | '\\')) => {
+                            self.word.push_quoted(offset, &c.to_string());
+                        }
+                        Some(c) => self.word.push_quoted(offset, &format!("\\{c}")),
+                        None => return Err(ParseError::UnterminatedQuote('"', start)),
+                    }
+                }
+                Some('$') => {
+                    let part = self.dollar(true)?;
+                    self.word.push(offset, part);
+                }
+                Some('`') => {
+                    self.bump();
+                    let src = self.backtick(offset)?;
+                    self.word.push(offset, WordPart::CommandSubst(src));
+                }
+                Some(c) => {
+                    self.bump();
+                    self.word.push_quoted(offset, &c.to_string());
+                }
+                None => return Err(ParseError::UnterminatedQuote('"', start)),
+            }
+        }
+    }
+
+    fn backtick(&mut self, start: usize) -> Result<String, ParseError> {
+        let mut src = String::new();
+        loop {
+            match self.bump() {
+                Some('`') => return Ok(src),
+                Some('\\') => match self.bump() {
+                    Some(c @ ('`' | '\\' | '$')) => src.push(c),
+                    Some(c) => {
+                        src.push('\\');
+                        src.push(c);
+                    }
+                    None => return Err(ParseError::UnterminatedQuote('`', start)),
+                },
+                Some(c) => src.push(c),
+                None => return Err(ParseError::UnterminatedQuote('`', start)),
+            }
+        }
+    }
+
+    /// Parse a `$` expansion; the cursor is on the `$`
+    fn dollar(&mut self, in_double_quotes: bool) -> Result<WordPart, ParseError> {
+        let start = self.pos;
+        self.bump();
+        match self.peek() {
+            Some('(') if self.peek_at(1) == Some('(') => {
+                self.pos += 2;
+                let inner = self.balanced(start, '(', ')')?;
+                // Arithmetic needs the second closing paren
+                if !self.eat(')') {
+                    return Err(ParseError::Unterminated("$((", start));
+                }
+                Ok(WordPart::Arith(inner))
+            }
+            Some('(') => {
+                self.bump();
+                Ok(WordPart::CommandSubst(self.balanced(start, '(', ')')?))
+            }
+            Some('{') => {
+                self.bump();
+                Ok(WordPart::Var(self.balanced(start, '{', '}')?))
+            }
+            Some('\'') if !in_double_quotes => {
+                self.bump();
+                Ok(WordPart::Quoted(self.ansi_c_quoted(start)?))
+            }
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+                let name_start = self.pos;
+                while let Some(c) = self.peek() {
+                    if c == '_' || c.is_ascii_alphanumeric() {
+                        self.bump();
+                    } else {
+                        break;
+                    }
+                }
+                Ok(WordPart::Var(self.src[name_start..self.pos].to_string()))
+            }
+            Some(c @ ('?' | '$' | '!' | '#' | '@' | '*' | '-' | '0'..='9')) => {
+                self.bump();
+                Ok(WordPart::Var(c.to_string()))
+            }
+            _ if in_double_quotes => Ok(WordPart::Quoted("$".to_string())),
+            _ => Ok(WordPart::Literal("$".to_string())),
+        }
+    }
+
+    /// Scan to the matching close delimiter, skipping quoted text.
+    /// The cursor is just past the opening delimiter; returns the inner source.
+    fn balanced(&mut self, start: usize, open: char, close: char) -> Result<String, ParseError> {
+        let inner_start = self.pos;
+        let mut depth = 1usize;
+        while let Some(c) = self.bump() {
+            match c {
+                '\\' => {
+                    self.bump();
+                }
+                '\'' => {
+                    self.single_quoted(self.pos)?;
+                }
+                '"' => loop {
+                    match self.bump() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            self.bump();
+                        }
+                        Some(_) => {}
+                        None => return Err(ParseError::UnterminatedQuote('"', start)),
+                    }
+                },
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(self.src[inner_start..self.pos - close.len_utf8()].to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        let what = match open {
+            '{' => "${",
+            _ => "$(",
+        };
+        Err(ParseError::Unterminated(what, start))
+    }
+
+    /// Decode `$'...'` ANSI-C quoting; the cursor is past the opening quote
+    fn ansi_c_quoted(&mut self, start: usize) -> Result<String, ParseError> {
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') => return Ok(text),
+                Some('\\') => {
+                    let Some(c) = self.bump() else {
+                        return Err(ParseError::UnterminatedQuote('\'', start));
+                    };
+                    match c {
+                        'n' => text.push('\n'),
+                        't' => text.push('\t'),
+                        'r' => text.push('\r'),
+                        'a' => text.push('\x07'),
+                        'b' => text.push('\x08'),
+                        'e' | 'E' => text.push('\x1b'),
+                        'f' => text.push('\x0c'),
+                        'v' => text.push('\x0b'),
+                        'x' => match self.radix_digits(16, 2) {
+                            Some(value) => text.push(char::from_u32(value).unwrap_or('\0')),
+                            // No hex digits: a literal `\x`, as in bash
+                            None => text.push_str("\\x"),
+                        },
+                        '0'..='7' => {
+                            let mut value = c.to_digit(8).unwrap_or(0);
+                            for _ in 0..2 {
+                                match self.peek().and_then(|d| d.to_digit(8)) {
+                                    Some(d) => {
+                                        value = value * 8 + d;
+                                        self.bump();
+                                    }
+                                    None => break,
+                                }
+                            }
+                            text.push(char::from_u32(value).unwrap_or('\0'));
+                        }
+                        other => {
+                            if !matches!(other, '\\' | '\'' | '"' | '?') {
+                                text.push('\\');
+                            }
+                            text.push(other);
+                        }
+                    }
+                }
+                Some(c) => text.push(c),
+                None => return Err(ParseError::UnterminatedQuote('\'', start)),
+            }
+        }
+    }
+
+    fn radix_digits(&mut self, radix: u32, max: usize) -> Option<u32> {
+        let mut value = None;
+        for _ in 0..max {
+            match self.peek().and_then(|d| d.to_digit(radix)) {
+                Some(d) => {
+                    value = Some(value.unwrap_or(0) * radix + d);
+                    self.bump();
+                }
+                None => break,
+            }
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Vec<Word> {
+        Lexer::new(input)
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .filter_map(|t| match t {
+                Token::Word(w) => Some(w),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quoting() {
+        let w = words(r#"echo "a b" 'c d' e\ f"#);
+        assert_eq!(w.len(), 4);
+        assert_eq!(w[1], "a b");
+        assert_eq!(w[2], "c d");
+        assert_eq!(w[3], "e f");
+    }
+
+    #[test]
+    fn test_adjacent_quotes_form_one_word() {
+        let w = words(r#"a"b"'c'"#);
+        assert_eq!(w.len(), 1);
+        assert_eq!(w[0], "abc");
+    }
+
+    #[test]
+    fn test_empty_quotes_are_a_word() {
+        let w = words(r#"echo "" ''"#);
+        assert_eq!(w.len(), 3);
+        assert_eq!(w[1], "");
+    }
+
+    #[test]
+    fn test_ansi_c_quoting() {
+        let w = words(r"echo $'a\tb\n\x41\101\''");
+        assert_eq!(w[1], "a\tb\nAA'");
+
+        // `\x` without hex digits stays literal
+        let w = words(r"echo $'\x' $'\xg'");
+        assert_eq!(w[1], "\\x");
+        assert_eq!(w[2], "\\xg");
+    }
+
+    #[test]
+    fn test_variables() {
+        let w = words(r#"echo $HOME "${USER}x" $? '$NOPE'"#);
+        assert_eq!(w[1].parts, vec![WordPart::Var("HOME".into())]);
+        assert_eq!(
+            w[2].parts,
+            vec![
+                WordPart::Quoted(String::new()),
+                WordPart::Var("USER".into()),
+                WordPart::Quoted("x".into())
+            ]
+        );
+        assert_eq!(w[3].parts, vec![WordPart::Var("?".into())]);
+        assert_eq!(w[4], "$NOPE");
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let w = words(r#"echo $(brew --prefix "x)") `date` $((1 + (2)))"#);
+        assert_eq!(
+            w[1].parts,
+            vec![WordPart::CommandSubst(r#"brew --prefix "x)""#.into())]
+        );
+        assert_eq!(w[2].parts, vec![WordPart::CommandSubst("date".into())]);
+        assert_eq!(w[3].parts, vec![WordPart::Arith("1 + (2)".into())]);
+        assert!(w[1].has_command_subst());
+    }
+
+    #[test]
+    fn test_double_quote_escapes() {
+        let w = words(r#"echo "\$x \"q\" \n""#);
+        assert_eq!(w[1], r#"$x "q" \n"#);
+    }
+
+    #[test]
+    fn test_comments_and_continuation() {
+        let w = words("echo a \\\n b # comment $(x)");
+        assert_eq!(w.len(), 3);
+        assert_eq!(w[2], "b");
+    }
+
+    #[test]
+    fn test_word_offsets() {
+        let w = words("ls  -la");
+        assert_eq!(w[0].offset, 0);
+        assert_eq!(w[1].offset, 4);
+    }
+
+    #[test]
+    fn test_operators() {
+        let tokens = Lexer::new("a|b&&c||d;e&").tokenize().unwrap();
+        let ops: Vec<Op> = tokens
+            .iter()
+            .filter_map(|t| match t {
+                Token::Op(op, _) => Some(*op),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ops, vec![Op::Pipe, Op::And, Op::Or, Op::Semi, Op::Amp]);
+    }
+
+    #[test]
+    fn test_io_number() {
+        let tokens = Lexer::new("cmd 2>&1 3<in").tokenize().unwrap();
+        assert!(tokens.contains(&Token::Redirect {
+            fd: Some(2),
+            kind: RedirectKind::DupOutput,
+            offset: 5
+        }));
+        assert!(tokens.contains(&Token::Redirect {
+            fd: Some(3),
+            kind: RedirectKind::Input,
+            offset: 10
+        }));
+    }
+
+    #[test]
+    fn test_unterminated_quotes() {
+        assert!(matches!(
+            Lexer::new("echo 'abc").tokenize(),
+            Err(ParseError::UnterminatedQuote('\'', 5))
+        ));
+        assert!(matches!(
+            Lexer::new("echo \"abc").tokenize(),
+            Err(ParseError::UnterminatedQuote('"', 5))
+        ));
+        assert!(matches!(
+            Lexer::new("echo $(abc").tokenize(),
+            Err(ParseError::Unterminated("$(", 5))
+        ));
+    }
+
+    #[test]
+    fn test_word_display() {
+        let w = words(r#"a$B"$C"$(e)"#);
+        assert_eq!(w[0].to_string(), "a${B}${C}$(e)");
+        assert!(w[0].as_literal().is_none());
+    }
+}
@@ -3,27 +3,122 @@
 //! O(1) parsing with 2ms budget constraint.
 //! Uses pre-compiled patterns and LRU caching.
 
+mod lexer;
+mod script;
+pub use lexer::{Word, WordPart};
+pub use script::{Script, ScriptCommand, parse_script};
+
 use crate::config::CompiledConfig;
 use crate::{MAX_PARSER_MS, PzshError, Result};
+use lexer::{Lexer, Op, Token};
 use lru::LruCache;
 use std::num::NonZeroUsize;
 use std::time::{Duration, Instant};
 
+/// Parse errors (offsets are byte positions in the input)
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("unterminated {0} quote at offset {1}")]
+    UnterminatedQuote(char, usize),
+
+    #[error("unterminated {0} at offset {1}")]
+    Unterminated(&'static str, usize),
+
+    #[error("unexpected `{0}` at offset {1}")]
+    UnexpectedToken(String, usize),
+
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+
+    #[error("unsupported syntax `{0}` at offset {1}")]
+    Unsupported(String, usize),
+}
+
 /// Parsed command representation
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum ParsedCommand {
     /// Simple command (no pipes, no redirects)
-    Simple { command: String, args: Vec<String> },
+    Simple { command: Word, args: Vec<Word> },
     /// Alias expansion
-    Alias { name: String, expansion: String },
+    Alias {
+        name: String,
+        expansion: String,
+        args: Vec<Word>,
+    },
     /// Built-in command
-    Builtin { name: String, args: Vec<String> },
+    Builtin { name: String, args: Vec<Word> },
+    /// Commands connected by `|`
+    Pipeline(Vec<Self>),
+    /// Commands joined by `&&`, `||`, `;` or `&` (operator follows each command)
+    List(Vec<(Self, ListOp)>),
+    /// Command with redirections
+    Redirect {
+        command: Box<Self>,
+        redirects: Vec<Redirection>,
+    },
+    /// `( ... )` subshell
+    Subshell(Box<Self>),
     /// Empty input
+    #[default]
     Empty,
 }
 
+/// Operator following a command in a [`ParsedCommand::List`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListOp {
+    /// `;` or newline: run the next command unconditionally
+    Seq,
+    /// `&&`: run the next command if this one succeeded
+    And,
+    /// `||`: run the next command if this one failed
+    Or,
+    /// `&`: run this command in the background
+    Background,
+}
+
+/// Redirection operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    /// `<`
+    Input,
+    /// `>` or `>|`
+    Output,
+    /// `>>`
+    Append,
+    /// `<<<`
+    HereString,
+    /// `<&`
+    DupInput,
+    /// `>&`
+    DupOutput,
+    /// `&>` (stdout and stderr)
+    OutputAll,
+    /// `&>>` (stdout and stderr)
+    AppendAll,
+}
+
+/// Single redirection (`2>&1`, `> out.txt`, ...)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirection {
+    /// Explicit file descriptor (`2` in `2>err`)
+    pub fd: Option<u32>,
+    pub kind: RedirectKind,
+    pub target: Word,
+}
+
+impl Redirection {
+    /// File descriptor being redirected (explicit or the operator default)
+    #[must_use]
+    pub fn source_fd(&self) -> u32 {
+        self.fd.unwrap_or(match self.kind {
+            RedirectKind::Input | RedirectKind::HereString | RedirectKind::DupInput => 0,
+            _ => 1,
+        })
+    }
+}
+
 /// Parser with O(1) lookup and LRU caching
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Parser {
     /// LRU cache for parsed commands
     cache: LruCache<String, ParsedCommand>,
@@ -85,45 +180,53 @@ impl Parser {
         result
     }
 
-    /// Parse without cache (internal)
-    fn parse_uncached(&self, input: &str) -> Result<ParsedCommand> {
-        let trimmed = input.trim();
-
-        if trimmed.is_empty() {
-            return Ok(ParsedCommand::Empty);
+    /// Parse without the cache or the time budget (whole scripts for `source`)
+    pub(crate) fn parse_uncached(&self, input: &str) -> Result<ParsedCommand> {
+        contract_pre_parser_correctness!(input);
+        let tokens = Lexer::new(input).tokenize()?;
+        let mut grammar = Grammar {
+            parser: self,
+            tokens,
+            pos: 0,
+        };
+        let command = grammar.list(false)?;
+        if let Some(token) = grammar.tokens.get(grammar.pos) {
+            return Err(unexpected(token).into());
         }
+        Ok(command)
+    }
 
-        // Split into words (simple tokenization)
-        let words: Vec<&str> = trimmed.split_whitespace().collect();
-
-        if words.is_empty() {
-            return Ok(ParsedCommand::Empty);
-        }
-
-        let command = words[0];
-        let args: Vec<String> = words[1..].iter().map(|s| (*s).to_string()).collect();
-
-        // Check for alias (O(1))
-        if let Some(expansion) = self.aliases.get(command) {
-            return Ok(ParsedCommand::Alias {
-                name: command.to_string(),
-                expansion: expansion.clone(),
-            });
+    /// Classify a simple command by its first word (O(1) lookups)
+    fn classify(&self, command: Word, args: Vec<Word>) -> ParsedCommand {
+        // Quoted or expanded command words bypass aliases and builtins
+        if command.is_plain()
+            && let Some(name) = command.as_literal()
+        {
+            if let Some(expansion) = self.aliases.get(&name) {
+                return ParsedCommand::Alias {
+                    name,
+                    expansion: expansion.clone(),
+                    args,
+                };
+            }
+            if self.builtins.contains(&name) {
+                return ParsedCommand::Builtin { name, args };
+            }
         }
+        ParsedCommand::Simple { command, args }
+    }
 
-        // Check for builtin (O(1))
-        if self.builtins.contains(command) {
-            return Ok(ParsedCommand::Builtin {
-                name: command.to_string(),
-                args,
-            });
+    /// Define (`Some`) or remove (`None`) an alias for later parses
+    pub(crate) fn set_alias(&mut self, name: &str, expansion: Option<&str>) {
+        match expansion {
+            Some(expansion) => {
+                self.aliases.insert(name.to_string(), expansion.to_string());
+            }
+            None => {
+                self.aliases.remove(name);
+            }
         }
-
-        // Simple command
-        Ok(ParsedCommand::Simple {
-            command: command.to_string(),
-            args,
-        })
+        self.cache.clear();
     }
 
     /// Clear the parse cache
@@ -138,6 +241,167 @@ impl Parser {
     }
 }
 
+/// Recursive-descent grammar over lexer tokens
+///
+/// ```text
+/// list     := pipeline ((&& | || | ; | & | newline) pipeline)*
+/// pipeline := command (| command)*
+/// command  := ( list ) redirect* | (word | redirect)+
+/// ```
+struct Grammar<'a> {
+    parser: &'a Parser,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Grammar<'_> {
+    fn peek_op(&self) -> Option<Op> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op, _)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek_op() == Some(Op::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    fn list(&mut self, in_subshell: bool) -> std::result::Result<ParsedCommand, ParseError> {
+        let mut items: Vec<(ParsedCommand, ListOp)> = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.pos >= self.tokens.len() || (in_subshell && self.peek_op() == Some(Op::RParen))
+            {
+                break;
+            }
+            let command = self.pipeline()?;
+            let op = match self.peek_op() {
+                Some(Op::And) => ListOp::And,
+                Some(Op::Or) => ListOp::Or,
+                Some(Op::Semi | Op::Newline) => ListOp::Seq,
+                Some(Op::Amp) => ListOp::Background,
+                _ => {
+                    items.push((command, ListOp::Seq));
+                    break;
+                }
+            };
+            self.pos += 1;
+            if matches!(op, ListOp::And | ListOp::Or) {
+                // `&&`/`||` require a following command (newlines allowed)
+                self.skip_newlines();
+                if self.pos >= self.tokens.len() {
+                    return Err(ParseError::UnexpectedEof);
+                }
+            }
+            items.push((command, op));
+        }
+
+        Ok(match items.len() {
+            0 => ParsedCommand::Empty,
+            1 if items[0].1 == ListOp::Seq => items.pop().map(|(c, _)| c).unwrap_or_default(),
+            _ => ParsedCommand::List(items),
+        })
+    }
+
+    fn pipeline(&mut self) -> std::result::Result<ParsedCommand, ParseError> {
+        let mut commands = vec![self.command()?];
+        while self.peek_op() == Some(Op::Pipe) {
+            self.pos += 1;
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        Ok(if commands.len() == 1 {
+            commands.pop().unwrap_or_default()
+        } else {
+            ParsedCommand::Pipeline(commands)
+        })
+    }
+
+    fn command(&mut self) -> std::result::Result<ParsedCommand, ParseError> {
+        let mut words = Vec::new();
+        let mut redirects = Vec::new();
+        let subshell = if self.peek_op() == Some(Op::LParen) {
+            self.pos += 1;
+            let inner = self.list(true)?;
+            match self.tokens.get(self.pos) {
+                Some(Token::Op(Op::RParen, _)) => self.pos += 1,
+                Some(token) => return Err(unexpected(token)),
+                None => return Err(ParseError::UnexpectedEof),
+            }
+            Some(ParsedCommand::Subshell(Box::new(inner)))
+        } else {
+            None
+        };
+
+        while let Some(token) = self.tokens.get(self.pos) {
+            match token {
+                Token::Word(word) => {
+                    if subshell.is_some() {
+                        return Err(unexpected(token));
+                    }
+                    words.push(word.clone());
+                    self.pos += 1;
+                }
+                Token::Redirect { fd, kind, .. } => {
+                    let (fd, kind) = (*fd, *kind);
+                    self.pos += 1;
+                    match self.tokens.get(self.pos) {
+                        Some(Token::Word(target)) => {
+                            redirects.push(Redirection {
+                                fd,
+                                kind,
+                                target: target.clone(),
+                            });
+                            self.pos += 1;
+                        }
+                        Some(token) => return Err(unexpected(token)),
+                        None => return Err(ParseError::UnexpectedEof),
+                    }
+                }
+                Token::Op(..) => break,
+            }
+        }
+
+        let command = match subshell {
+            Some(subshell) => subshell,
+            None if words.is_empty() => {
+                if redirects.is_empty() {
+                    return Err(self
+                        .tokens
+                        .get(self.pos)
+                        .map_or(ParseError::UnexpectedEof, unexpected));
+                }
+                ParsedCommand::Empty
+            }
+            None => {
+                let command = words.remove(0);
+                self.parser.classify(command, words)
+            }
+        };
+
+        Ok(if redirects.is_empty() {
+            command
+        } else {
+            ParsedCommand::Redirect {
+                command: Box::new(command),
+                redirects,
+            }
+        })
+    }
+}
+
+fn unexpected(token: &Token) -> ParseError {
+    match token {
+        Token::Word(word) => ParseError::UnexpectedToken(word.to_string(), word.offset),
+        Token::Op(op, offset) => ParseError::UnexpectedToken(op.to_string(), *offset),
+        Token::Redirect { offset, .. } => {
+            ParseError::UnexpectedToken("redirection".to_string(), *offset)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -193,9 +457,15 @@ mod tests {
         let result = parser.parse("ll").unwrap();
         assert!(matches!(result, ParsedCommand::Alias { .. }));
 
-        if let ParsedCommand::Alias { name, expansion } = result {
+        if let ParsedCommand::Alias {
+            name,
+            expansion,
+            args,
+        } = result
+        {
             assert_eq!(name, "ll");
             assert_eq!(expansion, "ls -la");
+            assert!(args.is_empty());
         }
     }
 
@@ -297,4 +567,214 @@ mod tests {
 
         assert_eq!(result1, result2, "Parser must be deterministic");
     }
+
+    #[test]
+    fn test_parse_pipe_with_stderr() {
+        let mut parser = Parser::new(&test_config());
+
+        // `|&` is `2>&1 |`
+        let result = parser.parse("make |& less").unwrap();
+        let ParsedCommand::Pipeline(commands) = result else {
+            panic!("expected pipeline, got {result:?}");
+        };
+        assert_eq!(commands.len(), 2);
+        let ParsedCommand::Redirect { command, redirects } = &commands[0] else {
+            panic!("expected redirect");
+        };
+        assert!(
+            matches!(command.as_ref(), ParsedCommand::Simple { command, .. } if command == "make")
+        );
+        assert_eq!(redirects.len(), 1);
+        assert_eq!(redirects[0].source_fd(), 2);
+        assert_eq!(redirects[0].kind, RedirectKind::DupOutput);
+        assert_eq!(redirects[0].target, "1");
+    }
+
+    #[test]
+    fn test_parse_quoted_pipeline_with_redirect() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse(r#"echo "a b" | grep a > out.txt"#).unwrap();
+        let ParsedCommand::Pipeline(commands) = result else {
+            panic!("expected pipeline, got {result:?}");
+        };
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(
+            &commands[0],
+            ParsedCommand::Builtin { name, args } if name == "echo" && args == &["a b"]
+        ));
+        let ParsedCommand::Redirect { command, redirects } = &commands[1] else {
+            panic!("expected redirect");
+        };
+        assert!(matches!(
+            command.as_ref(),
+            ParsedCommand::Simple { command, args } if command == "grep" && args == &["a"]
+        ));
+        assert_eq!(redirects.len(), 1);
+        assert_eq!(redirects[0].kind, RedirectKind::Output);
+        assert_eq!(redirects[0].source_fd(), 1);
+        assert_eq!(redirects[0].target, "out.txt");
+    }
+
+    #[test]
+    fn test_parse_list_operators() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser
+            .parse("make && make test || echo fail; ls &")
+            .unwrap();
+        let ParsedCommand::List(items) = result else {
+            panic!("expected list");
+        };
+        let ops: Vec<ListOp> = items.iter().map(|(_, op)| *op).collect();
+        assert_eq!(
+            ops,
+            vec![ListOp::And, ListOp::Or, ListOp::Seq, ListOp::Background]
+        );
+    }
+
+    #[test]
+    fn test_parse_trailing_semicolon_is_single_command() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse("ls -la;").unwrap();
+        assert!(matches!(result, ParsedCommand::Simple { .. }));
+    }
+
+    #[test]
+    fn test_parse_newlines_separate_commands() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse("export A=1\n# comment\nls\n").unwrap();
+        let ParsedCommand::List(items) = result else {
+            panic!("expected list");
+        };
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_subshell_with_redirect() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse("(cd /tmp && ls) 2>&1").unwrap();
+        let ParsedCommand::Redirect { command, redirects } = result else {
+            panic!("expected redirect");
+        };
+        assert!(matches!(command.as_ref(), ParsedCommand::Subshell(inner)
+            if matches!(inner.as_ref(), ParsedCommand::List(_))));
+        assert_eq!(redirects[0].fd, Some(2));
+        assert_eq!(redirects[0].kind, RedirectKind::DupOutput);
+        assert_eq!(redirects[0].target, "1");
+    }
+
+    #[test]
+    fn test_parse_redirect_kinds() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse("cmd < in >> log &> all <<< text").unwrap();
+        let ParsedCommand::Redirect { redirects, .. } = result else {
+            panic!("expected redirect");
+        };
+        let kinds: Vec<RedirectKind> = redirects.iter().map(|r| r.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RedirectKind::Input,
+                RedirectKind::Append,
+                RedirectKind::OutputAll,
+                RedirectKind::HereString
+            ]
+        );
+        assert_eq!(redirects[0].source_fd(), 0);
+    }
+
+    #[test]
+    fn test_parse_variable_words() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse("$EDITOR \"${HOME}/notes\"").unwrap();
+        let ParsedCommand::Simple { command, args } = result else {
+            panic!("expected simple command");
+        };
+        assert_eq!(command.parts, vec![WordPart::Var("EDITOR".into())]);
+        assert_eq!(args[0].parts[1], WordPart::Var("HOME".into()));
+        assert_eq!(args[0].parts[2], WordPart::Quoted("/notes".into()));
+    }
+
+    #[test]
+    fn test_parse_alias_keeps_args() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse("ll /tmp").unwrap();
+        assert!(matches!(result, ParsedCommand::Alias { ref args, .. } if args == &["/tmp"]));
+    }
+
+    #[test]
+    fn test_parse_quoted_command_bypasses_alias() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let result = parser.parse("'ll'").unwrap();
+        assert!(matches!(result, ParsedCommand::Simple { .. }));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        assert!(matches!(
+            parser.parse("echo 'abc"),
+            Err(PzshError::Parse(ParseError::UnterminatedQuote('\'', 5)))
+        ));
+        assert!(matches!(
+            parser.parse("| grep x"),
+            Err(PzshError::Parse(ParseError::UnexpectedToken(ref t, 0))) if t == "|"
+        ));
+        assert!(matches!(
+            parser.parse("ls &&"),
+            Err(PzshError::Parse(ParseError::UnexpectedEof))
+        ));
+        assert!(matches!(
+            parser.parse("(ls"),
+            Err(PzshError::Parse(ParseError::UnexpectedEof))
+        ));
+        assert!(matches!(
+            parser.parse("ls >"),
+            Err(PzshError::Parse(ParseError::UnexpectedEof))
+        ));
+        assert!(matches!(
+            parser.parse("ls )"),
+            Err(PzshError::Parse(ParseError::UnexpectedToken(..)))
+        ));
+        // Errors are not cached
+        assert_eq!(parser.cache_len(), 0);
+    }
+
+    #[test]
+    fn test_parse_complex_under_2ms() {
+        let config = test_config();
+        let mut parser = Parser::new(&config);
+
+        let start = Instant::now();
+        let result = parser.parse(
+            r#"FOO="$(cat ~/.x)" && (cd "$HOME" && ls -la | grep -v '^d' | sort -k 5 > /tmp/out 2>&1) || echo $'fail\n'"#,
+        );
+        let elapsed = start.elapsed();
+
+        assert!(result.is_ok());
+        assert!(
+            elapsed < Duration::from_millis(MAX_PARSER_MS),
+            "ANDON: Parser exceeded 2ms budget: {:?}",
+            elapsed
+        );
+    }
 }
@@ -0,0 +1,334 @@
+//! Whole-script parsing for static analysis
+//!
+//! [`Parser`](super::Parser) handles one interactive command line. Startup
+//! files also contain function definitions, `if`/`for` blocks and `case`
+//! statements, so [`parse_script`] works on the token stream instead: it
+//! splits the script into simple commands, drops shell keywords and tracks
+//! which commands sit inside a function body.
+
+use super::lexer::{Lexer, Op, Token};
+use super::{Redirection, Word, WordPart};
+use std::ops::Range;
+
+/// Keywords that may precede a command (`then source x`, `! cmd`)
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "do", "done", "while", "until", "esac", "!", "time",
+];
+
+/// Keywords whose words are not a command (`for x in $(ls)`)
+const HEADERS: &[&str] = &["for", "select", "case"];
+
+/// A simple command in a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCommand {
+    /// Words in source order, keywords removed (offsets are into the script)
+    pub words: Vec<Word>,
+    /// Index of the command name in `words`, after `NAME=value` assignments;
+    /// `None` for bare assignments and `for`/`select`/`case` headers
+    pub command: Option<usize>,
+    /// Redirections, in source order
+    pub redirects: Vec<Redirection>,
+    /// Inside a function body: runs when the function is called, not when
+    /// the script is sourced
+    pub in_function: bool,
+}
+
+impl ScriptCommand {
+    /// Literal command name (`source`, `.`, `eval`, ...)
+    #[must_use]
+    pub fn name(&self) -> Option<String> {
+        self.command.and_then(|i| self.words[i].as_literal())
+    }
+
+    /// Arguments after the command name
+    #[must_use]
+    pub fn args(&self) -> &[Word] {
+        self.command.map_or(&[], |i| &self.words[i + 1..])
+    }
+}
+
+/// Parsed script
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    /// Simple commands in source order
+    pub commands: Vec<ScriptCommand>,
+    /// Byte ranges of lines the tokenizer rejected (heredocs, `;;`, ...)
+    pub unparsed: Vec<Range<usize>>,
+}
+
+/// Parse a startup file into simple commands
+///
+/// The script is tokenized as a whole; if that fails, it is tokenized line
+/// by line and the lines that still fail are reported in
+/// [`Script::unparsed`]. Heredoc bodies after such a line are data, not
+/// commands, and are skipped up to their delimiter.
+#[must_use]
+pub fn parse_script(source: &str) -> Script {
+    let mut builder = Builder::default();
+    if let Ok(tokens) = Lexer::new(source).tokenize() {
+        builder.feed(tokens, 0);
+        builder.flush();
+        return builder.script;
+    }
+
+    let mut start = 0;
+    let mut heredocs: Vec<String> = Vec::new();
+    for line in source.split_inclusive('\n') {
+        if let Some(delimiter) = heredocs.first() {
+            if line.trim() == delimiter {
+                heredocs.remove(0);
+            }
+        } else if let Ok(tokens) = Lexer::new(line).tokenize() {
+            builder.feed(tokens, start);
+        } else {
+            builder.flush();
+            builder
+                .script
+                .unparsed
+                .push(start..start + line.trim_end_matches('\n').len());
+            heredocs = heredoc_delimiters(line);
+        }
+        start += line.len();
+    }
+    builder.flush();
+    builder.script
+}
+
+/// Delimiters of the `<<EOF` / `<<-'EOF'` heredocs started on `line`
+fn heredoc_delimiters(line: &str) -> Vec<String> {
+    let mut delimiters = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(q), c) if c == q => quote = None,
+            (None, '\\') => escaped = true,
+            (None, '\'' | '"') => quote = Some(c),
+            // `<<` but not `<<<` (a here-string)
+            (None, '<')
+                if line[i..].starts_with("<<")
+                    && !line[i..].starts_with("<<<")
+                    && !line[..i].ends_with('<') =>
+            {
+                let rest = line[i + 2..].trim_start_matches('-').trim_start();
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || ";&|<>()".contains(c))
+                    .unwrap_or(rest.len());
+                let delimiter: String = rest[..end]
+                    .chars()
+                    .filter(|c| !matches!(c, '\'' | '"' | '\\'))
+                    .collect();
+                if !delimiter.is_empty() {
+                    delimiters.push(delimiter);
+                }
+            }
+            _ => {}
+        }
+    }
+    delimiters
+}
+
+/// Token-stream state machine
+#[derive(Default)]
+struct Builder {
+    script: Script,
+    words: Vec<Word>,
+    redirects: Vec<Redirection>,
+    /// Current words are a `for`/`select`/`case` header
+    header: bool,
+    /// `function` seen, name not yet
+    function_keyword: bool,
+    /// Function header seen, body `{` not yet
+    pending_function: bool,
+    /// Open `{` groups; `true` for function bodies
+    braces: Vec<bool>,
+}
+
+impl Builder {
+    fn feed(&mut self, tokens: Vec<Token>, base: usize) {
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                Token::Word(mut word) => {
+                    word.offset += base;
+                    self.word(word);
+                }
+                Token::Redirect { fd, kind, .. } => {
+                    if let Some(Token::Word(mut target)) =
+                        tokens.next_if(|t| matches!(t, Token::Word(_)))
+                    {
+                        target.offset += base;
+                        self.redirects.push(Redirection { fd, kind, target });
+                    }
+                }
+                // `name() {`
+                Token::Op(Op::LParen, _)
+                    if self.words.len() == 1
+                        && !self.header
+                        && matches!(tokens.peek(), Some(Token::Op(Op::RParen, _))) =>
+                {
+                    tokens.next();
+                    self.words.clear();
+                    self.pending_function = true;
+                }
+                Token::Op(..) => self.flush(),
+            }
+        }
+    }
+
+    fn word(&mut self, word: Word) {
+        if self.function_keyword {
+            // `function name`: the name is not a command
+            self.function_keyword = false;
+            self.pending_function = true;
+            return;
+        }
+        if self.words.is_empty() && !self.header && self.redirects.is_empty() {
+            let keyword = word.as_literal().filter(|_| word.is_plain());
+            match keyword.as_deref() {
+                Some("{") => {
+                    self.braces.push(std::mem::take(&mut self.pending_function));
+                    return;
+                }
+                Some("}") => {
+                    self.braces.pop();
+                    return;
+                }
+                Some("function") => {
+                    self.function_keyword = true;
+                    return;
+                }
+                Some(k) if HEADERS.contains(&k) => {
+                    self.header = true;
+                    return;
+                }
+                Some(k) if KEYWORDS.contains(&k) => return,
+                _ => {}
+            }
+        }
+        self.words.push(word);
+    }
+
+    fn flush(&mut self) {
+        if self.words.is_empty() && self.redirects.is_empty() {
+            self.header = false;
+            return;
+        }
+        let command = if self.header {
+            None
+        } else {
+            self.words.iter().position(|w| !is_assignment(w))
+        };
+        self.script.commands.push(ScriptCommand {
+            words: std::mem::take(&mut self.words),
+            command,
+            redirects: std::mem::take(&mut self.redirects),
+            in_function: self.braces.iter().any(|&f| f),
+        });
+        self.header = false;
+        self.pending_function = false;
+    }
+}
+
+/// `NAME=value` word
+fn is_assignment(word: &Word) -> bool {
+    match word.parts.first() {
+        Some(WordPart::Literal(text)) => text.split_once('=').is_some_and(|(name, _)| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(script: &Script) -> Vec<Option<String>> {
+        script.commands.iter().map(ScriptCommand::name).collect()
+    }
+
+    #[test]
+    fn test_simple_commands_and_keywords() {
+        let script = parse_script(
+            "export EDITOR=vim\nif [ -f ~/.x ]; then\n  source ~/.x\nfi\nFOO=1 cmd arg\nBAR=2\n",
+        );
+        assert_eq!(
+            names(&script),
+            [
+                Some("export".to_string()),
+                Some("[".to_string()),
+                Some("source".to_string()),
+                Some("cmd".to_string()),
+                None
+            ]
+        );
+        assert_eq!(script.commands[3].args()[0], "arg");
+        assert!(script.unparsed.is_empty());
+    }
+
+    #[test]
+    fn test_function_bodies() {
+        let script = parse_script(
+            "nvm() { source ~/.nvm/nvm.sh; }\nfunction lazy {\n  eval x\n}\n{ a; } > log\nafter\n",
+        );
+        let in_function: Vec<(Option<String>, bool)> = script
+            .commands
+            .iter()
+            .map(|c| (c.name(), c.in_function))
+            .collect();
+        assert_eq!(
+            in_function,
+            [
+                (Some("source".to_string()), true),
+                (Some("eval".to_string()), true),
+                (Some("a".to_string()), false),
+                // Redirections of the group
+                (None, false),
+                (Some("after".to_string()), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_headers_are_not_commands() {
+        let script = parse_script("for f in $(ls); do\n  echo $f\ndone\n");
+        assert_eq!(script.commands[0].command, None);
+        assert!(script.commands[0].words[2].has_command_subst());
+        assert_eq!(names(&script)[1].as_deref(), Some("echo"));
+    }
+
+    #[test]
+    fn test_offsets_and_line_fallback() {
+        let source = "echo one\ncase $x in\n  a) b ;;\nesac\nsource ~/y\n";
+        let script = parse_script(source);
+        assert_eq!(script.unparsed.len(), 1);
+        assert_eq!(&source[script.unparsed[0].clone()], "  a) b ;;");
+
+        let last = script.commands.last().unwrap();
+        assert_eq!(last.name().as_deref(), Some("source"));
+        assert_eq!(&source[last.args()[0].offset..], "~/y\n");
+    }
+
+    #[test]
+    fn test_heredoc_bodies_are_skipped() {
+        let source = "cat <<EOF > a; cat <<-'END'\neval x\nEOF\n\tsource y\n\tEND\nafter\n";
+        let script = parse_script(source);
+        assert_eq!(script.unparsed.len(), 1);
+        assert_eq!(names(&script), [Some("after".to_string())]);
+
+        assert_eq!(heredoc_delimiters("cat <<< x"), Vec::<String>::new());
+        assert_eq!(heredoc_delimiters("echo '<<EOF' <<\\STOP"), ["STOP"]);
+    }
+
+    #[test]
+    fn test_redirect_targets() {
+        let script = parse_script("cmd > $(mktemp) 2>&1\n");
+        let command = &script.commands[0];
+        assert_eq!(command.words.len(), 1);
+        assert_eq!(command.redirects.len(), 2);
+        assert!(command.redirects[0].target.has_command_subst());
+    }
+}
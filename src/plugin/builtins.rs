@@ -31,6 +31,7 @@ impl Plugin for GitPlugin {
         PluginInfo::new("git")
             .with_description("Git aliases and integration")
             .with_version("1.0.0")
+            .with_trigger("git")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -62,6 +63,36 @@ zstyle ':vcs_info:git:*' formats '%b'
 __pzsh_git_branch() {
     git branch 2>/dev/null | grep '^\*' | sed 's/^\* //'
 }
+"
+                .to_string()
+            }
+            crate::ShellType::Fish => {
+                // Fish-specific git integration
+                r"
+# pzsh git plugin
+function __pzsh_git_branch
+    git symbolic-ref --short HEAD 2>/dev/null
+end
+"
+                .to_string()
+            }
+            crate::ShellType::Nushell => {
+                // Nushell custom command
+                r"
+# pzsh git plugin
+def __pzsh_git_branch [] {
+    ^git symbolic-ref --short HEAD | complete | get stdout | str trim
+}
+"
+                .to_string()
+            }
+            crate::ShellType::Sh => {
+                // POSIX function
+                r"
+# pzsh git plugin
+__pzsh_git_branch() {
+    git symbolic-ref --short HEAD 2>/dev/null
+}
 "
                 .to_string()
             }
@@ -113,6 +144,7 @@ impl Plugin for DockerPlugin {
         PluginInfo::new("docker")
             .with_description("Docker aliases and completions")
             .with_version("1.0.0")
+            .with_trigger("docker")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -164,6 +196,7 @@ impl Plugin for KubectlPlugin {
         PluginInfo::new("kubectl")
             .with_description("Kubernetes kubectl aliases")
             .with_version("1.0.0")
+            .with_trigger("kubectl")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -227,6 +260,8 @@ impl Plugin for NpmPlugin {
         PluginInfo::new("npm")
             .with_description("Node.js npm/yarn aliases")
             .with_version("1.0.0")
+            .with_trigger("npm")
+            .with_trigger("yarn")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -293,6 +328,9 @@ impl Plugin for PythonPlugin {
         PluginInfo::new("python")
             .with_description("Python/pip aliases")
             .with_version("1.0.0")
+            .with_trigger("python")
+            .with_trigger("python3")
+            .with_trigger("pip")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -358,6 +396,7 @@ impl Plugin for GolangPlugin {
         PluginInfo::new("golang")
             .with_description("Go language aliases")
             .with_version("1.0.0")
+            .with_trigger("go")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -411,6 +450,7 @@ impl Plugin for RustPlugin {
         PluginInfo::new("rust")
             .with_description("Rust/Cargo aliases")
             .with_version("1.0.0")
+            .with_trigger("cargo")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -466,6 +506,8 @@ impl Plugin for TerraformPlugin {
         PluginInfo::new("terraform")
             .with_description("Terraform/OpenTofu aliases")
             .with_version("1.0.0")
+            .with_trigger("terraform")
+            .with_trigger("tofu")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -527,6 +569,7 @@ impl Plugin for AwsPlugin {
         PluginInfo::new("aws")
             .with_description("AWS CLI aliases")
             .with_version("1.0.0")
+            .with_trigger("aws")
     }
 
     fn init(&mut self) -> Result<(), PluginError> {
@@ -643,6 +686,31 @@ mod tests {
         assert!(init.contains("__git_ps1") || init.is_empty());
     }
 
+    #[test]
+    fn test_git_plugin_shell_init_fish() {
+        let mut plugin = GitPlugin::new();
+        plugin.init().unwrap();
+        let init = plugin.shell_init(crate::ShellType::Fish);
+        assert!(init.contains("function __pzsh_git_branch"));
+        assert!(init.contains("\nend\n"));
+    }
+
+    #[test]
+    fn test_git_plugin_shell_init_nushell_and_sh() {
+        let mut plugin = GitPlugin::new();
+        plugin.init().unwrap();
+        assert!(
+            plugin
+                .shell_init(crate::ShellType::Nushell)
+                .contains("def __pzsh_git_branch [] {")
+        );
+        assert!(
+            plugin
+                .shell_init(crate::ShellType::Sh)
+                .contains("__pzsh_git_branch() {")
+        );
+    }
+
     #[test]
     fn test_git_plugin_aliases() {
         let plugin = GitPlugin::new();
@@ -672,6 +740,7 @@ mod tests {
         let info = plugin.info();
         assert_eq!(info.name, "docker");
         assert!(info.lazy_loadable);
+        assert_eq!(info.triggers, vec!["docker"]);
     }
 
     #[test]
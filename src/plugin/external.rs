@@ -0,0 +1,365 @@
+//! External plugins declared in `plugin.toml` manifests
+//!
+//! Each subdirectory of the plugin directory may contain a manifest:
+//!
+//! ```toml
+//! name = "mytool"
+//! description = "mytool aliases"
+//! version = "1.2.0"
+//! dependencies = ["git"]
+//! triggers = ["mytool"]
+//! completions = ["mytool"]
+//!
+//! [aliases]
+//! mt = "mytool run"
+//!
+//! [env]
+//! MYTOOL_HOME = "/opt/mytool"
+//!
+//! [init]
+//! zsh = "compdef _gnu_generic mytool"
+//! ```
+//!
+//! Manifests are data only: aliases, env values and init snippets must pass
+//! the same forbidden-pattern checks as the user configuration, and env
+//! values are interpolated the same way.
+
+use ahash::AHashMap;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use super::{Plugin, PluginError, PluginInfo};
+use crate::config::{CompiledConfig, interpolate_env};
+
+/// Manifest file name inside each plugin directory
+pub const MANIFEST_FILE: &str = "plugin.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "default_version")]
+    version: String,
+    #[serde(default)]
+    dependencies: Vec<String>,
+    #[serde(default = "default_true")]
+    lazy_loadable: bool,
+    #[serde(default)]
+    triggers: Vec<String>,
+    #[serde(default)]
+    completions: Vec<String>,
+    #[serde(default)]
+    aliases: AHashMap<String, String>,
+    #[serde(default)]
+    env: AHashMap<String, String>,
+    #[serde(default)]
+    init: InitSection,
+}
+
+/// Per-shell init snippets
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InitSection {
+    zsh: Option<String>,
+    bash: Option<String>,
+    fish: Option<String>,
+    nushell: Option<String>,
+    sh: Option<String>,
+}
+
+fn default_version() -> String {
+    "1.0.0".to_string()
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+/// Plugin loaded from a `plugin.toml` manifest
+#[derive(Debug, Clone)]
+pub struct ExternalPlugin {
+    manifest: Manifest,
+    path: PathBuf,
+    enabled: bool,
+}
+
+impl ExternalPlugin {
+    /// Read and validate a manifest file
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be read, is not a valid manifest or
+    /// contains forbidden patterns
+    pub fn from_file(path: &Path) -> Result<Self, PluginError> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_toml(&content, path)
+    }
+
+    /// Parse and validate a manifest (`path` is used in error messages)
+    ///
+    /// # Errors
+    /// Returns error if the manifest is invalid or contains forbidden patterns
+    pub fn from_toml(content: &str, path: &Path) -> Result<Self, PluginError> {
+        let invalid = |reason: String| PluginError::InvalidManifest {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let mut manifest: Manifest = toml::from_str(content).map_err(|e| invalid(e.to_string()))?;
+
+        if manifest.name.is_empty()
+            || !manifest
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(invalid(format!("invalid plugin name: {:?}", manifest.name)));
+        }
+
+        // `command` or `command:function`, both plain names
+        for completion in &manifest.completions {
+            let valid = completion.split(':').count() <= 2
+                && completion.split(':').all(|part| {
+                    !part.is_empty()
+                        && part
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                });
+            if !valid {
+                return Err(invalid(format!("invalid completion: {completion:?}")));
+            }
+        }
+
+        let init = &manifest.init;
+        let snippets = [&init.zsh, &init.bash, &init.fish, &init.nushell, &init.sh];
+        let values = manifest
+            .aliases
+            .iter()
+            .chain(&manifest.env)
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .chain(snippets.into_iter().flatten().map(|s| ("init", s.as_str())));
+        for (key, value) in values {
+            CompiledConfig::check_forbidden_patterns(key, value)
+                .map_err(|e| invalid(format!("{key}: {e}")))?;
+        }
+        manifest.env = interpolate_env(&manifest.env)
+            .map_err(|e| invalid(format!("env {e}")))?
+            .into_iter()
+            .collect();
+
+        Ok(Self {
+            manifest,
+            path: path.to_path_buf(),
+            enabled: false,
+        })
+    }
+
+    /// Manifest path
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Plugin for ExternalPlugin {
+    fn info(&self) -> PluginInfo {
+        let manifest = &self.manifest;
+        let mut info = PluginInfo::new(&manifest.name)
+            .with_description(&manifest.description)
+            .with_version(&manifest.version)
+            .with_lazy_loadable(manifest.lazy_loadable);
+        for dep in &manifest.dependencies {
+            info = info.with_dependency(dep);
+        }
+        for trigger in &manifest.triggers {
+            info = info.with_trigger(trigger);
+        }
+        info
+    }
+
+    fn init(&mut self) -> Result<(), PluginError> {
+        self.enabled = true;
+        Ok(())
+    }
+
+    fn shell_init(&self, shell: crate::ShellType) -> String {
+        if !self.enabled {
+            return String::new();
+        }
+        let init = &self.manifest.init;
+        let snippet = match shell {
+            crate::ShellType::Zsh => &init.zsh,
+            crate::ShellType::Bash => &init.bash,
+            crate::ShellType::Fish => &init.fish,
+            crate::ShellType::Nushell => &init.nushell,
+            crate::ShellType::Sh => &init.sh,
+        };
+        snippet.as_ref().map_or_else(String::new, |snippet| {
+            let mut code = format!("\n# pzsh {} plugin\n{snippet}", self.manifest.name);
+            if !code.ends_with('\n') {
+                code.push('\n');
+            }
+            code
+        })
+    }
+
+    fn aliases(&self) -> AHashMap<String, String> {
+        self.manifest.aliases.clone()
+    }
+
+    fn env_vars(&self) -> AHashMap<String, String> {
+        self.manifest.env.clone()
+    }
+
+    fn completions(&self) -> Vec<String> {
+        self.manifest.completions.clone()
+    }
+}
+
+/// Manifests in `<dir>/*/plugin.toml`, sorted by path
+///
+/// A missing directory has no plugins.
+///
+/// # Errors
+/// Returns error if the directory cannot be read
+pub fn discover(dir: &Path) -> Result<Vec<PathBuf>, PluginError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut manifests = Vec::new();
+    for entry in entries {
+        let manifest = entry?.path().join(MANIFEST_FILE);
+        if manifest.is_file() {
+            manifests.push(manifest);
+        }
+    }
+    manifests.sort();
+    Ok(manifests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+name = "mytool"
+description = "mytool aliases"
+version = "1.2.0"
+dependencies = ["git"]
+triggers = ["mytool"]
+completions = ["mytool", "mt:_mytool"]
+
+[aliases]
+mt = "mytool run"
+
+[env]
+MYTOOL_HOME = "/opt/mytool"
+MYTOOL_BIN = "${MYTOOL_HOME}/bin"
+
+[init]
+zsh = "compdef _gnu_generic mytool"
+"#;
+
+    fn parse(content: &str) -> Result<ExternalPlugin, PluginError> {
+        ExternalPlugin::from_toml(content, Path::new("mytool/plugin.toml"))
+    }
+
+    #[test]
+    fn test_manifest_info() {
+        let plugin = parse(MANIFEST).unwrap();
+        let info = plugin.info();
+        assert_eq!(info.name, "mytool");
+        assert_eq!(info.description, "mytool aliases");
+        assert_eq!(info.version, "1.2.0");
+        assert_eq!(info.dependencies, vec!["git"]);
+        assert_eq!(info.triggers, vec!["mytool"]);
+        assert!(info.lazy_loadable);
+        assert_eq!(plugin.path(), Path::new("mytool/plugin.toml"));
+    }
+
+    #[test]
+    fn test_manifest_aliases_env_and_init() {
+        let mut plugin = parse(MANIFEST).unwrap();
+        assert_eq!(plugin.aliases().get("mt").unwrap(), "mytool run");
+        assert_eq!(plugin.env_vars().get("MYTOOL_HOME").unwrap(), "/opt/mytool");
+        assert_eq!(
+            plugin.env_vars().get("MYTOOL_BIN").unwrap(),
+            "/opt/mytool/bin"
+        );
+        assert_eq!(plugin.completions(), ["mytool", "mt:_mytool"]);
+
+        // No init code before the plugin is loaded
+        assert!(plugin.shell_init(crate::ShellType::Zsh).is_empty());
+        plugin.init().unwrap();
+        assert_eq!(
+            plugin.shell_init(crate::ShellType::Zsh),
+            "\n# pzsh mytool plugin\ncompdef _gnu_generic mytool\n"
+        );
+        assert!(plugin.shell_init(crate::ShellType::Bash).is_empty());
+    }
+
+    #[test]
+    fn test_manifest_minimal() {
+        let plugin = parse("name = \"tiny\"\n").unwrap();
+        let info = plugin.info();
+        assert_eq!(info.version, "1.0.0");
+        assert!(plugin.aliases().is_empty());
+    }
+
+    #[test]
+    fn test_manifest_rejects_forbidden_patterns() {
+        for content in [
+            "name = \"x\"\n[aliases]\nnow = \"echo $(date)\"\n",
+            "name = \"x\"\n[env]\nGOROOT = \"`brew --prefix go`\"\n",
+            "name = \"x\"\n[init]\nbash = \"eval \\\"$(x init)\\\"\"\n",
+        ] {
+            let err = parse(content).unwrap_err();
+            assert!(
+                err.to_string().contains("forbidden"),
+                "{content:?} gave {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_manifest_rejects_invalid() {
+        // Unknown keys
+        assert!(parse("name = \"x\"\nalias = {}\n").is_err());
+        assert!(parse("name = \"x\"\n[init]\ntcsh = \"\"\n").is_err());
+        // Missing or unsafe name
+        assert!(parse("description = \"x\"\n").is_err());
+        assert!(parse("name = \"x; rm -rf\"\n").is_err());
+        // Unsafe completions
+        assert!(parse("name = \"x\"\ncompletions = [\"a;b\"]\n").is_err());
+        assert!(parse("name = \"x\"\ncompletions = [\"a:b:c\"]\n").is_err());
+        assert!(parse("name = \"x\"\ncompletions = [\"a:\"]\n").is_err());
+        // Env references that don't resolve
+        let err = parse("name = \"x\"\n[env]\nA = \"${B}\"\nB = \"$A\"\n").unwrap_err();
+        assert!(err.to_string().contains("env cycle: A -> B -> A"), "{err}");
+        let err = parse("name = \"\"\n").unwrap_err();
+        assert!(err.to_string().contains("mytool/plugin.toml"));
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = crate::test_util::temp_dir("discover");
+        for name in ["b", "a", "empty"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        std::fs::write(dir.join("b").join(MANIFEST_FILE), "name = \"b\"\n").unwrap();
+        std::fs::write(dir.join("a").join(MANIFEST_FILE), "name = \"a\"\n").unwrap();
+        std::fs::write(dir.join("stray.toml"), "").unwrap();
+
+        let manifests = discover(&dir).unwrap();
+        assert_eq!(
+            manifests,
+            vec![dir.join("a/plugin.toml"), dir.join("b/plugin.toml")]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Missing directory has no plugins
+        assert!(discover(&dir).unwrap().is_empty());
+    }
+}
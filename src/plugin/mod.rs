@@ -4,7 +4,9 @@
 //! Plugins are loaded lazily to maintain O(1) startup.
 
 mod builtins;
+mod external;
 pub use builtins::*;
+pub use external::{ExternalPlugin, MANIFEST_FILE, discover};
 
 use ahash::AHashMap;
 use std::path::PathBuf;
@@ -27,6 +29,8 @@ pub enum PluginState {
     Failed,
     /// Plugin disabled by user
     Disabled,
+    /// Plugin compiled to stubs, loaded on first use of a trigger
+    Deferred,
 }
 
 /// Plugin metadata
@@ -42,6 +46,8 @@ pub struct PluginInfo {
     pub dependencies: Vec<String>,
     /// Whether plugin supports lazy loading
     pub lazy_loadable: bool,
+    /// Commands that load the plugin on first use (besides its aliases)
+    pub triggers: Vec<String>,
 }
 
 impl PluginInfo {
@@ -54,6 +60,7 @@ impl PluginInfo {
             version: "1.0.0".to_string(),
             dependencies: Vec::new(),
             lazy_loadable: true,
+            triggers: Vec::new(),
         }
     }
 
@@ -77,6 +84,20 @@ impl PluginInfo {
         self.dependencies.push(dep.into());
         self
     }
+
+    /// Set whether the plugin may be lazy loaded
+    #[must_use]
+    pub const fn with_lazy_loadable(mut self, lazy_loadable: bool) -> Self {
+        self.lazy_loadable = lazy_loadable;
+        self
+    }
+
+    /// Add trigger command
+    #[must_use]
+    pub fn with_trigger(mut self, command: impl Into<String>) -> Self {
+        self.triggers.push(command.into());
+        self
+    }
 }
 
 /// Plugin definition trait
@@ -102,6 +123,9 @@ pub trait Plugin: Send + Sync {
     }
 
     /// Get completions provided by this plugin
+    ///
+    /// Each entry is a command (`mytool`, completed from its `--help`
+    /// output) or a command and completion function (`mytool:_mytool`).
     fn completions(&self) -> Vec<String> {
         Vec::new()
     }
@@ -122,6 +146,18 @@ pub enum PluginError {
     #[error("dependency not met: {0}")]
     DependencyNotMet(String),
 
+    #[error("dependency cycle: {}", chain.join(" -> "))]
+    DependencyCycle { chain: Vec<String> },
+
+    #[error("plugin not found in dependency chain: {}", chain.join(" -> "))]
+    MissingDependency { chain: Vec<String> },
+
+    #[error("plugin cannot be lazy loaded: {0}")]
+    NotLazyLoadable(String),
+
+    #[error("invalid plugin manifest {}: {reason}", path.display())]
+    InvalidManifest { path: PathBuf, reason: String },
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -134,6 +170,8 @@ pub struct PluginManager {
     states: AHashMap<String, PluginState>,
     /// Plugin load order
     load_order: Vec<String>,
+    /// Deferred (lazy) plugins, in order
+    lazy_order: Vec<String>,
     /// Plugin directory
     plugin_dir: Option<PathBuf>,
 }
@@ -144,6 +182,7 @@ impl std::fmt::Debug for PluginManager {
             .field("plugins", &self.plugins.keys().collect::<Vec<_>>())
             .field("states", &self.states)
             .field("load_order", &self.load_order)
+            .field("lazy_order", &self.lazy_order)
             .field("plugin_dir", &self.plugin_dir)
             .finish()
     }
@@ -157,6 +196,7 @@ impl PluginManager {
             plugins: AHashMap::new(),
             states: AHashMap::new(),
             load_order: Vec::new(),
+            lazy_order: Vec::new(),
             plugin_dir: None,
         };
 
@@ -179,6 +219,35 @@ impl PluginManager {
         self.plugin_dir = Some(dir);
     }
 
+    /// Discover and register external plugins from the plugin directory
+    ///
+    /// Returns the name of each registered plugin, or the error for each
+    /// rejected manifest. A manifest may not replace a registered plugin.
+    pub fn discover(&mut self) -> Vec<Result<String, PluginError>> {
+        let Some(dir) = self.plugin_dir.clone() else {
+            return Vec::new();
+        };
+        let manifests = match external::discover(&dir) {
+            Ok(manifests) => manifests,
+            Err(e) => return vec![Err(e)],
+        };
+        manifests
+            .into_iter()
+            .map(|path| {
+                let plugin = ExternalPlugin::from_file(&path)?;
+                let name = plugin.info().name;
+                if self.plugins.contains_key(&name) {
+                    return Err(PluginError::InvalidManifest {
+                        path,
+                        reason: format!("plugin already registered: {name}"),
+                    });
+                }
+                self.register(plugin);
+                Ok(name)
+            })
+            .collect()
+    }
+
     /// Register a plugin
     pub fn register(&mut self, plugin: impl Plugin + 'static) {
         let info = plugin.info();
@@ -230,13 +299,145 @@ impl PluginManager {
         // Mark as loaded
         self.states.insert(name.to_string(), PluginState::Loaded);
         self.load_order.push(name.to_string());
+        self.lazy_order.retain(|lazy| lazy != name);
 
         Ok(elapsed)
     }
 
-    /// Load multiple plugins
+    /// Defer a plugin until first use of its triggers or aliases
+    ///
+    /// The plugin is initialized now so that its shell init and aliases can
+    /// be compiled into the loader, but it is not part of the eager output.
+    /// Already loaded plugins stay loaded.
+    pub fn defer(&mut self, name: &str) -> Result<(), PluginError> {
+        contract_pre_config_validation!(name);
+        match self.states.get(name) {
+            Some(PluginState::Loaded | PluginState::Deferred) => return Ok(()),
+            Some(_) => {}
+            None => return Err(PluginError::NotFound(name.to_string())),
+        }
+
+        let plugin = self
+            .plugins
+            .get_mut(name)
+            .ok_or_else(|| PluginError::NotFound(name.to_string()))?;
+
+        // A plugin nothing can trigger would never load
+        let info = plugin.info();
+        if !info.lazy_loadable || (info.triggers.is_empty() && plugin.aliases().is_empty()) {
+            return Err(PluginError::NotLazyLoadable(name.to_string()));
+        }
+
+        // Dependencies load eagerly
+        let order = self.resolve(&[name.to_string()])?;
+        for dep in &order[..order.len() - 1] {
+            self.load(dep)?;
+        }
+
+        let plugin = self
+            .plugins
+            .get_mut(name)
+            .ok_or_else(|| PluginError::NotFound(name.to_string()))?;
+        plugin.init().inspect_err(|_| {
+            self.states.insert(name.to_string(), PluginState::Failed);
+        })?;
+
+        self.states.insert(name.to_string(), PluginState::Deferred);
+        self.lazy_order.push(name.to_string());
+        Ok(())
+    }
+
+    /// Loaded plugins, in load order
+    pub fn loaded(&self) -> impl Iterator<Item = &dyn Plugin> {
+        self.load_order
+            .iter()
+            .filter(|name| matches!(self.states.get(*name), Some(PluginState::Loaded)))
+            .filter_map(|name| self.plugins.get(name))
+            .map(AsRef::as_ref)
+    }
+
+    /// Deferred plugins, in the order they were deferred
+    pub fn deferred(&self) -> impl Iterator<Item = &dyn Plugin> {
+        self.lazy_order
+            .iter()
+            .filter_map(|name| self.plugins.get(name))
+            .map(AsRef::as_ref)
+    }
+
+    /// Load plugins and their transitive dependencies
+    ///
+    /// Returns one result per requested plugin (including the time spent on
+    /// dependencies it pulled in). Dependencies always load before their
+    /// dependents, whatever the order of `names`.
     pub fn load_all(&mut self, names: &[String]) -> Vec<Result<Duration, PluginError>> {
-        names.iter().map(|name| self.load(name)).collect()
+        names
+            .iter()
+            .map(|name| {
+                let mut elapsed = Duration::ZERO;
+                for plugin in self.resolve(std::slice::from_ref(name))? {
+                    elapsed += self.load(&plugin)?;
+                }
+                Ok(elapsed)
+            })
+            .collect()
+    }
+
+    /// Resolve plugins and their transitive dependencies into load order
+    ///
+    /// Dependencies come before their dependents; otherwise the order of
+    /// `names` is kept.
+    ///
+    /// # Errors
+    /// Returns error naming the whole chain from the requested plugin on a
+    /// dependency cycle or a missing dependency
+    pub fn resolve(&self, names: &[String]) -> Result<Vec<String>, PluginError> {
+        let mut order = Vec::new();
+        for name in names {
+            self.visit(name, &mut Vec::new(), &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Depth-first visit for [`Self::resolve`]; `chain` is the current path
+    fn visit(
+        &self,
+        name: &str,
+        chain: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> Result<(), PluginError> {
+        if order.iter().any(|done| done == name) {
+            return Ok(());
+        }
+        let cycle = chain.iter().any(|on_path| on_path == name);
+        chain.push(name.to_string());
+        if cycle {
+            return Err(PluginError::DependencyCycle {
+                chain: chain.clone(),
+            });
+        }
+
+        let Some(plugin) = self.plugins.get(name) else {
+            return Err(if chain.len() == 1 {
+                PluginError::NotFound(name.to_string())
+            } else {
+                PluginError::MissingDependency {
+                    chain: chain.clone(),
+                }
+            });
+        };
+        for dep in plugin.info().dependencies {
+            self.visit(&dep, chain, order)?;
+        }
+
+        chain.pop();
+        order.push(name.to_string());
+        Ok(())
+    }
+
+    /// Names of loaded plugins, in load order
+    #[must_use]
+    pub fn load_order(&self) -> &[String] {
+        &self.load_order
     }
 
     /// Get all aliases from loaded plugins
@@ -338,6 +539,7 @@ mod tests {
             PluginState::Loaded,
             PluginState::Failed,
             PluginState::Disabled,
+            PluginState::Deferred,
         ];
         for state in states {
             assert!(!format!("{:?}", state).is_empty());
@@ -368,6 +570,16 @@ mod tests {
         assert_eq!(info.version, "1.0.0");
         assert!(info.dependencies.is_empty());
         assert!(info.lazy_loadable);
+        assert!(info.triggers.is_empty());
+    }
+
+    #[test]
+    fn test_plugin_info_lazy_builder() {
+        let info = PluginInfo::new("test")
+            .with_trigger("tool")
+            .with_lazy_loadable(false);
+        assert_eq!(info.triggers, vec!["tool"]);
+        assert!(!info.lazy_loadable);
     }
 
     // ==================== PluginError TESTS ====================
@@ -394,6 +606,11 @@ mod tests {
                 .to_string()
                 .contains("dependency")
         );
+        assert!(
+            PluginError::NotLazyLoadable("x".into())
+                .to_string()
+                .contains("lazy")
+        );
     }
 
     // ==================== PluginManager TESTS ====================
@@ -453,11 +670,185 @@ mod tests {
         assert!(manager.load_all(&[]).is_empty());
     }
 
+    #[test]
+    fn test_manager_defer() {
+        let mut manager = PluginManager::new();
+        manager.defer("docker").unwrap();
+        assert_eq!(manager.state("docker"), Some(PluginState::Deferred));
+        assert_eq!(manager.loaded_count(), 0);
+
+        // Deferred plugins are initialized but not part of the eager output
+        assert!(manager.all_aliases().is_empty());
+        let deferred: Vec<String> = manager.deferred().map(|p| p.info().name).collect();
+        assert_eq!(deferred, vec!["docker"]);
+        assert!(manager.deferred().all(|p| p.aliases().contains_key("dps")));
+
+        // Deferring twice is idempotent
+        manager.defer("docker").unwrap();
+        assert_eq!(manager.deferred().count(), 1);
+    }
+
+    #[test]
+    fn test_manager_defer_rejects_ineligible() {
+        struct Eager;
+        impl Plugin for Eager {
+            fn info(&self) -> PluginInfo {
+                PluginInfo::new("eager")
+                    .with_trigger("eager")
+                    .with_lazy_loadable(false)
+            }
+            fn init(&mut self) -> Result<(), PluginError> {
+                Ok(())
+            }
+            fn shell_init(&self, _: crate::ShellType) -> String {
+                String::new()
+            }
+        }
+        struct Silent;
+        impl Plugin for Silent {
+            fn info(&self) -> PluginInfo {
+                PluginInfo::new("silent")
+            }
+            fn init(&mut self) -> Result<(), PluginError> {
+                Ok(())
+            }
+            fn shell_init(&self, _: crate::ShellType) -> String {
+                String::new()
+            }
+        }
+
+        let mut manager = PluginManager::new();
+        manager.register(Eager);
+        manager.register(Silent);
+        assert!(matches!(
+            manager.defer("nonexistent"),
+            Err(PluginError::NotFound(_))
+        ));
+        assert!(matches!(
+            manager.defer("eager"),
+            Err(PluginError::NotLazyLoadable(_))
+        ));
+        assert_eq!(manager.state("eager"), Some(PluginState::Registered));
+        // Nothing could ever trigger a plugin without triggers or aliases
+        assert!(matches!(
+            manager.defer("silent"),
+            Err(PluginError::NotLazyLoadable(_))
+        ));
+
+        // Loaded plugins stay loaded
+        manager.load("docker").unwrap();
+        manager.defer("docker").unwrap();
+        assert_eq!(manager.state("docker"), Some(PluginState::Loaded));
+        assert_eq!(manager.deferred().count(), 0);
+    }
+
+    /// Plugin with dependencies, for resolution tests
+    struct DepPlugin {
+        name: &'static str,
+        deps: &'static [&'static str],
+    }
+
+    impl Plugin for DepPlugin {
+        fn info(&self) -> PluginInfo {
+            let mut info = PluginInfo::new(self.name).with_trigger(self.name);
+            for dep in self.deps {
+                info = info.with_dependency(*dep);
+            }
+            info
+        }
+        fn init(&mut self) -> Result<(), PluginError> {
+            Ok(())
+        }
+        fn shell_init(&self, _: crate::ShellType) -> String {
+            String::new()
+        }
+    }
+
+    fn dep_manager(plugins: &[(&'static str, &'static [&'static str])]) -> PluginManager {
+        let mut manager = PluginManager::new();
+        for &(name, deps) in plugins {
+            manager.register(DepPlugin { name, deps });
+        }
+        manager
+    }
+
+    #[test]
+    fn test_manager_load_all_orders_dependencies() {
+        let mut manager =
+            dep_manager(&[("app", &["lib", "git"]), ("lib", &["base"]), ("base", &[])]);
+        let results = manager.load_all(&["app".into(), "docker".into(), "base".into()]);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(
+            manager.load_order(),
+            ["base", "lib", "git", "app", "docker"]
+        );
+    }
+
+    #[test]
+    fn test_manager_resolve() {
+        let manager = dep_manager(&[("app", &["lib"]), ("lib", &["base"]), ("base", &[])]);
+        assert_eq!(
+            manager.resolve(&["base".into(), "app".into()]).unwrap(),
+            ["base", "lib", "app"]
+        );
+        assert!(manager.resolve(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_manager_resolve_cycle_names_chain() {
+        let mut manager = dep_manager(&[("app", &["a"]), ("a", &["b"]), ("b", &["a"])]);
+        let err = manager.resolve(&["app".into()]).unwrap_err();
+        assert!(matches!(
+            &err,
+            PluginError::DependencyCycle { chain } if chain == &["app", "a", "b", "a"]
+        ));
+        assert_eq!(err.to_string(), "dependency cycle: app -> a -> b -> a");
+
+        // Nothing in the cycle is loaded; unrelated plugins still are
+        let results = manager.load_all(&["app".into(), "git".into()]);
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+        assert_eq!(manager.load_order(), ["git"]);
+    }
+
+    #[test]
+    fn test_manager_resolve_missing_names_chain() {
+        let manager = dep_manager(&[("app", &["lib"]), ("lib", &["gone"])]);
+        let err = manager.resolve(&["app".into()]).unwrap_err();
+        assert!(matches!(
+            &err,
+            PluginError::MissingDependency { chain } if chain == &["app", "lib", "gone"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "plugin not found in dependency chain: app -> lib -> gone"
+        );
+        assert!(matches!(
+            manager.resolve(&["nope".into()]),
+            Err(PluginError::NotFound(name)) if name == "nope"
+        ));
+    }
+
+    #[test]
+    fn test_manager_defer_loads_dependencies() {
+        let mut manager = dep_manager(&[("app", &["base"]), ("base", &[])]);
+        manager.defer("app").unwrap();
+        assert_eq!(manager.state("base"), Some(PluginState::Loaded));
+        assert_eq!(manager.state("app"), Some(PluginState::Deferred));
+
+        // Loading a deferred plugin takes it out of the lazy set
+        manager.load("app").unwrap();
+        assert_eq!(manager.deferred().count(), 0);
+    }
+
     #[test]
     fn test_manager_all_aliases() {
         let mut manager = PluginManager::new();
         manager.load("git").unwrap();
         manager.load("docker").unwrap();
+        let names: Vec<String> = manager.loaded().map(|p| p.info().name).collect();
+        assert_eq!(names, ["git", "docker"]);
         let aliases = manager.all_aliases();
         assert!(aliases.contains_key("gs"));
         assert!(aliases.contains_key("dps"));
@@ -494,6 +885,44 @@ mod tests {
         assert!(format!("{:?}", manager).contains("/tmp/plugins"));
     }
 
+    #[test]
+    fn test_manager_discover_external_plugins() {
+        let dir = crate::test_util::temp_dir("plugins");
+        for (name, manifest) in [
+            (
+                "mytool",
+                "name = \"mytool\"\n[aliases]\nmt = \"mytool run\"\n",
+            ),
+            ("git", "name = \"git\"\n"),
+            ("bad", "name = \"bad\"\n[env]\nX = \"$(slow)\"\n"),
+        ] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            std::fs::write(dir.join(name).join(MANIFEST_FILE), manifest).unwrap();
+        }
+
+        let mut manager = PluginManager::new();
+        assert!(manager.discover().is_empty());
+        manager.set_plugin_dir(dir.clone());
+        let results = manager.discover();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Sorted by directory: bad, git, mytool
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            &results[0],
+            Err(PluginError::InvalidManifest { reason, .. }) if reason.contains("forbidden")
+        ));
+        assert!(matches!(
+            &results[1],
+            Err(PluginError::InvalidManifest { reason, .. }) if reason.contains("already registered")
+        ));
+        assert_eq!(results[2].as_ref().unwrap(), "mytool");
+
+        manager.load("mytool").unwrap();
+        assert_eq!(manager.all_aliases().get("mt").unwrap(), "mytool run");
+        assert!(manager.state("bad").is_none());
+    }
+
     #[test]
     fn test_manager_register_custom() {
         #[derive(Clone, Debug)]
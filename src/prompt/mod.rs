@@ -2,13 +2,31 @@
 //!
 //! O(1) prompt rendering with 2ms budget constraint.
 //! Git status is async-updated, never blocks.
-
-use crate::color::{Styled, themes::DefaultTheme};
-use crate::config::CompiledConfig;
-use crate::{MAX_PROMPT_MS, PzshError, Result};
+//!
+//! `pzsh prompt` renders a [`Prompt`] for zsh and bash hooks: the shell
+//! passes its state as a [`PromptContext`], and escape sequences are
+//! wrapped as zero-width (`%{ %}`, `\[ \]`) for the target shell.
+
+use crate::color::{RESET, Style, Styled, themes::DefaultTheme};
+use crate::config::{CompiledConfig, Snapshot};
+use crate::shell::PromptStyles;
+use crate::{MAX_PROMPT_MS, PzshError, Result, ShellType};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Commands running shorter than this show no `{duration}`
+pub const MIN_DURATION_MS: u64 = 2000;
+
+/// File in the pzsh cache directory recording recent git dirty checks
+pub const GIT_STATUS_FILE: &str = "git-status";
+
+/// Repositories whose dirty state [`GIT_STATUS_FILE`] keeps
+const GIT_STATUS_ENTRIES: usize = 32;
 
 /// Compiled prompt segment (pre-rendered where possible)
 #[derive(Debug, Clone)]
@@ -25,10 +43,42 @@ pub enum PromptSegment {
     Git,
     /// Prompt character ($ or #)
     Char,
+    /// Exit code of the last command (when non-zero)
+    Status,
+    /// Run time of the last command (when at least [`MIN_DURATION_MS`])
+    Duration,
+    /// Number of background jobs (when any)
+    Jobs,
+    /// Line editor keymap
+    Keymap,
     /// Custom segment
     Custom(String),
 }
 
+impl PromptSegment {
+    /// Segment showing state only the shell knows, passed to `pzsh prompt`
+    #[must_use]
+    pub const fn is_shell_state(&self) -> bool {
+        matches!(
+            self,
+            Self::Status | Self::Duration | Self::Jobs | Self::Keymap
+        )
+    }
+}
+
+/// Shell state a prompt is rendered for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptContext {
+    /// Exit code of the last command
+    pub exit_code: i32,
+    /// Run time of the last command in milliseconds
+    pub duration_ms: u64,
+    /// Number of background jobs
+    pub jobs: usize,
+    /// Line editor keymap (zsh `$KEYMAP`, or `vi` / `emacs`)
+    pub keymap: Option<String>,
+}
+
 /// Cached git status (updated asynchronously)
 #[derive(Debug, Clone, Default)]
 pub struct GitCache {
@@ -62,6 +112,68 @@ impl GitCache {
         self.valid.store(false, Ordering::Relaxed);
     }
 
+    /// Git status of the repository containing `dir`
+    ///
+    /// The branch is read from `HEAD` without spawning git (a detached
+    /// `HEAD` shows its abbreviated commit); only the dirty check runs
+    /// `git status`.
+    #[must_use]
+    pub fn read(dir: &Path) -> Self {
+        git_dir(dir)
+            .and_then(|git_dir| git_branch(&git_dir))
+            .map_or_else(Self::new, |branch| Self::found(branch, git_dirty(dir)))
+    }
+
+    /// Like [`GitCache::read`], but reuses the dirty state recorded in
+    /// `status_file` while it is younger than `max_age` and the repository
+    /// index is unchanged; otherwise runs `git status` and records it
+    #[must_use]
+    pub fn read_cached(dir: &Path, status_file: &Path, max_age: Duration) -> Self {
+        let Some(git_dir) = git_dir(dir) else {
+            return Self::new();
+        };
+        let Some(branch) = git_branch(&git_dir) else {
+            return Self::new();
+        };
+        let repo = git_dir.to_string_lossy();
+        let now = since_epoch(SystemTime::now());
+        let mut states = DirtyState::read_all(status_file);
+        let cached = states.iter().find(|state| {
+            state.git_dir == repo
+                && state.index == index_modified(&git_dir)
+                && now.saturating_sub(state.checked) < max_age.as_nanos()
+        });
+        if let Some(state) = cached {
+            return Self::found(branch, state.dirty);
+        }
+
+        let dirty = git_dirty(dir);
+        // `git status` may refresh the index, so read its mtime afterwards
+        states.retain(|state| state.git_dir != repo);
+        states.push(DirtyState {
+            checked: now,
+            index: index_modified(&git_dir),
+            dirty,
+            git_dir: repo.into_owned(),
+        });
+        let excess = states.len().saturating_sub(GIT_STATUS_ENTRIES);
+        states.drain(..excess);
+        // A prompt without the cache is only slower
+        let _ = DirtyState::write_all(status_file, &states);
+        Self::found(branch, dirty)
+    }
+
+    /// Valid cache for a repository on `branch`
+    fn found(branch: String, dirty: bool) -> Self {
+        let cache = Self::new();
+        cache.valid.store(true, Ordering::Relaxed);
+        Self {
+            branch: Some(branch),
+            dirty,
+            ..cache
+        }
+    }
+
     /// Render git status string
     #[must_use]
     pub fn render(&self) -> String {
@@ -101,8 +213,20 @@ pub struct Prompt {
     /// Cached values
     user: String,
     host: String,
+    home: Option<String>,
+    /// Effective uid is 0 (only checked when the format has `{char}`)
+    is_root: bool,
     /// Color support enabled
     colors_enabled: bool,
+    /// Segment styles
+    styles: PromptStyles,
+    /// Shell state for dynamic segments
+    context: PromptContext,
+    /// Shell whose prompt escapes to emit (raw ANSI when `None`)
+    shell: Option<ShellType>,
+    /// Where git dirty checks are recorded, and for how long they hold
+    git_status_file: Option<PathBuf>,
+    git_cache_ttl: Duration,
 }
 
 impl Prompt {
@@ -117,6 +241,10 @@ impl Prompt {
             .ok()
             .and_then(|h| h.into_string().ok())
             .unwrap_or_else(|| "localhost".to_string());
+        let is_root = segments
+            .iter()
+            .any(|segment| matches!(segment, PromptSegment::Char))
+            && effective_uid() == Some(0);
 
         // Check color support
         let colors_enabled = config.colors_enabled && crate::color::supports_color();
@@ -126,8 +254,64 @@ impl Prompt {
             git_cache: GitCache::new(),
             user,
             host,
+            home: std::env::var("HOME").ok().filter(|home| !home.is_empty()),
+            is_root,
             colors_enabled,
+            styles: PromptStyles::pzsh_default(),
+            context: PromptContext::default(),
+            shell: None,
+            git_status_file: None,
+            git_cache_ttl: Duration::from_millis(config.git_cache_ms),
+        }
+    }
+
+    /// Create the prompt of a compiled snapshot, with its theme
+    #[must_use]
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let mut prompt = Self::new(&snapshot.config);
+        match snapshot.prompt_styles {
+            Some(styles) => prompt.styles = styles,
+            None => prompt.colors_enabled = false,
         }
+        prompt
+    }
+
+    /// Set the shell state for `{status}`, `{duration}`, `{jobs}` and
+    /// `{keymap}`
+    pub fn set_context(&mut self, context: PromptContext) {
+        self.context = context;
+    }
+
+    /// Render for a zsh `PROMPT` or bash `PS1`: escape sequences are wrapped
+    /// as zero-width and text is escaped. Other shells get raw output.
+    pub fn set_shell(&mut self, shell: ShellType) {
+        self.shell = matches!(shell, ShellType::Zsh | ShellType::Bash).then_some(shell);
+    }
+
+    /// Whether the format shows `{git}`
+    #[must_use]
+    pub fn uses_git(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, PromptSegment::Git))
+    }
+
+    /// Record git dirty checks in `path`, so prompts within `git_cache_ms`
+    /// of a check don't run `git status` again
+    pub fn set_git_status_file(&mut self, path: PathBuf) {
+        self.git_status_file = Some(path);
+    }
+
+    /// Read the git status of the repository containing `dir`, if the
+    /// format shows it
+    pub fn refresh_git(&mut self, dir: &Path) {
+        if !self.uses_git() {
+            return;
+        }
+        self.git_cache = match &self.git_status_file {
+            Some(file) => GitCache::read_cached(dir, file, self.git_cache_ttl),
+            None => GitCache::read(dir),
+        };
     }
 
     /// Enable or disable colors
@@ -142,7 +326,7 @@ impl Prompt {
     }
 
     /// Parse format string into segments
-    fn parse_format(format: &str) -> Vec<PromptSegment> {
+    pub(crate) fn parse_format(format: &str) -> Vec<PromptSegment> {
         contract_pre_parser_correctness!(format);
         let mut segments = Vec::new();
         let mut current_literal = String::new();
@@ -164,6 +348,10 @@ impl Prompt {
                         "cwd" => PromptSegment::Cwd,
                         "git" => PromptSegment::Git,
                         "char" => PromptSegment::Char,
+                        "status" => PromptSegment::Status,
+                        "duration" => PromptSegment::Duration,
+                        "jobs" => PromptSegment::Jobs,
+                        "keymap" => PromptSegment::Keymap,
                         other => PromptSegment::Custom(other.to_string()),
                     };
                     segments.push(segment);
@@ -197,52 +385,67 @@ impl Prompt {
 
         for segment in &self.segments {
             match segment {
-                PromptSegment::Literal(s) => output.push_str(s),
-                PromptSegment::User => {
-                    if self.colors_enabled {
-                        output.push_str(&Styled::new(&self.user, DefaultTheme::user()).render());
-                    } else {
-                        output.push_str(&self.user);
-                    }
-                }
-                PromptSegment::Host => {
-                    if self.colors_enabled {
-                        output.push_str(&Styled::new(&self.host, DefaultTheme::host()).render());
-                    } else {
-                        output.push_str(&self.host);
-                    }
-                }
+                PromptSegment::Literal(s) => output.push_str(&self.escape(s)),
+                PromptSegment::User => self.push_styled(&mut output, &self.user, self.styles.user),
+                PromptSegment::Host => self.push_styled(&mut output, &self.host, self.styles.host),
                 PromptSegment::Cwd => {
                     // Use PWD or current_dir (no subprocess!)
                     let cwd = std::env::var("PWD")
                         .or_else(|_| std::env::current_dir().map(|p| p.display().to_string()))
                         .unwrap_or_else(|_| "~".to_string());
-                    if self.colors_enabled {
-                        output.push_str(&Styled::new(&cwd, DefaultTheme::cwd()).render());
-                    } else {
-                        output.push_str(&cwd);
-                    }
+                    self.push_styled(&mut output, &self.abbreviate_home(cwd), self.styles.cwd);
                 }
                 PromptSegment::Git => {
                     // Use cached git status (never blocks)
-                    output.push_str(&self.git_cache.render_colored(self.colors_enabled));
-                }
-                PromptSegment::Char => {
-                    let is_root = self.user == "root";
-                    let ch = if is_root { '#' } else { '$' };
-                    if self.colors_enabled {
-                        let style = if is_root {
-                            DefaultTheme::prompt_root()
+                    if let Some(branch) = &self.git_cache.branch {
+                        let (marker, style) = if self.git_cache.dirty {
+                            ("*", self.styles.git_dirty)
                         } else {
-                            DefaultTheme::prompt_char()
+                            ("", self.styles.git_clean)
                         };
-                        output.push_str(&Styled::new(ch.to_string(), style).render());
+                        self.push_styled(&mut output, &format!("({branch}{marker})"), style);
+                    }
+                }
+                PromptSegment::Char => {
+                    let (ch, style) = if self.is_root {
+                        ("#", self.styles.prompt_root)
                     } else {
-                        output.push(ch);
+                        ("$", self.styles.prompt_char)
+                    };
+                    self.push_styled(&mut output, ch, style);
+                }
+                PromptSegment::Status => {
+                    let code = self.context.exit_code;
+                    if code != 0 {
+                        self.push_styled(&mut output, &format!("[{code}]"), DefaultTheme::error());
+                    }
+                }
+                PromptSegment::Duration => {
+                    let ms = self.context.duration_ms;
+                    if ms >= MIN_DURATION_MS {
+                        self.push_styled(
+                            &mut output,
+                            &format_duration(ms),
+                            DefaultTheme::warning(),
+                        );
+                    }
+                }
+                PromptSegment::Jobs => {
+                    let jobs = self.context.jobs;
+                    if jobs > 0 {
+                        output.push_str(&self.escape(&format!("&{jobs}")));
+                    }
+                }
+                PromptSegment::Keymap => {
+                    // The default keymaps need no indicator
+                    if let Some(keymap) = self.context.keymap.as_deref()
+                        && !matches!(keymap, "" | "main" | "emacs")
+                    {
+                        output.push_str(&self.escape(keymap));
                     }
                 }
                 PromptSegment::Custom(name) => {
-                    output.push_str(&format!("{{{name}}}"));
+                    output.push_str(&self.escape(&format!("{{{name}}}")));
                 }
             }
         }
@@ -275,6 +478,199 @@ impl Prompt {
     pub fn segment_count(&self) -> usize {
         self.segments.len()
     }
+
+    fn push_styled(&self, output: &mut String, text: &str, style: Style) {
+        let open = if self.colors_enabled {
+            style.to_ansi()
+        } else {
+            String::new()
+        };
+        if open.is_empty() {
+            output.push_str(&self.escape(text));
+            return;
+        }
+        output.push_str(&self.zero_width(&open));
+        output.push_str(&self.escape(text));
+        output.push_str(&self.zero_width(RESET));
+    }
+
+    /// Mark an escape sequence as taking no columns
+    fn zero_width(&self, escape: &str) -> String {
+        match self.shell {
+            Some(ShellType::Zsh) => format!("%{{{escape}%}}"),
+            Some(ShellType::Bash) => format!("\\[{escape}\\]"),
+            _ => escape.to_string(),
+        }
+    }
+
+    fn escape(&self, text: &str) -> String {
+        match self.shell {
+            Some(ShellType::Zsh) => zsh_escape(text),
+            Some(ShellType::Bash) => bash_escape(text),
+            _ => text.to_string(),
+        }
+    }
+
+    /// `~` for the home directory, like zsh's `%~` and bash's `\w`
+    fn abbreviate_home(&self, cwd: String) -> String {
+        match self.home.as_deref().and_then(|home| cwd.strip_prefix(home)) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{rest}"),
+            _ => cwd,
+        }
+    }
+}
+
+/// Escape text for a zsh `PROMPT` with `PROMPT_SUBST`
+pub(crate) fn zsh_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "%%")
+        .replace('$', "\\$")
+        .replace('`', "\\`")
+}
+
+/// Escape text for a bash `PS1` with `promptvars`
+pub(crate) fn bash_escape(text: &str) -> String {
+    text.replace('\\', "\\\\\\\\")
+        .replace('$', "\\\\$")
+        .replace('`', "\\\\`")
+}
+
+/// `42s`, `3m5s` or `2h10m`
+fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{}s", secs / 60, secs % 60),
+        _ => format!("{}h{}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Effective uid of this process
+///
+/// `$EUID` when exported, else the owner of `/proc/self`, else `id -u`.
+/// `$USER` is inherited through `su` and `sudo -E`, so it can't tell.
+fn effective_uid() -> Option<u32> {
+    if let Some(uid) = std::env::var("EUID").ok().and_then(|uid| uid.parse().ok()) {
+        return Some(uid);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(meta) = fs::metadata("/proc/self") {
+            return Some(meta.uid());
+        }
+    }
+    let out = Command::new("id")
+        .arg("-u")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    String::from_utf8(out.stdout).ok()?.trim().parse().ok()
+}
+
+/// Git directory of the repository containing `dir`
+fn git_dir(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|path| path.exists())?;
+    if dot_git.is_file() {
+        // Worktrees and submodules point to their git directory
+        let content = fs::read_to_string(&dot_git).ok()?;
+        let target = content.strip_prefix("gitdir:")?.trim();
+        Some(dot_git.parent()?.join(target))
+    } else {
+        Some(dot_git)
+    }
+}
+
+/// Branch checked out in the repository at `git_dir` (short hash when detached)
+fn git_branch(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => head.get(..7).map(str::to_string),
+    }
+}
+
+/// Whether `git status` lists changes in the repository containing `dir`
+fn git_dirty(dir: &Path) -> bool {
+    Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(dir)
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|out| out.status.success() && !out.stdout.is_empty())
+}
+
+fn index_modified(git_dir: &Path) -> u128 {
+    fs::metadata(git_dir.join("index"))
+        .and_then(|meta| meta.modified())
+        .map_or(0, since_epoch)
+}
+
+fn since_epoch(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos())
+}
+
+/// Line of [`GIT_STATUS_FILE`]: when the repository was checked, its index
+/// mtime then (nanoseconds since the epoch) and whether it was dirty
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirtyState {
+    checked: u128,
+    index: u128,
+    dirty: bool,
+    git_dir: String,
+}
+
+impl DirtyState {
+    /// Recorded states, oldest first; unreadable lines are dropped
+    fn read_all(path: &Path) -> Vec<Self> {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(4, '\t');
+                Some(Self {
+                    checked: fields.next()?.parse().ok()?,
+                    index: fields.next()?.parse().ok()?,
+                    dirty: fields.next()? == "1",
+                    git_dir: fields.next()?.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    fn write_all(path: &Path, states: &[Self]) -> std::io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut content = String::new();
+        for state in states {
+            let _ = writeln!(
+                content,
+                "{}\t{}\t{}\t{}",
+                state.checked,
+                state.index,
+                u8::from(state.dirty),
+                state.git_dir
+            );
+        }
+        // Prompts in other shells may read it meanwhile
+        let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+        let result = fs::write(&tmp, content).and_then(|()| fs::rename(&tmp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
 }
 
 #[cfg(test)]
@@ -628,8 +1024,19 @@ mod tests {
         let prompt = Prompt::new(&config);
 
         let rendered = prompt.render().unwrap();
-        // Non-root user should see $
-        assert!(rendered.contains('$') || rendered.contains('#'));
+        let expected = if effective_uid() == Some(0) { "#" } else { "$" };
+        assert_eq!(rendered, expected);
+    }
+
+    #[test]
+    fn test_effective_uid_matches_id() {
+        let out = std::process::Command::new("id").arg("-u").output().unwrap();
+        let uid: u32 = String::from_utf8(out.stdout)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert_eq!(effective_uid(), Some(uid));
     }
 
     #[test]
@@ -647,4 +1054,176 @@ mod tests {
         assert!(rendered.contains(':'));
         assert!(rendered.contains("(feature)"));
     }
+
+    fn context_prompt(format: &str) -> Prompt {
+        let mut prompt = Prompt::new(&CompiledConfig {
+            prompt_format: format.to_string(),
+            ..CompiledConfig::default()
+        });
+        prompt.set_colors_enabled(false);
+        prompt
+    }
+
+    #[test]
+    fn test_shell_state_segments() {
+        let mut prompt = context_prompt("{status}|{duration}|{jobs}|{keymap}");
+        assert_eq!(prompt.render().unwrap(), "|||");
+
+        prompt.set_context(PromptContext {
+            exit_code: 130,
+            duration_ms: 65_000,
+            jobs: 2,
+            keymap: Some("vicmd".to_string()),
+        });
+        assert_eq!(prompt.render().unwrap(), "[130]|1m5s|&2|vicmd");
+
+        prompt.set_context(PromptContext {
+            duration_ms: MIN_DURATION_MS - 1,
+            keymap: Some("emacs".to_string()),
+            ..PromptContext::default()
+        });
+        assert_eq!(prompt.render().unwrap(), "|||");
+        assert_eq!(format_duration(7_500_000), "2h5m");
+    }
+
+    #[test]
+    fn test_shell_escapes() {
+        let mut prompt = context_prompt("{status} 100% $(x) `y` \\ ");
+        prompt.set_context(PromptContext {
+            exit_code: 1,
+            ..PromptContext::default()
+        });
+        prompt.colors_enabled = true;
+        let error = DefaultTheme::error().to_ansi();
+
+        prompt.set_shell(ShellType::Zsh);
+        assert_eq!(
+            prompt.render().unwrap(),
+            format!("%{{{error}%}}[1]%{{{RESET}%}} 100%% \\$(x) \\`y\\` \\\\ ")
+        );
+
+        prompt.set_shell(ShellType::Bash);
+        assert_eq!(
+            prompt.render().unwrap(),
+            format!("\\[{error}\\][1]\\[{RESET}\\] 100% \\\\$(x) \\\\`y\\\\` \\\\\\\\ ")
+        );
+
+        // fish prints the prompt as is
+        prompt.set_shell(ShellType::Fish);
+        assert_eq!(
+            prompt.render().unwrap(),
+            format!("{error}[1]{RESET} 100% $(x) `y` \\ ")
+        );
+    }
+
+    #[test]
+    fn test_cwd_abbreviates_home() {
+        let mut prompt = context_prompt("{cwd}");
+        prompt.home = Some("/home/me".to_string());
+        assert_eq!(prompt.abbreviate_home("/home/me".to_string()), "~");
+        assert_eq!(prompt.abbreviate_home("/home/me/src".to_string()), "~/src");
+        assert_eq!(prompt.abbreviate_home("/home/meg".to_string()), "/home/meg");
+    }
+
+    #[test]
+    fn test_git_cache_read_from_head() {
+        let dir = crate::test_util::temp_dir("git-head");
+        fs::create_dir_all(dir.join("repo/.git")).unwrap();
+        fs::create_dir_all(dir.join("repo/src")).unwrap();
+        fs::create_dir_all(dir.join("worktree")).unwrap();
+
+        fs::write(dir.join("repo/.git/HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+        let cache = GitCache::read(&dir.join("repo/src"));
+        assert_eq!(cache.branch.as_deref(), Some("feature/x"));
+        assert!(cache.is_valid());
+
+        fs::write(dir.join("repo/.git/HEAD"), "0123456789abcdef\n").unwrap();
+        assert_eq!(
+            GitCache::read(&dir.join("repo")).branch.as_deref(),
+            Some("0123456")
+        );
+
+        // Worktrees have a `.git` file pointing to their git directory
+        fs::create_dir_all(dir.join("repo/.git/worktrees/wt")).unwrap();
+        fs::write(
+            dir.join("repo/.git/worktrees/wt/HEAD"),
+            "ref: refs/heads/wt\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("worktree/.git"),
+            "gitdir: ../repo/.git/worktrees/wt\n",
+        )
+        .unwrap();
+        assert_eq!(
+            GitCache::read(&dir.join("worktree")).branch.as_deref(),
+            Some("wt")
+        );
+
+        assert!(GitCache::read(&dir).branch.is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_git_cache_reuses_recent_dirty_checks() {
+        let dir = crate::test_util::temp_dir("git-status");
+        let repo = dir.join("repo");
+        let git_dir = repo.join(".git");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let file = dir.join("cache").join(GIT_STATUS_FILE);
+        let ttl = Duration::from_secs(60);
+
+        // Not a real repository, so `git status` finds it clean
+        let cache = GitCache::read_cached(&repo, &file, ttl);
+        assert_eq!(cache.branch.as_deref(), Some("main"));
+        assert!(!cache.dirty);
+        let states = DirtyState::read_all(&file);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].git_dir, git_dir.to_string_lossy());
+
+        // A recent check is reused instead of running `git status`
+        let dirty = DirtyState {
+            dirty: true,
+            ..states[0].clone()
+        };
+        DirtyState::write_all(&file, &[dirty]).unwrap();
+        assert!(GitCache::read_cached(&repo, &file, ttl).dirty);
+
+        // Until the index changes or the check expires
+        fs::write(git_dir.join("index"), "").unwrap();
+        assert!(!GitCache::read_cached(&repo, &file, ttl).dirty);
+        let dirty = DirtyState {
+            dirty: true,
+            ..DirtyState::read_all(&file)[0].clone()
+        };
+        DirtyState::write_all(&file, &[dirty]).unwrap();
+        assert!(!GitCache::read_cached(&repo, &file, Duration::ZERO).dirty);
+
+        assert!(GitCache::read_cached(&dir, &file, ttl).branch.is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prompt_from_snapshot() {
+        let config = CompiledConfig {
+            prompt_format: "{git}".to_string(),
+            prompt_theme: Some("agnoster".to_string()),
+            ..CompiledConfig::default()
+        };
+        let snapshot = crate::shell::ShellIntegration::new(ShellType::Zsh, config).snapshot();
+        let styles = snapshot.prompt_styles.unwrap();
+        let mut prompt = Prompt::from_snapshot(&snapshot);
+        assert!(prompt.uses_git());
+        prompt.colors_enabled = true;
+        prompt.update_git_cache(Some("main".to_string()), false);
+        assert_eq!(
+            prompt.render().unwrap(),
+            format!("{}(main){RESET}", styles.git_clean.to_ansi())
+        );
+
+        let mut snapshot = snapshot;
+        snapshot.prompt_styles = None;
+        assert!(!Prompt::from_snapshot(&snapshot).colors_enabled());
+    }
 }
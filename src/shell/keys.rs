@@ -0,0 +1,525 @@
+//! Key chords and line editor widgets for `[keybindings]`
+//!
+//! Bindings map a chord to a widget name that is the same in every shell:
+//!
+//! ```toml
+//! [keybindings]
+//! ctrl-r = "history-search"
+//! "ctrl-x ctrl-e" = "edit-command-line"
+//! ctrl-s = "none"
+//! ```
+//!
+//! Chords are modifiers joined with `-` (`ctrl-r`, `alt-f`, `ctrl-left`) or
+//! named keys (`up`, `home`, `delete`, `shift-tab`); a space separates the
+//! keys of a sequence. `none` removes a default binding.
+
+use crate::ShellType;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Line editor action with its name in each shell
+#[derive(Debug, PartialEq, Eq)]
+pub struct Widget {
+    /// Name used in `[keybindings]`
+    pub name: &'static str,
+    zsh: &'static str,
+    bash: &'static str,
+    /// `None` if fish has no equivalent
+    fish: Option<&'static str>,
+}
+
+impl Widget {
+    /// Widget name in `shell`, `None` if the shell has no equivalent
+    #[must_use]
+    pub const fn in_shell(&self, shell: ShellType) -> Option<&'static str> {
+        match shell {
+            ShellType::Zsh => Some(self.zsh),
+            ShellType::Bash => Some(self.bash),
+            ShellType::Fish => self.fish,
+            ShellType::Nushell | ShellType::Sh => None,
+        }
+    }
+
+    /// Look up a widget by its `[keybindings]` name
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        WIDGETS.iter().find(|w| w.name == name)
+    }
+}
+
+/// Widgets are stored by name
+impl Serialize for Widget {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+const fn widget(
+    name: &'static str,
+    zsh: &'static str,
+    bash: &'static str,
+    fish: Option<&'static str>,
+) -> Widget {
+    Widget {
+        name,
+        zsh,
+        bash,
+        fish,
+    }
+}
+
+/// Widgets available in `[keybindings]`
+pub const WIDGETS: &[Widget] = &[
+    widget(
+        "history-search",
+        "history-incremental-search-backward",
+        "reverse-search-history",
+        Some("history-pager"),
+    ),
+    widget(
+        "history-search-forward",
+        "history-incremental-search-forward",
+        "forward-search-history",
+        None,
+    ),
+    widget(
+        "history-prev",
+        "up-line-or-beginning-search",
+        "history-search-backward",
+        Some("up-or-search"),
+    ),
+    widget(
+        "history-next",
+        "down-line-or-beginning-search",
+        "history-search-forward",
+        Some("down-or-search"),
+    ),
+    widget(
+        "completion-prev",
+        "reverse-menu-complete",
+        "menu-complete-backward",
+        None,
+    ),
+    widget(
+        "completion-next",
+        "menu-complete",
+        "menu-complete",
+        Some("complete"),
+    ),
+    widget(
+        "forward-word",
+        "forward-word",
+        "forward-word",
+        Some("forward-word"),
+    ),
+    widget(
+        "backward-word",
+        "backward-word",
+        "backward-word",
+        Some("backward-word"),
+    ),
+    widget(
+        "beginning-of-line",
+        "beginning-of-line",
+        "beginning-of-line",
+        Some("beginning-of-line"),
+    ),
+    widget(
+        "end-of-line",
+        "end-of-line",
+        "end-of-line",
+        Some("end-of-line"),
+    ),
+    widget(
+        "delete-char",
+        "delete-char",
+        "delete-char",
+        Some("delete-char"),
+    ),
+    widget(
+        "backward-delete-char",
+        "backward-delete-char",
+        "backward-delete-char",
+        Some("backward-delete-char"),
+    ),
+    widget(
+        "backward-kill-word",
+        "backward-kill-word",
+        "backward-kill-word",
+        Some("backward-kill-word"),
+    ),
+    widget("kill-line", "kill-line", "kill-line", Some("kill-line")),
+    widget(
+        "backward-kill-line",
+        "backward-kill-line",
+        "unix-line-discard",
+        Some("backward-kill-line"),
+    ),
+    widget("yank", "yank", "yank", Some("yank")),
+    widget("undo", "undo", "undo", Some("undo")),
+    widget(
+        "clear-screen",
+        "clear-screen",
+        "clear-screen",
+        Some("clear-screen"),
+    ),
+    widget("accept-line", "accept-line", "accept-line", Some("execute")),
+    widget(
+        "edit-command-line",
+        "edit-command-line",
+        "edit-and-execute-command",
+        Some("edit_command_buffer"),
+    ),
+];
+
+/// Named keys and the sequences terminals send for them
+const NAMED_KEYS: &[(&str, &[&str])] = &[
+    ("up", &["\x1b[A", "\x1bOA"]),
+    ("down", &["\x1b[B", "\x1bOB"]),
+    ("right", &["\x1b[C"]),
+    ("left", &["\x1b[D"]),
+    ("home", &["\x1b[H"]),
+    ("end", &["\x1b[F"]),
+    ("delete", &["\x1b[3~"]),
+    ("backspace", &["\x7f"]),
+    ("tab", &["\t"]),
+    ("shift-tab", &["\x1b[Z"]),
+    ("enter", &["\r"]),
+    ("escape", &["\x1b"]),
+    ("ctrl-right", &["\x1b[1;5C"]),
+    ("ctrl-left", &["\x1b[1;5D"]),
+    ("alt-right", &["\x1b[1;3C"]),
+    ("alt-left", &["\x1b[1;3D"]),
+];
+
+/// A chord bound to a widget
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBinding {
+    /// Chord as written in `[keybindings]` (lowercase modifiers)
+    pub chord: String,
+    /// Byte sequences the terminal may send for the chord
+    pub keys: Vec<String>,
+    /// `None` removes the binding
+    #[serde(deserialize_with = "widget_by_name")]
+    pub widget: Option<&'static Widget>,
+}
+
+/// Deserialize a widget stored by [`Widget`]'s `Serialize` impl
+fn widget_by_name<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<&'static Widget>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|name| {
+            Widget::from_name(&name)
+                .ok_or_else(|| D::Error::custom(format!("unknown widget: {name}")))
+        })
+        .transpose()
+}
+
+impl KeyBinding {
+    /// Parse a `[keybindings]` entry
+    ///
+    /// # Errors
+    /// Returns error for unknown keys, modifiers or widgets
+    pub fn parse(chord: &str, widget: &str) -> Result<Self, String> {
+        let keys = parse_chord(chord)?;
+        let widget = if widget == "none" {
+            None
+        } else {
+            Some(Widget::from_name(widget).ok_or_else(|| {
+                let names: Vec<&str> = WIDGETS.iter().map(|w| w.name).collect();
+                format!(
+                    "unknown widget {widget:?} for {chord}; use one of: {}, none",
+                    names.join(", ")
+                )
+            })?)
+        };
+        Ok(Self {
+            chord: normalize(chord),
+            keys,
+            widget,
+        })
+    }
+
+    /// Binding lines for `shell`
+    ///
+    /// A widget the shell does not have becomes a comment; `none` removes
+    /// whatever the shell itself binds to the keys.
+    #[must_use]
+    pub fn render(&self, shell: ShellType) -> String {
+        let Some(widget) = self.widget else {
+            return self.render_unbind(shell);
+        };
+        let Some(name) = widget.in_shell(shell) else {
+            return format!(
+                "# {}: {} is not available in {}\n",
+                self.chord,
+                widget.name,
+                shell.name()
+            );
+        };
+        self.keys
+            .iter()
+            .map(|keys| match shell {
+                ShellType::Zsh => {
+                    format!("bindkey '{}' {name}  # {}\n", zsh_keys(keys), self.chord)
+                }
+                ShellType::Bash => {
+                    format!("bind '\"{}\": {name}'  # {}\n", bash_keys(keys), self.chord)
+                }
+                ShellType::Fish => {
+                    format!("    bind {} {name}  # {}\n", fish_keys(keys), self.chord)
+                }
+                ShellType::Nushell | ShellType::Sh => String::new(),
+            })
+            .collect()
+    }
+
+    fn render_unbind(&self, shell: ShellType) -> String {
+        self.keys
+            .iter()
+            .map(|keys| match shell {
+                ShellType::Zsh => format!("bindkey -r '{}'  # {}\n", zsh_keys(keys), self.chord),
+                ShellType::Bash => format!("bind -r '{}'  # {}\n", bash_keys(keys), self.chord),
+                ShellType::Fish => format!("    bind -e {}  # {}\n", fish_keys(keys), self.chord),
+                ShellType::Nushell | ShellType::Sh => String::new(),
+            })
+            .collect()
+    }
+}
+
+/// Default bindings (emacs mode) for `shell`
+#[must_use]
+pub fn default_bindings(shell: ShellType) -> Vec<KeyBinding> {
+    let common: &[(&str, &str)] = &[
+        ("up", "history-prev"),
+        ("down", "history-next"),
+        ("ctrl-r", "history-search"),
+        ("ctrl-s", "history-search-forward"),
+        ("ctrl-p", "history-prev"),
+        ("ctrl-n", "history-next"),
+        ("ctrl-right", "forward-word"),
+        ("ctrl-left", "backward-word"),
+        ("alt-f", "forward-word"),
+        ("alt-b", "backward-word"),
+        ("home", "beginning-of-line"),
+        ("end", "end-of-line"),
+        ("ctrl-a", "beginning-of-line"),
+        ("ctrl-e", "end-of-line"),
+        ("delete", "delete-char"),
+    ];
+    let editing: &[(&str, &str)] = &[
+        ("ctrl-w", "backward-kill-word"),
+        ("ctrl-u", "backward-kill-line"),
+        ("ctrl-k", "kill-line"),
+        ("ctrl-y", "yank"),
+    ];
+    let bindings: Vec<(&str, &str)> = match shell {
+        ShellType::Zsh => [common, &[("backspace", "backward-delete-char")], editing].concat(),
+        ShellType::Bash => [
+            common,
+            &[
+                ("ctrl-d", "delete-char"),
+                ("ctrl-h", "backward-delete-char"),
+            ],
+            editing,
+        ]
+        .concat(),
+        // fish binds arrows, history search and Home/End itself
+        ShellType::Fish => [
+            &[("ctrl-p", "history-prev"), ("ctrl-n", "history-next")],
+            &common[6..10],
+            &common[12..14],
+            editing,
+        ]
+        .concat(),
+        ShellType::Nushell | ShellType::Sh => Vec::new(),
+    };
+    bindings
+        .into_iter()
+        .filter_map(|(chord, widget)| KeyBinding::parse(chord, widget).ok())
+        .collect()
+}
+
+/// Defaults with `bindings` applied: a binding replaces the default for
+/// the same chord, and new chords follow the defaults; `none` bindings stay
+/// so they can be rendered as unbinds
+#[must_use]
+pub fn merge_bindings(shell: ShellType, bindings: &[KeyBinding]) -> Vec<KeyBinding> {
+    let mut merged = default_bindings(shell);
+    for binding in bindings {
+        match merged.iter_mut().find(|b| b.chord == binding.chord) {
+            Some(existing) => *existing = binding.clone(),
+            None => merged.push(binding.clone()),
+        }
+    }
+    merged
+}
+
+fn normalize(chord: &str) -> String {
+    chord.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Byte sequences for a chord (alternates for keys like `up`)
+fn parse_chord(chord: &str) -> Result<Vec<String>, String> {
+    let mut sequences = vec![String::new()];
+    let parts: Vec<&str> = chord.split_whitespace().collect();
+    if parts.is_empty() {
+        return Err("empty key chord".to_string());
+    }
+    for part in parts {
+        let alternates = parse_key(part).ok_or_else(|| format!("unknown key chord: {chord}"))?;
+        sequences = sequences
+            .iter()
+            .flat_map(|prefix| alternates.iter().map(move |key| format!("{prefix}{key}")))
+            .collect();
+    }
+    Ok(sequences)
+}
+
+fn parse_key(key: &str) -> Option<Vec<String>> {
+    if let Some((_, sequences)) = NAMED_KEYS.iter().find(|(name, _)| *name == key) {
+        return Some(sequences.iter().map(ToString::to_string).collect());
+    }
+    if let Some(letter) = key.strip_prefix("ctrl-") {
+        let mut chars = letter.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_lowercase() => {
+                Some(vec![char::from(c as u8 - b'a' + 1).to_string()])
+            }
+            _ => None,
+        };
+    }
+    if let Some(rest) = key.strip_prefix("alt-") {
+        let mut chars = rest.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_graphic() && !matches!(c, '\'' | '"' | '\\') => {
+                Some(vec![format!("\x1b{c}")])
+            }
+            _ => None,
+        };
+    }
+    None
+}
+
+/// `bindkey` notation: `^[` for escape, `^X` for control characters
+fn zsh_keys(keys: &str) -> String {
+    keys.chars()
+        .map(|c| match c {
+            '\x1b' => "^[".to_string(),
+            '\x7f' => "^?".to_string(),
+            c if c.is_ascii_control() => format!("^{}", char::from(c as u8 + 64)),
+            '^' | '\\' => format!("\\{c}"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// readline notation: `\e` for escape, `\C-x` for control characters
+fn bash_keys(keys: &str) -> String {
+    keys.chars()
+        .map(|c| match c {
+            '\x1b' => "\\e".to_string(),
+            '\x7f' => "\\C-?".to_string(),
+            c if c.is_ascii_control() => format!("\\C-{}", char::from(c as u8 + 96)),
+            '"' | '\\' => format!("\\{c}"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// fish notation: `\e`, `\cx`, other punctuation backslash-escaped
+fn fish_keys(keys: &str) -> String {
+    keys.chars()
+        .map(|c| match c {
+            '\x1b' => "\\e".to_string(),
+            '\x7f' => "\\x7f".to_string(),
+            c if c.is_ascii_control() => format!("\\c{}", char::from(c as u8 + 96)),
+            c if c.is_ascii_alphanumeric() => c.to_string(),
+            c => format!("\\{c}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(chord: &str, widget: &str, shell: ShellType) -> String {
+        KeyBinding::parse(chord, widget).unwrap().render(shell)
+    }
+
+    #[test]
+    fn test_render_per_shell() {
+        assert_eq!(
+            render("ctrl-r", "history-search", ShellType::Zsh),
+            "bindkey '^R' history-incremental-search-backward  # ctrl-r\n"
+        );
+        assert_eq!(
+            render("ctrl-r", "history-search", ShellType::Bash),
+            "bind '\"\\C-r\": reverse-search-history'  # ctrl-r\n"
+        );
+        assert_eq!(
+            render("ctrl-w", "backward-kill-word", ShellType::Fish),
+            "    bind \\cw backward-kill-word  # ctrl-w\n"
+        );
+        assert_eq!(
+            render("ctrl-left", "backward-word", ShellType::Fish),
+            "    bind \\e\\[1\\;5D backward-word  # ctrl-left\n"
+        );
+        assert_eq!(
+            render("ctrl-s", "history-search-forward", ShellType::Fish),
+            "# ctrl-s: history-search-forward is not available in fish\n"
+        );
+    }
+
+    #[test]
+    fn test_chords() {
+        assert_eq!(
+            render("up", "history-prev", ShellType::Zsh),
+            "bindkey '^[[A' up-line-or-beginning-search  # up\n\
+             bindkey '^[OA' up-line-or-beginning-search  # up\n"
+        );
+        assert_eq!(
+            render("ctrl-x  ctrl-e", "edit-command-line", ShellType::Bash),
+            "bind '\"\\C-x\\C-e\": edit-and-execute-command'  # ctrl-x ctrl-e\n"
+        );
+        assert_eq!(
+            render("backspace", "backward-delete-char", ShellType::Zsh),
+            "bindkey '^?' backward-delete-char  # backspace\n"
+        );
+        assert_eq!(
+            render("alt-.", "yank", ShellType::Fish),
+            "    bind \\e\\. yank  # alt-.\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_bindings() {
+        assert!(KeyBinding::parse("ctrl-R", "yank").is_err());
+        assert!(KeyBinding::parse("hyper-x", "yank").is_err());
+        assert!(KeyBinding::parse("alt-'", "yank").is_err());
+        assert!(KeyBinding::parse("", "yank").is_err());
+        let err = KeyBinding::parse("ctrl-r", "fzf").unwrap_err();
+        assert!(err.contains("unknown widget \"fzf\""));
+        assert!(err.contains("history-search"));
+    }
+
+    #[test]
+    fn test_merge_bindings() {
+        let bindings = [
+            KeyBinding::parse("ctrl-r", "history-search-forward").unwrap(),
+            KeyBinding::parse("ctrl-s", "none").unwrap(),
+            KeyBinding::parse("ctrl-x ctrl-e", "edit-command-line").unwrap(),
+        ];
+        let merged = merge_bindings(ShellType::Zsh, &bindings);
+        let find = |chord: &str| merged.iter().find(|b| b.chord == chord);
+        assert_eq!(
+            find("ctrl-r").unwrap().widget.unwrap().name,
+            "history-search-forward"
+        );
+        assert!(find("ctrl-s").unwrap().widget.is_none());
+        assert_eq!(merged.last().unwrap().chord, "ctrl-x ctrl-e");
+        assert_eq!(merged.len(), default_bindings(ShellType::Zsh).len() + 1);
+    }
+}
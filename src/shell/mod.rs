@@ -1,14 +1,182 @@
 //! Shell integration module for pzsh
 //!
-//! Generates working shell initialization code for zsh and bash.
-//! All code is designed to be sourced directly: `eval "$(pzsh init zsh)"`
+//! Generates working shell initialization code for zsh, bash, fish,
+//! Nushell and POSIX sh. All code is designed to be sourced directly:
+//! `eval "$(pzsh compile --shell zsh)"` (or `pzsh compile --shell fish | source`
+//! from `config.fish`)
 
 // Allow raw string hashes for shell code readability
 #![allow(clippy::needless_raw_string_hashes)]
 
 use crate::ShellType;
-use crate::config::CompiledConfig;
-use crate::plugin::PluginManager;
+use crate::color::Style;
+use crate::color::themes::DefaultTheme;
+use crate::config::{CompiledConfig, EnvPart, Snapshot, env_parts, references};
+use crate::plugin::{Plugin, PluginError, PluginManager};
+use crate::prompt::{Prompt, PromptSegment, bash_escape, zsh_escape};
+use crate::theme::{Theme, ThemeRegistry};
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+mod keys;
+mod paths;
+
+pub use keys::{KeyBinding, WIDGETS, Widget};
+
+/// A section of the generated script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Env,
+    Aliases,
+    Prompt,
+    Completion,
+    Options,
+    History,
+    Keybindings,
+    Plugins,
+}
+
+impl Section {
+    /// Section name as used in reports
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Env => "env",
+            Self::Aliases => "aliases",
+            Self::Prompt => "prompt",
+            Self::Completion => "completion",
+            Self::Options => "options",
+            Self::History => "history",
+            Self::Keybindings => "keybindings",
+            Self::Plugins => "plugins",
+        }
+    }
+}
+
+/// Where a definition comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The user configuration
+    Config,
+    /// A plugin, by name
+    Plugin(String),
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Config => write!(f, "config"),
+            Self::Plugin(name) => write!(f, "plugin {name}"),
+        }
+    }
+}
+
+/// What a definition defines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Alias,
+    Env,
+}
+
+impl DefinitionKind {
+    /// Kind name as used in reports
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Alias => "alias",
+            Self::Env => "env",
+        }
+    }
+}
+
+/// A name defined with different values by more than one source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// What is defined
+    pub kind: DefinitionKind,
+    /// Variable or alias name
+    pub name: String,
+    /// Definition that ends up in the generated script
+    pub winner: Origin,
+    /// Definitions it overrides, highest precedence first
+    pub shadowed: Vec<Origin>,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let shadowed: Vec<String> = self.shadowed.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "{} {}: {} overrides {}",
+            self.kind.name(),
+            self.name,
+            self.winner,
+            shadowed.join(", ")
+        )
+    }
+}
+
+/// Definitions merged by precedence, sorted by name
+struct Merged {
+    values: Vec<(String, String)>,
+    conflicts: Vec<Conflict>,
+}
+
+/// Every definition of each name, lowest precedence first: plugins in load
+/// order, then the config
+fn definitions(
+    config: &AHashMap<String, String>,
+    plugins: Vec<(String, AHashMap<String, String>)>,
+) -> BTreeMap<String, Vec<(Origin, String)>> {
+    let mut definitions: BTreeMap<String, Vec<(Origin, String)>> = BTreeMap::new();
+    for (plugin, values) in plugins {
+        for (name, value) in values {
+            definitions
+                .entry(name)
+                .or_default()
+                .push((Origin::Plugin(plugin.clone()), value));
+        }
+    }
+    for (name, value) in config {
+        definitions
+            .entry(name.clone())
+            .or_default()
+            .push((Origin::Config, value.clone()));
+    }
+    definitions
+}
+
+impl Merged {
+    /// Keep the highest precedence definition of each name
+    fn new(kind: DefinitionKind, definitions: BTreeMap<String, Vec<(Origin, String)>>) -> Self {
+        let mut merged = Self {
+            values: Vec::with_capacity(definitions.len()),
+            conflicts: Vec::new(),
+        };
+        for (name, mut sources) in definitions {
+            let Some((winner, value)) = sources.pop() else {
+                continue;
+            };
+            let shadowed: Vec<Origin> = sources
+                .into_iter()
+                .rev()
+                .filter(|(_, other)| *other != value)
+                .map(|(origin, _)| origin)
+                .collect();
+            if !shadowed.is_empty() {
+                merged.conflicts.push(Conflict {
+                    kind,
+                    name: name.clone(),
+                    winner,
+                    shadowed,
+                });
+            }
+            merged.values.push((name, value));
+        }
+        merged
+    }
+}
 
 /// Shell integration generator
 #[derive(Debug)]
@@ -16,6 +184,7 @@ pub struct ShellIntegration {
     shell_type: ShellType,
     config: CompiledConfig,
     plugins: PluginManager,
+    plugin_errors: Vec<PluginError>,
 }
 
 impl ShellIntegration {
@@ -23,119 +192,440 @@ impl ShellIntegration {
     #[must_use]
     pub fn new(shell_type: ShellType, config: CompiledConfig) -> Self {
         let mut plugins = PluginManager::new();
+        let mut plugin_errors = Vec::new();
+
+        // Register external plugins
+        if let Some(dir) = &config.plugin_dir {
+            plugins.set_plugin_dir(dir.clone());
+            plugin_errors.extend(plugins.discover().into_iter().filter_map(Result::err));
+        }
+
+        // Load enabled plugins (and their dependencies)
+        plugin_errors.extend(
+            plugins
+                .load_all(&config.plugins_enabled)
+                .into_iter()
+                .filter_map(Result::err),
+        );
 
-        // Load enabled plugins
-        for plugin_name in &config.plugins_enabled {
-            let _ = plugins.load(plugin_name);
+        // Lazy plugins compile to stubs; those that cannot be deferred (and
+        // all of them for Nushell, which cannot define commands at runtime)
+        // load eagerly
+        for plugin_name in &config.plugins_lazy {
+            if shell_type == ShellType::Nushell || plugins.defer(plugin_name).is_err() {
+                let name = std::slice::from_ref(plugin_name);
+                plugin_errors.extend(plugins.load_all(name).into_iter().filter_map(Result::err));
+            }
         }
 
         Self {
             shell_type,
             config,
             plugins,
+            plugin_errors,
         }
     }
 
-    /// Generate complete shell initialization script
+    /// Plugin manager with the configured plugins loaded or deferred
     #[must_use]
-    pub fn generate(&self) -> String {
-        let mut output = String::with_capacity(4096);
-
-        // Header
-        output.push_str(&self.generate_header());
+    pub const fn plugins(&self) -> &PluginManager {
+        &self.plugins
+    }
 
-        // Environment variables
-        output.push_str(&self.generate_env_exports());
+    /// Target shell
+    #[must_use]
+    pub const fn shell_type(&self) -> ShellType {
+        self.shell_type
+    }
 
-        // Aliases (from config + plugins)
-        output.push_str(&self.generate_aliases());
+    /// Plugins that could not be discovered or loaded
+    #[must_use]
+    pub fn plugin_errors(&self) -> &[PluginError] {
+        &self.plugin_errors
+    }
 
-        // Prompt
-        output.push_str(&self.generate_prompt());
+    /// Generate complete shell initialization script
+    #[must_use]
+    pub fn generate(&self) -> String {
+        let mut output = String::with_capacity(4096);
+        for (_, code) in self.generate_parts() {
+            output.push_str(&code);
+        }
 
-        // Completion setup
-        output.push_str(&self.generate_completion_setup());
+        // Footer
+        output.push_str(&self.generate_footer());
 
-        // Shell options (autocd, etc.)
-        output.push_str(&self.generate_shell_options());
+        output
+    }
 
-        // History configuration
-        output.push_str(&self.generate_history_config());
+    /// The script as named parts, in order, without the footer
+    ///
+    /// Parts are the header, each section the shell can express and, under
+    /// `plugins/`, each loaded plugin's init code, plugin completions and the
+    /// lazy plugin stubs.
+    #[must_use]
+    pub fn generate_parts(&self) -> Vec<(String, String)> {
+        let mut parts = vec![
+            ("header".to_string(), self.generate_header()),
+            // Environment variables
+            (Section::Env.name().to_string(), self.generate_env_exports()),
+            // Aliases (from config + plugins)
+            (Section::Aliases.name().to_string(), self.generate_aliases()),
+        ];
+
+        // Interactive sections: prompt, completion, shell options (autocd,
+        // etc.), history and key bindings
+        let skipped = self.skipped_sections();
+        for section in [
+            Section::Prompt,
+            Section::Completion,
+            Section::Options,
+            Section::History,
+            Section::Keybindings,
+        ] {
+            if !skipped.contains(&section) {
+                parts.push((section.name().to_string(), self.generate_section(section)));
+            }
+        }
+        if !skipped.is_empty() {
+            let names: Vec<&str> = skipped.iter().map(|s| s.name()).collect();
+            parts.push((
+                "skipped".to_string(),
+                format!(
+                    "# Skipped (not expressible in {}): {}\n\n",
+                    self.shell_type.name(),
+                    names.join(", ")
+                ),
+            ));
+        }
 
-        // Key bindings
-        output.push_str(&self.generate_keybindings());
+        // Plugin init code and lazy plugin stubs
+        parts.extend(self.plugin_parts());
+        parts
+    }
 
-        // Plugin init code
-        output.push_str(&self.plugins.shell_init(self.shell_type));
+    /// Script with a timestamp marker before each non-empty part
+    ///
+    /// Each marker appends `<part> <seconds>` to `$PZSH_PROFILE_LOG`, and a
+    /// final `end` marker follows the last part. Only zsh and bash have a
+    /// timestamp that does not cost a subprocess (`$EPOCHREALTIME`), so other
+    /// shells return `None`.
+    #[must_use]
+    pub fn generate_profiled(&self) -> Option<String> {
+        let prelude = match self.shell_type {
+            ShellType::Zsh => "zmodload zsh/datetime\n",
+            ShellType::Bash => "",
+            ShellType::Fish | ShellType::Nushell | ShellType::Sh => return None,
+        };
+        let marker = |name: &str| format!("printf '%s %s\\n' '{name}' \"$EPOCHREALTIME\" >&9\n");
 
-        // Footer
+        let mut output = format!("{prelude}exec 9>>\"$PZSH_PROFILE_LOG\"\n");
+        for (name, code) in self.generate_parts() {
+            if !code.is_empty() {
+                output.push_str(&marker(&name));
+                output.push_str(&code);
+            }
+        }
+        output.push_str(&marker("end"));
+        output.push_str("exec 9>&-\n");
         output.push_str(&self.generate_footer());
+        Some(output)
+    }
 
-        output
+    /// Generate a single section of the script
+    #[must_use]
+    pub fn generate_section(&self, section: Section) -> String {
+        match section {
+            Section::Env => self.generate_env_exports(),
+            Section::Aliases => self.generate_aliases(),
+            Section::Prompt => self.generate_prompt(),
+            Section::Completion => self.generate_completion_setup(),
+            Section::Options => self.generate_shell_options(),
+            Section::History => self.generate_history_config(),
+            Section::Keybindings => self.generate_keybindings(),
+            Section::Plugins => self.generate_plugins(),
+        }
+    }
+
+    /// Sections the target shell cannot express
+    ///
+    /// Nushell and POSIX sh only receive environment, aliases and plugin
+    /// functions; the interactive setup is left to the shell itself.
+    #[must_use]
+    pub fn skipped_sections(&self) -> Vec<Section> {
+        match self.shell_type {
+            ShellType::Zsh | ShellType::Bash | ShellType::Fish => Vec::new(),
+            ShellType::Nushell | ShellType::Sh => vec![
+                Section::Prompt,
+                Section::Completion,
+                Section::Options,
+                Section::History,
+                Section::Keybindings,
+            ],
+        }
     }
 
     fn generate_header(&self) -> String {
-        let shell_name = match self.shell_type {
-            ShellType::Zsh => "zsh",
-            ShellType::Bash => "bash",
+        let shell_name = self.shell_type.name();
+        let source = match self.shell_type {
+            ShellType::Zsh | ShellType::Bash | ShellType::Sh => {
+                format!("eval \"$(pzsh compile --shell {shell_name})\"")
+            }
+            ShellType::Fish => format!("pzsh compile --shell {shell_name} | source"),
+            // Nushell can only source files that exist at parse time
+            ShellType::Nushell => "pzsh compile --cache --shell nushell\n\
+                 # then add to config.nu: source ~/.cache/pzsh/init.nu"
+                .to_string(),
         };
         format!(
             "# pzsh shell integration for {shell_name}\n\
              # Generated by pzsh v{}\n\
-             # Source with: eval \"$(pzsh init {shell_name})\"\n\n",
+             # Source with: {source}\n\n",
             env!("CARGO_PKG_VERSION")
         )
     }
 
+    /// Definitions of `kind` from config and loaded plugins
+    fn definitions(&self, kind: DefinitionKind) -> BTreeMap<String, Vec<(Origin, String)>> {
+        self.definitions_from(kind, self.plugins.loaded())
+    }
+
+    /// Definitions of `kind` from config and every configured plugin, with
+    /// lazy plugins (which define theirs on first use) after eager ones
+    fn all_definitions(&self, kind: DefinitionKind) -> BTreeMap<String, Vec<(Origin, String)>> {
+        self.definitions_from(kind, self.plugins.loaded().chain(self.plugins.deferred()))
+    }
+
+    fn definitions_from<'a>(
+        &'a self,
+        kind: DefinitionKind,
+        plugins: impl Iterator<Item = &'a dyn Plugin>,
+    ) -> BTreeMap<String, Vec<(Origin, String)>> {
+        let (config, plugins) = match kind {
+            DefinitionKind::Alias => (
+                &self.config.aliases,
+                plugins.map(|p| (p.info().name, p.aliases())).collect(),
+            ),
+            DefinitionKind::Env => (
+                &self.config.env,
+                plugins.map(|p| (p.info().name, p.env_vars())).collect(),
+            ),
+        };
+        definitions(config, plugins)
+    }
+
+    /// Every definition of an alias or env var, highest precedence first
+    ///
+    /// The config wins over plugins, and later plugins in load order win
+    /// over earlier ones; the first entry is the one in the generated script.
+    #[must_use]
+    pub fn explain(&self, kind: DefinitionKind, name: &str) -> Vec<(Origin, String)> {
+        let mut sources = self.all_definitions(kind).remove(name).unwrap_or_default();
+        sources.reverse();
+        sources
+    }
+
+    /// Definitions that override others, sorted by kind and name
+    #[must_use]
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Merged::new(
+            DefinitionKind::Alias,
+            self.all_definitions(DefinitionKind::Alias),
+        )
+        .conflicts;
+        conflicts.extend(
+            Merged::new(
+                DefinitionKind::Env,
+                self.all_definitions(DefinitionKind::Env),
+            )
+            .conflicts,
+        );
+        conflicts
+    }
+
+    /// Winning env definitions in export order: config variables follow
+    /// their dependency order, after plugin ones
+    fn exported_env(&self) -> Vec<(String, String)> {
+        let mut env =
+            Merged::new(DefinitionKind::Env, self.definitions(DefinitionKind::Env)).values;
+        let rank: AHashMap<&str, usize> = self
+            .config
+            .env_order
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect();
+        env.sort_by_key(|(name, _)| rank.get(name.as_str()).copied());
+        // GH-12: Validate env key to prevent command injection
+        env.retain(|(name, _)| is_env_name(name));
+        dependencies_first(env)
+    }
+
+    /// The configuration with plugin definitions merged in and the theme
+    /// resolved, for [`Snapshot`] files
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        let env = self.exported_env();
+        let mut config = self.config.clone();
+        config.aliases = Merged::new(
+            DefinitionKind::Alias,
+            self.definitions(DefinitionKind::Alias),
+        )
+        .values
+        .into_iter()
+        .collect();
+        config.env_order = env.iter().map(|(name, _)| name.clone()).collect();
+        config.env = env.into_iter().collect();
+        Snapshot {
+            config,
+            plugins: self.plugins.loaded().map(|p| p.info().name).collect(),
+            prompt_styles: self.prompt_styles(),
+            inputs: Vec::new(),
+            input_key: 0,
+        }
+    }
+
     fn generate_env_exports(&self) -> String {
         let mut output = String::from("# Environment variables\n");
 
-        for (key, value) in &self.config.env {
-            // GH-12: Validate env key to prevent command injection
-            if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || key.is_empty() {
-                continue;
+        for (key, value) in &self.exported_env() {
+            output.push_str(&self.env_line(key, value));
+        }
+        output.push_str(&paths::path_lines(self.shell_type, &self.config.paths));
+
+        output.push('\n');
+        output
+    }
+
+    /// Export a variable in the target shell
+    ///
+    /// `value` is a compiled template (see [`CompiledConfig::env`]); only its
+    /// `${VAR}` references expand at startup.
+    fn env_line(&self, key: &str, value: &str) -> String {
+        match self.shell_type {
+            ShellType::Zsh | ShellType::Bash | ShellType::Sh => {
+                format!("export {key}=\"{}\"\n", posix_env_value(value, false))
             }
-            match self.shell_type {
-                ShellType::Zsh | ShellType::Bash => {
-                    // Escape special characters in value
-                    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
-                    output.push_str(&format!("export {key}=\"{escaped}\"\n"));
+            ShellType::Fish => {
+                let mut defaults = Vec::new();
+                let value = fish_env_value(value, &mut defaults);
+                let mut output = defaults.concat();
+                output.push_str(&format!("set -gx {key} {value}\n"));
+                if !defaults.is_empty() {
+                    let names: Vec<String> = (0..defaults.len()).map(fish_default_var).collect();
+                    output.push_str(&format!("set -e {}\n", names.join(" ")));
                 }
+                output
             }
+            ShellType::Nushell => format!("$env.{key} = {}\n", nu_env_value(value)),
         }
+    }
 
-        output.push('\n');
+    /// Completion registrations for plugin completion entries
+    ///
+    /// Entries are `command` or `command:function`; only zsh and bash have
+    /// registrations (fish completes from man pages).
+    fn completion_lines(&self, completions: &[String]) -> String {
+        let mut output = String::new();
+        if !self.config.completion.enabled {
+            return output;
+        }
+        for entry in completions {
+            let (command, function) = match entry.split_once(':') {
+                Some((command, function)) => (command, Some(function)),
+                None => (entry.as_str(), None),
+            };
+            // GH-12: Validate names to prevent command injection
+            if !is_alias_name(command) || !function.is_none_or(is_alias_name) {
+                continue;
+            }
+            match (self.shell_type, function) {
+                (ShellType::Zsh, function) => {
+                    let function = function.unwrap_or("_gnu_generic");
+                    output.push_str(&format!("compdef {function} {command}\n"));
+                }
+                (ShellType::Bash, Some(function)) => {
+                    output.push_str(&format!("complete -F {function} {command}\n"));
+                }
+                (ShellType::Bash, None) => {
+                    // `_longopt` (from bash-completion) parses `--help`
+                    output.push_str(&format!(
+                        "declare -F _longopt >/dev/null && complete -F _longopt {command}\n"
+                    ));
+                }
+                (ShellType::Fish | ShellType::Nushell | ShellType::Sh, _) => {}
+            }
+        }
         output
     }
 
     fn generate_aliases(&self) -> String {
         let mut output = String::from("# Aliases\n");
 
-        // Config aliases
-        for (name, expansion) in &self.config.aliases {
-            // GH-12: Validate alias name to prevent command injection
-            if !name
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
-                || name.is_empty()
-            {
-                continue;
-            }
-            let escaped = expansion.replace('\'', "'\\''");
-            output.push_str(&format!("alias {name}='{escaped}'\n"));
-        }
+        // Config aliases win over plugin aliases, sorted by name
+        // GH-12: Validate alias names to prevent command injection
+        let merged = Merged::new(
+            DefinitionKind::Alias,
+            self.definitions(DefinitionKind::Alias),
+        );
+        let aliases: Vec<(&String, &String)> = merged
+            .values
+            .iter()
+            .filter(|(name, _)| is_alias_name(name))
+            .map(|(name, expansion)| (name, expansion))
+            .collect();
 
-        // Plugin aliases
-        for (name, expansion) in self.plugins.all_aliases() {
-            if !name
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
-                || name.is_empty()
-            {
-                continue;
+        match self.shell_type {
+            ShellType::Zsh | ShellType::Bash => {
+                for (name, expansion) in aliases {
+                    let escaped = expansion.replace('\'', "'\\''");
+                    output.push_str(&format!("alias {name}='{escaped}'\n"));
+                }
+            }
+            ShellType::Fish => {
+                // Abbreviations expand visibly when typed; scripts get function aliases
+                let mut abbrs = String::new();
+                let mut functions = String::new();
+                for (name, expansion) in aliases {
+                    let quoted = fish_quote(expansion);
+                    abbrs.push_str(&format!("    abbr --add -- {name} {quoted}\n"));
+                    functions.push_str(&format!("    alias {name} {quoted}\n"));
+                }
+                if !abbrs.is_empty() {
+                    output.push_str(&format!(
+                        "if status is-interactive\n{abbrs}else\n{functions}end\n"
+                    ));
+                }
+            }
+            ShellType::Nushell => {
+                let names: Vec<&str> = aliases.iter().map(|(name, _)| name.as_str()).collect();
+                for (name, expansion) in &aliases {
+                    if is_nu_command(expansion) {
+                        let body = nu_external_pipeline(expansion, &names);
+                        output.push_str(&format!("alias {name} = {body}\n"));
+                    } else {
+                        output.push_str(&format!(
+                            "# skipped alias {name}: uses POSIX shell syntax\n"
+                        ));
+                    }
+                }
+            }
+            ShellType::Sh => {
+                // POSIX aliases are not expanded in non-interactive shells
+                // and dash ignores them in functions, so emit functions
+                for (name, expansion) in aliases {
+                    if is_posix_name(name) {
+                        output.push_str(&format!(
+                            "{name}() {{ {} \"$@\"; }}\n",
+                            alias_command(name, expansion)
+                        ));
+                    } else {
+                        output.push_str(&format!(
+                            "# skipped alias {name}: not a valid POSIX function name\n"
+                        ));
+                    }
+                }
             }
-            let escaped = expansion.replace('\'', "'\\''");
-            output.push_str(&format!("alias {name}='{escaped}'\n"));
         }
 
         output.push('\n');
@@ -146,89 +636,252 @@ impl ShellIntegration {
         match self.shell_type {
             ShellType::Zsh => self.generate_zsh_prompt(),
             ShellType::Bash => self.generate_bash_prompt(),
+            ShellType::Fish => self.generate_fish_prompt(),
+            ShellType::Nushell | ShellType::Sh => String::new(),
+        }
+    }
+
+    /// Segment styles for the prompt (None when colors are disabled)
+    fn prompt_styles(&self) -> Option<PromptStyles> {
+        if !self.config.colors_enabled {
+            return None;
         }
+        let registry = ThemeRegistry::new();
+        let styles = self
+            .config
+            .prompt_theme
+            .as_deref()
+            .and_then(|name| registry.get(name))
+            .map_or_else(PromptStyles::pzsh_default, PromptStyles::from_theme);
+        Some(styles)
     }
 
     fn generate_zsh_prompt(&self) -> String {
-        let colors_enabled = self.config.colors_enabled;
+        let segments = Prompt::parse_format(&self.config.prompt_format);
+        if segments.iter().any(is_dynamic) {
+            return zsh_dynamic_prompt(&segments);
+        }
+        let styles = self.prompt_styles();
 
         let mut output = String::from("# Prompt configuration\n");
         output.push_str("setopt PROMPT_SUBST\n");
+        if styles.is_some() {
+            output.push_str("autoload -U colors && colors\n");
+        }
+        output.push('\n');
 
-        if colors_enabled {
-            output.push_str("autoload -U colors && colors\n\n");
+        let mut prompt = String::new();
+        for segment in &segments {
+            let (text, style) = match segment {
+                PromptSegment::Literal(s) => (zsh_escape_literal(s), None),
+                PromptSegment::Custom(name) => (zsh_escape_literal(&format!("{{{name}}}")), None),
+                PromptSegment::User => ("%n".to_string(), styles.as_ref().map(|st| st.user)),
+                PromptSegment::Host => ("%m".to_string(), styles.as_ref().map(|st| st.host)),
+                PromptSegment::Cwd => ("%~".to_string(), styles.as_ref().map(|st| st.cwd)),
+                // Rendered by `pzsh prompt`
+                PromptSegment::Git
+                | PromptSegment::Status
+                | PromptSegment::Duration
+                | PromptSegment::Jobs
+                | PromptSegment::Keymap => continue,
+                PromptSegment::Char => {
+                    // Root and normal user styles are chosen by zsh at render time
+                    match &styles {
+                        Some(st) => {
+                            let (root, user) = (st.prompt_root, st.prompt_char);
+                            let reset = Style {
+                                fg: root.fg.or(user.fg),
+                                bg: root.bg.or(user.bg),
+                                bold: root.bold || user.bold,
+                                underline: root.underline || user.underline,
+                                ..Style::new()
+                            };
+                            prompt.push_str(&format!(
+                                "%(!.{}.{})%#{}",
+                                root.to_zsh(),
+                                user.to_zsh(),
+                                reset.zsh_reset(),
+                            ));
+                        }
+                        None => prompt.push_str("%#"),
+                    }
+                    continue;
+                }
+            };
+            match style {
+                Some(style) => prompt.push_str(&zsh_styled(style, &text)),
+                None => prompt.push_str(&text),
+            }
+        }
 
-            // Git info function
-            output.push_str(
-                r#"# Git status (cached, fast)
-__pzsh_git_info() {
-    local branch
-    branch=$(git symbolic-ref --short HEAD 2>/dev/null) || return
-    local dirty=""
-    [[ -n $(git status --porcelain 2>/dev/null) ]] && dirty="*"
-    if [[ -n "$dirty" ]]; then
-        echo "%F{yellow}($branch$dirty)%f"
-    else
-        echo "%F{green}($branch)%f"
-    fi
-}
+        output.push_str(&format!("PROMPT='{prompt}'\n"));
+        output.push('\n');
+        output
+    }
 
-"#,
-            );
+    fn generate_bash_prompt(&self) -> String {
+        let segments = Prompt::parse_format(&self.config.prompt_format);
+        if segments.iter().any(is_dynamic) {
+            return bash_dynamic_prompt(&segments);
+        }
+        let styles = self.prompt_styles();
 
-            // Colored prompt
-            output.push_str("PROMPT='%F{green}%B%n%b%f@%F{blue}%B%m%b%f %F{cyan}%~%f $(__pzsh_git_info) %F{white}%B%#%b%f '\n");
+        let mut output = String::from("# Prompt configuration\n");
+
+        let ps1 = |root: bool| {
+            let mut prompt = String::new();
+            for segment in &segments {
+                let (text, style) = match segment {
+                    PromptSegment::Literal(s) => (bash_escape_literal(s), None),
+                    PromptSegment::Custom(name) => {
+                        (bash_escape_literal(&format!("{{{name}}}")), None)
+                    }
+                    PromptSegment::User => ("\\u".to_string(), styles.as_ref().map(|st| st.user)),
+                    PromptSegment::Host => ("\\h".to_string(), styles.as_ref().map(|st| st.host)),
+                    PromptSegment::Cwd => ("\\w".to_string(), styles.as_ref().map(|st| st.cwd)),
+                    // Rendered by `pzsh prompt`
+                    PromptSegment::Git
+                    | PromptSegment::Status
+                    | PromptSegment::Duration
+                    | PromptSegment::Jobs
+                    | PromptSegment::Keymap => continue,
+                    PromptSegment::Char => (
+                        "\\$".to_string(),
+                        styles
+                            .as_ref()
+                            .map(|st| if root { st.prompt_root } else { st.prompt_char }),
+                    ),
+                };
+                match style {
+                    Some(style) => prompt.push_str(&bash_styled(style, &text)),
+                    None => prompt.push_str(&text),
+                }
+            }
+            prompt
+        };
+
+        let (normal, root) = (ps1(false), ps1(true));
+        if normal == root {
+            output.push_str(&format!("PS1='{normal}'\n"));
         } else {
-            output.push_str("PROMPT='%n@%m %~ %# '\n");
+            // Bash has no prompt conditional for root, so pick the style at source time
+            output.push_str(&format!(
+                "if [[ $EUID -eq 0 ]]; then\n    PS1='{root}'\nelse\n    PS1='{normal}'\nfi\n"
+            ));
         }
 
         output.push('\n');
         output
     }
 
-    fn generate_bash_prompt(&self) -> String {
-        let colors_enabled = self.config.colors_enabled;
+    fn generate_fish_prompt(&self) -> String {
+        let segments = Prompt::parse_format(&self.config.prompt_format);
+        if segments.iter().any(PromptSegment::is_shell_state) {
+            return fish_dynamic_prompt(&segments);
+        }
+        let styles = self.prompt_styles();
 
         let mut output = String::from("# Prompt configuration\n");
-
-        if colors_enabled {
-            output.push_str(
+        // Full paths like zsh's %~ and bash's \w
+        output.push_str("set -g fish_prompt_pwd_dir_length 0\n\n");
+
+        if segments.iter().any(|s| matches!(s, PromptSegment::Git)) {
+            let (clean, dirty) = styles.as_ref().map_or_else(
+                || (String::new(), String::new()),
+                |st| {
+                    (
+                        fish_set_color(st.git_clean, "        "),
+                        fish_set_color(st.git_dirty, "        "),
+                    )
+                },
+            );
+            output.push_str(&format!(
                 r#"# Git status (cached, fast)
-__pzsh_git_info() {
-    local branch
-    branch=$(git symbolic-ref --short HEAD 2>/dev/null) || return
-    local dirty=""
-    [[ -n $(git status --porcelain 2>/dev/null) ]] && dirty="*"
-    if [[ -n "$dirty" ]]; then
-        echo -e "\033[33m($branch$dirty)\033[0m"
+function __pzsh_git_info
+    set -l branch (git symbolic-ref --short HEAD 2>/dev/null)
+    or return
+    set -l dirty (git status --porcelain 2>/dev/null)
+    if test -n "$dirty"
+{dirty}        printf '(%s*)' $branch
     else
-        echo -e "\033[32m($branch)\033[0m"
-    fi
-}
-
-"#,
-            );
+{clean}        printf '(%s)' $branch
+    end
+    set_color normal
+end
 
-            // Colored prompt
-            output.push_str(r"PS1='\[\033[1;32m\]\u\[\033[0m\]@\[\033[1;34m\]\h\[\033[0m\] \[\033[36m\]\w\[\033[0m\] $(__pzsh_git_info) \[\033[1;37m\]\\$\[\033[0m\] '
-");
-        } else {
-            output.push_str("PS1='\\u@\\h \\w \\$ '\n");
+"#
+            ));
         }
 
-        output.push('\n');
+        output.push_str("function fish_prompt\n");
+        for segment in &segments {
+            let (statement, style) = match segment {
+                PromptSegment::Literal(s) => (format!("printf '%s' {}", fish_quote(s)), None),
+                PromptSegment::Custom(name) => (
+                    format!("printf '%s' {}", fish_quote(&format!("{{{name}}}"))),
+                    None,
+                ),
+                PromptSegment::User => (
+                    "printf '%s' $USER".to_string(),
+                    styles.as_ref().map(|st| st.user),
+                ),
+                PromptSegment::Host => (
+                    "printf '%s' (prompt_hostname)".to_string(),
+                    styles.as_ref().map(|st| st.host),
+                ),
+                PromptSegment::Cwd => (
+                    "printf '%s' (prompt_pwd)".to_string(),
+                    styles.as_ref().map(|st| st.cwd),
+                ),
+                PromptSegment::Git => ("__pzsh_git_info".to_string(), None),
+                // Rendered by `pzsh prompt`
+                PromptSegment::Status
+                | PromptSegment::Duration
+                | PromptSegment::Jobs
+                | PromptSegment::Keymap => continue,
+                PromptSegment::Char => {
+                    let (root, user) = styles.as_ref().map_or((Style::new(), Style::new()), |st| {
+                        (st.prompt_root, st.prompt_char)
+                    });
+                    output.push_str(&format!(
+                        "    if fish_is_root_user\n{}        printf '#'\n    else\n{}        printf '>'\n    end\n",
+                        fish_set_color(root, "        "),
+                        fish_set_color(user, "        "),
+                    ));
+                    if styles.is_some() {
+                        output.push_str("    set_color normal\n");
+                    }
+                    continue;
+                }
+            };
+            let open = style
+                .map(|st| fish_set_color(st, "    "))
+                .unwrap_or_default();
+            output.push_str(&open);
+            output.push_str(&format!("    {statement}\n"));
+            if !open.is_empty() {
+                output.push_str("    set_color normal\n");
+            }
+        }
+        output.push_str("end\n\n");
         output
     }
 
     fn generate_completion_setup(&self) -> String {
+        if !self.config.completion.enabled {
+            return String::new();
+        }
         match self.shell_type {
             ShellType::Zsh => self.generate_zsh_completion(),
             ShellType::Bash => self.generate_bash_completion(),
+            ShellType::Fish => self.generate_fish_completion(),
+            ShellType::Nushell | ShellType::Sh => String::new(),
         }
     }
 
     fn generate_zsh_completion(&self) -> String {
         let colors_enabled = self.config.colors_enabled;
+        let completion = &self.config.completion;
 
         let mut output = String::from(
             r#"# Completion system - oh-my-zsh compatible
@@ -246,13 +899,21 @@ compinit -C -d "${ZDOTDIR:-$HOME}/.zcompdump"
 autoload -Uz bashcompinit && bashcompinit
 
 # Completion styling (oh-my-zsh defaults)
-zstyle ':completion:*' menu select
-zstyle ':completion:*' matcher-list 'm:{a-zA-Z}={A-Za-z}' 'r:|=*' 'l:|=* r:|=*'
-zstyle ':completion:*' special-dirs true
-zstyle ':completion:*' squeeze-slashes true
-
 "#,
         );
+        if completion.menu {
+            output.push_str("zstyle ':completion:*' menu select\n");
+        }
+        let case = if completion.case_insensitive {
+            "'m:{a-zA-Z}={A-Za-z}' "
+        } else {
+            ""
+        };
+        output.push_str(&format!(
+            "zstyle ':completion:*' matcher-list {case}'r:|=*' 'l:|=* r:|=*'\n\
+             zstyle ':completion:*' special-dirs true\n\
+             zstyle ':completion:*' squeeze-slashes true\n\n"
+        ));
 
         if colors_enabled {
             output.push_str(
@@ -278,17 +939,25 @@ zstyle ':completion:*:warnings' format '-- no matches found --'
             );
         }
 
-        output.push_str(
-            r#"zstyle ':completion:*' group-name ''
+        output.push_str("zstyle ':completion:*' group-name ''\n\n");
 
-# Fuzzy matching (typo tolerance)
+        if completion.fuzzy {
+            output.push_str(
+                r#"# Fuzzy matching (typo tolerance)
 zstyle ':completion:*' completer _complete _match _approximate
 zstyle ':completion:*:match:*' original only
 zstyle ':completion:*:approximate:*' max-errors 2 numeric
 
-# Process completion
-zstyle ':completion:*:*:kill:*' menu yes select
-zstyle ':completion:*:kill:*' force-list always
+"#,
+            );
+        }
+
+        output.push_str("# Process completion\n");
+        if completion.menu {
+            output.push_str("zstyle ':completion:*:*:kill:*' menu yes select\n");
+        }
+        output.push_str(
+            r#"zstyle ':completion:*:kill:*' force-list always
 
 # Git completion enhancements
 zstyle ':completion:*:*:git:*' script ~/.zsh/completions/_git 2>/dev/null
@@ -304,18 +973,25 @@ zstyle ':completion:*:(ssh|scp|rsync):*' hosts $(
     cat ~/.ssh/known_hosts 2>/dev/null | grep -v '^[|#]' | cut -d' ' -f1 | cut -d',' -f1 | sort -u
 )
 
-# Caching for expensive completions
+"#,
+        );
+
+        if completion.cache {
+            output.push_str(
+                r#"# Caching for expensive completions
 zstyle ':completion:*' use-cache on
 zstyle ':completion:*' cache-path "${ZDOTDIR:-$HOME}/.zcompcache"
 
 "#,
-        );
+            );
+        }
 
         output
     }
 
     fn generate_bash_completion(&self) -> String {
         let colors_enabled = self.config.colors_enabled;
+        let completion = &self.config.completion;
 
         let mut output = String::from(
             r#"# Completion system - oh-my-zsh compatible
@@ -333,9 +1009,17 @@ if type brew &>/dev/null && [[ -f "$(brew --prefix)/etc/bash_completion" ]]; the
 fi
 
 # Completion settings (oh-my-zsh style)
-bind 'set completion-ignore-case on'           # Case-insensitive
+"#,
+        );
+        if completion.case_insensitive {
+            output.push_str(
+                r#"bind 'set completion-ignore-case on'           # Case-insensitive
 bind 'set completion-map-case on'              # Treat - and _ as equivalent
-bind 'set show-all-if-ambiguous on'            # Show completions on first tab
+"#,
+            );
+        }
+        output.push_str(
+            r#"bind 'set show-all-if-ambiguous on'            # Show completions on first tab
 bind 'set show-all-if-unmodified on'           # Show completions if no partial completion
 bind 'set mark-symlinked-directories on'       # Add trailing slash to symlinked dirs
 bind 'set completion-prefix-display-length 3'  # Show common prefix length
@@ -354,15 +1038,35 @@ bind 'set visible-stats on'                    # Show file type indicators
             );
         }
 
-        output.push_str(
-            r#"# Menu completion (like zsh)
+        if completion.menu {
+            output.push_str(
+                r#"# Menu completion (like zsh)
 bind 'set menu-complete-display-prefix on'
 bind 'TAB:menu-complete'                       # Tab cycles through completions
 bind '"\e[Z": menu-complete-backward'          # Shift-Tab cycles backward
 
 "#,
-        );
+            );
+        }
+
+        output
+    }
 
+    fn generate_fish_completion(&self) -> String {
+        let mut output = String::from(
+            "# Completion system\n\
+             # fish loads completions on demand from $fish_complete_path\n",
+        );
+        if self.config.colors_enabled {
+            output.push_str(
+                r#"set -g fish_pager_color_prefix cyan --bold
+set -g fish_pager_color_completion normal
+set -g fish_pager_color_description yellow
+set -g fish_pager_color_progress brwhite --background=cyan
+"#,
+            );
+        }
+        output.push('\n');
         output
     }
 
@@ -396,6 +1100,13 @@ shopt -s extglob            # Extended pattern matching
 
 "#
             .to_string(),
+            ShellType::Fish => r#"# Shell options
+# fish has autocd, ** globbing and case-insensitive completion built in
+set -g fish_greeting ''     # No startup banner
+
+"#
+            .to_string(),
+            ShellType::Nushell | ShellType::Sh => String::new(),
         }
     }
 
@@ -420,111 +1131,687 @@ HISTSIZE=50000
 HISTFILESIZE=100000
 HISTCONTROL=ignoreboth:erasedups
 shopt -s histappend
-PROMPT_COMMAND="history -a;$PROMPT_COMMAND"
+PROMPT_COMMAND="${PROMPT_COMMAND:+$PROMPT_COMMAND;}history -a"
 
 "#
             .to_string(),
+            ShellType::Fish => r#"# History configuration
+# fish saves history incrementally; run `history merge` to pull other sessions
+
+"#
+            .to_string(),
+            ShellType::Nushell | ShellType::Sh => String::new(),
         }
     }
 
+    /// Default emacs-mode bindings with `[keybindings]` applied
     fn generate_keybindings(&self) -> String {
+        let bindings = keys::merge_bindings(self.shell_type, &self.config.keybindings);
+        let lines: String = bindings.iter().map(|b| b.render(self.shell_type)).collect();
+        let uses = |widget: &str| {
+            bindings
+                .iter()
+                .any(|b| b.widget.is_some_and(|w| w.name == widget))
+        };
         match self.shell_type {
-            ShellType::Zsh => r"# Key bindings
-bindkey -e  # Emacs mode
-
-# Up/Down arrow: search history based on what's typed (oh-my-zsh style)
-autoload -U up-line-or-beginning-search down-line-or-beginning-search
-zle -N up-line-or-beginning-search
-zle -N down-line-or-beginning-search
-bindkey '^[[A' up-line-or-beginning-search    # Up arrow
-bindkey '^[[B' down-line-or-beginning-search  # Down arrow
-bindkey '^[OA' up-line-or-beginning-search    # Up arrow (alternate)
-bindkey '^[OB' down-line-or-beginning-search  # Down arrow (alternate)
-
-# Search
-bindkey '^R' history-incremental-search-backward
-bindkey '^S' history-incremental-search-forward
-bindkey '^P' up-line-or-beginning-search      # Ctrl-P
-bindkey '^N' down-line-or-beginning-search    # Ctrl-N
-
-# Word navigation
-bindkey '^[[1;5C' forward-word   # Ctrl+Right
-bindkey '^[[1;5D' backward-word  # Ctrl+Left
-bindkey '^[f' forward-word       # Alt+f
-bindkey '^[b' backward-word      # Alt+b
-
-# Line navigation
-bindkey '^[[H' beginning-of-line  # Home
-bindkey '^[[F' end-of-line        # End
-bindkey '^A' beginning-of-line    # Ctrl+A
-bindkey '^E' end-of-line          # Ctrl+E
-
-# Editing
-bindkey '^[[3~' delete-char       # Delete
-bindkey '^?' backward-delete-char # Backspace
-bindkey '^W' backward-kill-word   # Ctrl+W
-bindkey '^U' backward-kill-line   # Ctrl+U
-bindkey '^K' kill-line            # Ctrl+K
-bindkey '^Y' yank                 # Ctrl+Y
-
-"
-            .to_string(),
-            ShellType::Bash => r#"# Key bindings
-# History search with up/down arrows (oh-my-zsh style)
-bind '"\e[A": history-search-backward'    # Up arrow
-bind '"\e[B": history-search-forward'     # Down arrow
-bind '"\eOA": history-search-backward'    # Up arrow (alternate)
-bind '"\eOB": history-search-forward'     # Down arrow (alternate)
-
-# Search
-bind '"\C-r": reverse-search-history'
-bind '"\C-s": forward-search-history'
-bind '"\C-p": history-search-backward'    # Ctrl-P
-bind '"\C-n": history-search-forward'     # Ctrl-N
-
-# Word navigation
-bind '"\e[1;5C": forward-word'            # Ctrl+Right
-bind '"\e[1;5D": backward-word'           # Ctrl+Left
-bind '"\ef": forward-word'                # Alt+f
-bind '"\eb": backward-word'               # Alt+b
-
-# Line navigation
-bind '"\e[H": beginning-of-line'          # Home
-bind '"\e[F": end-of-line'                # End
-bind '"\C-a": beginning-of-line'          # Ctrl+A
-bind '"\C-e": end-of-line'                # Ctrl+E
-
-# Editing
-bind '"\e[3~": delete-char'               # Delete
-bind '"\C-d": delete-char'                # Ctrl+D
-bind '"\C-h": backward-delete-char'       # Ctrl+H (backspace)
-bind '"\C-w": backward-kill-word'         # Ctrl+W
-bind '"\C-u": unix-line-discard'          # Ctrl+U
-bind '"\C-k": kill-line'                  # Ctrl+K
-bind '"\C-y": yank'                       # Ctrl+Y
-
-"#
-            .to_string(),
+            ShellType::Zsh => {
+                let mut output = String::from("# Key bindings\nbindkey -e  # Emacs mode\n");
+                // Search history based on what's typed (oh-my-zsh style)
+                if uses("history-prev") || uses("history-next") {
+                    output.push_str(
+                        "autoload -U up-line-or-beginning-search down-line-or-beginning-search\n\
+                         zle -N up-line-or-beginning-search\n\
+                         zle -N down-line-or-beginning-search\n",
+                    );
+                }
+                if uses("edit-command-line") {
+                    output.push_str("autoload -U edit-command-line\nzle -N edit-command-line\n");
+                }
+                format!("{output}{lines}\n")
+            }
+            ShellType::Bash => format!("# Key bindings\n{lines}\n"),
+            ShellType::Fish => format!(
+                "# Key bindings\n\
+                 if status is-interactive\n    \
+                 fish_default_key_bindings  # Emacs mode\n\
+                 {lines}end\n\n"
+            ),
+            ShellType::Nushell | ShellType::Sh => String::new(),
         }
     }
 
-    #[allow(clippy::unused_self)]
-    fn generate_footer(&self) -> String {
-        "# pzsh loaded in <10ms\n\
-             # Run 'pzsh status' to verify\n"
-            .to_string()
+    fn generate_plugins(&self) -> String {
+        self.plugin_parts()
+            .into_iter()
+            .map(|(_, code)| code)
+            .collect()
     }
-}
 
-/// Generate shell init for a specific shell type
-#[must_use]
-pub fn generate_init(shell_type: ShellType, config: CompiledConfig) -> String {
-    ShellIntegration::new(shell_type, config).generate()
-}
+    /// Eager plugin init code (one part per plugin, in load order), plugin
+    /// completions and lazy plugin stubs
+    fn plugin_parts(&self) -> Vec<(String, String)> {
+        let mut parts: Vec<(String, String)> = self
+            .plugins
+            .loaded()
+            .map(|p| {
+                (
+                    format!("plugins/{}", p.info().name),
+                    p.shell_init(self.shell_type),
+                )
+            })
+            .collect();
+        let completions: Vec<String> = self
+            .plugins
+            .loaded()
+            .flat_map(|p| p.completions())
+            .collect();
+        let completions = self.completion_lines(&completions);
+        if !completions.is_empty() {
+            parts.push((
+                "plugins/completions".to_string(),
+                format!("\n# Plugin completions\n{completions}"),
+            ));
+        }
+        parts.push(("plugins/lazy".to_string(), self.generate_lazy_plugins()));
+        parts
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Stubs for deferred plugins
+    ///
+    /// Every trigger command and plugin alias becomes a stub function. The
+    /// first stub to run calls the plugin loader, which removes all stubs and
+    /// evaluates the plugin's shell init and aliases; the stub then
+    /// re-executes its command.
+    fn generate_lazy_plugins(&self) -> String {
+        let valid_name = |name: &str| {
+            // Config aliases take precedence over plugin commands
+            !self.config.aliases.contains_key(name)
+                && if self.shell_type == ShellType::Sh {
+                    is_posix_name(name)
+                } else {
+                    is_alias_name(name)
+                }
+        };
+
+        let mut output = String::new();
+        for plugin in self.plugins.deferred() {
+            let info = plugin.info();
+            let loader = format!(
+                "__pzsh_lazy_{}",
+                info.name.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+            );
+
+            let mut aliases: Vec<(String, String)> = plugin
+                .aliases()
+                .into_iter()
+                .filter(|(name, _)| valid_name(name))
+                .collect();
+            aliases.sort_unstable();
+
+            // Stub name and the command it re-executes
+            let mut stubs: Vec<(String, String)> = info
+                .triggers
+                .iter()
+                .filter(|t| valid_name(t) && !aliases.iter().any(|(name, _)| name == *t))
+                .map(|t| (t.clone(), t.clone()))
+                .collect();
+            stubs.extend(
+                aliases
+                    .iter()
+                    .map(|(name, expansion)| (name.clone(), alias_command(name, expansion))),
+            );
+            if stubs.is_empty() {
+                continue;
+            }
+
+            let names: Vec<&str> = stubs.iter().map(|(name, _)| name.as_str()).collect();
+            let names = names.join(" ");
+            // The init runs inside the loader function
+            let mut init =
+                global_declarations(&plugin.shell_init(self.shell_type), self.shell_type);
+            let mut env: Vec<(String, String)> = plugin
+                .env_vars()
+                .into_iter()
+                .filter(|(key, _)| is_env_name(key) && !self.config.env.contains_key(key))
+                .collect();
+            env.sort_unstable();
+            for (key, value) in dependencies_first(env) {
+                init.push_str(&format!("    {}", self.env_line(&key, &value)));
+            }
+            init.push_str(&self.completion_lines(&plugin.completions()));
+            output.push_str(&format!(
+                "\n# Lazy plugin: {} (loaded on first use)\n",
+                info.name
+            ));
+            match self.shell_type {
+                ShellType::Zsh | ShellType::Bash | ShellType::Sh => {
+                    output.push_str(&format!("{loader}() {{\n    unset -f {names} {loader}\n"));
+                    output.push_str(&init);
+                    for (name, expansion) in &aliases {
+                        if self.shell_type == ShellType::Sh {
+                            output.push_str(&format!(
+                                "    {name}() {{ {} \"$@\"; }}\n",
+                                alias_command(name, expansion)
+                            ));
+                        } else {
+                            let escaped = expansion.replace('\'', "'\\''");
+                            output.push_str(&format!("    alias {name}='{escaped}'\n"));
+                        }
+                    }
+                    output.push_str("}\n");
+                    for (name, command) in &stubs {
+                        // `function name` is not subject to alias expansion
+                        let head = if self.shell_type == ShellType::Sh {
+                            format!("{name}()")
+                        } else {
+                            format!("function {name}")
+                        };
+                        output.push_str(&format!("{head} {{ {loader}; {command} \"$@\"; }}\n"));
+                    }
+                }
+                ShellType::Fish => {
+                    output.push_str(&format!(
+                        "function {loader}\n    functions -e {names} {loader}\n"
+                    ));
+                    output.push_str(&init);
+                    for (name, expansion) in &aliases {
+                        output.push_str(&format!("    alias {name} {}\n", fish_quote(expansion)));
+                    }
+                    output.push_str("end\n");
+                    for (name, command) in &stubs {
+                        output.push_str(&format!(
+                            "function {name}\n    {loader}\n    {command} $argv\nend\n"
+                        ));
+                    }
+                }
+                // Never deferred (see `new`)
+                ShellType::Nushell => {}
+            }
+        }
+        output
+    }
+
+    #[allow(clippy::unused_self)]
+    fn generate_footer(&self) -> String {
+        "# pzsh loaded in <10ms\n\
+             # Run 'pzsh status' to verify\n"
+            .to_string()
+    }
+}
+
+/// Per-segment prompt styles, taken from a [`Theme`] or the pzsh defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptStyles {
+    pub user: Style,
+    pub host: Style,
+    pub cwd: Style,
+    pub git_clean: Style,
+    pub git_dirty: Style,
+    pub prompt_char: Style,
+    pub prompt_root: Style,
+}
+
+impl PromptStyles {
+    fn from_theme(theme: &dyn Theme) -> Self {
+        Self {
+            user: theme.user_style(),
+            host: theme.host_style(),
+            cwd: theme.cwd_style(),
+            git_clean: theme.git_clean_style(),
+            git_dirty: theme.git_dirty_style(),
+            prompt_char: theme.prompt_char_style(),
+            prompt_root: theme.prompt_root_style(),
+        }
+    }
+
+    pub(crate) const fn pzsh_default() -> Self {
+        Self {
+            user: DefaultTheme::user(),
+            host: DefaultTheme::host(),
+            cwd: DefaultTheme::cwd(),
+            git_clean: DefaultTheme::git_clean(),
+            git_dirty: DefaultTheme::git_dirty(),
+            prompt_char: DefaultTheme::prompt_char(),
+            prompt_root: DefaultTheme::prompt_root(),
+        }
+    }
+}
+
+/// Wrap text in zsh prompt style escapes
+fn zsh_styled(style: Style, text: &str) -> String {
+    format!("{}{text}{}", style.to_zsh(), style.zsh_reset())
+}
+
+/// Wrap text in bash `PS1` style escapes
+fn bash_styled(style: Style, text: &str) -> String {
+    let open = style.to_bash();
+    if open.is_empty() {
+        text.to_string()
+    } else {
+        format!("{open}{text}\\[\\033[0m\\]")
+    }
+}
+
+/// Escape literal prompt text for a single-quoted zsh `PROMPT` with `PROMPT_SUBST`
+fn zsh_escape_literal(text: &str) -> String {
+    zsh_escape(text).replace('\'', "'\\''")
+}
+
+/// Escape literal prompt text for a single-quoted bash `PS1` with `promptvars`
+fn bash_escape_literal(text: &str) -> String {
+    bash_escape(text).replace('\'', "'\\''")
+}
+
+/// Segment zsh and bash leave to `pzsh prompt`: shell state and `{git}`,
+/// which would otherwise fork git from the prompt
+fn is_dynamic(segment: &PromptSegment) -> bool {
+    segment.is_shell_state() || matches!(segment, PromptSegment::Git)
+}
+
+fn shows(segments: &[PromptSegment], segment: &PromptSegment) -> bool {
+    segments
+        .iter()
+        .any(|s| std::mem::discriminant(s) == std::mem::discriminant(segment))
+}
+
+/// zsh hooks setting `PROMPT` from `pzsh prompt` before each prompt
+fn zsh_dynamic_prompt(segments: &[PromptSegment]) -> String {
+    let duration = shows(segments, &PromptSegment::Duration);
+    let mut output = String::from("# Prompt configuration (rendered by `pzsh prompt`)\n");
+    output.push_str("setopt PROMPT_SUBST\n");
+    if duration {
+        output.push_str("zmodload zsh/datetime\n");
+    }
+    output.push_str(
+        r#"
+__pzsh_prompt() {
+    local rendered
+    rendered=$(pzsh prompt --shell zsh "$@") && PROMPT=$rendered
+}
+
+"#,
+    );
+    if duration {
+        output.push_str("__pzsh_preexec() {\n    __pzsh_start=$EPOCHREALTIME\n}\n\n");
+    }
+    output.push_str("__pzsh_precmd() {\n    local code=$?\n");
+    let mut args = String::from("--exit-code $code");
+    if duration {
+        output.push_str(
+            "    integer ms=0\n    \
+             if [[ -n $__pzsh_start ]]; then\n        \
+             (( ms = (EPOCHREALTIME - __pzsh_start) * 1000 ))\n        \
+             unset __pzsh_start\n    \
+             fi\n",
+        );
+        args.push_str(" --duration-ms $ms");
+    }
+    if shows(segments, &PromptSegment::Jobs) {
+        args.push_str(" --jobs ${(%):-%j}");
+    }
+    output.push_str(&format!(
+        "    __pzsh_prompt_args=({args})\n    __pzsh_prompt \"${{__pzsh_prompt_args[@]}}\"\n}}\n\n"
+    ));
+    if shows(segments, &PromptSegment::Keymap) {
+        output.push_str(
+            r#"zle-keymap-select() {
+    __pzsh_prompt "${__pzsh_prompt_args[@]}" --keymap $KEYMAP
+    zle reset-prompt
+}
+zle -N zle-keymap-select
+
+"#,
+        );
+    }
+    output.push_str("autoload -Uz add-zsh-hook\nadd-zsh-hook precmd __pzsh_precmd\n");
+    if duration {
+        output.push_str("add-zsh-hook preexec __pzsh_preexec\n");
+    }
+    output.push('\n');
+    output
+}
+
+/// bash `PROMPT_COMMAND` setting `PS1` from `pzsh prompt`
+fn bash_dynamic_prompt(segments: &[PromptSegment]) -> String {
+    let duration = shows(segments, &PromptSegment::Duration);
+    let mut output = String::from("# Prompt configuration (rendered by `pzsh prompt`)\n");
+    output.push_str("__pzsh_prompt() {\n    local code=$? rendered\n");
+    let mut args = String::from("--exit-code \"$code\"");
+    if duration {
+        output.push_str(
+            "    local ms=0\n    \
+             if [[ -n ${__pzsh_start-} ]]; then\n        \
+             ms=$(( (${EPOCHREALTIME/[!0-9]/} - __pzsh_start) / 1000 ))\n        \
+             unset __pzsh_start\n    \
+             fi\n",
+        );
+        args.push_str(" --duration-ms \"$ms\"");
+    }
+    if shows(segments, &PromptSegment::Jobs) {
+        output.push_str("    local jobs\n    jobs=$(jobs -p | wc -l)\n");
+        args.push_str(" --jobs $jobs");
+    }
+    if shows(segments, &PromptSegment::Keymap) {
+        output.push_str("    local keymap=emacs\n    [[ :$SHELLOPTS: == *:vi:* ]] && keymap=vi\n");
+        args.push_str(" --keymap \"$keymap\"");
+    }
+    output.push_str(&format!(
+        "    rendered=$(pzsh prompt --shell bash {args}) && PS1=$rendered\n    return \"$code\"\n}}\n"
+    ));
+    if duration {
+        // PS0 is expanded when a command starts; the arithmetic records the time
+        output.push_str(
+            "[[ ${PS0-} == *__pzsh_start* ]] || \
+             PS0='${PS1:0:$((__pzsh_start=${EPOCHREALTIME/[!0-9]/}+0, 0))}'\"${PS0-}\"\n",
+        );
+    }
+    // First, so it sees the exit code of the command
+    output.push_str(
+        "[[ $PROMPT_COMMAND == *__pzsh_prompt* ]] || \
+         PROMPT_COMMAND=\"__pzsh_prompt${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"\n\n",
+    );
+    output
+}
+
+/// fish prompt function calling `pzsh prompt` for segments showing shell
+/// state
+fn fish_dynamic_prompt(segments: &[PromptSegment]) -> String {
+    let mut output = String::from("# Prompt configuration (rendered by `pzsh prompt`)\n");
+    output.push_str("function fish_prompt\n    set -l code $status\n");
+    let mut args = String::from("--exit-code $code");
+    if shows(segments, &PromptSegment::Duration) {
+        output.push_str("    set -l ms 0\n    set -q CMD_DURATION[1]; and set ms $CMD_DURATION\n");
+        args.push_str(" --duration-ms $ms");
+    }
+    if shows(segments, &PromptSegment::Jobs) {
+        args.push_str(" --jobs (count (jobs -p))");
+    }
+    if shows(segments, &PromptSegment::Keymap) {
+        output.push_str(
+            "    set -l keymap\n    \
+             test \"$fish_key_bindings\" = fish_vi_key_bindings; and set keymap --keymap $fish_bind_mode\n",
+        );
+        args.push_str(" $keymap");
+    }
+    output.push_str(&format!("    pzsh prompt {args}\nend\n\n"));
+    output
+}
+
+/// Quote a string for fish (single quotes only treat `\\` and `'` specially)
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Compiled env value inside POSIX double quotes; `in_default` also
+/// escapes the `}` that would end a `${VAR:-default}`
+fn posix_env_value(template: &str, in_default: bool) -> String {
+    let mut output = String::with_capacity(template.len());
+    for part in env_parts(template) {
+        match part {
+            EnvPart::Literal(text) => {
+                for c in text.chars() {
+                    if matches!(c, '\\' | '"' | '$' | '`') || (in_default && c == '}') {
+                        output.push('\\');
+                    }
+                    output.push(c);
+                }
+            }
+            EnvPart::Ref {
+                name,
+                default: None,
+            } => output.push_str(&format!("${{{name}}}")),
+            EnvPart::Ref {
+                name,
+                default: Some(default),
+            } => output.push_str(&format!("${{{name}:-{}}}", posix_env_value(default, true))),
+        }
+    }
+    output
+}
+
+/// Compiled env value as fish words
+///
+/// Fish has no `${VAR:-default}`: each such reference becomes a local
+/// variable set by a line pushed to `defaults`, which must run first.
+fn fish_env_value(template: &str, defaults: &mut Vec<String>) -> String {
+    let mut output = String::new();
+    for part in env_parts(template) {
+        match part {
+            EnvPart::Literal(text) => output.push_str(&fish_quote(text)),
+            EnvPart::Ref {
+                name,
+                default: None,
+            } => output.push_str(&format!("\"${name}\"")),
+            EnvPart::Ref {
+                name,
+                default: Some(default),
+            } => {
+                let fallback = fish_env_value(default, defaults);
+                let var = fish_default_var(defaults.len());
+                defaults.push(format!(
+                    "set -l {var} {fallback}; test -n \"${name}\"; and set {var} \"${name}\"\n"
+                ));
+                output.push_str(&format!("\"${var}\""));
+            }
+        }
+    }
+    if output.is_empty() {
+        output.push_str("''");
+    }
+    output
+}
+
+/// Local holding the `index`th `${VAR:-default}` of a fish value
+fn fish_default_var(index: usize) -> String {
+    format!("__pzsh_default_{index}")
+}
+
+/// Compiled env value as a Nushell expression
+///
+/// References read `$env` at startup; list values such as `$env.PATH` are
+/// joined with the path separator.
+fn nu_env_value(template: &str) -> String {
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut terms = Vec::new();
+    let mut literal = String::new();
+    for part in env_parts(template) {
+        match part {
+            EnvPart::Literal(text) => literal.push_str(text),
+            EnvPart::Ref { name, default } => {
+                if !literal.is_empty() {
+                    terms.push(quote(&std::mem::take(&mut literal)));
+                }
+                let value = format!("([$env.{name}?] | flatten | compact | str join (char esep))");
+                terms.push(match default {
+                    None => value,
+                    Some(default) => format!(
+                        "(do {{ let v = {value}; if ($v | is-empty) {{ {} }} else {{ $v }} }})",
+                        nu_env_value(default)
+                    ),
+                });
+            }
+        }
+    }
+    if !literal.is_empty() || terms.is_empty() {
+        terms.push(quote(&literal));
+    }
+    if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        format!("({})", terms.join(" + "))
+    }
+}
+
+/// Move each variable after the exported variables it references
+fn dependencies_first(env: Vec<(String, String)>) -> Vec<(String, String)> {
+    fn visit(i: usize, env: &[(String, String)], done: &mut [bool], order: &mut Vec<usize>) {
+        if done[i] {
+            return;
+        }
+        done[i] = true;
+        for name in references(&env[i].1) {
+            if let Some(j) = env.iter().position(|(n, _)| n == name) {
+                visit(j, env, done, order);
+            }
+        }
+        order.push(i);
+    }
+
+    let mut done = vec![false; env.len()];
+    let mut order = Vec::with_capacity(env.len());
+    for i in 0..env.len() {
+        visit(i, &env, &mut done, &mut order);
+    }
+    let mut slots: Vec<Option<(String, String)>> = env.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+/// Check if `name` is safe to emit as a variable name
+fn is_env_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Check if `name` is safe to emit as an alias name
+// GH-12: Validate alias names to prevent command injection
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// `init` with its top-level `declare`, `typeset` and `local` made global
+///
+/// Lazy plugin init runs inside the loader function, where these would
+/// create variables local to the loader. Declarations in functions the init
+/// defines are left alone.
+fn global_declarations(init: &str, shell: ShellType) -> String {
+    let global = match shell {
+        ShellType::Zsh => "typeset -g",
+        ShellType::Bash => "declare -g",
+        _ => return init.to_string(),
+    };
+    let mut out = init.to_string();
+    let script = crate::parser::parse_script(init);
+    for command in script.commands.iter().rev().filter(|c| !c.in_function) {
+        let Some(name) = command.name() else {
+            continue;
+        };
+        let offset = command.words[command.command.unwrap_or_default()].offset;
+        let has_global = command.args().iter().any(|arg| {
+            arg.as_literal()
+                .is_some_and(|a| a.starts_with('-') && !a.starts_with("--") && a.contains('g'))
+        });
+        if matches!(name.as_str(), "declare" | "typeset" | "local")
+            && !has_global
+            && init[offset..].starts_with(name.as_str())
+        {
+            out.replace_range(offset..offset + name.len(), global);
+        }
+    }
+    out
+}
+
+/// Alias expansion as a command for a function body
+///
+/// `ls() { ls --color "$@"; }` would call itself, so a self-referencing
+/// expansion bypasses functions with `command`.
+fn alias_command(name: &str, expansion: &str) -> String {
+    if expansion.split_whitespace().next() == Some(name) {
+        format!("command {expansion}")
+    } else {
+        expansion.to_string()
+    }
+}
+
+/// Check if `name` is a valid POSIX function name
+fn is_posix_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Check if an alias expansion parses the same way in Nushell
+///
+/// Lists, substitutions, redirections, `$VAR` expansions and leading
+/// `VAR=x` assignments are POSIX syntax that breaks `config.nu`.
+fn is_nu_command(expansion: &str) -> bool {
+    let leading_assignment = expansion
+        .split_whitespace()
+        .next()
+        .and_then(|word| word.split_once('='))
+        .is_some_and(|(name, _)| is_posix_name(name));
+    !leading_assignment
+        && !expansion.contains("||")
+        && !expansion.contains(['&', ';', '$', '`', '<', '>'])
+}
+
+/// Nushell commands with no external equivalent, left unprefixed
+const NU_INTERNALS: &[&str] = &[
+    "alias",
+    "cd",
+    "def",
+    "do",
+    "exec",
+    "exit",
+    "help",
+    "hide",
+    "history",
+    "let",
+    "source",
+    "source-env",
+    "use",
+];
+
+/// Prefix every pipeline stage with `^` so Nushell runs the external command
+///
+/// Nushell built-ins such as `ls` and `sort` take different flags than
+/// their POSIX namesakes, so `alias l = ls -CF` fails to parse. Stages
+/// starting with a Nushell-only command or another alias stay as they are.
+fn nu_external_pipeline(expansion: &str, aliases: &[&str]) -> String {
+    let mut output = String::with_capacity(expansion.len() + 4);
+    let mut quote = None;
+    let mut stage_start = true;
+    for (i, c) in expansion.char_indices() {
+        if stage_start && !c.is_whitespace() {
+            stage_start = false;
+            let command = expansion[i..]
+                .split(|c: char| c.is_whitespace() || c == '|')
+                .next()
+                .unwrap_or_default();
+            if !NU_INTERNALS.contains(&command) && !aliases.contains(&command) {
+                output.push('^');
+            }
+        }
+        match (quote, c) {
+            (Some(q), _) if q == c => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '|') => stage_start = true,
+            _ => {}
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// `set_color` line for a style at the given indent (empty when unstyled)
+fn fish_set_color(style: Style, indent: &str) -> String {
+    let command = style.to_fish();
+    if command.is_empty() {
+        command
+    } else {
+        format!("{indent}{command}\n")
+    }
+}
+
+/// Generate shell init for a specific shell type
+#[must_use]
+pub fn generate_init(shell_type: ShellType, config: CompiledConfig) -> String {
+    ShellIntegration::new(shell_type, config).generate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     fn test_config() -> CompiledConfig {
         CompiledConfig {
@@ -549,7 +1836,7 @@ mod tests {
         let output = generate_init(ShellType::Zsh, config);
 
         assert!(output.contains("pzsh shell integration for zsh"));
-        assert!(output.contains("eval \"$(pzsh init zsh)\""));
+        assert!(output.contains("# Source with: eval \"$(pzsh compile --shell zsh)\"\n"));
     }
 
     #[test]
@@ -580,12 +1867,41 @@ mod tests {
 
     #[test]
     fn test_zsh_init_sets_colored_prompt() {
-        let config = test_config();
+        let mut config = test_config();
+        config.prompt_format = "{user}@{host} {cwd} {char} ".to_string();
         let output = generate_init(ShellType::Zsh, config);
 
         assert!(output.contains("PROMPT="));
         assert!(output.contains("%F{green}"));
-        assert!(output.contains("__pzsh_git_info"));
+        assert!(!output.contains("pzsh prompt"));
+    }
+
+    #[test]
+    fn test_zsh_dynamic_prompt_uses_pzsh_prompt() {
+        let output = generate_init(ShellType::Zsh, test_config());
+
+        // {git} is rendered by `pzsh prompt`, not by forking git from the prompt
+        assert!(!output.contains("PROMPT='"));
+        assert!(!output.contains("git symbolic-ref"));
+        assert!(
+            output.contains("rendered=$(pzsh prompt --shell zsh \"$@\") && PROMPT=$rendered\n")
+        );
+        assert!(output.contains("    __pzsh_prompt_args=(--exit-code $code)\n"));
+        assert!(output.contains("add-zsh-hook precmd __pzsh_precmd\n"));
+        assert!(!output.contains("preexec"));
+        assert!(!output.contains("zle-keymap-select"));
+
+        let mut config = test_config();
+        config.prompt_format = "{cwd} {duration}{jobs}{keymap}{status}{char} ".to_string();
+        let output = generate_init(ShellType::Zsh, config);
+        assert!(output.contains("zmodload zsh/datetime\n"));
+        assert!(output.contains(
+            "    __pzsh_prompt_args=(--exit-code $code --duration-ms $ms --jobs ${(%):-%j})\n"
+        ));
+        assert!(output.contains("add-zsh-hook preexec __pzsh_preexec\n"));
+        assert!(output.contains(
+            "    __pzsh_prompt \"${__pzsh_prompt_args[@]}\" --keymap $KEYMAP\n    zle reset-prompt\n"
+        ));
     }
 
     #[test]
@@ -618,16 +1934,129 @@ mod tests {
         assert!(output.contains("'^R'"));
     }
 
+    #[test]
+    fn test_keybindings_from_config() {
+        let mut config = test_config();
+        config.keybindings = vec![
+            KeyBinding::parse("ctrl-r", "history-search-forward").unwrap(),
+            KeyBinding::parse("ctrl-s", "none").unwrap(),
+            KeyBinding::parse("ctrl-x ctrl-e", "edit-command-line").unwrap(),
+        ];
+
+        let zsh = generate_init(ShellType::Zsh, config.clone());
+        assert!(zsh.contains("bindkey '^R' history-incremental-search-forward  # ctrl-r\n"));
+        assert!(zsh.contains("bindkey -r '^S'  # ctrl-s\n"));
+        assert!(!zsh.contains("'^S' history-incremental-search-forward"));
+        assert!(zsh.contains("zle -N edit-command-line\n"));
+        assert!(zsh.contains("bindkey '^X^E' edit-command-line  # ctrl-x ctrl-e\n"));
+
+        let bash = generate_init(ShellType::Bash, config.clone());
+        assert!(bash.contains("bind '\"\\C-r\": forward-search-history'  # ctrl-r\n"));
+        assert!(bash.contains("bind '\"\\C-x\\C-e\": edit-and-execute-command'"));
+        assert!(bash.contains("bind -r '\\C-s'  # ctrl-s\n"));
+
+        let fish = generate_init(ShellType::Fish, config);
+        assert!(fish.contains("# ctrl-r: history-search-forward is not available in fish\n"));
+        assert!(fish.contains("    bind \\cx\\ce edit_command_buffer  # ctrl-x ctrl-e\n"));
+    }
+
+    #[test]
+    fn test_keybinding_none_unbinds() {
+        let mut config = test_config();
+        config.keybindings = vec![KeyBinding::parse("ctrl-p", "none").unwrap()];
+
+        let zsh = generate_init(ShellType::Zsh, config.clone());
+        assert!(zsh.contains("bindkey -r '^P'  # ctrl-p\n"));
+        assert!(!zsh.contains("bindkey '^P'"));
+
+        let bash = generate_init(ShellType::Bash, config.clone());
+        assert!(bash.contains("bind -r '\\C-p'  # ctrl-p\n"));
+        assert!(!bash.contains("bind '\"\\C-p\""));
+
+        let fish = generate_init(ShellType::Fish, config);
+        assert!(fish.contains("    bind -e \\cp  # ctrl-p\n"));
+    }
+
+    #[test]
+    fn test_completion_toggles() {
+        let mut config = test_config();
+        config.completion.case_insensitive = false;
+        config.completion.fuzzy = false;
+        config.completion.menu = false;
+        config.completion.cache = false;
+
+        let zsh = generate_init(ShellType::Zsh, config.clone());
+        assert!(zsh.contains("compinit -C"));
+        assert!(zsh.contains("matcher-list 'r:|=*'"));
+        assert!(!zsh.contains("m:{a-zA-Z}"));
+        assert!(!zsh.contains("_approximate"));
+        assert!(!zsh.contains("menu select"));
+        assert!(!zsh.contains("use-cache"));
+
+        let bash = generate_init(ShellType::Bash, config.clone());
+        assert!(bash.contains("show-all-if-ambiguous"));
+        assert!(!bash.contains("completion-ignore-case"));
+        assert!(!bash.contains("menu-complete"));
+
+        config.completion.enabled = false;
+        let zsh = generate_init(ShellType::Zsh, config);
+        assert!(!zsh.contains("compinit"));
+        assert!(!zsh.contains("zstyle ':completion"));
+    }
+
     #[test]
     fn test_zsh_init_no_colors() {
         let mut config = test_config();
         config.colors_enabled = false;
+        config.prompt_format = "{user}@{host} {cwd} {char} ".to_string();
         let output = generate_init(ShellType::Zsh, config);
 
         assert!(output.contains("PROMPT='%n@%m %~ %# '"));
         assert!(!output.contains("%F{green}"));
     }
 
+    #[test]
+    fn test_zsh_prompt_follows_format() {
+        let mut config = test_config();
+        config.colors_enabled = false;
+        config.prompt_format = "[{cwd}] {char} ".to_string();
+        let output = generate_init(ShellType::Zsh, config);
+
+        assert!(output.contains("PROMPT='[%~] %# '"));
+        // No {git} segment, no git helper
+        assert!(!output.contains("__pzsh_git_info"));
+    }
+
+    #[test]
+    fn test_zsh_prompt_uses_theme_colors() {
+        let mut config = test_config();
+        config.prompt_format = "{user}@{host} {cwd} {char} ".to_string();
+        config.prompt_theme = Some("agnoster".to_string());
+        let output = generate_init(ShellType::Zsh, config);
+
+        // Agnoster user segment: bold black on blue
+        assert!(output.contains("%B%F{black}%K{blue}%n%k%f%b"));
+    }
+
+    #[test]
+    fn test_zsh_prompt_char_root_conditional() {
+        let mut config = test_config();
+        config.prompt_format = "{cwd} {char} ".to_string();
+        let output = generate_init(ShellType::Zsh, config);
+
+        assert!(output.contains("%(!.%B%F{red}.%B%F{white})%#%f%b"));
+    }
+
+    #[test]
+    fn test_zsh_prompt_escapes_literals() {
+        let mut config = test_config();
+        config.colors_enabled = false;
+        config.prompt_format = "100% $HOME it's {custom} ".to_string();
+        let output = generate_init(ShellType::Zsh, config);
+
+        assert!(output.contains(r"PROMPT='100%% \$HOME it'\''s {custom} '"));
+    }
+
     // ==================== BASH TESTS ====================
 
     #[test]
@@ -656,12 +2085,13 @@ mod tests {
 
     #[test]
     fn test_bash_init_sets_colored_prompt() {
-        let config = test_config();
+        let mut config = test_config();
+        config.prompt_format = "{user}@{host} {cwd} {char} ".to_string();
         let output = generate_init(ShellType::Bash, config);
 
         assert!(output.contains("PS1="));
         assert!(output.contains("\\033["));
-        assert!(output.contains("__pzsh_git_info"));
+        assert!(!output.contains("pzsh prompt"));
     }
 
     #[test]
@@ -730,6 +2160,7 @@ mod tests {
     fn test_bash_init_no_colors() {
         let mut config = test_config();
         config.colors_enabled = false;
+        config.prompt_format = "{user}@{host} {cwd} {char} ".to_string();
         let output = generate_init(ShellType::Bash, config);
 
         assert!(output.contains("PS1='\\u@\\h \\w \\$ '"));
@@ -754,6 +2185,754 @@ mod tests {
         assert!(output.contains("brew --prefix") || output.contains("Homebrew"));
     }
 
+    #[test]
+    fn test_bash_prompt_follows_format() {
+        let mut config = test_config();
+        config.colors_enabled = false;
+        config.prompt_format = "[{cwd}] {char} ".to_string();
+        let output = generate_init(ShellType::Bash, config);
+
+        assert!(output.contains("PS1='[\\w] \\$ '"));
+    }
+
+    #[test]
+    fn test_bash_dynamic_prompt_uses_pzsh_prompt() {
+        let output = generate_init(ShellType::Bash, test_config());
+
+        assert!(!output.contains("PS1='"));
+        assert!(!output.contains("git symbolic-ref"));
+        assert!(output.contains(
+            "    rendered=$(pzsh prompt --shell bash --exit-code \"$code\") && PS1=$rendered\n"
+        ));
+        assert!(!output.contains("PS0"));
+        // The prompt hook runs first, while $? is the command's exit code
+        let hook = output.find("PROMPT_COMMAND=\"__pzsh_prompt").unwrap();
+        assert!(hook < output.find("history -a\"").unwrap());
+
+        let mut config = test_config();
+        config.prompt_format = "{duration}{jobs}{keymap}{char} ".to_string();
+        let output = generate_init(ShellType::Bash, config);
+        assert!(output.contains(
+            "--exit-code \"$code\" --duration-ms \"$ms\" --jobs $jobs --keymap \"$keymap\")"
+        ));
+        assert!(output.contains("PS0='${PS1:0:$((__pzsh_start=${EPOCHREALTIME/[!0-9]/}+0, 0))}'"));
+    }
+
+    #[test]
+    fn test_bash_prompt_uses_theme_colors() {
+        let mut config = test_config();
+        config.prompt_format = "{user}@{host} {cwd} {char} ".to_string();
+        config.prompt_theme = Some("simple".to_string());
+        let output = generate_init(ShellType::Bash, config);
+
+        // Simple theme: green user, blue host, cyan cwd
+        assert!(output.contains("\\[\\033[32m\\]\\u\\[\\033[0m\\]"));
+        assert!(output.contains("\\[\\033[34m\\]\\h\\[\\033[0m\\]"));
+        assert!(output.contains("\\[\\033[36m\\]\\w\\[\\033[0m\\]"));
+    }
+
+    #[test]
+    fn test_bash_prompt_root_style() {
+        let mut config = test_config();
+        config.prompt_format = "{cwd} {char} ".to_string();
+        let output = generate_init(ShellType::Bash, config);
+
+        assert!(output.contains("if [[ $EUID -eq 0 ]]; then"));
+        assert!(output.contains("\\[\\033[1;31m\\]\\$\\[\\033[0m\\]"));
+        assert!(output.contains("\\[\\033[1;37m\\]\\$\\[\\033[0m\\]"));
+    }
+
+    #[test]
+    fn test_bash_prompt_escapes_literals() {
+        let mut config = test_config();
+        config.colors_enabled = false;
+        config.prompt_format = r"$HOME \ {char} ".to_string();
+        let output = generate_init(ShellType::Bash, config);
+
+        assert!(output.contains(r"PS1='\\$HOME \\\\ \$ '"));
+    }
+
+    // ==================== FISH TESTS ====================
+
+    #[test]
+    fn test_fish_init_contains_header() {
+        let output = generate_init(ShellType::Fish, test_config());
+
+        assert!(output.contains("# pzsh shell integration for fish"));
+        assert!(output.contains("# Source with: pzsh compile --shell fish | source\n"));
+    }
+
+    #[test]
+    fn test_fish_init_exports_env_vars() {
+        let output = generate_init(ShellType::Fish, test_config());
+
+        assert!(output.contains("set -gx EDITOR 'vim'"));
+        assert!(!output.contains("export "));
+    }
+
+    #[test]
+    fn test_fish_init_defines_abbrs_and_aliases() {
+        let output = generate_init(ShellType::Fish, test_config());
+
+        assert!(output.contains("if status is-interactive\n"));
+        assert!(output.contains("    abbr --add -- ll 'ls -la'\n"));
+        assert!(output.contains("    alias ll 'ls -la'\n"));
+        // Plugin aliases too
+        assert!(output.contains("    abbr --add -- g 'git'\n"));
+    }
+
+    #[test]
+    fn test_fish_init_defines_prompt_function() {
+        let mut config = test_config();
+        config.prompt_format = "{user}@{host} {cwd} {git} {char} ".to_string();
+        config.prompt_theme = Some("simple".to_string());
+        let output = generate_init(ShellType::Fish, config);
+
+        assert!(output.contains("function fish_prompt\n"));
+        assert!(
+            output.contains("    set_color green\n    printf '%s' $USER\n    set_color normal\n")
+        );
+        assert!(output.contains("    printf '%s' (prompt_pwd)\n"));
+        assert!(output.contains("function __pzsh_git_info\n"));
+        assert!(output.contains("    __pzsh_git_info\n"));
+        assert!(output.contains("    if fish_is_root_user\n"));
+        assert!(!output.contains("PROMPT="));
+        assert!(!output.contains("PS1="));
+    }
+
+    #[test]
+    fn test_fish_prompt_no_colors() {
+        let mut config = test_config();
+        config.colors_enabled = false;
+        config.prompt_format = "{cwd} {char} ".to_string();
+        let output = generate_init(ShellType::Fish, config);
+
+        assert!(!output.contains("set_color"));
+        assert!(!output.contains("__pzsh_git_info"));
+        assert!(output.contains("        printf '>'\n"));
+    }
+
+    #[test]
+    fn test_fish_prompt_with_shell_state_uses_pzsh_prompt() {
+        let mut config = test_config();
+        config.prompt_format = "{cwd} {status}{duration}{char} ".to_string();
+        let output = generate_init(ShellType::Fish, config);
+
+        assert!(output.contains("function fish_prompt\n    set -l code $status\n"));
+        assert!(output.contains("    pzsh prompt --exit-code $code --duration-ms $ms\nend\n"));
+        assert!(!output.contains("prompt_pwd"));
+    }
+
+    #[test]
+    fn test_fish_init_sets_keybindings() {
+        let output = generate_init(ShellType::Fish, test_config());
+
+        assert!(output.contains("fish_default_key_bindings"));
+        assert!(output.contains("bind \\cw backward-kill-word"));
+        assert!(!output.contains("bindkey"));
+    }
+
+    #[test]
+    fn test_fish_init_includes_plugin_init() {
+        let output = generate_init(ShellType::Fish, test_config());
+
+        assert!(output.contains("function __pzsh_git_branch"));
+        assert!(!output.contains("vcs_info"));
+    }
+
+    #[test]
+    fn test_fish_init_has_no_posix_syntax() {
+        let mut config = test_config();
+        config.prompt_format = "{user} {git} {char} ".to_string();
+        let output = generate_init(ShellType::Fish, config);
+
+        for bashism in [
+            "[[", "$(", "fi\n", "done\n", "() {", "export ", "setopt", "shopt",
+        ] {
+            assert!(
+                !output.contains(bashism),
+                "fish output contains {bashism:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fish_quote() {
+        assert_eq!(fish_quote("plain"), "'plain'");
+        assert_eq!(fish_quote(r"it's a\b"), r"'it\'s a\\b'");
+        assert_eq!(fish_quote("$HOME (cmd)"), "'$HOME (cmd)'");
+    }
+
+    // ==================== NUSHELL TESTS ====================
+
+    #[test]
+    fn test_nushell_init_contains_header() {
+        let output = generate_init(ShellType::Nushell, test_config());
+
+        assert!(output.contains("# pzsh shell integration for nushell"));
+        assert!(output.contains("pzsh compile --cache --shell nushell"));
+        assert!(output.contains("source ~/.cache/pzsh/init.nu"));
+    }
+
+    #[test]
+    fn test_nushell_init_sets_env_and_aliases() {
+        let mut config = test_config();
+        config
+            .env
+            .insert("GREETING".to_string(), r#"say "hi" \o/"#.to_string());
+        let output = generate_init(ShellType::Nushell, config);
+
+        assert!(output.contains("$env.EDITOR = \"vim\"\n"));
+        assert!(output.contains(r#"$env.GREETING = "say \"hi\" \\o/""#));
+        assert!(output.contains("alias ll = ^ls -la\n"));
+        assert!(!output.contains("export "));
+    }
+
+    #[test]
+    fn test_nushell_init_skips_posix_aliases() {
+        let mut config = test_config();
+        for (name, expansion) in [
+            ("up", "git pull && git push"),
+            ("here", "cd $(pwd)"),
+            ("lsc", "LC_ALL=C ls"),
+            ("quiet", "make 2>/dev/null"),
+            ("lg", "git log | head"),
+            ("either", "true || false"),
+        ] {
+            config
+                .aliases
+                .insert(name.to_string(), expansion.to_string());
+        }
+        let output = generate_init(ShellType::Nushell, config);
+
+        assert!(output.contains("alias lg = ^git log | ^head\n"));
+        for name in ["up", "here", "lsc", "quiet", "either"] {
+            assert!(output.contains(&format!(
+                "# skipped alias {name}: uses POSIX shell syntax\n"
+            )));
+            assert!(!output.contains(&format!("alias {name} =")));
+        }
+    }
+
+    #[test]
+    fn test_nushell_init_calls_external_commands() {
+        let mut config =
+            CompiledConfig::from_toml(&crate::cli::generate_init_config("nushell")).unwrap();
+        for (name, expansion) in [
+            ("up", "cd .."),
+            ("lal", "ll -h"),
+            ("pick", "grep 'a|b' | sort -u"),
+        ] {
+            config
+                .aliases
+                .insert(name.to_string(), expansion.to_string());
+        }
+        let output = generate_init(ShellType::Nushell, config);
+
+        assert!(output.contains("alias l = ^ls -CF\n"));
+        assert!(output.contains("alias gs = ^git status\n"));
+        assert!(output.contains("alias up = cd ..\n"));
+        assert!(output.contains("alias lal = ll -h\n"));
+        assert!(output.contains("alias pick = ^grep 'a|b' | ^sort -u\n"));
+        assert!(!output.contains("= ls "));
+    }
+
+    #[test]
+    fn test_nushell_init_defines_plugin_commands() {
+        let output = generate_init(ShellType::Nushell, test_config());
+
+        assert!(output.contains("def __pzsh_git_branch [] {"));
+    }
+
+    #[test]
+    fn test_nushell_init_reports_skipped_sections() {
+        let output = generate_init(ShellType::Nushell, test_config());
+
+        assert!(output.contains(
+            "# Skipped (not expressible in nushell): prompt, completion, options, history, keybindings"
+        ));
+        assert!(!output.contains("PROMPT="));
+        assert!(!output.contains("bindkey"));
+    }
+
+    // ==================== POSIX SH TESTS ====================
+
+    #[test]
+    fn test_sh_init_exports_env_vars() {
+        let output = generate_init(ShellType::Sh, test_config());
+
+        assert!(output.contains("# pzsh shell integration for sh"));
+        assert!(output.contains("export EDITOR=\"vim\"\n"));
+    }
+
+    #[test]
+    fn test_sh_init_uses_function_aliases() {
+        let mut config = test_config();
+        config
+            .aliases
+            .insert("ls".to_string(), "ls --color=auto".to_string());
+        config
+            .aliases
+            .insert("g.st".to_string(), "git status".to_string());
+        let output = generate_init(ShellType::Sh, config);
+
+        assert!(output.contains("ll() { ls -la \"$@\"; }\n"));
+        assert!(output.contains("ls() { command ls --color=auto \"$@\"; }\n"));
+        assert!(output.contains("# skipped alias g.st: not a valid POSIX function name\n"));
+        assert!(!output.contains("\nalias "));
+    }
+
+    #[test]
+    fn test_sh_init_skips_interactive_sections() {
+        let integration = ShellIntegration::new(ShellType::Sh, test_config());
+        let output = integration.generate();
+
+        assert_eq!(
+            integration.skipped_sections(),
+            vec![
+                Section::Prompt,
+                Section::Completion,
+                Section::Options,
+                Section::History,
+                Section::Keybindings,
+            ]
+        );
+        assert!(output.contains("# Skipped (not expressible in sh): prompt"));
+        assert!(output.contains("__pzsh_git_branch() {"));
+    }
+
+    #[test]
+    fn test_sh_init_has_no_bashisms() {
+        let mut config = test_config();
+        config.prompt_format = "{user} {git} {char} ".to_string();
+        let output = generate_init(ShellType::Sh, config);
+
+        for bashism in [
+            "[[",
+            "function ",
+            "local ",
+            "setopt",
+            "shopt",
+            "bindkey",
+            "bind ",
+            "PS1=",
+            "PROMPT=",
+            "source ",
+            "autoload",
+        ] {
+            assert!(!output.contains(bashism), "sh output contains {bashism:?}");
+        }
+    }
+
+    #[test]
+    fn test_interactive_shells_skip_nothing() {
+        for shell in [ShellType::Zsh, ShellType::Bash, ShellType::Fish] {
+            let integration = ShellIntegration::new(shell, test_config());
+            assert!(integration.skipped_sections().is_empty());
+            assert!(!integration.generate().contains("# Skipped"));
+        }
+    }
+
+    #[test]
+    fn test_is_posix_name() {
+        assert!(is_posix_name("ll"));
+        assert!(is_posix_name("_g2"));
+        assert!(!is_posix_name("2g"));
+        assert!(!is_posix_name("g.st"));
+        assert!(!is_posix_name("g-st"));
+    }
+
+    // ==================== LAZY PLUGIN TESTS ====================
+
+    fn lazy_config() -> CompiledConfig {
+        CompiledConfig {
+            aliases: std::iter::once(("dps".to_string(), "echo mine".to_string())).collect(),
+            plugins_lazy: vec!["docker".to_string()],
+            ..CompiledConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_lazy_plugin_compiles_to_stubs() {
+        let output = generate_init(ShellType::Zsh, lazy_config());
+
+        assert!(output.contains("# Lazy plugin: docker (loaded on first use)\n"));
+        assert!(output.contains("__pzsh_lazy_docker() {\n    unset -f docker d dc "));
+        assert!(output.contains("    alias di='docker images'\n"));
+        assert!(output.contains("function docker { __pzsh_lazy_docker; docker \"$@\"; }\n"));
+        assert!(output.contains("function di { __pzsh_lazy_docker; docker images \"$@\"; }\n"));
+        // Not part of the eager aliases
+        assert!(!output.contains("\nalias d='docker'\n"));
+    }
+
+    #[test]
+    fn test_lazy_plugin_respects_config_aliases() {
+        let output = generate_init(ShellType::Bash, lazy_config());
+
+        assert!(output.contains("alias dps='echo mine'\n"));
+        assert!(!output.contains("function dps"));
+        assert!(!output.contains("alias dps='docker ps'"));
+    }
+
+    #[test]
+    fn test_lazy_plugin_enabled_wins() {
+        let mut config = lazy_config();
+        config.plugins_enabled = vec!["docker".to_string()];
+        let output = generate_init(ShellType::Zsh, config);
+
+        assert!(output.contains("alias d='docker'\n"));
+        assert!(!output.contains("__pzsh_lazy_docker"));
+    }
+
+    #[test]
+    fn test_lazy_plugin_fish_stubs() {
+        let output = generate_init(ShellType::Fish, lazy_config());
+
+        assert!(output.contains("function __pzsh_lazy_docker\n    functions -e docker d "));
+        assert!(output.contains("    alias di 'docker images'\n"));
+        assert!(
+            output.contains("function di\n    __pzsh_lazy_docker\n    docker images $argv\nend\n")
+        );
+    }
+
+    #[test]
+    fn test_lazy_plugin_sh_stubs() {
+        let output = generate_init(ShellType::Sh, lazy_config());
+
+        assert!(output.contains("    di() { docker images \"$@\"; }\n"));
+        assert!(output.contains("docker() { __pzsh_lazy_docker; docker \"$@\"; }\n"));
+        assert!(!output.contains("function "));
+    }
+
+    #[test]
+    fn test_lazy_plugin_nushell_loads_eagerly() {
+        let output = generate_init(ShellType::Nushell, lazy_config());
+
+        assert!(output.contains("alias di = ^docker images\n"));
+        assert!(!output.contains("__pzsh_lazy_docker"));
+    }
+
+    #[test]
+    fn test_alias_command() {
+        assert_eq!(alias_command("ll", "ls -la"), "ls -la");
+        assert_eq!(alias_command("ls", "ls --color"), "command ls --color");
+    }
+
+    // ==================== EXTERNAL PLUGIN TESTS ====================
+
+    #[test]
+    fn test_external_plugin_from_plugin_dir() {
+        let dir = crate::test_util::temp_dir("shell-plugins");
+        std::fs::create_dir_all(dir.join("mytool")).unwrap();
+        std::fs::write(
+            dir.join("mytool/plugin.toml"),
+            "name = \"mytool\"\n[aliases]\nmt = \"mytool run\"\n[init]\nbash = \"complete -o default mytool\"\n",
+        )
+        .unwrap();
+
+        let config = CompiledConfig {
+            plugins_enabled: vec!["mytool".to_string(), "missing".to_string()],
+            plugin_dir: Some(dir.clone()),
+            ..CompiledConfig::default()
+        };
+        let integration = ShellIntegration::new(ShellType::Bash, config);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let output = integration.generate();
+
+        assert!(output.contains("alias mt='mytool run'\n"));
+        assert!(output.contains("# pzsh mytool plugin\ncomplete -o default mytool\n"));
+        assert_eq!(integration.plugin_errors().len(), 1);
+        assert!(matches!(
+            &integration.plugin_errors()[0],
+            PluginError::NotFound(name) if name == "missing"
+        ));
+    }
+
+    // ==================== PLUGIN ENV AND COMPLETION TESTS ====================
+
+    /// Integration with external plugins written from `(name, manifest)`
+    fn with_plugins(
+        shell: ShellType,
+        mut config: CompiledConfig,
+        manifests: &[(&str, &str)],
+    ) -> ShellIntegration {
+        let dir = crate::test_util::temp_dir(&format!("shell-{}", manifests[0].0));
+        for (name, manifest) in manifests {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            std::fs::write(dir.join(name).join("plugin.toml"), manifest).unwrap();
+        }
+        config.plugin_dir = Some(dir.clone());
+        let integration = ShellIntegration::new(shell, config);
+        std::fs::remove_dir_all(&dir).unwrap();
+        integration
+    }
+
+    #[test]
+    fn test_plugin_env_config_precedence() {
+        let mut config = test_config();
+        config.plugins_enabled = vec!["envone".to_string(), "envtwo".to_string()];
+        let integration = with_plugins(
+            ShellType::Zsh,
+            config,
+            &[
+                (
+                    "envone",
+                    "name = \"envone\"\n[env]\nEDITOR = \"nano\"\nPAGER = \"less\"\nSAME = \"x\"\n",
+                ),
+                (
+                    "envtwo",
+                    "name = \"envtwo\"\n[env]\nPAGER = \"most\"\nSAME = \"x\"\n",
+                ),
+            ],
+        );
+        let output = integration.generate();
+
+        assert!(output.contains("export EDITOR=\"vim\"\n"));
+        assert!(!output.contains("nano"));
+        // Later plugins win over earlier ones
+        assert!(output.contains("export PAGER=\"most\"\n"));
+        assert!(output.contains("export SAME=\"x\"\n"));
+        // Sorted by name
+        assert!(output.find("EDITOR").unwrap() < output.find("PAGER").unwrap());
+
+        let conflicts = integration.conflicts();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "env EDITOR: config overrides plugin envone"
+        );
+        assert_eq!(
+            conflicts[1],
+            Conflict {
+                kind: DefinitionKind::Env,
+                name: "PAGER".to_string(),
+                winner: Origin::Plugin("envtwo".to_string()),
+                shadowed: vec![Origin::Plugin("envone".to_string())],
+            }
+        );
+    }
+
+    #[test]
+    fn test_plugin_completions_zsh_and_bash() {
+        let manifest = "name = \"comp\"\ncompletions = [\"mytool\", \"other:_other\"]\n";
+        let config = CompiledConfig {
+            plugins_enabled: vec!["comp".to_string()],
+            ..CompiledConfig::default()
+        };
+
+        let zsh = with_plugins(ShellType::Zsh, config.clone(), &[("comp", manifest)]).generate();
+        assert!(
+            zsh.contains(
+                "# Plugin completions\ncompdef _gnu_generic mytool\ncompdef _other other\n"
+            )
+        );
+
+        let bash = with_plugins(ShellType::Bash, config.clone(), &[("comp", manifest)]).generate();
+        assert!(bash.contains("complete -F _longopt mytool\n"));
+        assert!(bash.contains("complete -F _other other\n"));
+
+        let fish = with_plugins(ShellType::Fish, config, &[("comp", manifest)]).generate();
+        assert!(!fish.contains("Plugin completions"));
+    }
+
+    #[test]
+    fn test_completion_disabled_skips_plugin_completions() {
+        let manifest = "name = \"comp\"\ncompletions = [\"mytool\"]\n";
+        let mut config = CompiledConfig {
+            plugins_enabled: vec!["comp".to_string()],
+            ..CompiledConfig::default()
+        };
+        config.completion.enabled = false;
+        let zsh = with_plugins(ShellType::Zsh, config, &[("comp", manifest)]).generate();
+        assert!(!zsh.contains("compinit"));
+        assert!(!zsh.contains("compdef"));
+    }
+
+    #[test]
+    fn test_lazy_plugin_loader_sets_env_and_completions() {
+        let manifest = "name = \"lazytool\"\ntriggers = [\"lazytool\"]\ncompletions = [\"lazytool\"]\n[env]\nLAZY_HOME = \"/opt/lazy\"\nEDITOR = \"nano\"\n";
+        let mut config = test_config();
+        config.plugins_lazy = vec!["lazytool".to_string()];
+        let output = with_plugins(ShellType::Zsh, config, &[("lazytool", manifest)]).generate();
+
+        let loader = &output[output.find("__pzsh_lazy_lazytool() {").unwrap()..];
+        assert!(loader.contains("    export LAZY_HOME=\"/opt/lazy\"\n"));
+        assert!(loader.contains("compdef _gnu_generic lazytool\n"));
+        assert!(!loader.contains("nano"));
+    }
+
+    #[test]
+    fn test_lazy_plugin_declarations_stay_global() {
+        let manifest = r#"name = "maptool"
+triggers = ["maptool"]
+[init]
+zsh = "typeset -A MAPTOOL_SEEN"
+bash = '''
+declare -A MAPTOOL_SEEN=([init]=1)
+typeset -g MAPTOOL_G=1
+maptool() { local n=2; MAPTOOL_SEEN[run]=$n; }
+'''
+"#;
+        let mut config = test_config();
+        config.plugins_lazy = vec!["maptool".to_string()];
+
+        let zsh = with_plugins(ShellType::Zsh, config.clone(), &[("maptool", manifest)]);
+        assert!(zsh.generate().contains("typeset -g -A MAPTOOL_SEEN\n"));
+
+        let bash = with_plugins(ShellType::Bash, config, &[("maptool", manifest)]).generate();
+        assert!(bash.contains("declare -g -A MAPTOOL_SEEN=([init]=1)\n"));
+        assert!(bash.contains("typeset -g MAPTOOL_G=1\n"));
+        assert!(bash.contains("local n=2;"));
+
+        // The stub loads the plugin; the array must outlive the loader
+        let ran = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(format!(
+                "{bash}\nmaptool\necho \"${{MAPTOOL_SEEN[init]}} ${{MAPTOOL_SEEN[run]}}\""
+            ))
+            .output();
+        if let Ok(ran) = ran {
+            assert_eq!(String::from_utf8_lossy(&ran.stdout).trim_end(), "1 2");
+        }
+    }
+
+    // ==================== ALIAS CONFLICT TESTS ====================
+
+    #[test]
+    fn test_config_alias_overrides_plugin_alias() {
+        let mut config = test_config();
+        config
+            .aliases
+            .insert("gp".to_string(), "git push --force-with-lease".to_string());
+        let integration = ShellIntegration::new(ShellType::Zsh, config);
+        let output = integration.generate();
+
+        assert!(output.contains("alias gp='git push --force-with-lease'\n"));
+        assert!(!output.contains("alias gp='git push'\n"));
+        // Same expansion is not a conflict
+        assert_eq!(output.matches("alias gs=").count(), 1);
+
+        let conflicts = integration.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "alias gp: config overrides plugin git"
+        );
+    }
+
+    #[test]
+    fn test_aliases_sorted_and_deterministic() {
+        let mut config = test_config();
+        config.plugins_enabled = vec!["git".to_string(), "docker".to_string()];
+        let output = generate_init(ShellType::Bash, config.clone());
+
+        let names: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.strip_prefix("alias "))
+            .map(|line| line.split('=').next().unwrap())
+            .collect();
+        let mut sorted = names.clone();
+        sorted.sort_unstable();
+        assert_eq!(names, sorted);
+        assert!(names.contains(&"dps") && names.contains(&"ll"));
+
+        for _ in 0..5 {
+            assert_eq!(generate_init(ShellType::Bash, config.clone()), output);
+        }
+    }
+
+    #[test]
+    fn test_explain_alias() {
+        let mut config = test_config();
+        config
+            .aliases
+            .insert("gp".to_string(), "git push --force-with-lease".to_string());
+        let integration = ShellIntegration::new(ShellType::Zsh, config);
+
+        assert_eq!(
+            integration.explain(DefinitionKind::Alias, "gp"),
+            vec![
+                (Origin::Config, "git push --force-with-lease".to_string()),
+                (Origin::Plugin("git".to_string()), "git push".to_string()),
+            ]
+        );
+        assert_eq!(
+            integration.explain(DefinitionKind::Env, "EDITOR"),
+            vec![(Origin::Config, "vim".to_string())]
+        );
+        assert!(
+            integration
+                .explain(DefinitionKind::Alias, "nope")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_explain_includes_lazy_plugins() {
+        let integration = ShellIntegration::new(ShellType::Zsh, lazy_config());
+
+        assert_eq!(
+            integration.explain(DefinitionKind::Alias, "dps"),
+            vec![
+                (Origin::Config, "echo mine".to_string()),
+                (
+                    Origin::Plugin("docker".to_string()),
+                    "docker ps".to_string()
+                ),
+            ]
+        );
+        assert!(integration.conflicts().iter().any(|c| c.name == "dps"
+            && c.winner == Origin::Config
+            && c.shadowed == [Origin::Plugin("docker".to_string())]));
+    }
+
+    // ==================== PROFILE TESTS ====================
+
+    #[test]
+    fn test_generate_parts_concatenate_to_script() {
+        let integration = ShellIntegration::new(ShellType::Zsh, test_config());
+        let parts = integration.generate_parts();
+        let names: Vec<&str> = parts.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "header",
+                "env",
+                "aliases",
+                "prompt",
+                "completion",
+                "options",
+                "history",
+                "keybindings",
+                "plugins/git",
+                "plugins/lazy"
+            ]
+        );
+
+        let mut script: String = parts.into_iter().map(|(_, code)| code).collect();
+        script.push_str(&integration.generate_footer());
+        assert_eq!(script, integration.generate());
+    }
+
+    #[test]
+    fn test_generate_profiled_markers() {
+        let integration = ShellIntegration::new(ShellType::Zsh, test_config());
+        let output = integration.generate_profiled().unwrap();
+        assert!(output.starts_with("zmodload zsh/datetime\nexec 9>>\"$PZSH_PROFILE_LOG\"\n"));
+        assert!(output.contains("printf '%s %s\\n' 'env' \"$EPOCHREALTIME\" >&9\n"));
+        assert!(output.contains("'plugins/git'"));
+        // Empty parts get no marker
+        assert!(!output.contains("'plugins/lazy'"));
+        assert!(output.contains("'end' \"$EPOCHREALTIME\" >&9\nexec 9>&-\n"));
+
+        let bash = ShellIntegration::new(ShellType::Bash, test_config());
+        assert!(bash.generate_profiled().unwrap().starts_with("exec 9>>"));
+        for shell in [ShellType::Fish, ShellType::Nushell, ShellType::Sh] {
+            let integration = ShellIntegration::new(shell, test_config());
+            assert!(integration.generate_profiled().is_none());
+        }
+    }
+
     // ==================== ESCAPE HANDLING TESTS ====================
 
     #[test]
@@ -780,6 +2959,75 @@ mod tests {
         assert!(output.contains("export MSG=\"say \\\"hello\\\"\""));
     }
 
+    #[test]
+    fn test_env_exported_in_dependency_order_as_literals() {
+        let config = CompiledConfig::from_toml(
+            "[env]\nGOBIN = \"${GOPATH}/bin\"\nGOPATH = \"/opt/go\"\nPRICE = \"$$5\"\n",
+        )
+        .unwrap();
+
+        let output = generate_init(ShellType::Bash, config);
+
+        let gopath = output.find("export GOPATH=\"/opt/go\"").unwrap();
+        let gobin = output.find("export GOBIN=\"/opt/go/bin\"").unwrap();
+        assert!(gopath < gobin);
+        assert!(output.contains("export PRICE=\"\\$5\"\n"));
+    }
+
+    #[test]
+    fn test_env_self_reference_expands_at_startup() {
+        let config =
+            CompiledConfig::from_toml("[env]\nPATH = \"/opt/x/bin:${PATH}\"\nPRICE = \"$$5\"\n")
+                .unwrap();
+
+        let bash = generate_init(ShellType::Bash, config.clone());
+        assert!(bash.contains("export PATH=\"/opt/x/bin:${PATH}\"\n"));
+        assert!(bash.contains("export PRICE=\"\\$5\"\n"));
+
+        let fish = generate_init(ShellType::Fish, config.clone());
+        assert!(fish.contains("set -gx PATH '/opt/x/bin:'\"$PATH\"\n"));
+
+        let nu = generate_init(ShellType::Nushell, config);
+        assert!(nu.contains(
+            "$env.PATH = (\"/opt/x/bin:\" + ([$env.PATH?] | flatten | compact | str join (char esep)))\n"
+        ));
+        assert!(nu.contains("$env.PRICE = \"$5\"\n"));
+    }
+
+    #[test]
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn test_env_defaults_fall_back_at_startup() {
+        let config = CompiledConfig::from_toml(
+            "[env]\nA = \"${HOME:-x}/a\"\nB = \"${PATH}:${PZSH_UNSET:-${HOME:-y}}\"\n",
+        )
+        .unwrap();
+        let home = std::env::var("HOME").unwrap();
+        let path = std::env::var("PATH").unwrap();
+
+        let fish = generate_init(ShellType::Fish, config.clone());
+        assert!(fish.contains(
+            "set -l __pzsh_default_0 'x'; test -n \"$HOME\"; and set __pzsh_default_0 \"$HOME\"\n\
+             set -gx A \"$__pzsh_default_0\"'/a'\n\
+             set -e __pzsh_default_0\n"
+        ));
+        assert!(fish.contains(
+            "set -l __pzsh_default_1 \"$__pzsh_default_0\"; test -n \"$PZSH_UNSET\"; and set __pzsh_default_1 \"$PZSH_UNSET\"\n\
+             set -gx B \"$PATH\"':'\"$__pzsh_default_1\"\n"
+        ));
+
+        let nu = generate_init(ShellType::Nushell, config);
+        assert!(nu.contains(
+            "$env.A = ((do { let v = ([$env.HOME?] | flatten | compact | str join (char esep)); \
+             if ($v | is-empty) { \"x\" } else { $v } }) + \"/a\")\n"
+        ));
+
+        // Nothing from the compiling environment is baked in
+        for output in [fish, nu] {
+            assert!(!output.contains(&home), "{output}");
+            assert!(!output.contains(&path), "{output}");
+        }
+    }
+
     // ==================== PERFORMANCE TESTS ====================
 
     #[test]
@@ -0,0 +1,292 @@
+//! Search path updates for `[path]`
+//!
+//! Every shell gets the same semantics: prepended directories move to the
+//! front and appended ones are added at the end unless already present, so
+//! a configured directory appears once. An unset `MANPATH` keeps an empty
+//! entry so `man` still searches its default directories.
+
+use super::fish_quote;
+use crate::ShellType;
+use crate::config::{PathVar, SearchPath};
+use std::path::Path;
+
+/// POSIX helper: `__pzsh_path_add VAR prepend|append DIR`
+const POSIX_HELPER: &str = r#"__pzsh_path_add() {
+    eval "__pzsh_old=\${$1-}"
+    case ":$__pzsh_old:" in
+        *":$3:"*) [ "$2" = append ] && return 0 ;;
+    esac
+    __pzsh_rest="$__pzsh_old:"
+    __pzsh_new=
+    while [ -n "$__pzsh_rest" ]; do
+        __pzsh_dir=${__pzsh_rest%%:*}
+        __pzsh_rest=${__pzsh_rest#*:}
+        [ "$__pzsh_dir" = "$3" ] || __pzsh_new="$__pzsh_new:$__pzsh_dir"
+    done
+    __pzsh_new=${__pzsh_new#:}
+    if [ "$2" = prepend ]; then
+        __pzsh_new="$3${__pzsh_new:+:$__pzsh_new}"
+    else
+        __pzsh_new="${__pzsh_new:+$__pzsh_new:}$3"
+    fi
+    eval "export $1=\"\$__pzsh_new\""
+    unset __pzsh_old __pzsh_rest __pzsh_new __pzsh_dir
+}
+"#;
+
+/// Lines updating the search paths in `shell`
+pub(super) fn path_lines(shell: ShellType, paths: &[SearchPath]) -> String {
+    // fpath only exists in zsh
+    let paths: Vec<&SearchPath> = paths
+        .iter()
+        .filter(|p| p.var != PathVar::Fpath || shell == ShellType::Zsh)
+        .filter(|p| !p.prepend.is_empty() || !p.append.is_empty())
+        .collect();
+    if paths.is_empty() {
+        return String::new();
+    }
+    let mut output = String::from("# Search paths\n");
+    match shell {
+        ShellType::Zsh => {
+            output.push_str("typeset -gU path fpath manpath\n");
+            for path in paths {
+                output.push_str(&zsh_lines(path));
+            }
+        }
+        ShellType::Bash | ShellType::Sh => {
+            output.push_str(POSIX_HELPER);
+            for path in paths {
+                output.push_str(&posix_lines(path));
+            }
+            output.push_str("unset -f __pzsh_path_add\n");
+        }
+        ShellType::Fish => {
+            for path in paths {
+                output.push_str(&fish_lines(path));
+            }
+        }
+        ShellType::Nushell => {
+            for path in paths {
+                output.push_str(&nu_line(path));
+            }
+        }
+    }
+    output
+}
+
+fn sh_quote(dir: &Path) -> String {
+    format!("'{}'", dir.display().to_string().replace('\'', "'\\''"))
+}
+
+fn quoted(dirs: &[std::path::PathBuf], quote: fn(&Path) -> String) -> Vec<String> {
+    dirs.iter().map(|d| quote(d)).collect()
+}
+
+fn zsh_lines(path: &SearchPath) -> String {
+    let prepend = quoted(&path.prepend, sh_quote);
+    let append = quoted(&path.append, sh_quote);
+    let array = match path.var {
+        PathVar::Path => "path",
+        PathVar::Manpath => "manpath",
+        PathVar::Fpath => "fpath",
+        PathVar::LdLibraryPath => {
+            // No tied array; go through a unique temporary one
+            let dirs: Vec<String> = prepend
+                .into_iter()
+                .chain(std::iter::once("${(s.:.)LD_LIBRARY_PATH}".to_string()))
+                .chain(append)
+                .collect();
+            return format!(
+                "typeset -gaU __pzsh_dirs\n\
+                 __pzsh_dirs=({})\n\
+                 export LD_LIBRARY_PATH=${{(j.:.)__pzsh_dirs}}\n\
+                 unset __pzsh_dirs\n",
+                dirs.join(" ")
+            );
+        }
+    };
+    let mut output = String::new();
+    if path.var == PathVar::Manpath {
+        output.push_str("[[ -n $MANPATH ]] || manpath=('')\n");
+    }
+    let dirs: Vec<String> = prepend
+        .into_iter()
+        .chain(std::iter::once(format!("\"${{{array}[@]}}\"")))
+        .chain(append)
+        .collect();
+    output.push_str(&format!("{array}=({})\n", dirs.join(" ")));
+    if path.var == PathVar::Manpath {
+        output.push_str("export MANPATH\n");
+    }
+    output
+}
+
+fn posix_lines(path: &SearchPath) -> String {
+    let name = path.var.name();
+    let mut output = String::new();
+    if path.var == PathVar::Manpath {
+        output.push_str("[ -n \"${MANPATH-}\" ] || MANPATH=:\n");
+    }
+    // Prepend in reverse so the first directory ends up in front
+    for dir in path.prepend.iter().rev() {
+        output.push_str(&format!(
+            "__pzsh_path_add {name} prepend {}\n",
+            sh_quote(dir)
+        ));
+    }
+    for dir in &path.append {
+        output.push_str(&format!(
+            "__pzsh_path_add {name} append {}\n",
+            sh_quote(dir)
+        ));
+    }
+    output
+}
+
+fn fish_dir(dir: &Path) -> String {
+    fish_quote(&dir.display().to_string())
+}
+
+/// `string match` glob matching exactly `dir`
+fn fish_pattern(dir: &Path) -> String {
+    let text = dir.display().to_string();
+    fish_quote(
+        &text
+            .replace('\\', "\\\\")
+            .replace('*', "\\*")
+            .replace('?', "\\?"),
+    )
+}
+
+fn fish_lines(path: &SearchPath) -> String {
+    let name = path.var.name();
+    let mut output = String::new();
+    if path.var == PathVar::Manpath {
+        output.push_str("set -q MANPATH; or set -gx MANPATH ''\n");
+    }
+    if !path.prepend.is_empty() {
+        let dirs = quoted(&path.prepend, fish_dir).join(" ");
+        let filters: Vec<String> = path
+            .prepend
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| {
+                let values = if i == 0 {
+                    format!(" ${name}")
+                } else {
+                    String::new()
+                };
+                format!("string match -v -- {}{values}", fish_pattern(dir))
+            })
+            .collect();
+        output.push_str(&format!(
+            "set -gx {name} {dirs} ({})\n",
+            filters.join(" | ")
+        ));
+    }
+    for dir in &path.append {
+        let dir = fish_dir(dir);
+        output.push_str(&format!(
+            "contains -- {dir} ${name}; or set -gx {name} ${name} {dir}\n"
+        ));
+    }
+    output
+}
+
+fn nu_string(dir: &Path) -> String {
+    let text = dir.display().to_string();
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn nu_line(path: &SearchPath) -> String {
+    let name = path.var.name();
+    let mut pipeline = if path.var == PathVar::Path {
+        "$env.PATH | split row (char esep)".to_string()
+    } else {
+        format!("$env.{name}? | default '' | split row (char esep)")
+    };
+    if !path.prepend.is_empty() {
+        let dirs = quoted(&path.prepend, nu_string).join(", ");
+        pipeline.push_str(&format!(" | prepend [{dirs}]"));
+    }
+    if !path.append.is_empty() {
+        let dirs = quoted(&path.append, nu_string).join(", ");
+        pipeline.push_str(&format!(" | append [{dirs}]"));
+    }
+    pipeline.push_str(" | uniq");
+    if path.var != PathVar::Path {
+        pipeline.push_str(" | str join (char esep)");
+    }
+    format!("$env.{name} = ({pipeline})\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn search_path(var: PathVar, prepend: &[&str], append: &[&str]) -> SearchPath {
+        SearchPath {
+            var,
+            prepend: prepend.iter().map(PathBuf::from).collect(),
+            append: append.iter().map(PathBuf::from).collect(),
+            missing: Vec::new(),
+        }
+    }
+
+    fn paths() -> Vec<SearchPath> {
+        vec![
+            search_path(PathVar::Path, &["/a", "/b"], &["/c"]),
+            search_path(PathVar::Manpath, &["/m"], &[]),
+            search_path(PathVar::Fpath, &["/f"], &[]),
+            search_path(PathVar::LdLibraryPath, &[], &["/l"]),
+        ]
+    }
+
+    #[test]
+    fn test_zsh_path_lines() {
+        let output = path_lines(ShellType::Zsh, &paths());
+        assert!(output.contains("typeset -gU path fpath manpath\n"));
+        assert!(output.contains("path=('/a' '/b' \"${path[@]}\" '/c')\n"));
+        assert!(output.contains(
+            "[[ -n $MANPATH ]] || manpath=('')\nmanpath=('/m' \"${manpath[@]}\")\nexport MANPATH\n"
+        ));
+        assert!(output.contains("fpath=('/f' \"${fpath[@]}\")\n"));
+        assert!(output.contains("__pzsh_dirs=(${(s.:.)LD_LIBRARY_PATH} '/l')\n"));
+    }
+
+    #[test]
+    fn test_fish_and_nushell_path_lines() {
+        let fish = path_lines(ShellType::Fish, &paths());
+        assert!(fish.contains(
+            "set -gx PATH '/a' '/b' (string match -v -- '/a' $PATH | string match -v -- '/b')\n"
+        ));
+        assert!(fish.contains("contains -- '/c' $PATH; or set -gx PATH $PATH '/c'\n"));
+        assert!(!fish.contains("FPATH"));
+
+        let nu = path_lines(ShellType::Nushell, &paths());
+        assert!(nu.contains(
+            "$env.PATH = ($env.PATH | split row (char esep) | prepend [\"/a\", \"/b\"] | append [\"/c\"] | uniq)\n"
+        ));
+        assert!(nu.contains("$env.MANPATH = ($env.MANPATH? | default '' | split row (char esep) | prepend [\"/m\"] | uniq | str join (char esep))\n"));
+    }
+
+    #[test]
+    fn test_posix_path_lines_run() {
+        let script = format!(
+            "PATH=/c:/x:/b:/x\nunset MANPATH LD_LIBRARY_PATH\n{}\
+             printf '%s\\n' \"$PATH\" \"$MANPATH\" \"$LD_LIBRARY_PATH\"\n\
+             command -v __pzsh_path_add || echo gone\n",
+            path_lines(ShellType::Sh, &paths())
+        );
+        assert!(!script.contains("fpath") && !script.contains("FPATH"));
+        let Ok(output) = Command::new("sh").arg("-c").arg(&script).output() else {
+            return;
+        };
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "/a:/b:/c:/x:/x\n/m::\n/l\ngone\n"
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! Helpers shared by unit tests
+
+use std::path::PathBuf;
+
+/// Empty scratch directory under the system temp dir, unique per test
+/// binary run
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pzsh-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -41,6 +41,9 @@ pub trait Theme: Send + Sync {
 
     /// Generate bash prompt string
     fn bash_prompt(&self) -> String;
+
+    /// Generate fish `fish_prompt` function
+    fn fish_prompt(&self) -> String;
 }
 
 /// Robbyrussell theme (oh-my-zsh default)
@@ -95,6 +98,23 @@ impl Theme for RobbyRussellTheme {
     fn bash_prompt(&self) -> String {
         r"PS1='\[\033[32m\]➜ \[\033[36m\]\W\[\033[0m\] $(__pzsh_git_info) '".to_string()
     }
+
+    fn fish_prompt(&self) -> String {
+        r"function fish_prompt
+    set -l last_status $status
+    if test $last_status -eq 0
+        set_color green
+    else
+        set_color red
+    end
+    printf '➜ '
+    set_color cyan
+    printf '%s' (basename (prompt_pwd))
+    set_color normal
+    printf ' %s ' (__pzsh_git_info)
+end"
+        .to_string()
+    }
 }
 
 /// Agnoster theme (powerline-style)
@@ -156,6 +176,20 @@ impl Theme for AgnosterTheme {
     fn bash_prompt(&self) -> String {
         r"PS1='\[\033[44m\]\[\033[30m\] \u@\h \[\033[0m\]\[\033[34m\]\[\033[46m\]\[\033[30m\] \w \[\033[0m\]\[\033[36m\] '".to_string()
     }
+
+    fn fish_prompt(&self) -> String {
+        r"function fish_prompt
+    set_color -b blue black
+    printf ' %s@%s ' $USER (prompt_hostname)
+    set_color -b cyan black
+    printf ' %s ' (prompt_pwd)
+    set_color normal
+    set_color cyan
+    printf ' '
+    set_color normal
+end"
+        .to_string()
+    }
 }
 
 /// Simple/minimal theme
@@ -211,6 +245,29 @@ impl Theme for SimpleTheme {
         r"PS1='\[\033[32m\]\u\[\033[0m\]@\[\033[34m\]\h\[\033[0m\] \[\033[36m\]\w\[\033[0m\] \$ '"
             .to_string()
     }
+
+    fn fish_prompt(&self) -> String {
+        r"function fish_prompt
+    set_color green
+    printf '%s' $USER
+    set_color normal
+    printf '@'
+    set_color blue
+    printf '%s' (prompt_hostname)
+    set_color normal
+    printf ' '
+    set_color cyan
+    printf '%s' (prompt_pwd)
+    set_color normal
+    printf ' %s ' (__pzsh_git_info)
+    if fish_is_root_user
+        printf '# '
+    else
+        printf '%% '
+    end
+end"
+        .to_string()
+    }
 }
 
 /// Pure theme (async, minimal, fast)
@@ -270,6 +327,25 @@ impl Theme for PureTheme {
         r"PS1='\n\[\033[34m\]\w\[\033[0m\] $(__pzsh_git_info)\n\[\033[35m\]❯\[\033[0m\] '"
             .to_string()
     }
+
+    fn fish_prompt(&self) -> String {
+        r"function fish_prompt
+    set -l last_status $status
+    printf '\n'
+    set_color blue
+    printf '%s' (prompt_pwd)
+    set_color normal
+    printf ' %s\n' (__pzsh_git_info)
+    if test $last_status -eq 0
+        set_color magenta
+    else
+        set_color red
+    end
+    printf '❯ '
+    set_color normal
+end"
+        .to_string()
+    }
 }
 
 /// Spaceship theme (feature-rich)
@@ -328,6 +404,25 @@ impl Theme for SpaceshipTheme {
         r"PS1='\n\[\033[36m\]\w\[\033[0m\] $(__pzsh_git_info)\n\[\033[32m\]❯\[\033[0m\] '"
             .to_string()
     }
+
+    fn fish_prompt(&self) -> String {
+        r"function fish_prompt
+    set -l last_status $status
+    printf '\n'
+    set_color cyan
+    printf '%s' (prompt_pwd)
+    set_color normal
+    printf ' %s\n' (__pzsh_git_info)
+    if test $last_status -eq 0
+        set_color green
+    else
+        set_color red
+    end
+    printf '❯ '
+    set_color normal
+end"
+        .to_string()
+    }
 }
 
 /// Theme registry
@@ -428,6 +523,14 @@ mod tests {
         assert!(prompt.contains("➜"));
     }
 
+    #[test]
+    fn test_robbyrussell_fish_prompt() {
+        let prompt = RobbyRussellTheme.fish_prompt();
+        assert!(prompt.contains("set -l last_status $status"));
+        assert!(prompt.contains("set_color cyan"));
+        assert!(prompt.contains("➜"));
+    }
+
     #[test]
     fn test_agnoster_theme() {
         let theme = AgnosterTheme;
@@ -542,9 +645,14 @@ mod tests {
             let theme = registry.get(name).unwrap();
             let zsh = theme.zsh_prompt();
             let bash = theme.bash_prompt();
+            let fish = theme.fish_prompt();
 
             assert!(!zsh.is_empty(), "Theme {name} has empty zsh prompt");
             assert!(!bash.is_empty(), "Theme {name} has empty bash prompt");
+            assert!(
+                fish.starts_with("function fish_prompt") && fish.ends_with("end"),
+                "Theme {name} has malformed fish prompt"
+            );
         }
     }
 
@@ -637,6 +745,10 @@ mod tests {
         fn bash_prompt(&self) -> String {
             "PS1='custom> '".to_string()
         }
+
+        fn fish_prompt(&self) -> String {
+            "function fish_prompt; printf 'custom> '; end".to_string()
+        }
     }
 
     #[test]
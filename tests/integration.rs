@@ -190,12 +190,27 @@ fn test_shell_init_both_shells() {
     assert!(zsh_init.contains("compinit"), "Zsh should have completion");
 
     // Test bash
-    let bash_init = generate_init(ShellType::Bash, config);
+    let bash_init = generate_init(ShellType::Bash, config.clone());
     assert!(bash_init.contains("bind"), "Bash should have keybindings");
     assert!(
         bash_init.contains("completion"),
         "Bash should have completion"
     );
+
+    // Test fish
+    let fish_init = generate_init(ShellType::Fish, config.clone());
+    assert!(fish_init.contains("bind "), "Fish should have keybindings");
+    assert!(
+        fish_init.contains("function fish_prompt"),
+        "Fish should have a prompt function"
+    );
+
+    // Nushell and POSIX sh only get env, aliases and plugin functions
+    let nu_init = generate_init(ShellType::Nushell, config.clone());
+    assert!(nu_init.contains("# Skipped (not expressible in nushell)"));
+    let sh_init = generate_init(ShellType::Sh, config);
+    assert!(sh_init.contains("# Skipped (not expressible in sh)"));
+    assert!(!sh_init.contains("[["), "sh output must be POSIX");
 }
 
 /// Test prompt rendering performance